@@ -0,0 +1,9 @@
+#![no_main]
+use didcomm_rs::Message;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|message: Message| {
+    if let Ok(serialized) = serde_json::to_string(&message) {
+        let _ = serde_json::from_str::<Message>(&serialized);
+    }
+});
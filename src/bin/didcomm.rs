@@ -0,0 +1,203 @@
+//! Command line tool to pack, unpack, sign and verify DIDComm envelopes.
+//! Reads message from stdin and writes result to stdout.
+//!
+//! ```sh
+//! didcomm pack --sender-key alice.jwk --recipient-key bob.pub < message.json
+//! didcomm unpack --recipient-key bob.jwk --sender-key alice.pub < envelope.json
+//! didcomm sign --key alice-sign.jwk --kid did:example:alice#key-1 < message.json
+//! didcomm verify --key alice-sign.pub < envelope.json
+//! ```
+//!
+//! Key files may contain a JWK (`d` is used for private, `x`/`y` for public keys),
+//!     a hex string or raw key bytes.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    env,
+    fs,
+    io::{self, Read},
+    process,
+};
+
+use didcomm_rs::{
+    crypto::{CryptoAlgorithm, SignatureAlgorithm, Signer},
+    Error,
+    Message,
+};
+use serde_json::Value;
+
+const USAGE: &str = "usage:
+    didcomm pack --sender-key <file> --recipient-key <file>...
+                 [--alg XC20P|A256GCM|A256CBC] [--flat]
+    didcomm unpack --recipient-key <file> [--sender-key <file>] [--verify-key <file>]
+    didcomm sign --key <file> [--alg EdDSA|Ed448|ES256|ES256K|ES256K-R] [--kid <kid>] [--flat]
+    didcomm verify --key <file>
+
+    message or envelope is read from stdin, result is written to stdout";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => println!("{}", output),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<String, Error> {
+    let (command, options) = args
+        .split_first()
+        .ok_or_else(|| Error::Generic("missing command".into()))?;
+    let options = Options::parse(options)?;
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| Error::Generic(format!("failed to read stdin; {}", e)))?;
+
+    match command.as_str() {
+        "pack" => pack(&input, &options),
+        "unpack" => unpack(&input, &options),
+        "sign" => sign(&input, &options),
+        "verify" => verify(&input, &options),
+        _ => Err(Error::Generic(format!("unknown command `{}`", command))),
+    }
+}
+
+fn pack(input: &str, options: &Options) -> Result<String, Error> {
+    let sender_key = read_key(options.required("sender-key")?, true)?;
+    let recipient_keys = options
+        .all("recipient-key")
+        .iter()
+        .map(|path| read_key(path, false))
+        .collect::<Result<Vec<Vec<u8>>, Error>>()?;
+    let alg = match options.single("alg").unwrap_or("XC20P") {
+        "XC20P" => CryptoAlgorithm::XC20P,
+        "A256GCM" => CryptoAlgorithm::A256GCM,
+        "A256CBC" => CryptoAlgorithm::A256CBC,
+        other => return Err(Error::Generic(format!("unsupported alg `{}`", other))),
+    };
+    let message: Message = input.parse()?;
+    let message = if options.flag("flat") {
+        message.as_flat_jwe(&alg, recipient_keys.first().cloned())
+    } else {
+        message.as_jwe(&alg, recipient_keys.first().cloned())
+    };
+    message.seal(
+        sender_key,
+        Some(recipient_keys.into_iter().map(Some).collect()),
+    )
+}
+
+fn unpack(input: &str, options: &Options) -> Result<String, Error> {
+    let recipient_key = read_key(options.required("recipient-key")?, true)?;
+    let sender_key = options
+        .single("sender-key")
+        .map(|path| read_key(path, false))
+        .transpose()?;
+    let verify_key = options
+        .single("verify-key")
+        .map(|path| read_key(path, false))
+        .transpose()?;
+    let message = Message::receive(
+        input,
+        Some(&recipient_key),
+        sender_key,
+        verify_key.as_deref(),
+    )?;
+    pretty(&message)
+}
+
+fn sign(input: &str, options: &Options) -> Result<String, Error> {
+    let key = read_key(options.required("key")?, true)?;
+    let alg = SignatureAlgorithm::try_from(&options.single("alg").unwrap_or("EdDSA").to_string())?;
+    let mut message: Message = input.parse()?;
+    if let Some(kid) = options.single("kid") {
+        message = message.kid(kid);
+    }
+    let message = if options.flag("flat") {
+        message.as_flat_jws(&alg)
+    } else {
+        message.as_jws(&alg)
+    };
+    message.sign(alg.signer(), &key)
+}
+
+fn verify(input: &str, options: &Options) -> Result<String, Error> {
+    let key = read_key(options.required("key")?, false)?;
+    pretty(&Message::verify(input.as_bytes(), &key)?)
+}
+
+fn pretty(message: &Message) -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(message)?)
+}
+
+/// Reads key from JWK, hex or raw key file.
+fn read_key(path: &str, private: bool) -> Result<Vec<u8>, Error> {
+    let content =
+        fs::read(path).map_err(|e| Error::Generic(format!("failed to read {}; {}", path, e)))?;
+    let text = String::from_utf8_lossy(&content);
+    let text = text.trim();
+    if text.starts_with('{') {
+        let jwk: Value = serde_json::from_str(text)?;
+        let member = |name: &'static str| -> Result<Vec<u8>, Error> {
+            let value = jwk[name].as_str().ok_or(Error::PropertyIsNotSet(name))?;
+            Ok(base64_url::decode(value)?)
+        };
+        return if private {
+            member("d")
+        } else if jwk["kty"] == "EC" {
+            // uncompressed SEC1 point
+            let mut key = vec![0x04];
+            key.extend(member("x")?);
+            key.extend(member("y")?);
+            Ok(key)
+        } else {
+            member("x")
+        };
+    }
+    match hex::decode(text) {
+        Ok(key) => Ok(key),
+        Err(_) => Ok(content),
+    }
+}
+
+/// Command line options as `--name value` pairs and `--name` flags.
+struct Options {
+    values: HashMap<String, Vec<String>>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| Error::Generic(format!("unexpected argument `{}`", arg)))?;
+            let entry = values.entry(name.to_string()).or_default();
+            if let Some(value) = args.next_if(|next| !next.starts_with("--")) {
+                entry.push(value.clone());
+            }
+        }
+        Ok(Options { values })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    fn all(&self, name: &str) -> &[String] {
+        self.values.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn single(&self, name: &str) -> Option<&str> {
+        self.all(name).first().map(String::as_str)
+    }
+
+    fn required(&self, name: &'static str) -> Result<&str, Error> {
+        self.single(name).ok_or(Error::PropertyIsNotSet(name))
+    }
+}
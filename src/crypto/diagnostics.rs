@@ -0,0 +1,62 @@
+//! Opt-in inspection of intermediate key material.
+//!
+//! Crypto paths never log secrets. Debugging interoperability issues sometimes needs
+//! them anyway, so a callback can be registered to receive them instead.
+//! Never enable this in production.
+
+use std::sync::{Arc, RwLock};
+
+/// Callback receiving label and raw value of intermediate key material,
+/// e.g. `("kek", ...)` or `("shared_secret", ...)`.
+pub type DiagnosticsCallback = dyn Fn(&str, &[u8]) + Send + Sync;
+
+static DIAGNOSTICS: RwLock<Option<Arc<DiagnosticsCallback>>> = RwLock::new(None);
+
+/// Registers process wide callback for intermediate key material.
+/// Replaces previously registered one.
+///
+/// # Arguments
+///
+/// * `callback` - receives label and raw bytes of every reported value
+pub fn set_diagnostics_callback(callback: impl Fn(&str, &[u8]) + Send + Sync + 'static) {
+    if let Ok(mut diagnostics) = DIAGNOSTICS.write() {
+        *diagnostics = Some(Arc::new(callback));
+    }
+}
+
+/// Removes registered diagnostics callback, if any.
+pub fn clear_diagnostics_callback() {
+    if let Ok(mut diagnostics) = DIAGNOSTICS.write() {
+        *diagnostics = None;
+    }
+}
+
+/// Hands value over to registered callback. No-op if none is registered.
+pub(crate) fn report(label: &str, value: &[u8]) {
+    let callback = match DIAGNOSTICS.read() {
+        Ok(diagnostics) => diagnostics.clone(),
+        Err(_) => None,
+    };
+    if let Some(callback) = callback {
+        callback(label, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn reports_only_while_callback_is_registered() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        set_diagnostics_callback(move |label, _| sink.lock().unwrap().push(label.to_string()));
+        report("test-reported", &[1, 2, 3]);
+        clear_diagnostics_callback();
+        report("test-not-reported", &[4, 5, 6]);
+        assert!(reported.lock().unwrap().contains(&"test-reported".to_string()));
+        assert!(!reported.lock().unwrap().contains(&"test-not-reported".to_string()));
+    }
+}
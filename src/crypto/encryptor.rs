@@ -11,7 +11,7 @@ use super::*;
 ///
 /// Allowed (and implemented) cryptographical algorithms (JWA).
 /// According to [spec](https://identity.foundation/didcomm-messaging/spec/#sender-authenticated-encryption)
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub enum CryptoAlgorithm {
     XC20P,
     A256GCM,
@@ -124,13 +124,135 @@ impl TryFrom<&String> for CryptoAlgorithm {
     type Error = Error;
     fn try_from(incoming: &String) -> Result<Self, Error> {
         match &incoming[..] {
-            "ECDH-1PU+A256KW" => Ok(Self::A256GCM),
-            "ECDH-1PU+XC20PKW" => Ok(Self::XC20P),
+            "ECDH-1PU+A256KW" | "ECDH-ES+A256KW" => Ok(Self::A256GCM),
+            "ECDH-1PU+XC20PKW" | "ECDH-ES+XC20PKW" => Ok(Self::XC20P),
             _ => Err(Error::JweParseError),
         }
     }
 }
 
+impl CryptoAlgorithm {
+    /// Key agreement `alg` of authcrypt envelopes sealed with this shorthand,
+    ///     `None` for `A256CBC`.
+    pub fn key_agreement_alg(&self) -> Option<KeyAgreementAlg> {
+        match self {
+            CryptoAlgorithm::XC20P => Some(KeyAgreementAlg::Ecdh1puXc20pkw),
+            CryptoAlgorithm::A256GCM => Some(KeyAgreementAlg::Ecdh1puA256kw),
+            CryptoAlgorithm::A256CBC => None,
+        }
+    }
+
+    /// Content encryption `enc` of envelopes sealed with this shorthand,
+    ///     `None` for `A256CBC`.
+    pub fn content_enc_alg(&self) -> Option<ContentEncAlg> {
+        match self {
+            CryptoAlgorithm::XC20P => Some(ContentEncAlg::XC20P),
+            CryptoAlgorithm::A256GCM => Some(ContentEncAlg::A256GCM),
+            CryptoAlgorithm::A256CBC => None,
+        }
+    }
+}
+
+/// JWE `alg`: key agreement and wrapping of content encryption key per recipient.
+/// Independent of content encryption `enc`, see [`ContentEncAlg`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyAgreementAlg {
+    /// `ECDH-1PU+A256KW`, authcrypt
+    Ecdh1puA256kw,
+    /// `ECDH-1PU+XC20PKW`, authcrypt
+    Ecdh1puXc20pkw,
+    /// `ECDH-ES+A256KW`, anoncrypt
+    EcdhEsA256kw,
+    /// `ECDH-ES+XC20PKW`, anoncrypt
+    EcdhEsXc20pkw,
+}
+
+impl KeyAgreementAlg {
+    /// Value of JWE `alg` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyAgreementAlg::Ecdh1puA256kw => "ECDH-1PU+A256KW",
+            KeyAgreementAlg::Ecdh1puXc20pkw => "ECDH-1PU+XC20PKW",
+            KeyAgreementAlg::EcdhEsA256kw => "ECDH-ES+A256KW",
+            KeyAgreementAlg::EcdhEsXc20pkw => "ECDH-ES+XC20PKW",
+        }
+    }
+
+    /// Checks if sender stays anonymous (ECDH-ES).
+    pub fn is_anonymous(&self) -> bool {
+        matches!(
+            self,
+            KeyAgreementAlg::EcdhEsA256kw | KeyAgreementAlg::EcdhEsXc20pkw
+        )
+    }
+
+    /// Anonymous (ECDH-ES) variant with same key wrapping.
+    pub fn anonymous(&self) -> Self {
+        match self {
+            KeyAgreementAlg::Ecdh1puA256kw | KeyAgreementAlg::EcdhEsA256kw => {
+                KeyAgreementAlg::EcdhEsA256kw
+            }
+            KeyAgreementAlg::Ecdh1puXc20pkw | KeyAgreementAlg::EcdhEsXc20pkw => {
+                KeyAgreementAlg::EcdhEsXc20pkw
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for KeyAgreementAlg {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "ECDH-1PU+A256KW" => Ok(Self::Ecdh1puA256kw),
+            "ECDH-1PU+XC20PKW" => Ok(Self::Ecdh1puXc20pkw),
+            "ECDH-ES+A256KW" => Ok(Self::EcdhEsA256kw),
+            "ECDH-ES+XC20PKW" => Ok(Self::EcdhEsXc20pkw),
+            _ => Err(Error::JweParseError),
+        }
+    }
+}
+
+/// JWE `enc`: encryption of message content, shared by all recipients.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContentEncAlg {
+    /// `A256GCM`
+    A256GCM,
+    /// `XC20P`
+    XC20P,
+}
+
+impl ContentEncAlg {
+    /// Value of JWE `enc` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncAlg::A256GCM => "A256GCM",
+            ContentEncAlg::XC20P => "XC20P",
+        }
+    }
+}
+
+impl TryFrom<&str> for ContentEncAlg {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "A256GCM" => Ok(Self::A256GCM),
+            "XC20P" => Ok(Self::XC20P),
+            _ => Err(Error::JweParseError),
+        }
+    }
+}
+
+impl From<ContentEncAlg> for CryptoAlgorithm {
+    fn from(enc: ContentEncAlg) -> Self {
+        match enc {
+            ContentEncAlg::A256GCM => CryptoAlgorithm::A256GCM,
+            ContentEncAlg::XC20P => CryptoAlgorithm::XC20P,
+        }
+    }
+}
+
 // inner helper function
 fn check_nonce(nonce: &[u8], expected_len: usize) -> Result<(), Error> {
     if nonce.len() < expected_len {
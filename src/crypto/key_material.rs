@@ -0,0 +1,311 @@
+//! Detection of key type from the format keys are given in.
+//!
+//! Keys come as multicodec bytes, multibase strings or `did:key`s, JWKs or
+//! verification methods of DID documents. [`KeyMaterial::detect`] identifies
+//! curve and role of all of them, so they can be used with
+//! `Message::seal_key_material` and `Message::verify_key_material`.
+
+use base58::FromBase58;
+
+use super::Key;
+use crate::{Error, Jwk};
+
+/// Curve of detected key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Curve {
+    Ed25519,
+    Ed448,
+    X25519,
+    P256,
+    Secp256k1,
+}
+
+/// What detected key is used for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyRole {
+    /// signing and verification of JWS
+    Signing,
+    /// key agreement of JWE
+    KeyAgreement,
+}
+
+/// Formats accepted by [`KeyMaterial::detect`].
+#[derive(Debug, Clone, Copy)]
+pub enum KeyInput<'a> {
+    /// key prefixed with its varint encoded multicodec, e.g. `0xed01` for ed25519 public keys
+    Multicodec(&'a [u8]),
+    /// base58btc multibase (`z...`) encoded multicodec key, `did:key` or its DID URL
+    Multibase(&'a str),
+    /// public or private JWK
+    Jwk(&'a Jwk),
+    /// raw key of a DID document verification method with given `type`,
+    ///     e.g. `Ed25519VerificationKey2020`
+    VerificationMethod {
+        /// `type` of verification method
+        kind: &'a str,
+        /// raw key bytes, multicodec prefixed for `Multikey`
+        key: &'a [u8],
+    },
+}
+
+impl<'a> From<&'a [u8]> for KeyInput<'a> {
+    fn from(multicodec: &'a [u8]) -> Self {
+        KeyInput::Multicodec(multicodec)
+    }
+}
+
+impl<'a> From<&'a str> for KeyInput<'a> {
+    fn from(multibase: &'a str) -> Self {
+        KeyInput::Multibase(multibase)
+    }
+}
+
+impl<'a> From<&'a Jwk> for KeyInput<'a> {
+    fn from(jwk: &'a Jwk) -> Self {
+        KeyInput::Jwk(jwk)
+    }
+}
+
+/// Multicodec prefix of Ed25519 public keys, varint encoded `0xed`.
+pub(crate) const ED25519_MULTICODEC_PREFIX: &[u8] = &[0xed, 0x01];
+
+/// Multicodec prefix of X25519 public keys, varint encoded `0xec`.
+pub(crate) const X25519_MULTICODEC_PREFIX: &[u8] = &[0xec, 0x01];
+
+/// Known multicodecs: varint encoded prefix, curve and if key is private.
+const MULTICODECS: [(&[u8], Curve, bool); 9] = [
+    (ED25519_MULTICODEC_PREFIX, Curve::Ed25519, false),
+    (&[0x83, 0x24], Curve::Ed448, false),
+    (X25519_MULTICODEC_PREFIX, Curve::X25519, false),
+    (&[0x80, 0x24], Curve::P256, false),
+    (&[0xe7, 0x01], Curve::Secp256k1, false),
+    (&[0x80, 0x26], Curve::Ed25519, true),
+    (&[0x82, 0x26], Curve::X25519, true),
+    (&[0x86, 0x26], Curve::P256, true),
+    (&[0x81, 0x26], Curve::Secp256k1, true),
+];
+
+/// Key with detected curve and role.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyMaterial {
+    /// curve of key
+    pub curve: Curve,
+
+    /// what key is used for
+    pub role: KeyRole,
+
+    /// `true` for private keys
+    pub private: bool,
+
+    /// raw key bytes, without multicodec prefix; SEC1 encoded for public `EC` keys
+    #[serde(with = "hex")]
+    pub bytes: Vec<u8>,
+}
+
+impl KeyMaterial {
+    /// Identifies curve and role of given key.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnsupportedKeyMaterial` if key format or type is not known.
+    ///
+    /// # Parameters
+    ///
+    /// * `input` - key as multicodec bytes, multibase string, JWK or verification method
+    pub fn detect<'a>(input: impl Into<KeyInput<'a>>) -> Result<Self, Error> {
+        match input.into() {
+            KeyInput::Multicodec(multicodec) => Self::from_multicodec(multicodec),
+            KeyInput::Multibase(multibase) => {
+                let multibase = multibase.strip_prefix("did:key:").unwrap_or(multibase);
+                let multibase = multibase.split('#').next().unwrap_or_default();
+                let multicodec = multibase
+                    .strip_prefix('z')
+                    .and_then(|key| key.from_base58().ok())
+                    .ok_or_else(|| unsupported("only base58btc multibase is supported"))?;
+                Self::from_multicodec(&multicodec)
+            }
+            KeyInput::Jwk(jwk) => Self::from_jwk(jwk),
+            KeyInput::VerificationMethod { kind, key } => {
+                let curve = match kind {
+                    "Ed25519VerificationKey2018" | "Ed25519VerificationKey2020" => Curve::Ed25519,
+                    "X25519KeyAgreementKey2019" | "X25519KeyAgreementKey2020" => Curve::X25519,
+                    "EcdsaSecp256k1VerificationKey2019" => Curve::Secp256k1,
+                    "Multikey" => return Self::from_multicodec(key),
+                    _ => return Err(unsupported(&format!("verification method type {}", kind))),
+                };
+                Ok(Self::new(curve, false, key.to_vec()))
+            }
+        }
+    }
+
+    /// Key agreement key usable with `seal` and `receive`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnsupportedKeyMaterial` if key is not an X25519 key.
+    pub fn agreement_key(&self) -> Result<&[u8], Error> {
+        match self.curve {
+            Curve::X25519 => Ok(&self.bytes),
+            curve => Err(unsupported(&format!(
+                "{:?} keys can't be used for key agreement",
+                curve
+            ))),
+        }
+    }
+
+    /// Typed private signing key usable with `Message::sign_auto`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnsupportedKeyMaterial` if key is public or no signing key.
+    pub fn signing_key(&self) -> Result<Key, Error> {
+        if !self.private {
+            return Err(unsupported("public keys can't be used for signing"));
+        }
+        match self.curve {
+            Curve::Ed25519 => Ok(Key::Ed25519(self.bytes.clone())),
+            Curve::Ed448 => Ok(Key::Ed448(self.bytes.clone())),
+            Curve::P256 => Ok(Key::P256(self.bytes.clone())),
+            Curve::Secp256k1 => Ok(Key::Secp256k1(self.bytes.clone())),
+            Curve::X25519 => Err(unsupported("X25519 keys can't be used for signing")),
+        }
+    }
+
+    fn new(curve: Curve, private: bool, bytes: Vec<u8>) -> Self {
+        let role = match curve {
+            Curve::X25519 => KeyRole::KeyAgreement,
+            _ => KeyRole::Signing,
+        };
+        Self {
+            curve,
+            role,
+            private,
+            bytes,
+        }
+    }
+
+    fn from_multicodec(multicodec: &[u8]) -> Result<Self, Error> {
+        MULTICODECS
+            .iter()
+            .find_map(|(prefix, curve, private)| {
+                multicodec
+                    .strip_prefix(*prefix)
+                    .map(|key| Self::new(*curve, *private, key.to_vec()))
+            })
+            .ok_or_else(|| unsupported("unknown multicodec"))
+    }
+
+    fn from_jwk(jwk: &Jwk) -> Result<Self, Error> {
+        let curve = match jwk.crv.as_deref() {
+            Some("Ed25519") => Curve::Ed25519,
+            Some("Ed448") => Curve::Ed448,
+            Some("X25519") => Curve::X25519,
+            Some("P-256") => Curve::P256,
+            Some("secp256k1") => Curve::Secp256k1,
+            crv => return Err(unsupported(&format!("JWK curve {}", crv.unwrap_or("none")))),
+        };
+        let parameter = |name: &str| {
+            jwk.other
+                .get(name)
+                .and_then(|value| value.as_str())
+                .map(base64_url::decode)
+                .transpose()
+        };
+        let mut material = match (parameter("d")?, parameter("x")?, parameter("y")?) {
+            (Some(d), _, _) => Self::new(curve, true, d),
+            (None, Some(mut x), Some(y)) => {
+                // uncompressed SEC1 point
+                x.insert(0, 0x04);
+                x.extend(y);
+                Self::new(curve, false, x)
+            }
+            (None, Some(x), None) => Self::new(curve, false, x),
+            (None, None, _) => return Err(unsupported("JWK has neither `d` nor `x`")),
+        };
+        if jwk.use_.as_deref() == Some("enc") {
+            material.role = KeyRole::KeyAgreement;
+        }
+        Ok(material)
+    }
+}
+
+fn unsupported(reason: &str) -> Error {
+    Error::UnsupportedKeyMaterial(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use base58::ToBase58;
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{
+        crypto::{CryptoAlgorithm, SignatureAlgorithm},
+        x25519_did_key,
+        Message,
+    };
+
+    #[test]
+    fn detects_keys_and_routes_them_through_seal_and_verify() -> Result<(), Error> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+
+        // X25519 keys as did:key, multicodec and JWK
+        let bob = KeyMaterial::detect(x25519_did_key(&bobs_public).as_str())?;
+        assert_eq!(bob.curve, Curve::X25519);
+        assert_eq!(bob.role, KeyRole::KeyAgreement);
+        assert_eq!(bob.bytes, bobs_public);
+        let alice = KeyMaterial::detect(&[&[0x82, 0x26][..], &alice_private[..]].concat()[..])?;
+        assert!(alice.private);
+        let mut jwk = Jwk::new();
+        jwk.crv = Some("X25519".into());
+        jwk.add_other_header("d".into(), base64_url::encode(&bobs_private));
+        let bob_private = KeyMaterial::detect(&jwk)?;
+
+        let sealed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, None)
+            .seal_key_material(&alice, &[bob])?;
+        Message::receive(
+            &sealed,
+            Some(bob_private.agreement_key()?),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+
+        // signing key as multibase, verification key from DID document
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut rand_core::OsRng);
+        let multibase = format!(
+            "z{}",
+            [&[0x80, 0x26][..], &signing_key.to_bytes()[..]]
+                .concat()
+                .to_base58()
+        );
+        let signing = KeyMaterial::detect(multibase.as_str())?;
+        assert_eq!(signing.role, KeyRole::Signing);
+        let key = signing.signing_key()?;
+        assert!(matches!(
+            SignatureAlgorithm::for_key(&key),
+            SignatureAlgorithm::EdDsa
+        ));
+        let jws = Message::new().sign_auto(&key)?;
+        let verification = KeyMaterial::detect(KeyInput::VerificationMethod {
+            kind: "Ed25519VerificationKey2020",
+            key: signing_key.verifying_key().as_bytes(),
+        })?;
+        Message::verify_key_material(jws.as_bytes(), &verification)?;
+
+        assert!(verification.signing_key().is_err());
+        assert!(matches!(
+            KeyMaterial::detect(&[0x00, 0x01][..]),
+            Err(Error::UnsupportedKeyMaterial(_))
+        ));
+        Ok(())
+    }
+}
@@ -1,26 +1,42 @@
 //! Collection of utilities for cryptography related components.
+//!
+//! Cryptography closures are `Send + Sync`, so packing and unpacking can run
+//! inside async tasks and thread pools.
+pub mod diagnostics;
 pub mod encryptor;
+pub mod key_material;
+pub mod passphrase;
+#[cfg(feature = "seed-keys")]
+pub mod seed;
 pub mod signer;
 
-pub use {encryptor::CryptoAlgorithm, signer::SignatureAlgorithm};
+use std::{future::Future, pin::Pin};
+
+pub use {
+    encryptor::{ContentEncAlg, CryptoAlgorithm, KeyAgreementAlg},
+    key_material::{Curve, KeyInput, KeyMaterial, KeyRole},
+    signer::{Key, SignatureAlgorithm},
+};
 
 use crate::Error;
 
 /// Return `FnOnce` signature definition for symmetric cryptography method.
 /// Arguments sequence: Nonce, Key, Message.
-pub type SymmetricCypherMethod = Box<dyn Fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Error>>;
+pub type SymmetricCypherMethod =
+    Box<dyn Fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Error> + Send + Sync>;
 
 /// Return `FnOnce` signature definition for asymmetric cryptography method.
 /// Arguments sequence: Nonce, Key, Message.
-pub type AsymmetricCypherMethod = Box<dyn Fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Error>>;
+pub type AsymmetricCypherMethod =
+    Box<dyn Fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Error> + Send + Sync>;
 
 /// Return `FnOnce` signature definition for signature signing method.
 /// .0 == `key: &[u8]`; .1 == `message`;
-pub type SigningMethod = Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, Error>>;
+pub type SigningMethod = Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, Error> + Send + Sync>;
 
 /// Return `FnOnce` signature definition for signature validating method.
 /// .0 == `key: &[u8]`; .1 == `message`; .2 == `signature`;
-pub type ValidationMethod = Box<dyn Fn(&[u8], &[u8], &[u8]) -> Result<bool, Error>>;
+pub type ValidationMethod = Box<dyn Fn(&[u8], &[u8], &[u8]) -> Result<bool, Error> + Send + Sync>;
 
 /// Trait must be implemented for pluggable cryptography.
 /// Implemented by `CryptoAlgorithm` with `raw-crypto` feature.
@@ -36,3 +52,55 @@ pub trait Signer {
     fn signer(&self) -> SigningMethod;
     fn validator(&self) -> ValidationMethod;
 }
+
+/// Trait must be implemented for signing with keys held outside of this process,
+///     e.g. by remote wallets or secure enclaves, see `Message::sign_external`.
+/// Implemented for closures `Fn(&[u8]) -> Result<Vec<u8>, Error>`.
+pub trait ExternalSigner: Send + Sync {
+    /// Produces signature over given JWS signing input.
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> ExternalSigner for F
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync,
+{
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self(data)
+    }
+}
+
+/// Trait must be implemented for keys which never leave their keystore,
+///     e.g. iOS Secure Enclave or Android Keystore keys, accessed via opaque handle.
+/// Used for signing with `Message::sign_hardware` and for X25519 key agreement
+///     with `Message::seal_hardware` and `Message::receive_hardware`.
+pub trait HardwareKey: Send + Sync {
+    /// Opaque identifier of the key within its keystore, e.g. keychain tag or alias.
+    fn handle(&self) -> &str;
+
+    /// Produces signature over given JWS signing input.
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Produces 32 byte X25519 shared secret of this key and given public key.
+    fn key_agreement(&self, public_key: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Future returned by [`AsyncExternalSigner`].
+pub type SignatureFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>, Error>> + Send + 'a>>;
+
+/// Async variant of [`ExternalSigner`], see `Message::sign_external_async`.
+/// Implemented for closures `Fn(Vec<u8>) -> impl Future<Output = Result<Vec<u8>, Error>>`.
+pub trait AsyncExternalSigner: Send + Sync {
+    /// Produces signature over given JWS signing input.
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignatureFuture<'a>;
+}
+
+impl<F, Fut> AsyncExternalSigner for F
+where
+    F: Fn(Vec<u8>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<u8>, Error>> + Send + 'static,
+{
+    fn sign<'a>(&'a self, data: &'a [u8]) -> SignatureFuture<'a> {
+        Box::pin(self(data.to_vec()))
+    }
+}
@@ -0,0 +1,116 @@
+//! Derivation of symmetric keys from passphrases, used to protect exported state.
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::Error;
+
+/// Argon2id parameters a key was derived with.
+/// Stored next to protected data, so keys can be derived again even after
+///     defaults changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassphraseKdf {
+    /// name of key derivation function, always `argon2id`
+    pub alg: String,
+
+    /// base64url encoded random salt
+    pub salt: String,
+
+    /// memory cost in KiB
+    pub m_cost: u32,
+
+    /// number of iterations
+    pub t_cost: u32,
+
+    /// degree of parallelism
+    pub p_cost: u32,
+}
+
+impl PassphraseKdf {
+    /// Name of the only supported key derivation function.
+    pub const ARGON2ID: &'static str = "argon2id";
+
+    /// Largest accepted memory cost in KiB, 1 GiB.
+    pub const MAX_M_COST: u32 = 1024 * 1024;
+
+    /// Largest accepted number of iterations.
+    pub const MAX_T_COST: u32 = 64;
+
+    /// Largest accepted degree of parallelism.
+    pub const MAX_P_COST: u32 = 16;
+
+    /// Parameters with random salt and default Argon2id costs.
+    pub fn generate() -> Self {
+        Self::with_costs(
+            Params::DEFAULT_M_COST,
+            Params::DEFAULT_T_COST,
+            Params::DEFAULT_P_COST,
+        )
+    }
+
+    /// Parameters with random salt and given Argon2id costs.
+    ///
+    /// # Parameters
+    ///
+    /// * `m_cost` - memory cost in KiB
+    ///
+    /// * `t_cost` - number of iterations
+    ///
+    /// * `p_cost` - degree of parallelism
+    pub fn with_costs(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        Self {
+            alg: Self::ARGON2ID.into(),
+            salt: base64_url::encode(&rand::random::<[u8; 16]>()),
+            m_cost,
+            t_cost,
+            p_cost,
+        }
+    }
+
+    /// Checks costs are within `MAX_M_COST`, `MAX_T_COST` and `MAX_P_COST`,
+    ///     so parameters read from untrusted input can't exhaust memory or CPU.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Generic` naming the first cost out of bounds.
+    pub fn check_costs(&self) -> Result<(), Error> {
+        let costs = [
+            ("m_cost", self.m_cost, Self::MAX_M_COST),
+            ("t_cost", self.t_cost, Self::MAX_T_COST),
+            ("p_cost", self.p_cost, Self::MAX_P_COST),
+        ];
+        match costs.iter().find(|(_, cost, max)| cost > max) {
+            Some((name, cost, max)) => Err(Error::Generic(format!(
+                "{} {} exceeds maximum of {}",
+                name, cost, max
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Derives 256 bit key from passphrase.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Generic` if function or its parameters are not supported
+    ///     or costs are out of bounds of `check_costs`.
+    ///
+    /// # Parameters
+    ///
+    /// * `passphrase` - secret given by the user
+    pub fn derive_key(&self, passphrase: &[u8]) -> Result<[u8; 32], Error> {
+        if self.alg != Self::ARGON2ID {
+            return Err(Error::Generic(format!(
+                "unsupported key derivation function {}",
+                self.alg
+            )));
+        }
+        self.check_costs()?;
+        let salt = base64_url::decode(&self.salt)?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, &salt, &mut key)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        Ok(key)
+    }
+}
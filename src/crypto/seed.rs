@@ -0,0 +1,228 @@
+//! Derivation of DIDComm keys from BIP39 mnemonics, so wallets can recreate their
+//!     keys from a backup phrase.
+//!
+//! Available with `seed-keys` feature only.
+//!
+//! Ed25519 signing keys are derived with [SLIP-0010](https://github.com/satoshilabs/slips/blob/master/slip-0010.md)
+//!     along hardened path `m/{account}'/{index}'`. X25519 key agreement keys are converted
+//!     from them the same way `did:key` resolvers do, so the resulting `did:key` resolves
+//!     to both keys.
+
+use bip39::Mnemonic;
+use ed25519_dalek::SigningKey;
+use hmac_sha512::HMAC;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{ed25519_did_key, x25519_did_key, Error};
+
+/// HMAC key of SLIP-0010 master key generation for Ed25519.
+const ED25519_SEED_KEY: &[u8] = b"ed25519 seed";
+
+/// Offset of hardened child indexes.
+const HARDENED: u32 = 0x8000_0000;
+
+/// Keys of one account and index derived from a seed, with their `did:key`.
+#[derive(Clone)]
+pub struct SeedKeys {
+    /// `did:key` of Ed25519 public key, e.g. `did:key:z6Mk...`
+    pub did: String,
+
+    /// Ed25519 private key (seed), usable with `SignatureAlgorithm::EdDsa`
+    pub signing_private: [u8; 32],
+
+    /// Ed25519 public key
+    pub signing_public: [u8; 32],
+
+    /// X25519 private key, usable to seal and receive JWE
+    pub agreement_private: [u8; 32],
+
+    /// X25519 public key
+    pub agreement_public: [u8; 32],
+}
+
+impl SeedKeys {
+    /// Derives keys from BIP39 mnemonic.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidMnemonic` if phrase has unknown words or wrong checksum.
+    ///
+    /// # Parameters
+    ///
+    /// * `phrase` - English BIP39 mnemonic
+    ///
+    /// * `passphrase` - optional BIP39 passphrase, empty if not used
+    ///
+    /// * `account` - account number, below 2^31
+    ///
+    /// * `index` - key index within account, below 2^31
+    pub fn from_mnemonic(
+        phrase: &str,
+        passphrase: &str,
+        account: u32,
+        index: u32,
+    ) -> Result<Self, Error> {
+        Self::from_seed(&mnemonic_to_seed(phrase, passphrase)?, account, index)
+    }
+
+    /// Derives keys from BIP39 seed or any other seed of 16 to 64 bytes.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Generic` if `account` or `index` is not below 2^31.
+    ///
+    /// # Parameters
+    ///
+    /// * `seed` - master seed, e.g. output of [`mnemonic_to_seed`]
+    ///
+    /// * `account` - account number, below 2^31
+    ///
+    /// * `index` - key index within account, below 2^31
+    pub fn from_seed(seed: &[u8], account: u32, index: u32) -> Result<Self, Error> {
+        let signing = SigningKey::from_bytes(&derive_ed25519(seed, &[account, index])?);
+        let verifying = signing.verifying_key();
+        let agreement = StaticSecret::from(signing.to_scalar_bytes());
+        Ok(Self {
+            did: ed25519_did_key(verifying.as_bytes()),
+            signing_private: signing.to_bytes(),
+            signing_public: verifying.to_bytes(),
+            agreement_private: agreement.to_bytes(),
+            agreement_public: PublicKey::from(&agreement).to_bytes(),
+        })
+    }
+
+    /// `kid` of signing key, DID URL with multibase key as fragment.
+    pub fn signing_kid(&self) -> String {
+        let fragment = self.did.trim_start_matches("did:key:");
+        format!("{}#{}", self.did, fragment)
+    }
+
+    /// `kid` of key agreement key, DID URL with multibase X25519 key as fragment.
+    pub fn agreement_kid(&self) -> String {
+        let x25519 = x25519_did_key(&self.agreement_public);
+        format!("{}#{}", self.did, x25519.trim_start_matches("did:key:"))
+    }
+}
+
+/// Generates new random 24 word English BIP39 mnemonic.
+pub fn generate_mnemonic() -> String {
+    Mnemonic::generate(24)
+        .expect("24 is a valid word count")
+        .to_string()
+}
+
+/// Computes BIP39 seed of mnemonic.
+///
+/// # Errors
+///
+/// `Error::InvalidMnemonic` if phrase has unknown words or wrong checksum.
+///
+/// # Parameters
+///
+/// * `phrase` - English BIP39 mnemonic
+///
+/// * `passphrase` - optional BIP39 passphrase, empty if not used
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], Error> {
+    let mnemonic = Mnemonic::parse(phrase).map_err(|e| Error::InvalidMnemonic(e.to_string()))?;
+    Ok(mnemonic.to_seed(passphrase))
+}
+
+/// SLIP-0010 derivation of Ed25519 private key along path of hardened indexes.
+fn derive_ed25519(seed: &[u8], path: &[u32]) -> Result<[u8; 32], Error> {
+    let mut node = HMAC::mac(seed, ED25519_SEED_KEY);
+    for &index in path {
+        if index >= HARDENED {
+            return Err(Error::Generic(format!(
+                "derivation index {} is not below 2^31",
+                index
+            )));
+        }
+        let mut data = vec![0u8];
+        data.extend(&node[..32]);
+        data.extend(&(index | HARDENED).to_be_bytes());
+        node = HMAC::mac(&data, &node[32..]);
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&node[..32]);
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::{CryptoAlgorithm, KeyMaterial, SignatureAlgorithm},
+        Message,
+    };
+
+    #[test]
+    fn derivation_matches_slip10_and_bip39_vectors() -> Result<(), Error> {
+        // SLIP-0010 test vector 1 for ed25519, chain m/0'/1'
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let keys = SeedKeys::from_seed(&seed, 0, 1)?;
+        assert_eq!(
+            hex::encode(keys.signing_private),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+        assert_eq!(
+            hex::encode(keys.signing_public),
+            "1932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187"
+        );
+
+        // BIP39 test vector with passphrase `TREZOR`
+        let phrase = "abandon abandon abandon abandon abandon abandon \
+                      abandon abandon abandon abandon abandon about";
+        assert_eq!(
+            hex::encode(&mnemonic_to_seed(phrase, "TREZOR")?[..]),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        assert!(matches!(
+            mnemonic_to_seed(&phrase.replace("about", "abandon"), ""),
+            Err(Error::InvalidMnemonic(_))
+        ));
+        assert!(SeedKeys::from_seed(&seed, HARDENED, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_are_recreated_from_backup_phrase() -> Result<(), Error> {
+        let phrase = generate_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+        let alice = SeedKeys::from_mnemonic(&phrase, "", 0, 0)?;
+        let restored = SeedKeys::from_mnemonic(&phrase, "", 0, 0)?;
+        assert_eq!(alice.did, restored.did);
+        assert_eq!(alice.agreement_private, restored.agreement_private);
+        assert_ne!(SeedKeys::from_mnemonic(&phrase, "", 0, 1)?.did, alice.did);
+        assert_ne!(SeedKeys::from_mnemonic(&phrase, "", 1, 0)?.did, alice.did);
+
+        // did:key resolves to both keys
+        let signing = KeyMaterial::detect(alice.signing_kid().as_str())?;
+        assert_eq!(signing.bytes, alice.signing_public);
+        let agreement_kid = alice.agreement_kid();
+        let (_, fragment) = agreement_kid.split_once('#').unwrap();
+        let agreement = KeyMaterial::detect(fragment)?;
+        assert_eq!(agreement.bytes, alice.agreement_public);
+        let verifying = ed25519_dalek::VerifyingKey::from_bytes(&alice.signing_public)?;
+        assert_eq!(verifying.to_montgomery().to_bytes(), alice.agreement_public);
+
+        let bob = SeedKeys::from_mnemonic(&generate_mnemonic(), "", 0, 0)?;
+        let sealed = Message::new()
+            .from(&alice.did)
+            .to(&[&bob.did])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bob.agreement_public.to_vec()))
+            .seal_signed(
+                &alice.agreement_private,
+                Some(vec![Some(bob.agreement_public.to_vec())]),
+                SignatureAlgorithm::EdDsa,
+                &alice.signing_private,
+            )?;
+        let received = Message::receive(
+            &sealed,
+            Some(&bob.agreement_private),
+            Some(alice.agreement_public.to_vec()),
+            Some(&alice.signing_public),
+        )?;
+        assert_eq!(received.get_didcomm_header().from, Some(alice.did));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use std::convert::{TryFrom, TryInto};
 
 use super::*;
+use crate::Jwk;
 
 /// Signature related batteries for DIDComm.
 /// Implementation of all algorithms required by (spec)[https://identity.foundation/didcomm-messaging/spec/#algorithms]
@@ -8,10 +9,16 @@ use super::*;
 pub enum SignatureAlgorithm {
     /// `ed25519` signature
     EdDsa,
+    /// `ed448` signature, fully specified JOSE `alg` `Ed448`
+    Ed448,
     /// `ECDSA/P-256` NIST signature
     Es256,
     /// `ECDSA/secp256k1` signature
     Es256k,
+    /// `ECDSA/secp256k1` recoverable signature, 65 bytes `r || s || recovery id`,
+    ///     JOSE `alg` `ES256K-R`. Signer key can be recovered from it, e.g. to derive
+    ///     an address of `did:ethr`.
+    Es256kR,
 }
 
 impl Signer for SignatureAlgorithm {
@@ -37,6 +44,16 @@ impl Signer for SignatureAlgorithm {
                     Ok(s.to_bytes().to_vec())
                 })
             }
+            // an &[u8] representing the 57 bytes seed of the secret key
+            SignatureAlgorithm::Ed448 => {
+                Box::new(|key: &[u8], message: &[u8]| -> Result<Vec<u8>, Error> {
+                    use ed448_goldilocks_plus::{SigningKey, SECRET_KEY_LENGTH};
+                    let key = SigningKey::try_from(key).map_err(|_| {
+                        Error::InvalidKeySize(format!("ed448 expects key size of {}", SECRET_KEY_LENGTH))
+                    })?;
+                    Ok(key.sign_raw(message).to_bytes().to_vec())
+                })
+            }
             SignatureAlgorithm::Es256 => {
                 Box::new(|key: &[u8], message: &[u8]| -> Result<Vec<u8>, Error> {
                     use p256::ecdsa::{signature::Signer, Signature, SigningKey};
@@ -57,6 +74,19 @@ impl Signer for SignatureAlgorithm {
                     Ok(signature.to_bytes().to_vec())
                 })
             }
+            SignatureAlgorithm::Es256kR => {
+                Box::new(|key: &[u8], message: &[u8]| -> Result<Vec<u8>, Error> {
+                    use k256::ecdsa::SigningKey;
+                    if key.len() != 32 {
+                        return Err(Error::InvalidKeySize("k256 expects key size of 32".into()));
+                    }
+                    let sk = SigningKey::from_slice(key)?;
+                    let (signature, recovery_id) = sk.sign_recoverable(message)?;
+                    let mut signature = signature.to_bytes().to_vec();
+                    signature.push(recovery_id.to_byte());
+                    Ok(signature)
+                })
+            }
         }
     }
 
@@ -81,6 +111,22 @@ impl Signer for SignatureAlgorithm {
                     Ok(key.verify(message, &s).is_ok())
                 },
             ),
+            SignatureAlgorithm::Ed448 => Box::new(
+                |key: &[u8], message: &[u8], signature: &[u8]| -> Result<bool, Error> {
+                    use ed448_goldilocks_plus::{Signature, VerifyingKey, PUBLIC_KEY_LENGTH};
+                    let ed448_key = key.try_into().map_err(|_| {
+                        Error::InvalidKeySize(format!("ed448 expects key size of {}", PUBLIC_KEY_LENGTH))
+                    })?;
+                    let key = VerifyingKey::from_bytes(ed448_key)
+                        .map_err(|e| Error::Generic(format!("invalid ed448 public key; {}", e)))?;
+                    let s = signature
+                        .try_into()
+                        .ok()
+                        .and_then(|signature| Signature::from_bytes(signature).ok())
+                        .ok_or_else(|| Error::Generic("malformed ed448 signature".into()))?;
+                    Ok(key.verify_raw(&s, message).is_ok())
+                },
+            ),
             SignatureAlgorithm::Es256 => Box::new(
                 |key: &[u8], message: &[u8], signature: &[u8]| -> Result<bool, Error> {
                     use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
@@ -97,6 +143,22 @@ impl Signer for SignatureAlgorithm {
                     Ok(vk.verify(message, &signature).is_ok())
                 },
             ),
+            SignatureAlgorithm::Es256kR => Box::new(
+                |key: &[u8], message: &[u8], signature: &[u8]| -> Result<bool, Error> {
+                    use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+                    let vk = VerifyingKey::from_sec1_bytes(key)?;
+                    let (signature, recovery_id) = match signature {
+                        [signature @ .., recovery_id] if signature.len() == 64 => (
+                            Signature::try_from(signature)?,
+                            RecoveryId::from_byte(*recovery_id)
+                                .ok_or_else(|| Error::Generic("invalid recovery id".into()))?,
+                        ),
+                        _ => return Err(Error::Generic("ES256K-R signature must have 65 bytes".into())),
+                    };
+                    let recovered = VerifyingKey::recover_from_msg(message, &signature, recovery_id);
+                    Ok(matches!(recovered, Ok(recovered) if recovered == vk))
+                },
+            ),
         }
     }
 }
@@ -107,13 +169,80 @@ impl TryFrom<&String> for SignatureAlgorithm {
     fn try_from(value: &String) -> Result<Self, Self::Error> {
         match &value[..] {
             "EdDSA" => Ok(Self::EdDsa),
+            "Ed448" => Ok(Self::Ed448),
             "ES256" => Ok(Self::Es256),
             "ES256K" => Ok(Self::Es256k),
+            "ES256K-R" => Ok(Self::Es256kR),
             _ => Err(Error::JwsParseError),
         }
     }
 }
 
+impl SignatureAlgorithm {
+    /// Picks signature algorithm matching type of signing key.
+    /// secp256k1 keys are mapped to non-recoverable `ES256K`.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - typed signing key
+    pub fn for_key(key: &Key) -> Self {
+        match key {
+            Key::Ed25519(_) => SignatureAlgorithm::EdDsa,
+            Key::Ed448(_) => SignatureAlgorithm::Ed448,
+            Key::P256(_) => SignatureAlgorithm::Es256,
+            Key::Secp256k1(_) => SignatureAlgorithm::Es256k,
+        }
+    }
+}
+
+/// Private signing key tagged with its type, so signature algorithm
+///     can be inferred with [`SignatureAlgorithm::for_key`].
+#[derive(Clone)]
+pub enum Key {
+    /// 32 bytes `ed25519` secret key
+    Ed25519(Vec<u8>),
+    /// 57 bytes `ed448` secret key seed
+    Ed448(Vec<u8>),
+    /// 32 bytes `P-256` scalar
+    P256(Vec<u8>),
+    /// 32 bytes `secp256k1` scalar
+    Secp256k1(Vec<u8>),
+}
+
+impl Key {
+    /// Raw bytes of private key, as expected by `SignatureAlgorithm::signer`.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::Ed25519(key) | Key::Ed448(key) | Key::P256(key) | Key::Secp256k1(key) => key,
+        }
+    }
+
+    /// Builds key from private JWK, using its `crv` and `d` values.
+    ///
+    /// # Parameters
+    ///
+    /// * `jwk` - private `OKP` or `EC` JWK
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, Error> {
+        let d = jwk
+            .other
+            .get("d")
+            .and_then(|d| d.as_str())
+            .ok_or_else(|| Error::Generic("JWK is missing private key `d`".into()))?;
+        let d = base64_url::decode(d)?;
+        match (jwk.kty.as_deref(), jwk.crv.as_deref()) {
+            (Some("OKP") | None, Some("Ed25519")) => Ok(Key::Ed25519(d)),
+            (Some("OKP") | None, Some("Ed448")) => Ok(Key::Ed448(d)),
+            (Some("EC") | None, Some("P-256")) => Ok(Key::P256(d)),
+            (Some("EC") | None, Some("secp256k1")) => Ok(Key::Secp256k1(d)),
+            (kty, crv) => Err(Error::Generic(format!(
+                "unsupported signing key type {} with curve {}",
+                kty.unwrap_or("none"),
+                crv.unwrap_or("none"),
+            ))),
+        }
+    }
+}
+
 #[test]
 fn es256k_test() {
     use k256::{ecdsa::SigningKey, elliptic_curve::rand_core::OsRng};
@@ -132,3 +261,51 @@ fn es256k_test() {
     assert!(&validation.is_ok());
     assert!(validation.unwrap());
 }
+
+#[test]
+fn ed448_test() {
+    use ed448_goldilocks_plus::SigningKey;
+    use rand_core::OsRng;
+    // Arrange
+    let sk = SigningKey::generate(&mut OsRng);
+    let vk = sk.verifying_key();
+    let m = b"this is the message we're signing in this test...";
+    // Act
+    let signer = SignatureAlgorithm::Ed448.signer();
+    let validator = SignatureAlgorithm::Ed448.validator();
+    let sk: Vec<u8> = sk.to_bytes().to_vec();
+    let vk = vk.to_bytes().to_vec();
+    let signature = signer(&sk, m).unwrap();
+    // Assert
+    assert_eq!(signature.len(), 114);
+    assert!(validator(&vk, m, &signature).unwrap());
+    assert!(!validator(&vk, b"some other message", &signature).unwrap());
+    assert!(matches!(signer(&sk[..32], m), Err(Error::InvalidKeySize(_))));
+    assert!(matches!(validator(&vk[..32], m, &signature), Err(Error::InvalidKeySize(_))));
+    let alg = SignatureAlgorithm::try_from(&"Ed448".to_string()).unwrap();
+    assert_eq!(alg.signer()(&sk, m).unwrap(), signature);
+}
+
+#[test]
+fn es256k_recoverable_test() {
+    use k256::{ecdsa::SigningKey, elliptic_curve::rand_core::OsRng};
+    // Arrange
+    let sk = SigningKey::random(&mut OsRng);
+    let other = SigningKey::random(&mut OsRng);
+    let m = b"this is the message we're signing in this test...";
+    // Act
+    let signer = SignatureAlgorithm::Es256kR.signer();
+    let validator = SignatureAlgorithm::Es256kR.validator();
+    let signature = signer(&sk.to_bytes(), m).unwrap();
+    let vk = sk.verifying_key().to_sec1_bytes().to_vec();
+    let uncompressed = sk.verifying_key().to_encoded_point(false).as_bytes().to_vec();
+    let other_vk = other.verifying_key().to_sec1_bytes().to_vec();
+    // Assert
+    assert_eq!(signature.len(), 65);
+    assert!(validator(&vk, m, &signature).unwrap());
+    assert!(validator(&uncompressed, m, &signature).unwrap());
+    assert!(!validator(&other_vk, m, &signature).unwrap());
+    assert!(!validator(&vk, b"some other message", &signature).unwrap());
+    assert!(validator(&vk, m, &signature[..64]).is_err());
+    assert!(matches!(signer(&sk.to_bytes()[..31], m), Err(Error::InvalidKeySize(_))));
+}
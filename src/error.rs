@@ -7,10 +7,14 @@ pub enum Error {
     PlugCryptoFailure,
     #[error("not a rotation message")]
     NoRotationData,
+    #[error("invalid from_prior claims; {0}")]
+    InvalidPriorClaims(String),
     #[error("malformed DID string")]
     BadDid,
     #[error("no recipient set for jwe")]
     NoJweRecipient,
+    #[error("flat serialization requires exactly one recipient, {0} given")]
+    FlatSerializationRecipients(usize),
     #[error("not a JWS compact representation")]
     JwsParseError,
     #[error("failed to parse as JWE")]
@@ -45,8 +49,56 @@ pub enum Error {
     SystemTimeError(#[from] std::time::SystemTimeError),
     #[error(transparent)]
     Base64DecodeError(#[from] base64_url::base64::DecodeError),
-    #[error("invalid attachment{0}")]
+    #[error("message with id {0} has already been received")]
+    ReplayedMessage(String),
+    #[error("message from {0} rejected by sender policy")]
+    SenderRejected(String),
+    #[error("from {from} does not match authenticated sender {authenticated}")]
+    SenderMismatch { from: String, authenticated: String },
+    #[error("message with id {0} has expired")]
+    MessageExpired(String),
+    #[error("thread {0} is closed")]
+    ThreadClosed(String),
+    #[error("unsupported JWE compression algorithm {0}")]
+    UnsupportedCompression(String),
+    #[error("decompressed plaintext exceeds limit of {0} bytes")]
+    DecompressedSizeExceeded(usize),
+    #[error("out-of-band URL has {0} bytes, more than allowed {1}")]
+    OutOfBandUrlTooLong(usize, usize),
+    #[error("out-of-band URL has {0} bytes, more than {1} fitting a QR code; use shortened URL")]
+    QrCodeTooLarge(usize, usize),
+    #[error("invalid DIDCommMessaging service; {0}")]
+    InvalidService(String),
+    #[error("transport failure; {0}")]
+    TransportError(String),
+    #[error("invalid attachment; {0}")]
     AttachmentError(String),
+    #[error("message type {0} is not a well-formed PIURI")]
+    InvalidPiuri(String),
+    #[error("envelope typ {0} does not match its content, expected {1}")]
+    EnvelopeTypeMismatch(String, String),
+    #[error("critical header parameter {0} is not supported or missing")]
+    InvalidCriticalHeader(String),
+    #[error("{0} is not allowed by conformance profile")]
+    ProfileViolation(String),
+    #[error("algorithm {alg} is not allowed")]
+    AlgorithmNotAllowed { alg: String },
+    #[error("unsupported key material; {0}")]
+    UnsupportedKeyMaterial(String),
+    #[error("protected header {0} does not match its plaintext copy")]
+    ProtectedHeaderMismatch(String),
+    #[error("no key of {0} in keystore")]
+    KeyNotFound(String),
+    #[error("no handler registered for message type {0}")]
+    UnhandledMessageType(String),
+    #[error("storage failure; {0}")]
+    StorageError(String),
+    #[error("invalid state backup; {0}")]
+    InvalidBackup(String),
+    #[error("invalid mnemonic; {0}")]
+    InvalidMnemonic(String),
+    #[error("invalid key export; {0}")]
+    InvalidKeyExport(String),
     #[error(transparent)]
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
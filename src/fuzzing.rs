@@ -0,0 +1,159 @@
+//! Fuzzing entry points and [`Arbitrary`] implementations.
+//!
+//! Available with `fuzzing` feature only.
+//!
+//! Entry points never return errors - the only thing a fuzzer should be able to observe
+//! is a panic, a hang or a memory issue. Targets for `cargo fuzz` live in `fuzz/`.
+
+use arbitrary::{Arbitrary, Unstructured};
+use serde_json::Value;
+
+use crate::{Attachment, AttachmentData, DidCommHeader, Jwe, JwmHeader, Message, MessageType};
+
+/// Static key used in place of every key `receive` can be given.
+/// Lets fuzzed envelopes reach decryption and signature validation code.
+#[cfg(feature = "raw-crypto")]
+const FUZZ_KEY: [u8; 32] = [7; 32];
+
+/// Feeds raw bytes into [`Message::receive`].
+///
+/// # Arguments
+///
+/// * `data` - bytes provided by fuzzer
+#[cfg(feature = "raw-crypto")]
+pub fn fuzz_receive(data: &[u8]) {
+    if let Ok(incoming) = std::str::from_utf8(data) {
+        let _ = Message::receive(
+            incoming,
+            Some(&FUZZ_KEY),
+            Some(FUZZ_KEY.to_vec()),
+            Some(&FUZZ_KEY),
+        );
+    }
+}
+
+/// Parses raw bytes as [`Jwe`] and touches its header getters and serialization.
+///
+/// # Arguments
+///
+/// * `data` - bytes provided by fuzzer
+pub fn fuzz_parse_jwe(data: &[u8]) {
+    if let Ok(jwe) = serde_json::from_slice::<Jwe>(data) {
+        let _ = jwe.get_alg();
+        let _ = jwe.get_enc();
+        let _ = jwe.get_kid();
+        let _ = jwe.get_skid();
+        let _ = jwe.get_epk();
+        let _ = serde_json::to_string(&jwe);
+    }
+}
+
+impl<'a> Arbitrary<'a> for MessageType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary()? {
+            return Ok(MessageType::Other(u.arbitrary()?));
+        }
+        Ok(u.choose(&[
+            MessageType::DidCommJwe,
+            MessageType::DidCommJws,
+            MessageType::DidCommRaw,
+            MessageType::DidCommForward,
+            MessageType::DidCommInvitation,
+            MessageType::DidCommKeyExport,
+        ])?
+        .clone())
+    }
+}
+
+impl<'a> Arbitrary<'a> for JwmHeader {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(JwmHeader {
+            typ: u.arbitrary()?,
+            enc: u.arbitrary()?,
+            kid: u.arbitrary()?,
+            skid: u.arbitrary()?,
+            alg: u.arbitrary()?,
+            jku: u.arbitrary()?,
+            cty: u.arbitrary()?,
+            ..Default::default()
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AttachmentData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(AttachmentData {
+            jws: u.arbitrary()?,
+            hash: u.arbitrary()?,
+            links: u.arbitrary()?,
+            base64: u.arbitrary()?,
+            json: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Attachment {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Attachment {
+            id: u.arbitrary()?,
+            description: u.arbitrary()?,
+            filename: u.arbitrary()?,
+            media_type: u.arbitrary()?,
+            format: u.arbitrary()?,
+            lastmod_time: u.arbitrary()?,
+            byte_count: u.arbitrary()?,
+            data: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut didcomm_header = DidCommHeader::new();
+        didcomm_header.id = u.arbitrary()?;
+        didcomm_header.thid = u.arbitrary()?;
+        didcomm_header.pthid = u.arbitrary()?;
+        didcomm_header.m_type = u.arbitrary()?;
+        didcomm_header.to = u.arbitrary()?;
+        didcomm_header.from = u.arbitrary()?;
+        didcomm_header.created_time = u.arbitrary()?;
+        didcomm_header.expires_time = u.arbitrary()?;
+        let other: std::collections::HashMap<String, String> = u.arbitrary()?;
+        didcomm_header.other = other
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect();
+        // body is either valid JSON taken from input or wrapped raw string
+        let raw_body: String = u.arbitrary()?;
+        let body = serde_json::from_str(&raw_body).unwrap_or(Value::String(raw_body));
+        Ok(Message {
+            jwm_header: u.arbitrary()?,
+            didcomm_header,
+            body,
+            attachments: u.arbitrary()?,
+            ..Message::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitrary_message_survives_round_trip() {
+        let raw: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&raw);
+        let message = Message::arbitrary(&mut u).expect("failed to create arbitrary message");
+        let serialized = serde_json::to_string(&message).expect("failed to serialize");
+        let _: Message = serde_json::from_str(&serialized).expect("failed to deserialize");
+    }
+
+    #[test]
+    fn entry_points_do_not_fail_on_garbage() {
+        fuzz_parse_jwe(b"{\"ciphertext\":\"\",\"iv\":\"\"}");
+        fuzz_parse_jwe(&[0xff, 0x00, 0x7b]);
+        #[cfg(feature = "raw-crypto")]
+        fuzz_receive(b"{\"id\":\"1\",\"type\":\"x\",\"typ\":\"application/didcomm-plain+json\"}");
+    }
+}
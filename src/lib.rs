@@ -430,7 +430,6 @@ assert!(received_second.is_ok());
 //! [send_receive_didkey_test]: https://github.com/evannetwork/didcomm-rs/blob/master/src/messages/message.rs#L482
 //! [shape_desired_test]: https://github.com/evannetwork/didcomm-rs/blob/main/tests/shape.rs#L21
 //! [signer]: https://github.com/evannetwork/didcomm-rs/blob/master/src/crypto/mod.rs#L39
-extern crate env_logger;
 #[cfg_attr(feature = "raw-crypto", macro_use)]
 extern crate log;
 
@@ -440,8 +439,19 @@ extern crate base64_url;
 #[cfg(feature = "raw-crypto")]
 pub mod crypto;
 mod error;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 mod messages;
+#[cfg(feature = "proptest")]
+pub mod proptest;
 mod result;
+#[cfg(feature = "json-schema")]
+pub mod schema;
+pub mod storage;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(any(feature = "http", feature = "ws"))]
+pub mod transport;
 
 pub use error::*;
 pub use messages::*;
@@ -0,0 +1,655 @@
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    crypto::KeyRole,
+    messages::helpers::AgreementKey,
+    AsyncDidResolver,
+    Channel,
+    DidCommMessagingService,
+    DidResolver,
+    EnvelopeKind,
+    Error,
+    Jwe,
+    Jws,
+    KeyStore,
+    Message,
+    PackResult,
+    Packer,
+    Piuri,
+    PrefetchedDidResolver,
+    ReceiveOptions,
+    Result,
+    Transport,
+};
+
+/// Trait must be implemented for handlers of protocols registered with [`Agent::register`].
+/// Implemented for closures `Fn(&Message) -> Result<Option<Message>>`.
+pub trait ProtocolHandler: Send + Sync {
+    /// Handles received message of the protocol.
+    /// Returns reply to be sent back to the sender, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - unpacked message
+    fn handle(&self, message: &Message) -> Result<Option<Message>>;
+}
+
+impl<F> ProtocolHandler for F
+where
+    F: Fn(&Message) -> Result<Option<Message>> + Send + Sync,
+{
+    fn handle(&self, message: &Message) -> Result<Option<Message>> {
+        self(message)
+    }
+}
+
+/// Entry point for messaging as one of our DIDs.
+/// Our keys are taken from [`KeyStore`], keys and services of other parties are
+///     resolved with [`DidResolver`], so messages can be packed to and unpacked from
+///     any resolvable DID without handling keys at call sites.
+/// Received messages are dispatched to handlers of registered protocols.
+#[derive(Clone)]
+pub struct Agent {
+    did: String,
+    keys: Arc<dyn KeyStore>,
+    resolver: Arc<dyn DidResolver>,
+    transports: Vec<Arc<dyn Transport>>,
+    packer: Packer,
+    receive_options: ReceiveOptions,
+    protocols: Vec<(Piuri, Arc<dyn ProtocolHandler>)>,
+}
+
+impl Agent {
+    /// Constructor of agent without transports and protocols.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - our DID, its key agreement key has to be in `keys`
+    ///
+    /// * `keys` - storage of our private keys; agents of different tenants sharing
+    ///   a store should get a [`TenantKeyStore`][crate::TenantKeyStore] each
+    ///
+    /// * `resolver` - resolver of DIDs of other parties and their mediators
+    pub fn new(did: &str, keys: Arc<dyn KeyStore>, resolver: Arc<dyn DidResolver>) -> Self {
+        Self {
+            did: did.into(),
+            keys,
+            resolver,
+            transports: vec![],
+            packer: Packer::new(),
+            receive_options: ReceiveOptions::new(),
+            protocols: vec![],
+        }
+    }
+
+    /// Appends transport used by `send_to`.
+    /// Transports are tried in order they were added.
+    ///
+    /// # Parameters
+    ///
+    /// * `transport` - transport, e.g. `HttpTransport`
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transports.push(transport);
+        self
+    }
+
+    /// Replaces packer run on sent messages before packing.
+    ///
+    /// # Parameters
+    ///
+    /// * `packer` - preconfigured packer
+    pub fn with_packer(mut self, packer: Packer) -> Self {
+        self.packer = packer;
+        self
+    }
+
+    /// Replaces options applied to received messages.
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - preconfigured options
+    pub fn with_receive_options(mut self, options: ReceiveOptions) -> Self {
+        self.receive_options = options;
+        self
+    }
+
+    /// Registers handler of protocol.
+    /// Messages of same protocol with same major version are dispatched to it.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidPiuri` if `protocol` is not a protocol URI.
+    ///
+    /// # Parameters
+    ///
+    /// * `protocol` - protocol URI without message type name,
+    ///   e.g. `https://didcomm.org/trust-ping/2.0`
+    ///
+    /// * `handler` - handler of messages of the protocol
+    pub fn register(mut self, protocol: &str, handler: Arc<dyn ProtocolHandler>) -> Result<Self> {
+        let piuri = Piuri::from_str(&format!("{}/_", protocol.trim_end_matches('/')))
+            .map_err(|_| Error::InvalidPiuri(protocol.into()))?;
+        self.protocols.push((piuri, handler));
+        Ok(self)
+    }
+
+    /// Our DID.
+    pub fn did(&self) -> &str {
+        &self.did
+    }
+
+    /// Builds [`Channel`] to given DID with keys and first service resolved.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID of the other party
+    pub fn channel(&self, did: &str) -> Result<Channel> {
+        self.resolve_channel(did).map(|(channel, _)| channel)
+    }
+
+    /// Packs message from our DID to given DID, forwarded through mediators of its service.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID of recipient
+    ///
+    /// * `message` - message to send; `from` and `to` are replaced
+    pub fn pack_to(&self, did: &str, message: Message) -> Result<PackResult> {
+        self.channel(did)?.send(message)
+    }
+
+    /// Packs message like `pack_to` and delivers it to first service of given DID
+    ///     with first transport supporting it.
+    /// Returns message routed back in response, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID of recipient
+    ///
+    /// * `message` - message to send; `from` and `to` are replaced
+    pub fn send_to(&self, did: &str, message: Message) -> Result<Option<String>> {
+        let (channel, service) = self.resolve_channel(did)?;
+        let service = service.ok_or_else(|| {
+            Error::InvalidService(format!("{} has no DIDCommMessaging service", did))
+        })?;
+        let packed = channel.send(message)?;
+        let mut last_error = None;
+        for transport in &self.transports {
+            match transport.deliver(&service, &packed.envelope) {
+                Err(e @ Error::InvalidService(_)) => last_error = Some(e),
+                result => return result,
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            Error::TransportError(format!("no transport for endpoint `{}`", service.uri))
+        }))
+    }
+
+    /// Unpacks received envelope with our key it is encrypted for
+    ///     and keys of sender resolved from `skid` or `kid`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::KeyNotFound` if none of our keys is a recipient of envelope.
+    ///
+    /// `Error::SenderMismatch` if `from` of message is not the DID of `skid` or `kid`.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - received JWE, JWS or plaintext message
+    pub fn unpack(&self, envelope: &[u8]) -> Result<Message> {
+        self.unpack_authenticated(envelope)
+            .map(|(message, _)| message)
+    }
+
+    /// Implementation of `unpack` also returning DID of sender authenticated
+    ///     by `skid` or `kid` of envelope, if any.
+    fn unpack_authenticated(&self, envelope: &[u8]) -> Result<(Message, Option<String>)> {
+        let envelope = std::str::from_utf8(envelope)?;
+        let mut recipient_key = None;
+        let mut sender_key = None;
+        let mut sender_did = None;
+        match EnvelopeKind::detect(envelope) {
+            EnvelopeKind::Jwe => {
+                let jwe: Jwe = envelope.parse()?;
+                let kids: Vec<String> = jwe
+                    .get_recipients()
+                    .iter()
+                    .filter_map(|recipient| recipient.header.kid.clone())
+                    .collect();
+                let (_, key) = kids
+                    .iter()
+                    .find_map(|kid| self.keys.find(kid, KeyRole::KeyAgreement))
+                    .ok_or_else(|| Error::KeyNotFound(kids.join(", ")))?;
+                recipient_key = Some(key.agreement_key()?.to_vec());
+                if let Some(skid) = jwe.get_skid().filter(|skid| !skid.is_empty()) {
+                    let sender = self.resolver.resolve(did_of(&skid))?;
+                    let key = sender
+                        .key_agreement_key(Some(&skid))
+                        .ok_or(Error::DidResolveFailed)?;
+                    sender_key = Some(key.agreement_key()?.to_vec());
+                    sender_did = Some(sender.did);
+                }
+            }
+            EnvelopeKind::Jws => {
+                let jws: Jws = envelope.parse()?;
+                sender_did = jws
+                    .get_signatures()
+                    .iter()
+                    .find_map(|signature| signature.get_kid())
+                    .map(|kid| did_of(&kid).to_string());
+            }
+            _ => {}
+        }
+        let authentication = match &sender_did {
+            Some(did) => self.resolver.resolve(did)?.authentication,
+            None => vec![],
+        };
+        let candidates: Vec<&[u8]> = authentication
+            .iter()
+            .map(|(_, key)| key.bytes.as_slice())
+            .collect();
+        let mut options = self.receive_options.clone();
+        if options.did_resolver.is_none() {
+            options.did_resolver = Some(self.resolver.clone());
+        }
+        Message::receive_audited(
+            envelope,
+            recipient_key.as_deref().map(AgreementKey::Raw),
+            sender_key,
+            &candidates,
+            &options,
+        )
+        .map(|(message, _, sender)| (message, sender.authenticated))
+    }
+
+    /// Async variant of [`Agent::unpack`] resolving DIDs with given resolver
+    ///     instead of the one of agent, e.g. with `JsDidResolver` on wasm.
+    /// Envelope is unpacked again after each DID resolved, until none is missing.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - received JWE, JWS or plaintext message
+    ///
+    /// * `resolver` - resolver of DIDs of sender
+    pub async fn receive_async(
+        &self,
+        envelope: &[u8],
+        resolver: &dyn AsyncDidResolver,
+    ) -> Result<Message> {
+        self.with_async_resolver(resolver, |agent| agent.unpack(envelope))
+            .await
+    }
+
+    /// Async variant of [`Agent::pack_to`] resolving DIDs with given resolver
+    ///     instead of the one of agent, e.g. with `JsDidResolver` on wasm.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID of recipient
+    ///
+    /// * `message` - message to send; `from` and `to` are replaced
+    ///
+    /// * `resolver` - resolver of DIDs of recipient and its mediators
+    pub async fn pack_async(
+        &self,
+        did: &str,
+        message: Message,
+        resolver: &dyn AsyncDidResolver,
+    ) -> Result<PackResult> {
+        self.with_async_resolver(resolver, |agent| agent.pack_to(did, message.clone()))
+            .await
+    }
+
+    /// Runs `op` with agent resolving only DIDs already resolved by `resolver`,
+    ///     until it no longer fails on a missing one.
+    async fn with_async_resolver<T>(
+        &self,
+        resolver: &dyn AsyncDidResolver,
+        op: impl Fn(&Agent) -> Result<T>,
+    ) -> Result<T> {
+        let prefetched = Arc::new(PrefetchedDidResolver::default());
+        let agent = Self {
+            resolver: prefetched.clone(),
+            ..self.clone()
+        };
+        loop {
+            match op(&agent) {
+                Err(Error::DidResolveFailed) => {
+                    if !prefetched.resolve_missing(resolver).await? {
+                        return Err(Error::DidResolveFailed);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Passes message to handler of its protocol.
+    /// Returns reply of handler, if any.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnhandledMessageType` if no handler is registered for protocol of message.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - unpacked message
+    pub fn dispatch(&self, message: &Message) -> Result<Option<Message>> {
+        let m_type = &message.get_didcomm_header().m_type;
+        let piuri = Piuri::from_str(m_type)?;
+        let (_, handler) = self
+            .protocols
+            .iter()
+            .find(|(protocol, _)| {
+                protocol.doc_uri == piuri.doc_uri
+                    && protocol.protocol_name == piuri.protocol_name
+                    && major_version(&protocol.version) == major_version(&piuri.version)
+            })
+            .ok_or_else(|| Error::UnhandledMessageType(m_type.clone()))?;
+        handler.handle(message)
+    }
+
+    /// Unpacks envelope, dispatches it and packs reply of handler to the sender,
+    ///     as authenticated by `skid` or `kid` of envelope.
+    ///
+    /// # Errors
+    ///
+    /// `Error::PropertyIsNotSet` if handler replies to message of unauthenticated sender.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - received JWE, JWS or plaintext message
+    pub fn handle(&self, envelope: &[u8]) -> Result<Option<PackResult>> {
+        let (message, sender) = self.unpack_authenticated(envelope)?;
+        let reply = match self.dispatch(&message)? {
+            Some(reply) => reply,
+            None => return Ok(None),
+        };
+        let sender = sender.ok_or(Error::PropertyIsNotSet("authenticated sender"))?;
+        self.channel(&sender)?.reply(&message, reply).map(Some)
+    }
+
+    /// Resolves keys and first service of given DID into channel.
+    fn resolve_channel(&self, did: &str) -> Result<(Channel, Option<DidCommMessagingService>)> {
+        let (_, our_key) = self
+            .keys
+            .find(&self.did, KeyRole::KeyAgreement)
+            .ok_or_else(|| Error::KeyNotFound(self.did.clone()))?;
+        let resolved = self.resolver.resolve(did_of(did))?;
+        let their_key = resolved
+            .key_agreement_key(Some(did))
+            .ok_or(Error::DidResolveFailed)?;
+        let mut channel = Channel::new(
+            &self.did,
+            our_key.agreement_key()?,
+            did,
+            their_key.agreement_key()?,
+        )
+        .with_packer(self.packer.clone())
+        .with_receive_options(self.receive_options.clone());
+        let service = resolved.services.first().cloned();
+        if let Some(service) = &service {
+            let routing_public_keys = service
+                .routing_keys
+                .iter()
+                .map(|kid| {
+                    let mediator = self.resolver.resolve(did_of(kid))?;
+                    let key = mediator
+                        .key_agreement_key(Some(kid))
+                        .ok_or(Error::DidResolveFailed)?;
+                    Ok(Some(key.agreement_key()?.to_vec()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            channel = channel.with_service(service.clone(), Some(routing_public_keys));
+        }
+        Ok((channel, service))
+    }
+}
+
+/// DID of DID URL, e.g. key id.
+fn did_of(did_url: &str) -> &str {
+    did_url.split('#').next().unwrap_or_default()
+}
+
+/// Major version of `major.minor` semantic version.
+fn major_version(version: &str) -> &str {
+    version.split('.').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{
+        crypto::{Curve, KeyMaterial, SignatureAlgorithm, Signer},
+        x25519_did_key,
+        InMemoryDidResolver,
+        InMemoryKeyStore,
+        Mediated,
+        ResolvedDid,
+    };
+
+    fn x25519(key: &[u8], private: bool) -> KeyMaterial {
+        KeyMaterial {
+            curve: Curve::X25519,
+            role: KeyRole::KeyAgreement,
+            private,
+            bytes: key.to_vec(),
+        }
+    }
+
+    #[test]
+    fn agents_pack_unpack_and_dispatch_via_resolved_dids() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let mediator = x25519_did_key(&mediators_public);
+        let resolver = Arc::new(InMemoryDidResolver::new());
+        resolver.insert(
+            ResolvedDid::new("did:example:alice")
+                .with_key("did:example:alice#key-1", x25519(&alice_public, false)),
+        );
+        resolver.insert(
+            ResolvedDid::new("did:example:bob")
+                .with_key("did:example:bob#key-1", x25519(&bobs_public, false))
+                .with_service(
+                    DidCommMessagingService::new("https://mediator.example")
+                        .with_routing_keys(&[&mediator]),
+                ),
+        );
+        let alice_keys = Arc::new(InMemoryKeyStore::new());
+        alice_keys.put("did:example:alice#key-1", x25519(&alice_private, true));
+        let bob_keys = Arc::new(InMemoryKeyStore::new());
+        bob_keys.put("did:example:bob#key-1", x25519(&bobs_private, true));
+
+        let bob = Agent::new("did:example:bob", bob_keys, resolver.clone()).register(
+            "https://didcomm.org/trust-ping/2.0",
+            Arc::new(|_: &Message| {
+                Ok(Some(Message::new().m_type(
+                    "https://didcomm.org/trust-ping/2.0/ping-response",
+                )))
+            }),
+        )?;
+        // mediator unwraps forward and returns reply of bob in response
+        let transport = move |service: &DidCommMessagingService, envelope: &str| {
+            assert_eq!(service.uri, "https://mediator.example");
+            let forward = Message::receive(envelope, Some(&mediators_private), None, None)?;
+            let mediated: Mediated = serde_json::from_str(&forward.get_body()?)?;
+            Ok(bob.handle(&mediated.payload)?.map(|reply| reply.envelope))
+        };
+        let alice = Agent::new("did:example:alice", alice_keys, resolver)
+            .with_transport(Arc::new(transport));
+
+        let ping = Message::new().m_type("https://didcomm.org/trust-ping/2.1/ping");
+        let ping_id = ping.get_id().to_string();
+        let response = alice
+            .send_to("did:example:bob", ping)?
+            .unwrap_or_default();
+        let pong = alice.unpack(response.as_bytes())?;
+        let header = pong.get_didcomm_header();
+        assert_eq!(
+            header.m_type,
+            "https://didcomm.org/trust-ping/2.0/ping-response"
+        );
+        assert_eq!(header.thid.as_deref(), Some(ping_id.as_str()));
+        assert_eq!(header.from.as_deref(), Some("did:example:bob"));
+
+        assert!(matches!(
+            alice.dispatch(&pong),
+            Err(Error::UnhandledMessageType(_))
+        ));
+        assert!(matches!(
+            alice.pack_to("did:example:carol", Message::new()),
+            Err(Error::DidResolveFailed)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn agents_reply_only_to_authenticated_senders() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let alice_signing = ed25519_dalek::SigningKey::generate(&mut rand_core::OsRng);
+        let resolver = Arc::new(InMemoryDidResolver::new());
+        resolver.insert(
+            ResolvedDid::new("did:example:alice")
+                .with_key("did:example:alice#key-1", x25519(&alice_public, false))
+                .with_key(
+                    "did:example:alice#key-2",
+                    KeyMaterial {
+                        curve: Curve::Ed25519,
+                        role: KeyRole::Signing,
+                        private: false,
+                        bytes: alice_signing.verifying_key().to_bytes().to_vec(),
+                    },
+                ),
+        );
+        let bob_keys = Arc::new(InMemoryKeyStore::new());
+        bob_keys.put("did:example:bob#key-1", x25519(&bobs_private, true));
+        resolver.insert(
+            ResolvedDid::new("did:example:bob")
+                .with_key("did:example:bob#key-1", x25519(&bobs_public, false)),
+        );
+        let bob = Agent::new("did:example:bob", bob_keys, resolver).register(
+            "https://didcomm.org/trust-ping/2.0",
+            Arc::new(|_: &Message| {
+                Ok(Some(Message::new().m_type(
+                    "https://didcomm.org/trust-ping/2.0/ping-response",
+                )))
+            }),
+        )?;
+        let ping = |from: &str| {
+            Message::new()
+                .from(from)
+                .to(&["did:example:bob"])
+                .m_type("https://didcomm.org/trust-ping/2.0/ping")
+        };
+        let signed = |from: &str| {
+            ping(from)
+                .as_flat_jws(&SignatureAlgorithm::EdDsa)
+                .kid("did:example:alice#key-2")
+                .sign(
+                    SignatureAlgorithm::EdDsa.signer(),
+                    &alice_signing.to_bytes(),
+                )
+        };
+
+        assert!(bob
+            .handle(signed("did:example:alice")?.as_bytes())?
+            .is_some());
+        assert!(matches!(
+            bob.unpack(signed("did:example:carol")?.as_bytes()),
+            Err(Error::SenderMismatch { authenticated, .. })
+                if authenticated == "did:example:alice"
+        ));
+        let unsigned = ping("did:example:alice").as_raw_json()?;
+        assert!(bob.unpack(unsigned.as_bytes()).is_ok());
+        assert!(matches!(
+            bob.handle(unsigned.as_bytes()),
+            Err(Error::PropertyIsNotSet(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn agents_resolve_dids_asynchronously() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let alice_keys = Arc::new(InMemoryKeyStore::new());
+        alice_keys.put("did:example:alice#key-1", x25519(&alice_private, true));
+        let bob_keys = Arc::new(InMemoryKeyStore::new());
+        bob_keys.put("did:example:bob#key-1", x25519(&bobs_private, true));
+        // resolvers of agents know no DIDs, all are resolved asynchronously
+        let alice = Agent::new(
+            "did:example:alice",
+            alice_keys,
+            Arc::new(InMemoryDidResolver::new()),
+        );
+        let bob = Agent::new(
+            "did:example:bob",
+            bob_keys,
+            Arc::new(InMemoryDidResolver::new()),
+        );
+        let resolve = move |did: String| {
+            let resolved = match did.as_str() {
+                "did:example:alice" => Ok(ResolvedDid::new(&did)
+                    .with_key("did:example:alice#key-1", x25519(&alice_public, false))),
+                "did:example:bob" => Ok(ResolvedDid::new(&did)
+                    .with_key("did:example:bob#key-1", x25519(&bobs_public, false))),
+                _ => Err(Error::DidResolveFailed),
+            };
+            std::future::ready(resolved)
+        };
+
+        assert!(matches!(
+            alice.pack_to("did:example:bob", Message::new()),
+            Err(Error::DidResolveFailed)
+        ));
+        let message = Message::new().m_type("https://didcomm.org/basicmessage/2.0/message");
+        let packed = block_on(alice.pack_async("did:example:bob", message, &resolve))?;
+        let received = block_on(bob.receive_async(packed.envelope.as_bytes(), &resolve))?;
+        assert_eq!(
+            received.get_didcomm_header().from.as_deref(),
+            Some("did:example:alice")
+        );
+        assert!(matches!(
+            block_on(alice.pack_async("did:example:carol", Message::new(), &resolve)),
+            Err(Error::DidResolveFailed)
+        ));
+        Ok(())
+    }
+
+    /// Polls future to completion on current thread, enough for futures not waiting on IO.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct NoopWaker;
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+}
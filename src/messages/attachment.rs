@@ -1,12 +1,13 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, path::Path};
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{Error, Message, Result};
 
 /// Attachment holding structure
 ///
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Attachment {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
@@ -25,8 +26,34 @@ pub struct Attachment {
     pub data: AttachmentData,
 }
 
+impl Attachment {
+    /// Returns content of attachment as bytes.
+    /// Decodes `base64` data or takes `json` data as is.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if data is only referenced by `links` or `jws`.
+    ///
+    pub fn decoded_content(&self) -> Result<Vec<u8>> {
+        if let Some(base64) = &self.data.base64 {
+            return Ok(base64_url::decode(base64)?);
+        }
+        if let Some(json) = &self.data.json {
+            return Ok(json.as_bytes().to_vec());
+        }
+        Err(Error::AttachmentError("has no inline content".into()))
+    }
+
+    /// Deserializes content of attachment into `T`.
+    /// Works for both `json` and base64 encoded JSON data.
+    pub fn content_as<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.decoded_content()?)?)
+    }
+}
+
 /// Attachment Data holding structure
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct AttachmentData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub jws: Option<String>,
@@ -150,9 +177,9 @@ impl AttachmentBuilder {
     /// # Parameters
     ///
     /// * `included_mod_time` - `bool` value indicating
-    /// if this attachment should be timestamped on attaching.
-    /// If `true` - will update `lastmod_time` property on
-    /// builder consumption.
+    ///   if this attachment should be timestamped on attaching.
+    ///   If `true` - will update `lastmod_time` property on
+    ///   builder consumption, with `chrono` or `time` feature only.
     ///
     pub fn new(include_mod_time: bool) -> Self {
         Self {
@@ -161,6 +188,41 @@ impl AttachmentBuilder {
         }
     }
 
+    /// Builder of attachment embedding content of file at `path`.
+    /// `filename`, `media_type` (guessed from extension), `byte_count` and,
+    ///     with `chrono` or `time` feature, `lastmod_time` are taken from the file
+    ///     and can be overridden by other setters afterwards.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if file can not be read.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - path of file to be attached
+    ///
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let io_error =
+            |e: std::io::Error| Error::AttachmentError(format!("file {}; {}", path.display(), e));
+        let content = std::fs::read(path).map_err(io_error)?;
+        let mut builder = Self::new(false)
+            .with_media_type(mime_guess::from_path(path).first_or_octet_stream().as_ref())
+            .external_size(content.len())
+            .with_data(AttachmentDataBuilder::new().with_raw_payload(content));
+        #[cfg(any(feature = "chrono", feature = "time"))]
+        {
+            let modified = std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(io_error)?;
+            builder = builder.with_lastmod_time(&lastmod_time(modified));
+        }
+        if let Some(filename) = path.file_name() {
+            builder = builder.with_filename(&filename.to_string_lossy());
+        }
+        Ok(builder)
+    }
+
     /// Optional, but recommended identifier of attachment content.
     ///
     /// # Parameters
@@ -228,6 +290,19 @@ impl AttachmentBuilder {
         self
     }
 
+    /// Last modification time of attached content, e.g. of attached file.
+    /// Not updated on attaching, even if builder was created as timestamped.
+    ///
+    /// # Parameters
+    ///
+    /// * `lastmod_time` - time of last modification
+    ///
+    pub fn with_lastmod_time(mut self, lastmod_time: &str) -> Self {
+        self.inner.lastmod_time = Some(lastmod_time.into());
+        self.timed = false;
+        self
+    }
+
     /// Attach actual payload in form of `AttachmentData`
     /// Consumes `AttachmentDataBuilder` to do so.
     ///
@@ -241,17 +316,48 @@ impl AttachmentBuilder {
     }
 
     fn timestamp(&mut self) {
+        #[cfg(any(feature = "chrono", feature = "time"))]
         if self.timed {
-            self.inner.lastmod_time = Some(chrono::Utc::now().to_string());
+            self.inner.lastmod_time = Some(lastmod_time(std::time::SystemTime::now()));
         }
     }
 
-    fn finalize(mut self) -> Attachment {
+    /// Builder around already constructed attachment, which is appended unchanged.
+    pub(crate) fn from_attachment(attachment: Attachment) -> Self {
+        Self {
+            inner: attachment,
+            timed: false,
+        }
+    }
+
+    pub(crate) fn finalize(mut self) -> Attachment {
         self.timestamp();
         self.inner
     }
 }
 
+/// `lastmod_time` value of given point in time.
+#[cfg(feature = "chrono")]
+fn lastmod_time(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_string()
+}
+
+/// `lastmod_time` value of given point in time, formatted like with `chrono` feature.
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+fn lastmod_time(time: std::time::SystemTime) -> String {
+    let time = time::OffsetDateTime::from(time);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}.{:09} UTC",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.nanosecond()
+    )
+}
+
 impl<T> TryFrom<(&str, T)> for AttachmentBuilder
 where
     T: Serialize,
@@ -284,6 +390,48 @@ impl Message {
         self.attachments.iter()
     }
 
+    /// Returns attachment with given `id`, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - identifier of the attachment
+    ///
+    pub fn attachment_by_id(&self, id: &str) -> Option<&Attachment> {
+        self.attachments
+            .iter()
+            .find(|attachment| attachment.id.as_deref() == Some(id))
+    }
+
+    /// Returns iterator of attachments with given media (MIME) type.
+    ///
+    /// # Parameters
+    ///
+    /// * `media_type` - media type to filter by, e.g. `application/json`
+    ///
+    pub fn attachments_with_media_type<'a>(
+        &'a self,
+        media_type: &'a str,
+    ) -> impl DoubleEndedIterator<Item = &'a Attachment> {
+        self.attachments
+            .iter()
+            .filter(move |attachment| attachment.media_type.as_deref() == Some(media_type))
+    }
+
+    /// Returns iterator of attachments with given format.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - format identifier to filter by
+    ///
+    pub fn attachments_with_format<'a>(
+        &'a self,
+        format: &'a str,
+    ) -> impl DoubleEndedIterator<Item = &'a Attachment> {
+        self.attachments
+            .iter()
+            .filter(move |attachment| attachment.format.as_deref() == Some(format))
+    }
+
     /// Deserializes a the attachements with media-type `fmt` into `Vec<T>`.
     ///
     /// # Error:
@@ -352,6 +500,96 @@ mod tests {
         assert_eq!(data.len(), 1)
     }
 
+    #[test]
+    fn query_attachments_by_id_media_type_and_format() {
+        let mut message = Message::new();
+        message.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id("json")
+                .with_media_type("application/json")
+                .with_format("example/data@v1.0")
+                .with_data(AttachmentDataBuilder::new().with_json(r#"{"answer":42}"#)),
+        );
+        message.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id("raw")
+                .with_media_type("application/octet-stream")
+                .with_data(AttachmentDataBuilder::new().with_raw_payload(b"raw bytes")),
+        );
+
+        let json = message.attachment_by_id("json").expect("missing attachment");
+        let value: serde_json::Value = json.content_as().expect("failed to decode");
+        assert_eq!(value["answer"], 42);
+        let raw = message.attachment_by_id("raw").expect("missing attachment");
+        assert_eq!(raw.decoded_content().unwrap(), b"raw bytes");
+        assert!(message.attachment_by_id("missing").is_none());
+        assert_eq!(
+            message
+                .attachments_with_media_type("application/octet-stream")
+                .count(),
+            1
+        );
+        assert_eq!(message.attachments_with_format("example/data@v1.0").count(), 1);
+        assert_eq!(message.attachments_with_format("other").count(), 0);
+    }
+
+    #[test]
+    fn link_only_attachment_has_no_inline_content() {
+        let attachment = Attachment {
+            data: AttachmentData {
+                links: vec!["https://example.com/file".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            attachment.decoded_content(),
+            Err(Error::AttachmentError(_))
+        ));
+    }
+
+    #[test]
+    fn timed_attachments_are_timestamped() {
+        let timed = AttachmentBuilder::new(true).finalize();
+        let untimed = AttachmentBuilder::new(false).finalize();
+        assert_eq!(
+            timed.lastmod_time.is_some(),
+            cfg!(any(feature = "chrono", feature = "time"))
+        );
+        assert_eq!(untimed.lastmod_time, None);
+    }
+
+    #[test]
+    fn file_metadata_is_populated_from_path() {
+        let path = std::env::temp_dir().join(format!("didcomm-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"%PDF-1.7").unwrap();
+        let filename = path.file_name().unwrap().to_string_lossy().to_string();
+
+        let attachment = AttachmentBuilder::from_path(&path).unwrap().finalize();
+        let overridden = AttachmentBuilder::from_path(&path)
+            .unwrap()
+            .with_filename("report.pdf")
+            .with_media_type("application/x-pdf")
+            .finalize();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(attachment.filename.as_deref(), Some(filename.as_str()));
+        assert_eq!(attachment.media_type.as_deref(), Some("application/pdf"));
+        assert_eq!(attachment.byte_count, Some(8));
+        assert_eq!(
+            attachment.lastmod_time.is_some(),
+            cfg!(any(feature = "chrono", feature = "time"))
+        );
+        assert_eq!(attachment.decoded_content().unwrap(), b"%PDF-1.7");
+        assert_eq!(overridden.filename.as_deref(), Some("report.pdf"));
+        assert_eq!(overridden.media_type.as_deref(), Some("application/x-pdf"));
+        assert_eq!(overridden.lastmod_time, attachment.lastmod_time);
+        assert!(matches!(
+            AttachmentBuilder::from_path(&path),
+            Err(Error::AttachmentError(_))
+        ));
+    }
+
     #[test]
     #[should_panic(expected = "unsupported media type")]
     fn cannot_deserialize_attachments_with_invalid_format() {
@@ -0,0 +1,334 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Attachment, Error, Message, PackMiddleware, ReceiveMiddleware, Result, SenderInfo};
+
+/// Multihash prefix of SHA2-256 digests: function code `0x12`, digest length `0x20`.
+const SHA2_256_MULTIHASH_PREFIX: [u8; 2] = [0x12, 0x20];
+
+/// Computes `hash` attachment data value of given content:
+///     SHA2-256 multihash, BASE64URL encoded.
+///
+/// # Parameters
+///
+/// * `content` - raw (decoded) attachment content
+pub fn attachment_hash(content: &[u8]) -> String {
+    let mut multihash = SHA2_256_MULTIHASH_PREFIX.to_vec();
+    multihash.extend(Sha256::digest(content).as_slice());
+    base64_url::encode(&multihash)
+}
+
+/// Default limit of content kept by [`InMemoryAttachmentCache`], 16 MiB.
+pub const DEFAULT_ATTACHMENT_CACHE_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Trait must be implemented for pluggable attachment content storage,
+///     see [`AttachmentDeduplicator`] for using it.
+/// Content is scoped by thread, so it is only resolved for messages of the thread
+///     it was sent or received in.
+pub trait AttachmentCache: Send + Sync {
+    /// Gets content with given hash cached for given thread.
+    fn get(&self, thid: &str, hash: &str) -> Option<Vec<u8>>;
+
+    /// Inserts content under its hash for given thread.
+    fn put(&self, thid: &str, hash: &str, content: Vec<u8>);
+
+    /// Records that content with given hash was delivered inline in given thread.
+    fn mark_sent(&self, thid: &str, hash: &str);
+
+    /// Checks if content with given hash was delivered inline in given thread before.
+    fn was_sent(&self, thid: &str, hash: &str) -> bool;
+}
+
+/// In-memory [`AttachmentCache`], content is lost on drop.
+/// Keeps at most `capacity` bytes of content, evicting oldest entries first
+///     together with their sent records.
+pub struct InMemoryAttachmentCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    contents: HashMap<(String, String), Vec<u8>>,
+    sent: HashSet<(String, String)>,
+    /// keys of `contents`, oldest first
+    order: VecDeque<(String, String)>,
+    size: usize,
+}
+
+impl InMemoryAttachmentCache {
+    /// Constructor of empty cache with capacity of `DEFAULT_ATTACHMENT_CACHE_CAPACITY`.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_ATTACHMENT_CACHE_CAPACITY)
+    }
+
+    /// Constructor of empty cache.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity` - maximal size of cached content in bytes;
+    ///   larger content is not cached at all
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Default for InMemoryAttachmentCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttachmentCache for InMemoryAttachmentCache {
+    fn get(&self, thid: &str, hash: &str) -> Option<Vec<u8>> {
+        self.state()
+            .contents
+            .get(&(thid.into(), hash.into()))
+            .cloned()
+    }
+
+    fn put(&self, thid: &str, hash: &str, content: Vec<u8>) {
+        if content.len() > self.capacity {
+            return;
+        }
+        let key = (thid.to_string(), hash.to_string());
+        let mut state = self.state();
+        if let Some(previous) = state.contents.remove(&key) {
+            state.size -= previous.len();
+            state.order.retain(|cached| cached != &key);
+        }
+        state.size += content.len();
+        state.contents.insert(key.clone(), content);
+        state.order.push_back(key);
+        while state.size > self.capacity {
+            let evicted = match state.order.pop_front() {
+                Some(evicted) => evicted,
+                None => break,
+            };
+            if let Some(content) = state.contents.remove(&evicted) {
+                state.size -= content.len();
+            }
+            state.sent.remove(&evicted);
+        }
+    }
+
+    fn mark_sent(&self, thid: &str, hash: &str) {
+        let key = (thid.to_string(), hash.to_string());
+        let mut state = self.state();
+        // evicted content has to be sent inline again
+        if state.contents.contains_key(&key) {
+            state.sent.insert(key);
+        }
+    }
+
+    fn was_sent(&self, thid: &str, hash: &str) -> bool {
+        self.state().sent.contains(&(thid.into(), hash.into()))
+    }
+}
+
+/// Avoids re-embedding same attachment content within a thread.
+/// Added to [`Packer`][crate::Packer] it sets `hash` of inline `base64` attachments
+///     and, if same content was already delivered in the thread (`thid` or `id`),
+///     drops `base64` so only `hash` and `links` reference it.
+/// Content counts as delivered once `mark_delivered` is called for the message
+///     containing it, so content of lost messages is embedded again.
+/// Added to [`ReceivePipeline`][crate::ReceivePipeline] it caches received inline
+///     content by thread and hash and fills `base64` of attachments referencing cached content.
+/// Received inline content not matching its `hash` rejects the message,
+///     as do attachments referencing content only by `hash` which is not cached for the thread.
+#[derive(Clone)]
+pub struct AttachmentDeduplicator {
+    cache: Arc<dyn AttachmentCache>,
+}
+
+impl AttachmentDeduplicator {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `cache` - storage shared between packing and receiving
+    pub fn new(cache: Arc<dyn AttachmentCache>) -> Self {
+        Self { cache }
+    }
+
+    /// Records inline attachments of message as delivered in its thread,
+    ///     so later messages of the thread reference them by `hash` only.
+    /// To be called once transport delivered the message.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message prepared by packer with this deduplicator
+    pub fn mark_delivered(&self, message: &Message) {
+        let thid = thread_of(message);
+        for attachment in &message.attachments {
+            if let (Some(_), Some(hash)) = (&attachment.data.base64, &attachment.data.hash) {
+                self.cache.mark_sent(&thid, hash);
+            }
+        }
+    }
+
+    /// Fills `base64` of attachment from cache if it only references content by `hash`.
+    /// Returns `true` if attachment has inline content afterwards.
+    ///
+    /// # Parameters
+    ///
+    /// * `thid` - thread of message containing the attachment
+    ///
+    /// * `attachment` - attachment to complete
+    pub fn resolve(&self, thid: &str, attachment: &mut Attachment) -> bool {
+        let data = &mut attachment.data;
+        if data.base64.is_none() && data.json.is_none() {
+            if let Some(content) = data
+                .hash
+                .as_deref()
+                .and_then(|hash| self.cache.get(thid, hash))
+            {
+                data.base64 = Some(base64_url::encode(&content));
+            }
+        }
+        data.base64.is_some() || data.json.is_some()
+    }
+}
+
+impl PackMiddleware for AttachmentDeduplicator {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        let thid = thread_of(message);
+        for attachment in &mut message.attachments {
+            let content = match &attachment.data.base64 {
+                Some(base64) => base64_url::decode(base64)?,
+                None => continue,
+            };
+            let hash = attachment_hash(&content);
+            attachment.data.hash = Some(hash.clone());
+            if self.cache.was_sent(&thid, &hash) {
+                attachment.data.base64 = None;
+            } else {
+                self.cache.put(&thid, &hash, content);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReceiveMiddleware for AttachmentDeduplicator {
+    fn handle(&self, message: &mut Message, _sender: &SenderInfo) -> Result<()> {
+        let thid = thread_of(message);
+        for attachment in &mut message.attachments {
+            if let Some(base64) = &attachment.data.base64 {
+                let content = base64_url::decode(base64)?;
+                let hash = attachment_hash(&content);
+                if let Some(expected) = &attachment.data.hash {
+                    if expected != &hash {
+                        return Err(Error::AttachmentError(format!(
+                            "content does not match hash `{}`",
+                            expected
+                        )));
+                    }
+                }
+                self.cache.put(&thid, &hash, content);
+            } else if !self.resolve(&thid, attachment) && attachment.data.links.is_empty() {
+                if let Some(hash) = &attachment.data.hash {
+                    return Err(Error::AttachmentError(format!(
+                        "content with hash `{}` is not cached for thread",
+                        hash
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Thread of message, its `thid` or `id`.
+fn thread_of(message: &Message) -> String {
+    let header = &message.didcomm_header;
+    header.thid.clone().unwrap_or_else(|| header.id.clone())
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use super::*;
+    use crate::{AttachmentBuilder, AttachmentDataBuilder, Packer, ReceivePipeline};
+
+    fn with_file(message: Message) -> Message {
+        let mut message = message;
+        message.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id("file")
+                .with_data(AttachmentDataBuilder::new().with_raw_payload(b"file content")),
+        );
+        message
+    }
+
+    #[test]
+    fn references_repeated_content_by_hash() -> Result<()> {
+        let deduplicator = Arc::new(AttachmentDeduplicator::new(Arc::new(
+            InMemoryAttachmentCache::new(),
+        )));
+        let sender = Packer::new().with(deduplicator.clone());
+        let receiver = ReceivePipeline::new().with(Arc::new(AttachmentDeduplicator::new(
+            Arc::new(InMemoryAttachmentCache::new()),
+        )));
+
+        let mut first = sender.prepare(with_file(Message::new()))?;
+        let thid = first.get_id().to_string();
+        let undelivered = sender.prepare(with_file(Message::new().thid(&thid)))?;
+        deduplicator.mark_delivered(&first);
+        let mut second = sender.prepare(with_file(Message::new().thid(&thid)))?;
+        let other_thread = sender.prepare(with_file(Message::new()))?;
+        let hash = attachment_hash(b"file content");
+        assert_eq!(first.attachments[0].data.hash.as_deref(), Some(hash.as_str()));
+        assert!(first.attachments[0].data.base64.is_some());
+        assert!(undelivered.attachments[0].data.base64.is_some());
+        assert!(second.attachments[0].data.base64.is_none());
+        assert!(other_thread.attachments[0].data.base64.is_some());
+
+        let mut unresolvable = second.clone().thid("other-thread");
+        assert!(matches!(
+            receiver.run(&mut unresolvable, &SenderInfo::default()),
+            Err(Error::AttachmentError(_))
+        ));
+        receiver.run(&mut first, &SenderInfo::default())?;
+        receiver.run(&mut second, &SenderInfo::default())?;
+        assert_eq!(second.attachments[0].decoded_content()?, b"file content");
+
+        let mut tampered = with_file(Message::new());
+        tampered.attachments[0].data.hash = Some(attachment_hash(b"other content"));
+        assert!(matches!(
+            receiver.run(&mut tampered, &SenderInfo::default()),
+            Err(Error::AttachmentError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn in_memory_cache_evicts_oldest_content() {
+        let cache = InMemoryAttachmentCache::with_capacity(8);
+        cache.put("thread-1", "a", vec![1; 4]);
+        cache.mark_sent("thread-1", "a");
+        cache.put("thread-1", "b", vec![2; 4]);
+        cache.put("thread-2", "c", vec![3; 4]);
+        cache.put("thread-2", "too-large", vec![4; 9]);
+
+        assert_eq!(cache.get("thread-1", "a"), None);
+        assert!(!cache.was_sent("thread-1", "a"));
+        assert_eq!(cache.get("thread-1", "b"), Some(vec![2; 4]));
+        assert_eq!(cache.get("thread-2", "b"), None);
+        assert_eq!(cache.get("thread-2", "c"), Some(vec![3; 4]));
+        assert_eq!(cache.get("thread-2", "too-large"), None);
+    }
+}
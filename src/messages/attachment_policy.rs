@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use crate::{attachment_hash, Attachment, Error, Message, PackMiddleware, Result};
+
+/// Trait must be implemented for publishing attachment content too large to be
+///     sent inline, see [`AttachmentSizePolicy::with_uploader`].
+/// Implemented for closures `Fn(&str, &[u8]) -> Result<String>`.
+pub trait AttachmentUploader: Send + Sync {
+    /// Uploads content and returns link it can be fetched from.
+    ///
+    /// # Parameters
+    ///
+    /// * `hash` - `hash` attachment data value of content, see [`attachment_hash`]
+    ///
+    /// * `content` - raw (decoded) attachment content
+    fn upload(&self, hash: &str, content: &[u8]) -> Result<String>;
+}
+
+impl<F> AttachmentUploader for F
+where
+    F: Fn(&str, &[u8]) -> Result<String> + Send + Sync,
+{
+    fn upload(&self, hash: &str, content: &[u8]) -> Result<String> {
+        self(hash, content)
+    }
+}
+
+/// Chooses between embedding and linking attachment content by its size.
+/// Added to [`Packer`][crate::Packer] it keeps inline `base64` and `json` data of
+///     attachments up to `max_inline_bytes` and replaces data of larger ones by
+///     `links` and `hash`, so sealed envelopes stay within transport limits.
+/// Content is published with the uploader if one is set, otherwise it is expected
+///     to be served below link base URL by its hash.
+#[derive(Clone)]
+pub struct AttachmentSizePolicy {
+    max_inline_bytes: usize,
+    link_base_url: Option<String>,
+    uploader: Option<Arc<dyn AttachmentUploader>>,
+}
+
+impl AttachmentSizePolicy {
+    /// Constructor of policy without link target; attachments over budget are refused.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_inline_bytes` - maximum size of raw content embedded into message
+    pub fn new(max_inline_bytes: usize) -> Self {
+        Self {
+            max_inline_bytes,
+            link_base_url: None,
+            uploader: None,
+        }
+    }
+
+    /// Setter of base URL content over budget is linked at as `<base url>/<hash>`.
+    /// Ignored if uploader is set.
+    ///
+    /// # Parameters
+    ///
+    /// * `base_url` - URL content is served below, e.g. `https://example.com/attachments`
+    pub fn with_link_base_url(mut self, base_url: &str) -> Self {
+        self.link_base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Setter of uploader publishing content over budget.
+    ///
+    /// # Parameters
+    ///
+    /// * `uploader` - uploader returning link of published content
+    pub fn with_uploader(mut self, uploader: Arc<dyn AttachmentUploader>) -> Self {
+        self.uploader = Some(uploader);
+        self
+    }
+
+    /// Keeps attachment inline if within budget, otherwise publishes its content
+    ///     and references it via `links` and `hash`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if content is over budget and neither uploader
+    ///     nor link base URL is set; errors of uploader are passed through.
+    ///
+    /// # Parameters
+    ///
+    /// * `attachment` - attachment to apply policy to
+    pub fn apply(&self, attachment: &mut Attachment) -> Result<()> {
+        if attachment.data.base64.is_none() && attachment.data.json.is_none() {
+            return Ok(());
+        }
+        let content = attachment.decoded_content()?;
+        if content.len() <= self.max_inline_bytes {
+            return Ok(());
+        }
+        let hash = attachment_hash(&content);
+        let link = match (&self.uploader, &self.link_base_url) {
+            (Some(uploader), _) => uploader.upload(&hash, &content)?,
+            (None, Some(base_url)) => format!("{}/{}", base_url, hash),
+            (None, None) => {
+                return Err(Error::AttachmentError(format!(
+                    "content of {} bytes exceeds inline budget of {} bytes",
+                    content.len(),
+                    self.max_inline_bytes
+                )))
+            }
+        };
+        let data = &mut attachment.data;
+        data.base64 = None;
+        data.json = None;
+        data.hash = Some(hash);
+        if !data.links.contains(&link) {
+            data.links.push(link);
+        }
+        attachment.byte_count.get_or_insert(content.len());
+        Ok(())
+    }
+}
+
+impl PackMiddleware for AttachmentSizePolicy {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        for attachment in &mut message.attachments {
+            self.apply(attachment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{
+        crypto::CryptoAlgorithm,
+        AttachmentBuilder,
+        AttachmentData,
+        AttachmentDataBuilder,
+        Packer,
+    };
+
+    #[test]
+    fn attachments_over_budget_are_linked() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let store = uploaded.clone();
+        let packer = Packer::new().with(Arc::new(AttachmentSizePolicy::new(16).with_uploader(
+            Arc::new(move |hash: &str, content: &[u8]| {
+                store.lock().unwrap().push(content.to_vec());
+                Ok(format!("https://example.com/{}", hash))
+            }),
+        )));
+        let mut message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()));
+        message.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id("small")
+                .with_data(AttachmentDataBuilder::new().with_raw_payload(b"small")),
+        );
+        message.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id("large")
+                .with_data(AttachmentDataBuilder::new().with_raw_payload([7u8; 64])),
+        );
+
+        let sealed = packer.seal(
+            message,
+            alice_private,
+            Some(vec![Some(bobs_public.to_vec())]),
+        )?;
+        let received = Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        let small = received.attachment_by_id("small").unwrap();
+        assert_eq!(small.decoded_content()?, b"small");
+        let large = received.attachment_by_id("large").unwrap();
+        let hash = attachment_hash(&[7u8; 64]);
+        assert!(large.data.base64.is_none());
+        assert_eq!(large.data.hash.as_deref(), Some(hash.as_str()));
+        assert_eq!(
+            large.data.links,
+            vec![format!("https://example.com/{}", hash)]
+        );
+        assert_eq!(large.byte_count, Some(64));
+        assert_eq!(*uploaded.lock().unwrap(), vec![vec![7u8; 64]]);
+
+        let mut linked = large.clone();
+        linked.data = AttachmentData {
+            json: Some(format!("\"{}\"", "x".repeat(32))),
+            ..Default::default()
+        };
+        AttachmentSizePolicy::new(16)
+            .with_link_base_url("https://example.com/files/")
+            .apply(&mut linked)?;
+        assert!(linked.data.json.is_none());
+        assert!(linked.data.links[0].starts_with("https://example.com/files/"));
+        assert!(matches!(
+            AttachmentSizePolicy::new(16).apply(&mut large.clone()),
+            Ok(())
+        ));
+        let mut unlinkable = small.clone();
+        unlinkable.data = AttachmentData {
+            base64: Some(base64_url::encode(&[0u8; 17])),
+            ..Default::default()
+        };
+        assert!(matches!(
+            AttachmentSizePolicy::new(16).apply(&mut unlinkable),
+            Err(Error::AttachmentError(_))
+        ));
+        Ok(())
+    }
+}
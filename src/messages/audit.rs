@@ -0,0 +1,166 @@
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "raw-crypto")]
+use std::time::SystemTime;
+
+use crate::EnvelopeKind;
+#[cfg(feature = "raw-crypto")]
+use crate::{Message, Result};
+
+/// Whether message was packed for sending or unpacked after receiving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditDirection {
+    /// message was sealed or signed
+    Pack,
+    /// message was received
+    Unpack,
+}
+
+/// Structured record handed over to [`AuditSink`] for every packed or unpacked message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// seconds since Unix Epoch when record was created
+    pub timestamp: u64,
+
+    /// packing or unpacking
+    pub direction: AuditDirection,
+
+    /// `id` header, `None` if received message could not be unpacked
+    pub message_id: Option<String>,
+
+    /// `from` header of plaintext message
+    pub from: Option<String>,
+
+    /// `to` header of plaintext message
+    pub to: Vec<String>,
+
+    /// `type` header of plaintext message
+    pub message_type: Option<String>,
+
+    /// kind of envelope produced or received
+    pub envelope: EnvelopeKind,
+
+    /// `None` on success, description of the error otherwise
+    pub error: Option<String>,
+}
+
+/// Trait must be implemented for pluggable audit logs, see [`set_audit_sink`].
+/// Records never contain message bodies or key material.
+///
+/// Implemented for closures `Fn(&AuditRecord)`.
+pub trait AuditSink: Send + Sync {
+    /// Called once per packed or unpacked message.
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord) + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+static AUDIT_SINK: RwLock<Option<Arc<dyn AuditSink>>> = RwLock::new(None);
+
+/// Registers process wide audit sink invoked on `seal`, `sign` and `receive`.
+/// Replaces previously registered one.
+///
+/// # Arguments
+///
+/// * `sink` - receives record of every packed or unpacked message
+pub fn set_audit_sink(sink: Arc<dyn AuditSink>) {
+    if let Ok(mut current) = AUDIT_SINK.write() {
+        *current = Some(sink);
+    }
+}
+
+/// Removes registered audit sink, if any.
+pub fn clear_audit_sink() {
+    if let Ok(mut current) = AUDIT_SINK.write() {
+        *current = None;
+    }
+}
+
+#[cfg(feature = "raw-crypto")]
+fn current_sink() -> Option<Arc<dyn AuditSink>> {
+    match AUDIT_SINK.read() {
+        Ok(current) => current.clone(),
+        Err(_) => None,
+    }
+}
+
+/// Starts record for message about to be packed.
+/// `None` if no sink is registered, so nothing is copied in that case.
+#[cfg(feature = "raw-crypto")]
+pub(crate) fn pack_record(message: &Message, envelope: EnvelopeKind) -> Option<AuditRecord> {
+    current_sink()?;
+    let header = &message.didcomm_header;
+    Some(AuditRecord {
+        timestamp: now(),
+        direction: AuditDirection::Pack,
+        message_id: Some(header.id.clone()),
+        from: header.from.clone(),
+        to: header.to.clone(),
+        message_type: Some(header.m_type.clone()),
+        envelope,
+        error: None,
+    })
+}
+
+/// Starts record for received payload.
+/// `None` if no sink is registered, so payload is not inspected in that case.
+#[cfg(feature = "raw-crypto")]
+pub(crate) fn unpack_record(incoming: &str) -> Option<AuditRecord> {
+    current_sink()?;
+    Some(AuditRecord {
+        timestamp: now(),
+        direction: AuditDirection::Unpack,
+        message_id: None,
+        from: None,
+        to: vec![],
+        message_type: None,
+        envelope: EnvelopeKind::detect(incoming),
+        error: None,
+    })
+}
+
+/// Completes record with outcome and hands it over to registered sink.
+///
+/// # Arguments
+///
+/// * `record` - record started with `pack_record` or `unpack_record`
+///
+/// * `result` - outcome of packing or unpacking
+///
+/// * `unpacked` - received message, used to fill in headers of unpack records
+#[cfg(feature = "raw-crypto")]
+pub(crate) fn submit<T>(
+    record: Option<AuditRecord>,
+    result: &Result<T>,
+    unpacked: Option<&Message>,
+) {
+    let (mut record, sink) = match (record, current_sink()) {
+        (Some(record), Some(sink)) => (record, sink),
+        _ => return,
+    };
+    if let Err(e) = result {
+        record.error = Some(e.to_string());
+    }
+    if let Some(message) = unpacked {
+        let header = &message.didcomm_header;
+        record.message_id = Some(header.id.clone());
+        record.from = header.from.clone();
+        record.to = header.to.clone();
+        record.message_type = Some(header.m_type.clone());
+    }
+    sink.record(&record);
+}
+
+#[cfg(feature = "raw-crypto")]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
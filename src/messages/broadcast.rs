@@ -0,0 +1,97 @@
+use crate::{messages::audit, EnvelopeKind, Jwe, Message, Result};
+
+/// Output of [`Message::seal_broadcast`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastMode {
+    /// single JWE with one `recipients` entry per `to`
+    Shared,
+    /// one flat JWE per `to`, in same order, all sharing same ciphertext
+    PerRecipient,
+}
+
+impl Message {
+    /// Seals (encrypts) self for many recipients at once.
+    /// Message is serialized and encrypted once, only the content encryption key
+    ///     is wrapped per recipient.
+    /// Every recipient can see all `to` entries, as they are part of the shared plaintext.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipients,
+    ///   same order as `to`; can be provided if keys should not be resolved via recipients DIDs
+    ///
+    /// * `mode` - whether to emit one JWE or one flat JWE per recipient
+    pub fn seal_broadcast(
+        mut self,
+        sender_private_key: impl AsRef<[u8]>,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mode: BroadcastMode,
+    ) -> Result<Vec<String>> {
+        self.serialize_flat_jwe = false;
+        let record = audit::pack_record(&self, EnvelopeKind::Jwe);
+        let result = self.seal_jwe(sender_private_key, recipient_public_keys);
+        audit::submit(record, &result, None);
+        let sealed = result?;
+        match mode {
+            BroadcastMode::Shared => Ok(vec![sealed]),
+            BroadcastMode::PerRecipient => {
+                let mut jwe: Jwe = serde_json::from_str(&sealed)?;
+                let recipients = jwe.recipients.take().unwrap_or_default();
+                recipients
+                    .into_iter()
+                    .map(|recipient| {
+                        jwe.recipient = Some(recipient);
+                        Ok(serde_json::to_string(&jwe)?)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::crypto::CryptoAlgorithm;
+
+    #[test]
+    fn per_recipient_envelopes_share_ciphertext() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let sealed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob", "did:example:carol"])
+            .as_jwe(&CryptoAlgorithm::XC20P, None)
+            .seal_broadcast(
+                alice_private,
+                Some(vec![
+                    Some(bobs_public.to_vec()),
+                    Some(mediators_public.to_vec()),
+                ]),
+                BroadcastMode::PerRecipient,
+            )?;
+        assert_eq!(sealed.len(), 2);
+
+        let jwes = sealed
+            .iter()
+            .map(|jwe| serde_json::from_str::<Jwe>(jwe))
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        assert_eq!(jwes[0].get_ciphertext()?, jwes[1].get_ciphertext()?);
+        assert!(jwes.iter().all(|jwe| jwe.recipients.is_none()));
+        for (jwe, key) in sealed.iter().zip([bobs_private, mediators_private]) {
+            let received = Message::receive(jwe, Some(&key), Some(alice_public.to_vec()), None)?;
+            assert_eq!(received.get_didcomm_header().to.len(), 2);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use crate::{
+    DidCommMessagingService,
+    Error,
+    Message,
+    PackEncryptedOptions,
+    PackResult,
+    Packer,
+    ReceiveOptions,
+    Result,
+    ThreadStore,
+    ThreadTracker,
+};
+
+/// Messaging between one of our DIDs and a DID of the other party with keys and
+///     endpoint resolved once, so each interaction is a single call.
+/// `send` and `reply` address, thread, pack and route messages,
+///     `receive` unpacks envelopes and checks they come from the other party.
+#[derive(Clone)]
+pub struct Channel {
+    our_did: String,
+    our_private_key: Vec<u8>,
+    their_did: String,
+    their_public_key: Vec<u8>,
+    pack_options: PackEncryptedOptions,
+    packer: Packer,
+    receive_options: ReceiveOptions,
+}
+
+impl Channel {
+    /// Constructor of channel sending authcrypted messages directly to the other party.
+    ///
+    /// # Parameters
+    ///
+    /// * `our_did` - DID messages are sent from and received at
+    ///
+    /// * `our_private_key` - our X25519 private key
+    ///
+    /// * `their_did` - DID of the other party
+    ///
+    /// * `their_public_key` - X25519 public key of the other party
+    pub fn new(
+        our_did: &str,
+        our_private_key: &[u8],
+        their_did: &str,
+        their_public_key: &[u8],
+    ) -> Self {
+        Self {
+            our_did: our_did.into(),
+            our_private_key: our_private_key.to_vec(),
+            their_did: their_did.into(),
+            their_public_key: their_public_key.to_vec(),
+            pack_options: PackEncryptedOptions::new(),
+            packer: Packer::new(),
+            receive_options: ReceiveOptions::new(),
+        }
+    }
+
+    /// Setter of endpoint of the other party; sent messages are forwarded
+    ///     through its `routing_keys`.
+    ///
+    /// # Parameters
+    ///
+    /// * `service` - resolved `DIDCommMessaging` service of the other party
+    ///
+    /// * `routing_public_keys` - keys of `routing_keys` of `service`, same order;
+    ///   can be provided if keys should not be resolved via mediators DIDs
+    pub fn with_service(
+        mut self,
+        service: DidCommMessagingService,
+        routing_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Self {
+        self.pack_options.messaging_service = Some(service);
+        self.pack_options.routing_public_keys = routing_public_keys;
+        self
+    }
+
+    /// Replaces options of packing, e.g. to protect sender or change algorithms.
+    /// Service set with `with_service` is kept if `options` have none.
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - options passed to `Message::pack_encrypted`
+    pub fn with_pack_options(mut self, mut options: PackEncryptedOptions) -> Self {
+        if options.messaging_service.is_none() {
+            options.messaging_service = self.pack_options.messaging_service.take();
+            options.routing_public_keys = self.pack_options.routing_public_keys.take();
+        }
+        self.pack_options = options;
+        self
+    }
+
+    /// Replaces packer run on sent messages before packing.
+    ///
+    /// # Parameters
+    ///
+    /// * `packer` - preconfigured packer, can be shared between channels
+    pub fn with_packer(mut self, packer: Packer) -> Self {
+        self.packer = packer;
+        self
+    }
+
+    /// Replaces options applied to received messages.
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - preconfigured options, can be shared between channels
+    pub fn with_receive_options(mut self, options: ReceiveOptions) -> Self {
+        self.receive_options = options;
+        self
+    }
+
+    /// Keeps `store` up to date with threads of sent and received messages
+    ///     by adding [`ThreadTracker`] to packer and receive options.
+    ///
+    /// # Parameters
+    ///
+    /// * `store` - storage of threads, can be shared between channels
+    pub fn with_thread_store(mut self, store: Arc<dyn ThreadStore>) -> Self {
+        let tracker = Arc::new(ThreadTracker::new(store));
+        self.packer = self.packer.with(tracker.clone());
+        self.receive_options = self.receive_options.with_middleware(tracker);
+        self
+    }
+
+    /// DID messages are sent from and received at.
+    pub fn our_did(&self) -> &str {
+        &self.our_did
+    }
+
+    /// DID of the other party.
+    pub fn their_did(&self) -> &str {
+        &self.their_did
+    }
+
+    /// Addresses message from our DID to the other party, runs packer and packs it.
+    /// Returned envelope is ready to be sent to `service_endpoint`.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message to send; `from` and `to` are replaced
+    pub fn send(&self, message: Message) -> Result<PackResult> {
+        let message = message
+            .clear_to()
+            .to(&[&self.their_did])
+            .from(&self.our_did);
+        self.packer.prepare(message)?.pack_encrypted(
+            Some(&self.our_private_key),
+            Some(vec![Some(self.their_public_key.clone())]),
+            &self.pack_options,
+        )
+    }
+
+    /// Same as `send` but puts message into thread of `received`.
+    ///
+    /// # Parameters
+    ///
+    /// * `received` - message of the other party we reply to
+    ///
+    /// * `message` - reply to send; `from`, `to`, `thid` and `pthid` are replaced
+    pub fn reply(&self, received: &Message, message: Message) -> Result<PackResult> {
+        let header = received.get_didcomm_header();
+        let mut message = message.thid(header.thid.as_deref().unwrap_or(&header.id));
+        message.didcomm_header.pthid = header.pthid.clone();
+        self.send(message)
+    }
+
+    /// Unpacks envelope sent by the other party.
+    ///
+    /// # Errors
+    ///
+    /// `Error::SenderRejected` if message is not from DID of the other party.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - received JWE, JWS or plaintext message
+    pub fn receive(&self, envelope: &str) -> Result<Message> {
+        let (message, _) = Message::receive_with_sender_info(
+            envelope,
+            Some(&self.our_private_key),
+            Some(self.their_public_key.clone()),
+            None,
+            &self.receive_options,
+        )?;
+        match message.get_didcomm_header().from.as_deref() {
+            Some(from) if from == self.their_did => Ok(message),
+            from => Err(Error::SenderRejected(from.unwrap_or_default().into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{InMemoryThreadStore, Mediated};
+
+    #[test]
+    fn channels_send_reply_and_receive() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let threads = Arc::new(InMemoryThreadStore::new());
+        let alice = Channel::new(
+            "did:example:alice",
+            &alice_private,
+            "did:example:bob",
+            &bobs_public,
+        )
+        .with_service(
+            DidCommMessagingService::new("https://mediator.example")
+                .with_routing_keys(&["did:example:mediator#key-1"]),
+            Some(vec![Some(mediators_public.to_vec())]),
+        )
+        .with_thread_store(threads.clone());
+        let bob = Channel::new(
+            "did:example:bob",
+            &bobs_private,
+            "did:example:alice",
+            &alice_public,
+        );
+
+        let sent = alice.send(Message::new().m_type("https://example.org/ping/1.0/ping"))?;
+        assert_eq!(
+            sent.service_endpoint.as_deref(),
+            Some("https://mediator.example")
+        );
+        assert_eq!(threads.len(), 1);
+        let forward = Message::receive(&sent.envelope, Some(&mediators_private), None, None)?;
+        let mediated: Mediated = serde_json::from_str(&forward.get_body()?)?;
+        let ping = bob.receive(std::str::from_utf8(&mediated.payload)?)?;
+        assert_eq!(ping.get_didcomm_header().to, ["did:example:bob"]);
+
+        let pong = bob.reply(
+            &ping,
+            Message::new().m_type("https://example.org/ping/1.0/ping-response"),
+        )?;
+        assert_eq!(pong.service_endpoint, None);
+        let pong = alice.receive(&pong.envelope)?;
+        assert_eq!(
+            pong.get_didcomm_header().thid.as_deref(),
+            Some(ping.get_id())
+        );
+        assert_eq!(threads.len(), 1);
+
+        // messages of other senders are rejected even if keys match
+        let impostor = Channel::new(
+            "did:example:bob",
+            &bobs_private,
+            "did:example:carol",
+            &alice_public,
+        );
+        let plain = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_raw_json()?;
+        assert!(matches!(
+            impostor.receive(&plain),
+            Err(Error::SenderRejected(from)) if from == "did:example:alice"
+        ));
+        Ok(())
+    }
+}
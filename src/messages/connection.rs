@@ -0,0 +1,376 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde_json::Value;
+
+use crate::{
+    DidCommMessagingService,
+    Message,
+    MessageType,
+    PackMiddleware,
+    ReceiveMiddleware,
+    Result,
+    SenderInfo,
+};
+
+/// Lifecycle state of a [`Connection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    /// Out-of-band invitation was sent or received, no message of the other party seen yet
+    Invited,
+    /// Both DIDs are known
+    Active,
+    /// Connection was closed by the application
+    Closed,
+}
+
+/// Single DID rotation of either party of a [`Connection`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DidRotation {
+    /// DID rotated away from, `iss` of `from_prior` claims
+    pub prior: String,
+
+    /// DID rotated to, `sub` of `from_prior` claims
+    pub new: String,
+
+    /// `true` if we rotated our DID, `false` if the other party did
+    pub ours: bool,
+
+    /// seconds since Unix Epoch of sending or receiving the rotation
+    pub time: u64,
+}
+
+/// Relationship between one of our DIDs and a DID of the other party.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connection {
+    /// id of the out-of-band invitation which started the connection
+    pub id: String,
+
+    /// our current DID, `None` if not yet used
+    pub our_did: Option<String>,
+
+    /// current DID of the other party, `None` until they answer our invitation
+    pub their_did: Option<String>,
+
+    /// service endpoints of the other party, as announced in their invitation
+    pub their_services: Vec<DidCommMessagingService>,
+
+    /// key ids the other party used as `skid` when sending to us
+    pub their_kids: Vec<String>,
+
+    /// lifecycle state
+    pub state: ConnectionState,
+
+    /// DID rotations of both parties, oldest first
+    pub rotations: Vec<DidRotation>,
+
+    /// seconds since Unix Epoch of last update
+    pub updated_time: u64,
+}
+
+impl Connection {
+    /// Constructor of invited connection without any DIDs.
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - id of the out-of-band invitation
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.into(),
+            our_did: None,
+            their_did: None,
+            their_services: vec![],
+            their_kids: vec![],
+            state: ConnectionState::Invited,
+            rotations: vec![],
+            updated_time: 0,
+        }
+    }
+
+    fn touch(&mut self) {
+        self.updated_time = now();
+    }
+}
+
+/// Trait must be implemented for pluggable connection storage,
+///     see [`ConnectionTracker`] for keeping it up to date.
+pub trait ConnectionStore: Send + Sync {
+    /// Gets connection with given id.
+    fn get(&self, id: &str) -> Option<Connection>;
+
+    /// Inserts or replaces connection with `connection.id`.
+    fn put(&self, connection: Connection);
+
+    /// Gets all stored connections.
+    fn all(&self) -> Vec<Connection>;
+
+    /// Finds connection with given current DID of the other party.
+    fn find_by_their_did(&self, did: &str) -> Option<Connection> {
+        self.all()
+            .into_iter()
+            .find(|c| c.their_did.as_deref() == Some(did))
+    }
+
+    /// Finds connection with given current DID of ours.
+    fn find_by_our_did(&self, did: &str) -> Option<Connection> {
+        self.all()
+            .into_iter()
+            .find(|c| c.our_did.as_deref() == Some(did))
+    }
+}
+
+/// In-memory [`ConnectionStore`], connections are lost on drop.
+#[derive(Default)]
+pub struct InMemoryConnectionStore {
+    connections: Mutex<HashMap<String, Connection>>,
+}
+
+impl InMemoryConnectionStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn connections(&self) -> std::sync::MutexGuard<'_, HashMap<String, Connection>> {
+        match self.connections.lock() {
+            Ok(connections) => connections,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl ConnectionStore for InMemoryConnectionStore {
+    fn get(&self, id: &str) -> Option<Connection> {
+        self.connections().get(id).cloned()
+    }
+
+    fn put(&self, connection: Connection) {
+        self.connections().insert(connection.id.clone(), connection);
+    }
+
+    fn all(&self) -> Vec<Connection> {
+        self.connections().values().cloned().collect()
+    }
+}
+
+/// Keeps [`ConnectionStore`] up to date with out-of-band and DID rotation flows.
+/// Added to [`Packer`][crate::Packer] it records sent invitations and rotations of our DID.
+/// Added to [`ReceivePipeline`][crate::ReceivePipeline] it records received invitations,
+///     answers to our invitations (messages with invitation id as `pthid`),
+///     sender key ids and rotations of their DID.
+/// Received `from_prior` claims are validated, invalid ones reject the message.
+#[derive(Clone)]
+pub struct ConnectionTracker {
+    store: Arc<dyn ConnectionStore>,
+}
+
+impl ConnectionTracker {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `store` - storage shared between packing and receiving
+    pub fn new(store: Arc<dyn ConnectionStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl PackMiddleware for ConnectionTracker {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        let from = non_empty(message.didcomm_header.from.as_deref());
+        if is_invitation(message) {
+            let mut connection = Connection::new(message.get_id());
+            connection.our_did = from.map(Into::into);
+            connection.touch();
+            self.store.put(connection);
+        } else if let Ok(claims) = message.get_prior() {
+            if let Some(mut connection) = self.store.find_by_our_did(claims.get_iss()) {
+                let new = claims.get_sub().or(from).unwrap_or_default();
+                connection.rotations.push(DidRotation {
+                    prior: claims.get_iss().into(),
+                    new: new.into(),
+                    ours: true,
+                    time: now(),
+                });
+                connection.our_did = Some(new.into());
+                connection.touch();
+                self.store.put(connection);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReceiveMiddleware for ConnectionTracker {
+    fn handle(&self, message: &mut Message, sender: &SenderInfo) -> Result<()> {
+        let from = sender
+            .from
+            .as_deref()
+            .or_else(|| non_empty(message.didcomm_header.from.as_deref()));
+        if is_invitation(message) {
+            let mut connection = Connection::new(message.get_id());
+            connection.their_did = from.map(Into::into);
+            read_services(message, &mut connection);
+            connection.touch();
+            self.store.put(connection);
+            return Ok(());
+        }
+        let mut connection = if let Ok(claims) = message.get_prior() {
+            let connection = self.store.find_by_their_did(claims.get_iss());
+            if let Some(mut connection) = connection {
+                claims.validate(connection.our_did.as_deref())?;
+                let new = claims.get_sub().unwrap_or_default();
+                connection.rotations.push(DidRotation {
+                    prior: claims.get_iss().into(),
+                    new: new.into(),
+                    ours: false,
+                    time: now(),
+                });
+                connection.their_did = Some(new.into());
+                connection
+            } else {
+                return Ok(());
+            }
+        } else if let Some(connection) = from.and_then(|from| self.store.find_by_their_did(from)) {
+            connection
+        } else {
+            let invited = message
+                .get_pthid()
+                .and_then(|pthid| self.store.get(pthid))
+                .filter(|c| c.state == ConnectionState::Invited && c.their_did.is_none());
+            match invited {
+                Some(connection) => connection,
+                None => return Ok(()),
+            }
+        };
+        if connection.state == ConnectionState::Invited {
+            if connection.their_did.is_none() {
+                connection.their_did = from.map(Into::into);
+            }
+            if connection.our_did.is_none() {
+                connection.our_did = message.didcomm_header.to.first().cloned();
+            }
+            connection.state = ConnectionState::Active;
+        }
+        if let Some(skid) = &sender.skid {
+            if !connection.their_kids.contains(skid) {
+                connection.their_kids.push(skid.clone());
+            }
+        }
+        connection.touch();
+        self.store.put(connection);
+        Ok(())
+    }
+}
+
+fn is_invitation(message: &Message) -> bool {
+    message.didcomm_header.m_type == MessageType::DidCommInvitation.as_str()
+}
+
+fn non_empty(did: Option<&str>) -> Option<&str> {
+    did.filter(|did| !did.is_empty())
+}
+
+/// Reads inline `services` of invitation body.
+/// Services given as DIDs are left for DID resolution, invalid ones are skipped.
+fn read_services(message: &Message, connection: &mut Connection) {
+    let services = message.body_at("/services").and_then(Value::as_array);
+    connection.their_services = services
+        .into_iter()
+        .flatten()
+        .filter_map(|service| DidCommMessagingService::try_from(service).ok())
+        .collect();
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Packer, PriorClaims, ReceivePipeline};
+
+    fn bob() -> SenderInfo {
+        SenderInfo {
+            from: Some("did:example:bob".into()),
+            skid: Some("did:example:bob#key-1".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sent_invitation_becomes_active_connection() -> Result<()> {
+        let store = Arc::new(InMemoryConnectionStore::new());
+        let tracker = Arc::new(ConnectionTracker::new(store.clone()));
+        let packer = Packer::new().with(tracker.clone());
+        let pipeline = ReceivePipeline::new().with(tracker);
+
+        let invitation = Message::new()
+            .from("did:example:alice")
+            .m_type(MessageType::DidCommInvitation.as_str());
+        let invitation = packer.prepare(invitation)?;
+        let connection = store.get(invitation.get_id()).expect("invitation not tracked");
+        assert_eq!(connection.state, ConnectionState::Invited);
+
+        let mut answer = Message::new()
+            .from("did:example:bob")
+            .to(&["did:example:alice"])
+            .pthid(invitation.get_id());
+        pipeline.run(&mut answer, &bob())?;
+        let connection = store.find_by_their_did("did:example:bob").expect("not answered");
+        assert_eq!(connection.state, ConnectionState::Active);
+        assert_eq!(connection.our_did.as_deref(), Some("did:example:alice"));
+        assert_eq!(connection.their_kids, ["did:example:bob#key-1"]);
+
+        let mut rotation = Message::new()
+            .from("did:example:bob2")
+            .from_prior(PriorClaims::new("did:example:bob").with_sub("did:example:bob2"));
+        pipeline.run(&mut rotation, &bob())?;
+        let connection = store.get(invitation.get_id()).unwrap();
+        assert_eq!(connection.their_did.as_deref(), Some("did:example:bob2"));
+        assert_eq!(connection.rotations[0].prior, "did:example:bob");
+
+        let rotation = Message::new()
+            .from("did:example:alice2")
+            .from_prior(PriorClaims::new("did:example:alice").with_sub("did:example:alice2"));
+        packer.prepare(rotation)?;
+        let connection = store.get(invitation.get_id()).unwrap();
+        assert_eq!(connection.our_did.as_deref(), Some("did:example:alice2"));
+        assert!(connection.rotations[1].ours);
+        Ok(())
+    }
+
+    #[test]
+    fn received_invitation_records_services() -> Result<()> {
+        let store = Arc::new(InMemoryConnectionStore::new());
+        let pipeline = ReceivePipeline::new().with(Arc::new(ConnectionTracker::new(store.clone())));
+
+        let mut invitation = Message::new()
+            .from("did:example:bob")
+            .m_type(MessageType::DidCommInvitation.as_str())
+            .merge_body(json!({
+                "services": [{
+                    "serviceEndpoint": "https://bob.example.com",
+                    "routingKeys": ["did:example:mediator#key-1"],
+                }],
+            }));
+        pipeline.run(&mut invitation, &bob())?;
+
+        let connection = store.find_by_their_did("did:example:bob").expect("not tracked");
+        assert_eq!(connection.state, ConnectionState::Invited);
+        let service = &connection.their_services[0];
+        assert_eq!(service.uri, "https://bob.example.com");
+        assert_eq!(service.routing_keys, ["did:example:mediator#key-1"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, MutexGuard},
+};
+
+use base58::FromBase58;
+use serde_json::Value;
+
+use crate::{
+    crypto::{KeyInput, KeyMaterial, KeyRole},
+    DidCommMessagingService,
+    Error,
+    Jwk,
+    Result,
+    DIDCOMM_MESSAGING_SERVICE_TYPE,
+};
+
+/// Keys and services of a resolved DID document, as needed for messaging.
+#[derive(Clone)]
+pub struct ResolvedDid {
+    /// resolved DID
+    pub did: String,
+
+    /// ids and public keys of `keyAgreement` verification methods
+    pub key_agreement: Vec<(String, KeyMaterial)>,
+
+    /// ids and public keys of `authentication` verification methods
+    pub authentication: Vec<(String, KeyMaterial)>,
+
+    /// `DIDCommMessaging` services, in order of preference
+    pub services: Vec<DidCommMessagingService>,
+}
+
+impl ResolvedDid {
+    /// Constructor of DID without keys and services.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - resolved DID
+    pub fn new(did: &str) -> Self {
+        Self {
+            did: did.into(),
+            key_agreement: vec![],
+            authentication: vec![],
+            services: vec![],
+        }
+    }
+
+    /// Adds `keyAgreement` or `authentication` key, depending on role of `key`.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - id of verification method, e.g. `did:example:bob#key-1`
+    ///
+    /// * `key` - public key
+    pub fn with_key(mut self, kid: &str, key: KeyMaterial) -> Self {
+        match key.role {
+            KeyRole::KeyAgreement => self.key_agreement.push((kid.into(), key)),
+            KeyRole::Signing => self.authentication.push((kid.into(), key)),
+        }
+        self
+    }
+
+    /// Adds `DIDCommMessaging` service.
+    ///
+    /// # Parameters
+    ///
+    /// * `service` - service of DID document
+    pub fn with_service(mut self, service: DidCommMessagingService) -> Self {
+        self.services.push(service);
+        self
+    }
+
+    /// Parses keys and `DIDCommMessaging` services of DID document,
+    ///     e.g. as returned by a universal resolver.
+    /// Verification methods of `keyAgreement` and `authentication` are read from
+    ///     `publicKeyMultibase`, `publicKeyJwk` or `publicKeyBase58`, embedded or
+    ///     referenced by id. Keys of unsupported types and other services are skipped.
+    ///
+    /// # Errors
+    ///
+    /// `Error::DidResolveFailed` if document has no `id`.
+    ///
+    /// # Parameters
+    ///
+    /// * `document` - DID document as JSON
+    pub fn from_document(document: &Value) -> Result<Self> {
+        let did = document["id"].as_str().ok_or(Error::DidResolveFailed)?;
+        let methods: Vec<&Value> = document["verificationMethod"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .collect();
+        let mut resolved = Self::new(did);
+        for (relationship, role) in [
+            ("keyAgreement", KeyRole::KeyAgreement),
+            ("authentication", KeyRole::Signing),
+        ]
+        .iter()
+        {
+            for entry in document[*relationship].as_array().into_iter().flatten() {
+                let method = match entry {
+                    Value::String(id) => methods
+                        .iter()
+                        .find(|method| {
+                            method["id"].as_str().map(|kid| absolute_kid(did, kid))
+                                == Some(absolute_kid(did, id))
+                        })
+                        .copied(),
+                    method => Some(method),
+                };
+                if let Some((kid, key)) = method.and_then(|method| verification_key(did, method)) {
+                    if key.role == *role {
+                        resolved = resolved.with_key(&kid, key);
+                    }
+                }
+            }
+        }
+        for service in document["service"].as_array().into_iter().flatten() {
+            if service["type"] != DIDCOMM_MESSAGING_SERVICE_TYPE {
+                continue;
+            }
+            match DidCommMessagingService::try_from(service) {
+                Ok(service) => resolved = resolved.with_service(service),
+                Err(e) => log::warn!("skipped service of {}: {}", did, e),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Gets `keyAgreement` key with given id or, if there is none, first one.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - preferred key id, e.g. `skid` of received message
+    pub fn key_agreement_key(&self, kid: Option<&str>) -> Option<&KeyMaterial> {
+        self.key_agreement
+            .iter()
+            .find(|(id, _)| Some(id.as_str()) == kid)
+            .or_else(|| self.key_agreement.first())
+            .map(|(_, key)| key)
+    }
+}
+
+/// Trait must be implemented for pluggable DID resolution,
+///     see [`Agent`][crate::Agent] for using it.
+pub trait DidResolver: Send + Sync {
+    /// Resolves DID into its keys and services.
+    ///
+    /// # Errors
+    ///
+    /// `Error::DidResolveFailed` if DID can't be resolved.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID without fragment
+    fn resolve(&self, did: &str) -> Result<ResolvedDid>;
+}
+
+/// Future returned by [`AsyncDidResolver`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<ResolvedDid>> + Send + 'a>>;
+
+/// Future returned by [`AsyncDidResolver`].
+/// Not `Send` on wasm, where it may await JavaScript promises.
+#[cfg(target_arch = "wasm32")]
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<ResolvedDid>> + 'a>>;
+
+/// Async variant of [`DidResolver`] for resolution which has to be awaited,
+///     e.g. by a resolver of the browser, see `Agent::receive_async` and `Agent::pack_async`.
+/// Implemented for closures `Fn(String) -> impl Future<Output = Result<ResolvedDid>>`.
+pub trait AsyncDidResolver {
+    /// Resolves DID into its keys and services.
+    ///
+    /// # Errors
+    ///
+    /// `Error::DidResolveFailed` if DID can't be resolved.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID without fragment
+    fn resolve<'a>(&'a self, did: &'a str) -> ResolveFuture<'a>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<F, Fut> AsyncDidResolver for F
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<ResolvedDid>> + Send + 'static,
+{
+    fn resolve<'a>(&'a self, did: &'a str) -> ResolveFuture<'a> {
+        Box::pin(self(did.to_string()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<F, Fut> AsyncDidResolver for F
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<ResolvedDid>> + 'static,
+{
+    fn resolve<'a>(&'a self, did: &'a str) -> ResolveFuture<'a> {
+        Box::pin(self(did.to_string()))
+    }
+}
+
+/// [`DidResolver`] of DIDs resolved ahead by an [`AsyncDidResolver`].
+/// Remembers DID it was asked for but didn't have, so it can be resolved
+///     before the next attempt.
+#[derive(Default)]
+pub(crate) struct PrefetchedDidResolver {
+    dids: Mutex<HashMap<String, ResolvedDid>>,
+    missing: Mutex<Option<String>>,
+}
+
+impl PrefetchedDidResolver {
+    /// Resolves DID missing in last attempt, if any.
+    /// Returns `false` if nothing was missing.
+    pub(crate) async fn resolve_missing(&self, resolver: &dyn AsyncDidResolver) -> Result<bool> {
+        let missing = lock(&self.missing).take();
+        match missing {
+            Some(did) => {
+                let resolved = resolver.resolve(&did).await?;
+                lock(&self.dids).insert(did, resolved);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl DidResolver for PrefetchedDidResolver {
+    fn resolve(&self, did: &str) -> Result<ResolvedDid> {
+        match lock(&self.dids).get(did) {
+            Some(resolved) => Ok(resolved.clone()),
+            None => {
+                *lock(&self.missing) = Some(did.into());
+                Err(Error::DidResolveFailed)
+            }
+        }
+    }
+}
+
+/// In-memory [`DidResolver`] of explicitly registered DIDs.
+/// Unregistered `did:key`s are resolved from their key.
+#[derive(Default)]
+pub struct InMemoryDidResolver {
+    dids: Mutex<HashMap<String, ResolvedDid>>,
+}
+
+impl InMemoryDidResolver {
+    /// Constructor of resolver without registered DIDs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers or replaces resolved DID.
+    ///
+    /// # Parameters
+    ///
+    /// * `resolved` - keys and services of `resolved.did`
+    pub fn insert(&self, resolved: ResolvedDid) {
+        self.dids().insert(resolved.did.clone(), resolved);
+    }
+
+    fn dids(&self) -> MutexGuard<'_, HashMap<String, ResolvedDid>> {
+        lock(&self.dids)
+    }
+}
+
+impl DidResolver for InMemoryDidResolver {
+    fn resolve(&self, did: &str) -> Result<ResolvedDid> {
+        if let Some(resolved) = self.dids().get(did) {
+            return Ok(resolved.clone());
+        }
+        let multibase = did
+            .strip_prefix("did:key:")
+            .ok_or(Error::DidResolveFailed)?;
+        let key = KeyMaterial::detect(did).map_err(|_| Error::DidResolveFailed)?;
+        Ok(ResolvedDid::new(did).with_key(&format!("{}#{}", did, multibase), key))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Id of verification method, with relative ids like `#key-1` resolved against DID.
+fn absolute_kid(did: &str, id: &str) -> String {
+    if id.starts_with('#') {
+        format!("{}{}", did, id)
+    } else {
+        id.to_string()
+    }
+}
+
+/// Id and public key of verification method, if its type is supported.
+fn verification_key(did: &str, method: &Value) -> Option<(String, KeyMaterial)> {
+    let kid = absolute_kid(did, method["id"].as_str()?);
+    let key = if let Some(multibase) = method["publicKeyMultibase"].as_str() {
+        KeyMaterial::detect(multibase)
+    } else if let Some(jwk) = method.get("publicKeyJwk") {
+        serde_json::from_value::<Jwk>(jwk.clone())
+            .map_err(Into::into)
+            .and_then(|jwk| KeyMaterial::detect(&jwk))
+    } else {
+        let key = method["publicKeyBase58"].as_str()?.from_base58().ok()?;
+        KeyMaterial::detect(KeyInput::VerificationMethod {
+            kind: method["type"].as_str()?,
+            key: &key,
+        })
+    };
+    match key {
+        Ok(key) => Some((kid, key)),
+        Err(e) => {
+            log::warn!("skipped verification method {}: {}", kid, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base58::ToBase58;
+    use serde_json::json;
+
+    use super::*;
+    use crate::x25519_did_key;
+
+    #[test]
+    fn resolved_did_is_parsed_from_document() -> Result<()> {
+        let x25519 = x25519_did_key(&[1; 32]);
+        let ed25519 = [2; 32];
+        let document = json!({
+            "id": "did:example:bob",
+            "verificationMethod": [
+                {
+                    "id": "#key-1",
+                    "type": "X25519KeyAgreementKey2020",
+                    "controller": "did:example:bob",
+                    "publicKeyMultibase": x25519.trim_start_matches("did:key:"),
+                },
+                {
+                    "id": "did:example:bob#key-2",
+                    "type": "JsonWebKey2020",
+                    "controller": "did:example:bob",
+                    "publicKeyJwk": {
+                        "kty": "OKP",
+                        "crv": "Ed25519",
+                        "x": base64_url::encode(&ed25519),
+                    },
+                },
+            ],
+            "keyAgreement": ["did:example:bob#key-1", "#key-2"],
+            "authentication": [
+                "#key-2",
+                {
+                    "id": "#key-3",
+                    "type": "Ed25519VerificationKey2018",
+                    "controller": "did:example:bob",
+                    "publicKeyBase58": ([3; 32]).to_base58(),
+                },
+                {
+                    "id": "#key-4",
+                    "type": "UnknownKey2099",
+                    "publicKeyBase58": ([4; 32]).to_base58(),
+                },
+            ],
+            "service": [
+                {
+                    "id": "#didcomm",
+                    "type": "DIDCommMessaging",
+                    "serviceEndpoint": { "uri": "https://bob.example", "routingKeys": [] },
+                },
+                { "id": "#web", "type": "LinkedDomains", "serviceEndpoint": "https://bob.example" },
+            ],
+        });
+        let resolved = ResolvedDid::from_document(&document)?;
+        assert_eq!(resolved.did, "did:example:bob");
+        let kids = |keys: &[(String, KeyMaterial)]| -> Vec<String> {
+            keys.iter().map(|(kid, _)| kid.clone()).collect()
+        };
+        // Ed25519 key is no key agreement key, even if listed as one
+        assert_eq!(kids(&resolved.key_agreement), ["did:example:bob#key-1"]);
+        assert_eq!(
+            kids(&resolved.authentication),
+            ["did:example:bob#key-2", "did:example:bob#key-3"]
+        );
+        assert_eq!(resolved.key_agreement_key(None).unwrap().bytes, [1; 32]);
+        assert_eq!(resolved.authentication[0].1.bytes, ed25519);
+        assert_eq!(resolved.services.len(), 1);
+        assert_eq!(resolved.services[0].uri, "https://bob.example");
+
+        assert!(matches!(
+            ResolvedDid::from_document(&json!({})),
+            Err(Error::DidResolveFailed)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use serde_json::value::RawValue;
+
+use crate::{Jwe, MessageType, DIDCOMM_V2_PROFILE};
+
+/// `type` of DIDComm routing forward messages.
+const FORWARD_TYPE: &str = "https://didcomm.org/routing/2.0/forward";
+
+/// Kind of raw DIDComm payload, as detected by [`EnvelopeKind::detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeKind {
+    /// plain JSON message
+    Plain,
+    /// signed JWS envelope
+    Jws,
+    /// encrypted JWE envelope
+    Jwe,
+    /// routing forward message, either plain or wrapped into JWE
+    Forward,
+    /// not a DIDComm message this crate can handle
+    Unknown,
+}
+
+/// Helper type to check for well known top level fields without parsing whole message
+#[derive(Deserialize)]
+struct EnvelopeFields<'a> {
+    #[serde(borrow)]
+    signature: Option<&'a RawValue>,
+
+    #[serde(borrow)]
+    signatures: Option<&'a RawValue>,
+
+    #[serde(borrow)]
+    iv: Option<&'a RawValue>,
+
+    #[serde(borrow)]
+    ciphertext: Option<&'a RawValue>,
+
+    typ: Option<String>,
+
+    #[serde(rename = "type")]
+    m_type: Option<String>,
+}
+
+impl EnvelopeKind {
+    /// Detects kind of raw payload by its well known fields.
+    /// Payload is not decrypted or verified, so result is only a routing hint.
+    ///
+    /// # Arguments
+    ///
+    /// * `incoming` - raw, serialized message as received by transport
+    pub fn detect(incoming: &str) -> Self {
+        let fields: EnvelopeFields = match serde_json::from_str(incoming) {
+            Ok(fields) => fields,
+            Err(_) => return EnvelopeKind::Unknown,
+        };
+        if fields.iv.is_some() || fields.ciphertext.is_some() {
+            return match serde_json::from_str::<Jwe>(incoming) {
+                Ok(jwe) => {
+                    let header = jwe.protected.as_ref().or(jwe.unprotected.as_ref());
+                    match header.map(|header| &header.typ) {
+                        Some(MessageType::DidCommForward) => EnvelopeKind::Forward,
+                        Some(MessageType::DidCommKeyExport) => EnvelopeKind::Unknown,
+                        _ => EnvelopeKind::Jwe,
+                    }
+                }
+                Err(_) => EnvelopeKind::Unknown,
+            };
+        }
+        if fields.signatures.is_some() || fields.signature.is_some() {
+            return EnvelopeKind::Jws;
+        }
+        match (fields.typ, fields.m_type) {
+            (_, Some(m_type)) if m_type == FORWARD_TYPE => EnvelopeKind::Forward,
+            (_, Some(_)) => EnvelopeKind::Plain,
+            (Some(typ), None) if typ.contains("didcomm-plain") => EnvelopeKind::Plain,
+            _ => EnvelopeKind::Unknown,
+        }
+    }
+
+    /// Media type of envelopes of this kind, e.g. for `Content-Type` of transports.
+    /// Forward messages are expected to be encrypted.
+    pub fn media_type(&self) -> Option<&'static str> {
+        match self {
+            EnvelopeKind::Plain => Some("application/didcomm-plain+json"),
+            EnvelopeKind::Jws => Some("application/didcomm-signed+json"),
+            EnvelopeKind::Jwe | EnvelopeKind::Forward => Some("application/didcomm-encrypted+json"),
+            EnvelopeKind::Unknown => None,
+        }
+    }
+
+    /// Envelope kind of given media type, `None` for unknown media types.
+    ///
+    /// # Arguments
+    ///
+    /// * `media_type` - media type, e.g. `application/didcomm-signed+json`
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/didcomm-plain+json" => Some(EnvelopeKind::Plain),
+            "application/didcomm-signed+json" => Some(EnvelopeKind::Jws),
+            "application/didcomm-encrypted+json" => Some(EnvelopeKind::Jwe),
+            _ => None,
+        }
+    }
+
+    /// Picks envelope for replies from `accept` list of received message,
+    ///     see [`Message::get_accept`][crate::Message::get_accept()].
+    /// First entry this crate can produce wins, `didcomm/v2` profile means encrypted envelope.
+    /// Encrypted envelope is picked if list is empty, `None` if no entry is supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `accept` - media types and profiles in order of preference
+    pub fn negotiate(accept: &[&str]) -> Option<Self> {
+        if accept.is_empty() {
+            return Some(EnvelopeKind::Jwe);
+        }
+        accept.iter().find_map(|entry| match *entry {
+            DIDCOMM_V2_PROFILE => Some(EnvelopeKind::Jwe),
+            media_type => Self::from_media_type(media_type),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JwmHeader, Message};
+
+    #[test]
+    fn detects_plain_messages() {
+        let plain = Message::new().m_type("https://example.com/test/1.0/ping");
+        assert_eq!(
+            EnvelopeKind::detect(&plain.to_string()),
+            EnvelopeKind::Plain
+        );
+        let forward = Message::new().m_type(FORWARD_TYPE);
+        assert_eq!(
+            EnvelopeKind::detect(&forward.to_string()),
+            EnvelopeKind::Forward
+        );
+    }
+
+    #[test]
+    fn detects_envelopes() {
+        let jwe = Jwe::new(None, None, b"payload", Some(JwmHeader::default()), None::<&[u8]>, None);
+        assert_eq!(EnvelopeKind::detect(&jwe.to_string()), EnvelopeKind::Jwe);
+        let forward_header = JwmHeader {
+            typ: MessageType::DidCommForward,
+            ..Default::default()
+        };
+        let forward = Jwe::new(None, None, b"payload", Some(forward_header), None::<&[u8]>, None);
+        assert_eq!(
+            EnvelopeKind::detect(&forward.to_string()),
+            EnvelopeKind::Forward
+        );
+        assert_eq!(
+            EnvelopeKind::detect(r#"{"payload":"e30","signatures":[]}"#),
+            EnvelopeKind::Jws
+        );
+    }
+
+    #[test]
+    fn negotiates_reply_envelope() {
+        let invitation = Message::new()
+            .m_type(MessageType::DidCommInvitation.as_str())
+            .accept(&["didcomm/aip2;env=rfc19", "application/didcomm-signed+json"]);
+        assert_eq!(
+            invitation.body_at("/accept/1"),
+            Some(&"application/didcomm-signed+json".into())
+        );
+        assert_eq!(
+            EnvelopeKind::negotiate(&invitation.get_accept()),
+            Some(EnvelopeKind::Jws)
+        );
+        let message: Message = Message::new().accept(&["didcomm/v2"]).to_string().parse().unwrap();
+        assert_eq!(message.get_accept(), ["didcomm/v2"]);
+        assert_eq!(EnvelopeKind::negotiate(&message.get_accept()), Some(EnvelopeKind::Jwe));
+        assert_eq!(EnvelopeKind::negotiate(&["didcomm/aip1"]), None);
+    }
+
+    #[test]
+    fn detects_garbage_as_unknown() {
+        assert_eq!(EnvelopeKind::detect("not json"), EnvelopeKind::Unknown);
+        assert_eq!(EnvelopeKind::detect(r#"{"foo":1}"#), EnvelopeKind::Unknown);
+    }
+}
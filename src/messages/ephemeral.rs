@@ -0,0 +1,163 @@
+use std::convert::TryInto;
+
+use base58::{FromBase58, ToBase58};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    crypto::key_material::{ED25519_MULTICODEC_PREFIX, X25519_MULTICODEC_PREFIX},
+    Error, Message, Result,
+};
+
+/// Builds `did:key` of X25519 public key, e.g. `did:key:z6LS...`.
+///
+/// # Parameters
+///
+/// * `public_key` - X25519 public key
+pub fn x25519_did_key(public_key: &[u8; 32]) -> String {
+    did_key(X25519_MULTICODEC_PREFIX, public_key)
+}
+
+/// Builds `did:key` of Ed25519 public key, e.g. `did:key:z6Mk...`.
+///
+/// # Parameters
+///
+/// * `public_key` - Ed25519 public key
+pub fn ed25519_did_key(public_key: &[u8; 32]) -> String {
+    did_key(ED25519_MULTICODEC_PREFIX, public_key)
+}
+
+/// `did:key` of public key with given multicodec prefix.
+fn did_key(multicodec_prefix: &[u8], public_key: &[u8]) -> String {
+    format!(
+        "did:key:z{}",
+        [multicodec_prefix, public_key].concat().to_base58()
+    )
+}
+
+/// Extracts X25519 public key from `did:key` or its DID URL, e.g. `skid` of
+///     a message sealed with [`Message::seal_ephemeral`].
+///
+/// # Errors
+///
+/// `Error::BadDid` if `did` is not a `did:key` of an X25519 key.
+///
+/// # Parameters
+///
+/// * `did` - `did:key` DID or DID URL
+pub fn x25519_key_from_did_key(did: &str) -> Result<[u8; 32]> {
+    let multibase = did
+        .strip_prefix("did:key:z")
+        .and_then(|key| key.split('#').next())
+        .ok_or(Error::BadDid)?;
+    let multicodec = multibase.from_base58().map_err(|_| Error::BadDid)?;
+    multicodec
+        .strip_prefix(X25519_MULTICODEC_PREFIX)
+        .and_then(|key| key.try_into().ok())
+        .ok_or(Error::BadDid)
+}
+
+/// Sender identity generated for a single message, see [`Message::seal_ephemeral`].
+/// Keep it to decrypt replies to `did`, drop it to stay unlinkable.
+pub struct EphemeralSender {
+    /// `did:key` of sender, used as `from` and `skid`
+    pub did: String,
+
+    /// X25519 private key of `did`
+    pub private_key: [u8; 32],
+}
+
+impl EphemeralSender {
+    /// Generates new random sender.
+    pub fn generate() -> Self {
+        let secret = StaticSecret::random_from_rng(rand_core::OsRng);
+        Self {
+            did: x25519_did_key(PublicKey::from(&secret).as_bytes()),
+            private_key: secret.to_bytes(),
+        }
+    }
+
+    /// X25519 public key of `did`.
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&StaticSecret::from(self.private_key)).to_bytes()
+    }
+}
+
+impl Message {
+    /// Seals (encrypts) self authcrypted from freshly generated `did:key` sender,
+    ///     so recipients can reply without learning our stable DID.
+    /// `from` is replaced by ephemeral DID and `from_prior` is removed.
+    /// Returns JWE and ephemeral sender, needed to receive replies.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///   can be provided if key should not be resolved via recipients DID
+    pub fn seal_ephemeral(
+        mut self,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<(String, EphemeralSender)> {
+        let sender = EphemeralSender::generate();
+        self.didcomm_header.set_from_prior(None);
+        let sealed = self
+            .from(&sender.did)
+            .seal(sender.private_key, recipient_public_keys)?;
+        Ok((sealed, sender))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{crypto::CryptoAlgorithm, Jwe};
+
+    #[test]
+    fn ephemeral_sender_can_receive_reply() -> Result<()> {
+        let KeyPairSet {
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let (sealed, sender) = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal_ephemeral(Some(vec![Some(bobs_public.to_vec())]))?;
+        assert!(sender.did.starts_with("did:key:z6LS"));
+
+        // recipient takes sender key from `skid`
+        let skid = sealed.parse::<Jwe>()?.get_skid().unwrap_or_default();
+        assert_eq!(skid, sender.did);
+        let sender_public = x25519_key_from_did_key(&skid)?;
+        assert_eq!(sender_public, sender.public_key());
+        let received = Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(sender_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(
+            received.get_didcomm_header().from.as_deref(),
+            Some(sender.did.as_str())
+        );
+
+        let reply = Message::new()
+            .from("did:example:bob")
+            .to(&[&sender.did])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(sender_public.to_vec()))
+            .seal(bobs_private, Some(vec![Some(sender_public.to_vec())]))?;
+        Message::receive(
+            &reply,
+            Some(&sender.private_key),
+            Some(bobs_public.to_vec()),
+            None,
+        )?;
+
+        assert!(matches!(
+            x25519_key_from_did_key("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"),
+            Err(Error::BadDid)
+        ));
+        Ok(())
+    }
+}
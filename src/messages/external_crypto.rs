@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use base64_url::{decode, encode};
+
+use crate::{
+    messages::helpers::{compress, jwm_header_to_string},
+    Error,
+    Jwe,
+    Jwk,
+    JwmHeader,
+    Message,
+    Recipient,
+    Result,
+};
+
+/// Everything needed to build a correct JWE with content encryption done outside of this crate,
+///     e.g. in an HSM or a platform crypto API.
+/// Created by [`Message::export_encryption_context`] and consumed by
+///     [`Message::seal_pre_encrypted_with`].
+#[derive(Debug, Clone)]
+pub struct ExternalCryptoContext {
+    /// protected header of the JWE
+    pub protected: JwmHeader,
+
+    /// protected header serialized as JSON, as it will be encoded into the JWE
+    pub protected_bytes: Vec<u8>,
+
+    /// additional authenticated data for content encryption, base64url encoded `protected_bytes`
+    pub aad: Vec<u8>,
+
+    /// initialization vector for content encryption
+    pub iv: Vec<u8>,
+
+    /// serialized message to encrypt, already compressed if `zip` is set
+    pub plaintext: Vec<u8>,
+
+    /// key ids of recipients the content encryption key has to be wrapped for
+    pub recipients: Vec<String>,
+
+    /// expected content encryption algorithm, `enc` header if set, `alg` otherwise
+    pub enc: Option<String>,
+
+    /// `true` to serialize flat JWE JSON, taken from `as_flat_jwe`, requires single recipient
+    pub flat: bool,
+
+    /// recipient entries prepared with `add_recipient`
+    pub(crate) recipient_templates: Option<Vec<Recipient>>,
+
+    /// externally wrapped content encryption keys and their per-recipient headers by `kid`
+    pub(crate) wrapped_keys: HashMap<String, (Jwk, Vec<u8>)>,
+}
+
+impl ExternalCryptoContext {
+    /// Adds externally wrapped content encryption key of one recipient.
+    /// JWE gets per-recipient `encrypted_key` entries once any key is added.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - key id of the recipient, one of `recipients`
+    ///
+    /// * `header` - per-recipient header, e.g. with `epk` of key agreement; `kid` is set to `kid`
+    ///
+    /// * `encrypted_key` - wrapped content encryption key
+    pub fn with_encrypted_key(mut self, kid: &str, mut header: Jwk, encrypted_key: &[u8]) -> Self {
+        header.kid = Some(kid.into());
+        self.wrapped_keys
+            .insert(kid.into(), (header, encrypted_key.to_vec()));
+        self
+    }
+
+    /// Setter of flat JWE JSON serialization.
+    pub fn with_flat(mut self, flat: bool) -> Self {
+        self.flat = flat;
+        self
+    }
+
+    /// Builds recipient entries, `None` if the single recipient is named in protected header.
+    fn into_recipients(mut self) -> Result<Option<Vec<Recipient>>> {
+        if self.wrapped_keys.is_empty() {
+            return Ok(self.recipient_templates);
+        }
+        let mut recipients = vec![];
+        for kid in &self.recipients {
+            let (mut header, encrypted_key) = self.wrapped_keys.remove(kid).ok_or_else(|| {
+                Error::Generic(format!("no encrypted key given for recipient {}", kid))
+            })?;
+            let template = self
+                .recipient_templates
+                .iter()
+                .flatten()
+                .find(|template| template.header.kid.as_deref() == Some(kid.as_str()));
+            if let Some(template) = template {
+                for (key, value) in &template.header.other {
+                    header.other.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            recipients.push(Recipient::new(header, encode(&encrypted_key)));
+        }
+        if let Some(kid) = self.wrapped_keys.keys().next() {
+            return Err(Error::Generic(format!("{} is not a recipient", kid)));
+        }
+        Ok(Some(recipients))
+    }
+}
+
+impl Message {
+    /// Exports protected header, AAD, IV, plaintext and recipients for encryption outside of
+    ///     this crate. Result of the encryption is sealed with `seal_pre_encrypted_with`.
+    /// Message has to be prepared with `as_jwe` before.
+    pub fn export_encryption_context(&self) -> Result<ExternalCryptoContext> {
+        self.check_recipient_templates()?;
+        let protected = self.jwe_protected_header();
+        let protected_bytes = jwm_header_to_string(&protected)?.into_bytes();
+        let aad = encode(&protected_bytes).into_bytes();
+        let plaintext = compress(
+            serde_json::to_string(&self)?.as_bytes(),
+            protected.zip.as_deref(),
+        )?;
+        let recipients = match self.jwe_recipients() {
+            Some(recipients) => recipients
+                .iter()
+                .filter_map(|recipient| recipient.header.kid.clone())
+                .collect(),
+            None => self.didcomm_header.to.clone(),
+        };
+        let enc = protected.enc.clone().or_else(|| protected.alg.clone());
+        Ok(ExternalCryptoContext {
+            protected,
+            protected_bytes,
+            aad,
+            iv: decode(&Jwe::generate_iv())?,
+            plaintext,
+            recipients,
+            enc,
+            flat: self.serialize_flat_jwe,
+            recipient_templates: self.jwe_recipients().map(<[Recipient]>::to_vec),
+            wrapped_keys: HashMap::new(),
+        })
+    }
+
+    /// Builds JWE from context exported with `export_encryption_context`
+    ///     and externally produced ciphertext.
+    /// Content encryption key wrapped externally per recipient is added to the context
+    ///     with `with_encrypted_key`.
+    /// Flat JWE JSON is built if `context.flat` is set, which requires exactly one recipient entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - context the ciphertext was produced with
+    ///
+    /// * `ciphertext` - `context.plaintext` encrypted with `context.iv` and `context.aad`
+    ///
+    /// * `tag` - authentication tag of the encryption
+    pub fn seal_pre_encrypted_with(
+        context: ExternalCryptoContext,
+        ciphertext: impl AsRef<[u8]>,
+        tag: impl AsRef<[u8]>,
+    ) -> Result<String> {
+        if tag.as_ref().is_empty() {
+            return Err(Error::Generic("authentication tag is empty".into()));
+        }
+        let protected = Some(context.protected.clone());
+        let iv = Some(encode(&context.iv));
+        let flat = context.flat;
+        let recipients = context.into_recipients()?;
+        let jwe = if flat {
+            match recipients {
+                Some(mut recipients) if recipients.len() == 1 => {
+                    Jwe::new_flat(None, recipients.remove(0), ciphertext, protected, Some(tag), iv)
+                }
+                recipients => {
+                    return Err(Error::FlatSerializationRecipients(
+                        recipients.map_or(0, |recipients| recipients.len()),
+                    ))
+                }
+            }
+        } else {
+            Jwe::new(None, recipients, ciphertext, protected, Some(tag), iv)
+        };
+        Ok(serde_json::to_string(&jwe)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chacha20poly1305::{
+        aead::{Aead, KeyInit, Payload},
+        XChaCha20Poly1305,
+        XNonce,
+    };
+
+    use super::*;
+    use crate::crypto::CryptoAlgorithm;
+
+    #[test]
+    fn externally_encrypted_message_can_be_decrypted() -> Result<()> {
+        let cek = [7u8; 32];
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, None)
+            .compressed();
+        let id = message.get_id().to_string();
+        let context = message.export_encryption_context()?;
+        assert_eq!(context.recipients, ["did:example:bob"]);
+        assert_eq!(context.aad, encode(&context.protected_bytes).into_bytes());
+
+        let aead = XChaCha20Poly1305::new(cek.as_ref().into());
+        let encrypted = aead
+            .encrypt(
+                XNonce::from_slice(&context.iv),
+                Payload {
+                    msg: &context.plaintext,
+                    aad: &context.aad,
+                },
+            )
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let (ciphertext, tag) = encrypted.split_at(encrypted.len() - 16);
+        let jwe = Message::seal_pre_encrypted_with(context, ciphertext, tag)?;
+
+        let decrypter = Box::new(|n: &[u8], k: &[u8], m: &[u8], a: &[u8]| {
+            XChaCha20Poly1305::new(k.into())
+                .decrypt(XNonce::from_slice(n), Payload { msg: m, aad: a })
+                .map_err(|e| Error::Generic(e.to_string()))
+        });
+        let received = Message::decrypt(jwe.as_bytes(), decrypter, &cek)?;
+        assert_eq!(received.get_id(), id);
+        Ok(())
+    }
+
+    #[test]
+    fn externally_wrapped_keys_are_added_to_flat_jwe() -> Result<()> {
+        let context = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob#key-1"])
+            .as_flat_jwe(&CryptoAlgorithm::XC20P, None)
+            .export_encryption_context()?;
+        assert!(context.flat);
+        let mut header = Jwk::new();
+        header.add_other_header("epk".into(), "ephemeral".into());
+        let context = context.with_encrypted_key("did:example:bob#key-1", header, b"wrapped");
+
+        let jwe: serde_json::Value = serde_json::from_str(&Message::seal_pre_encrypted_with(
+            context.clone(),
+            b"ciphertext",
+            [1u8; 16],
+        )?)?;
+        assert_eq!(jwe["encrypted_key"], encode(b"wrapped"));
+        assert_eq!(jwe["header"]["kid"], "did:example:bob#key-1");
+        assert_eq!(jwe["header"]["epk"], "ephemeral");
+        assert!(jwe.get("recipients").is_none());
+
+        let unknown = context
+            .with_flat(false)
+            .with_encrypted_key("did:example:carol", Jwk::new(), b"wrapped");
+        assert!(Message::seal_pre_encrypted_with(unknown, b"ciphertext", [1u8; 16]).is_err());
+        Ok(())
+    }
+}
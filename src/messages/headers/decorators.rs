@@ -10,12 +10,13 @@
 //! For more details, see Aries RFC
 //!
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 
 /// A `~thread` message decorator that provides request/reply
 /// and threading semantics according to Aries RFC 0008.
 #[derive(Default, Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Thread {
     /// The ID of the message that serves as the thread start.
     pub thid: String,
@@ -32,7 +33,8 @@ pub struct Thread {
     pub sender_order: Option<usize>,
 
     /// A dictionary of sender_order/highest messages received on the thread.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Serialized sorted by sender.
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted")]
     pub received_orders: Option<HashMap<String, usize>>,
 
     /// Code to convey an action.
@@ -96,6 +98,13 @@ impl Thread {
     }
 }
 
+/// Serializes map in key order, so same thread always serializes into same bytes.
+fn sorted<S: Serializer>(map: &Option<HashMap<String, usize>>, s: S) -> Result<S::Ok, S::Error> {
+    map.as_ref()
+        .map(|map| map.iter().collect::<BTreeMap<_, _>>())
+        .serialize(s)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
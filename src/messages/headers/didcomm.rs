@@ -1,10 +1,13 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::{collections::BTreeMap, time::SystemTime};
 
-use crate::{Error, PriorClaims, Thread};
+use serde_json::Value;
+
+use crate::{messages::id_generator::generate_id, Error, PriorClaims, ReturnRoute, Thread};
 
 /// Collection of DIDComm message specific headers, will be flattened into DIDComm plain message
 /// according to [spec](https://datatracker.ietf.org/doc/html/draft-looker-jwm-01#section-4).
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct DidCommHeader {
     pub id: String,
 
@@ -20,6 +23,8 @@ pub struct DidCommHeader {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub to: Vec<String>,
 
+    /// Sender DID, `None` for anonymous messages.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub from: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -27,16 +32,36 @@ pub struct DidCommHeader {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_time: Option<u64>,
+
+    /// Time in seconds since Unix Epoch when message was handed over to transport.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sent_time: Option<u64>,
+
+    /// Milliseconds message was held back by sender or mediators before delivery.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_milli: Option<u64>,
+
+    /// Language of user facing content, as IETF BCP 47 tag, e.g. `en` or `de-CH`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
     /// A JWT, with sub: new DID and iss: prior DID,
     /// with a signature from a key authorized by prior DID.
     #[serde(skip_serializing_if = "Option::is_none")]
     from_prior: Option<PriorClaims>,
 
+    /// Envelope media types and profiles the sender can handle, in order of preference.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+
+    /// Transport return route extension, see [`ReturnRoute`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_route: Option<ReturnRoute>,
+
     /// Optional thread decorator.
     #[serde(skip_serializing_if = "Option::is_none", rename = "~thread")]
     pub thread: Option<Thread>,
-    #[serde(flatten, skip_serializing_if = "HashMap::is_empty")]
-    pub(crate) other: HashMap<String, String>,
+    #[serde(flatten, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) other: BTreeMap<String, Value>,
 }
 
 impl DidCommHeader {
@@ -51,16 +76,21 @@ impl DidCommHeader {
             from: Some(String::default()),
             created_time: None,
             expires_time: None,
+            sent_time: None,
+            delay_milli: None,
+            lang: None,
             from_prior: None,
+            accept: vec![],
+            return_route: None,
             thread: None,
-            other: HashMap::new(),
+            other: BTreeMap::new(),
         }
     }
 
-    /// Generates random `id`
+    /// Generates `id` with configured `IdGenerator`, random UUIDv4 by default.
     /// TODO: Should this be public?
     pub fn gen_random_id() -> String {
-        uuid::Uuid::new_v4().to_string()
+        generate_id()
     }
 
     /// Returns DIDComm message URI as defined by spec:
@@ -101,6 +131,11 @@ impl DidCommHeader {
         self.from_prior.as_ref()
     }
 
+    /// Setter method for `from_prior`, `None` removes it
+    pub fn set_from_prior(&mut self, claims: Option<PriorClaims>) {
+        self.from_prior = claims;
+    }
+
     /// Creates set of DIDComm related headers with the static forward type
     pub fn forward(
         to: Vec<String>,
@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use serde_json::Value;
 
 /// Encryption public key
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Epk {
     pub kty: String,
 
@@ -15,6 +18,7 @@ pub struct Epk {
 
 /// Json Web Keys structure defined by [RFC](https://tools.ietf.org/html/rfc7517)
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Jwk {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kty: Option<String>,
@@ -25,8 +29,12 @@ pub struct Jwk {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub crv: Option<String>,
 
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub key_ops: Vec<KeyOps>,
 
+    #[serde(default)]
+    #[serde(skip_serializing_if = "KeyAlgorithm::is_none")]
     pub alg: KeyAlgorithm,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -36,7 +44,7 @@ pub struct Jwk {
     pub epk: Option<Epk>,
 
     #[serde(flatten)]
-    pub(crate) other: HashMap<String, String>,
+    pub(crate) other: BTreeMap<String, Value>,
 }
 
 impl Jwk {
@@ -54,12 +62,48 @@ impl Jwk {
 
     /// Insert new custom, non-defined by spec, header.
     pub fn add_other_header(&mut self, k: String, v: String) {
-        self.other.insert(k, v);
+        self.other.insert(k, Value::String(v));
+    }
+
+    /// Gets raw JSON value of any header member, e.g. `kid`, `alg` or custom ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header member
+    pub fn get_header(&self, key: &str) -> Option<Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(mut members)) => members.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Sets any header member, known ones like `alg` are set on their typed field.
+    /// Returns `Err` if value is not valid for a known member.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header member
+    ///
+    /// * `value` - value of the header member
+    pub fn set_header(&mut self, key: &str, value: Value) -> Result<(), crate::Error> {
+        let mut members = match serde_json::to_value(&*self)? {
+            Value::Object(members) => members,
+            _ => serde_json::Map::new(),
+        };
+        members.insert(key.into(), value);
+        *self = serde_json::from_value(Value::Object(members))?;
+        Ok(())
+    }
+
+    /// Custom header members, which are not known to this struct.
+    pub fn other_headers(&self) -> &BTreeMap<String, Value> {
+        &self.other
     }
 }
 
 // WARN: Does not support other key operation types ATM.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub enum KeyOps {
     Sign,
@@ -76,6 +120,7 @@ pub enum KeyOps {
 /// `alg` field values provided by [RFC](https://tools.ietf.org/html/rfc7518)
 ///
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum KeyAlgorithm {
     HS256,
     HS384,
@@ -105,6 +150,8 @@ pub enum KeyAlgorithm {
     EcdhEsPulsA192kw,
     #[serde(rename = "ECDH-ES+A256KW")]
     EcdhEsA256kw,
+    #[serde(rename = "ECDH-ES+XC20PKW")]
+    EcdhEsXc20pkw,
     A128GCMKW,
     A192GCMKW,
     A256GCMKW,
@@ -131,6 +178,13 @@ impl std::string::ToString for KeyAlgorithm {
     }
 }
 
+impl KeyAlgorithm {
+    /// Checks if no algorithm is set.
+    pub fn is_none(&self) -> bool {
+        *self == KeyAlgorithm::None
+    }
+}
+
 impl std::default::Default for KeyAlgorithm {
     fn default() -> Self {
         KeyAlgorithm::None
@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
 #[cfg(feature = "raw-crypto")]
-use crate::crypto::{CryptoAlgorithm, SignatureAlgorithm};
-use crate::{Jwk, MessageType};
+use crate::crypto::{ContentEncAlg, CryptoAlgorithm, KeyAgreementAlg, SignatureAlgorithm};
+use crate::{messages::helpers::JWM_HEADER_FIELDS, Jwk, MessageType};
 
 /// JWM Header as specified in [RFC](https://tools.ietf.org/html/draft-looker-jwm-01#section-2.3)
 /// With single deviation - allows raw text JWM to support DIDComm spec
@@ -9,7 +13,12 @@ use crate::{Jwk, MessageType};
 ///
 /// `iv` property is not explicitly listed in the registered properties on the RFC but is present
 ///     within example lists - used here as DIDComm crypto nonce sharing property.
+///
+/// Serialized protected headers have stable field order: known parameters in order
+///     of declaration below, followed by parameters not known to this crate sorted by name.
+/// Same header therefore always serializes into same bytes, so does AAD and signing input.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct JwmHeader {
     pub typ: MessageType,
 
@@ -45,10 +54,40 @@ pub struct JwmHeader {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub epk: Option<Jwk>,
 
+    // Agreement PartyUInfo, base64url encoded information about the producer.
+    // Used as input of key derivation when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apu: Option<String>,
+
+    // Agreement PartyVInfo, base64url encoded information about the recipient.
+    // Used as input of key derivation when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apv: Option<String>,
+
     // Some("JWM") should be used if nested JWS inside JWE.
     // None otherwise is *STRONGLY RECOMMENDED* by RFC.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cty: Option<String>,
+
+    // Some("DEF") if plaintext is DEFLATE compressed before encryption.
+    // Only valid for JWE.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zip: Option<String>,
+
+    // Some(false) if JWS payload is not base64url encoded (RFC 7797).
+    // Only valid for JWS, requires "b64" to be listed in `crit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b64: Option<bool>,
+
+    // Header parameters which must be understood by recipient.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<Vec<String>>,
+
+    // Header parameters not known to this crate.
+    // Preserved when header is (de)serialized as standalone JOSE header,
+    // ends up in `DidCommHeader` when flattened into plain message.
+    #[serde(skip)]
+    pub(crate) other: BTreeMap<String, Value>,
 }
 
 impl JwmHeader {
@@ -61,12 +100,18 @@ impl JwmHeader {
             SignatureAlgorithm::EdDsa => {
                 self.alg = Some(String::from("EdDSA"));
             }
+            SignatureAlgorithm::Ed448 => {
+                self.alg = Some(String::from("Ed448"));
+            }
             SignatureAlgorithm::Es256 => {
                 self.alg = Some(String::from("ES256"));
             }
             SignatureAlgorithm::Es256k => {
                 self.alg = Some(String::from("ES256K"));
             }
+            SignatureAlgorithm::Es256kR => {
+                self.alg = Some(String::from("ES256K-R"));
+            }
         }
     }
 
@@ -74,26 +119,92 @@ impl JwmHeader {
     /// Modifies `enc`, `typ` and `alg` headers.
     #[cfg(feature = "raw-crypto")]
     pub fn as_encrypted(&mut self, alg: &CryptoAlgorithm) {
-        self.typ = MessageType::DidCommJwe;
-        match alg {
-            CryptoAlgorithm::A256GCM => {
-                self.enc = Some("A256GCM".into());
-                self.alg = Some("ECDH-1PU+A256KW".into());
-            }
-            CryptoAlgorithm::XC20P => {
-                self.enc = Some("XC20P".into());
-                self.alg = Some("ECDH-1PU+XC20PKW".into());
+        match (alg.key_agreement_alg(), alg.content_enc_alg()) {
+            (Some(key_agreement), Some(content_enc)) => {
+                self.as_encrypted_with(key_agreement, content_enc)
             }
-            CryptoAlgorithm::A256CBC => {
+            _ => {
+                self.typ = MessageType::DidCommJwe;
                 self.alg = Some("A256CBC".into());
                 self.enc = Some("ECDH-1PU+A256KW".into())
             }
         }
     }
 
+    /// Setter of JOSE header properties for key agreement `alg` and content
+    ///     encryption `enc` chosen independently, e.g. `ECDH-1PU+XC20PKW` with `A256GCM`.
+    /// Modifies `enc`, `typ` and `alg` headers.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - key agreement and wrapping of content encryption key
+    ///
+    /// * `enc` - content encryption
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_encrypted_with(&mut self, alg: KeyAgreementAlg, enc: ContentEncAlg) {
+        self.typ = MessageType::DidCommJwe;
+        self.alg = Some(alg.as_str().into());
+        self.enc = Some(enc.as_str().into());
+    }
+
     pub fn kid(&mut self, kid: Option<String>) {
         self.kid = kid;
     }
+
+    /// Setter of `apu` header, base64url encoded agreement PartyUInfo.
+    pub fn apu(&mut self, apu: Option<String>) {
+        self.apu = apu;
+    }
+
+    /// Getter of `apu` header, base64url encoded agreement PartyUInfo.
+    pub fn get_apu(&self) -> Option<&str> {
+        self.apu.as_deref()
+    }
+
+    /// Setter of `apv` header, base64url encoded agreement PartyVInfo.
+    pub fn apv(&mut self, apv: Option<String>) {
+        self.apv = apv;
+    }
+
+    /// Getter of `apv` header, base64url encoded agreement PartyVInfo.
+    pub fn get_apv(&self) -> Option<&str> {
+        self.apv.as_deref()
+    }
+
+    /// Setter of `epk` header, ephemeral public key of key agreement.
+    pub fn epk(&mut self, epk: Option<Jwk>) {
+        self.epk = epk;
+    }
+
+    /// Getter of `epk` header, ephemeral public key of key agreement.
+    pub fn get_epk(&self) -> Option<&Jwk> {
+        self.epk.as_ref()
+    }
+
+    /// Setter of application defined header parameter, e.g. `nonce` or `url`.
+    /// Ends up in integrity protected header of `Jwe`/`Jws` envelopes.
+    /// Names of parameters with own fields (`typ`, `alg`, ...) are ignored.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header parameter
+    ///
+    /// * `value` - value of the header parameter
+    pub fn set_extra(&mut self, key: &str, value: Value) {
+        if !JWM_HEADER_FIELDS.contains(&key) {
+            self.other.insert(key.into(), value);
+        }
+    }
+
+    /// Getter of application defined header parameter set with `set_extra`
+    ///     or parsed from protected header by `Message::decrypt`/`Message::verify`.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header parameter
+    pub fn get_extra(&self, key: &str) -> Option<&Value> {
+        self.other.get(key)
+    }
 }
 
 impl Default for JwmHeader {
@@ -105,10 +216,16 @@ impl Default for JwmHeader {
             kid: None,
             skid: None,
             epk: None,
+            apu: None,
+            apv: None,
             alg: None,
             cty: None,
+            zip: None,
+            b64: None,
+            crit: None,
             jku: None,
             jwk: None,
+            other: BTreeMap::new(),
         }
     }
 }
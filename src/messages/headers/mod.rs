@@ -2,11 +2,13 @@ pub mod decorators;
 mod didcomm;
 mod jwk;
 mod jwm;
+mod piuri;
 mod prior_claims;
 mod types;
 pub use decorators::*;
 pub use didcomm::*;
 pub use jwk::*;
 pub use jwm::*;
+pub use piuri::*;
 pub use prior_claims::*;
 pub use types::*;
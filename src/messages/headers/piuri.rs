@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use regex::Regex;
+
+use crate::Error;
+
+/// [Protocol identifier URI](https://identity.foundation/didcomm-messaging/spec/#message-type-uri)
+///     of plaintext message `type`, e.g. `https://didcomm.org/trust-ping/2.0/ping`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piuri {
+    /// URI of protocol documentation, e.g. `https://didcomm.org`
+    pub doc_uri: String,
+
+    /// name of the protocol, e.g. `trust-ping`
+    pub protocol_name: String,
+
+    /// semantic version of the protocol, `major.minor` with optional patch
+    pub version: String,
+
+    /// name of the message type within protocol, e.g. `ping`
+    pub message_type_name: String,
+}
+
+impl FromStr for Piuri {
+    type Err = Error;
+
+    /// Parses `<doc-uri>/<protocol-name>/<semver>/<message-type-name>`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidPiuri` if value is not a well-formed PIURI.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let re = Regex::new(
+            r"^(?P<doc_uri>[a-zA-Z][a-zA-Z0-9+.-]*:\S*?)/(?P<protocol_name>[a-zA-Z0-9._-]+)/(?P<version>\d+\.\d+(\.\d+)?)/(?P<message_type_name>[a-zA-Z0-9._-]+)$",
+        )?;
+        let captures = re
+            .captures(value)
+            .ok_or_else(|| Error::InvalidPiuri(value.into()))?;
+        Ok(Piuri {
+            doc_uri: captures["doc_uri"].into(),
+            protocol_name: captures["protocol_name"].into(),
+            version: captures["version"].into(),
+            message_type_name: captures["message_type_name"].into(),
+        })
+    }
+}
+
+impl fmt::Display for Piuri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}",
+            self.doc_uri, self.protocol_name, self.version, self.message_type_name
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_well_formed_piuris() -> Result<(), Error> {
+        let piuri: Piuri = "https://didcomm.org/trust-ping/2.0/ping".parse()?;
+        assert_eq!(piuri.doc_uri, "https://didcomm.org");
+        assert_eq!(piuri.protocol_name, "trust-ping");
+        assert_eq!(piuri.version, "2.0");
+        assert_eq!(piuri.message_type_name, "ping");
+        assert_eq!(piuri.to_string(), "https://didcomm.org/trust-ping/2.0/ping");
+        assert!(
+            "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0/invitation"
+                .parse::<Piuri>()
+                .is_ok()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_piuris() {
+        for invalid in [
+            "JWM",
+            "https://didcomm.org/trust-ping/ping",
+            "https://didcomm.org/trust-ping/two/ping",
+            "didcomm.org/trust-ping/2.0/ping",
+            "https://didcomm.org/trust-ping/2.0/",
+        ] {
+            assert!(
+                matches!(invalid.parse::<Piuri>(), Err(Error::InvalidPiuri(_))),
+                "{} accepted",
+                invalid
+            );
+        }
+    }
+}
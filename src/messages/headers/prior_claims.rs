@@ -1,13 +1,172 @@
-use std::{convert::TryFrom, str::FromStr};
+use std::{convert::TryFrom, str::FromStr, time::SystemTime};
 
 use crate::Error as CrateError;
 
 /// header used for [DID rotation](https://identity.foundation/didcomm-messaging/spec/#did-rotation)
+/// Contains claims of `from_prior` JWT, times are seconds since Unix Epoch.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct PriorClaims {
     sub: Option<String>,
 
     iss: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iat: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+}
+
+impl PriorClaims {
+    /// Constructor with prior DID as issuer and all other claims unset.
+    ///
+    /// # Parameters
+    ///
+    /// * `iss` - prior DID, which is rotated away from
+    pub fn new(iss: &str) -> Self {
+        PriorClaims {
+            sub: None,
+            iss: iss.into(),
+            aud: None,
+            iat: None,
+            nbf: None,
+            exp: None,
+            jti: None,
+        }
+    }
+
+    /// Setter of `sub` claim, new DID.
+    pub fn with_sub(mut self, sub: &str) -> Self {
+        self.sub = Some(sub.into());
+        self
+    }
+
+    /// Setter of `aud` claim, intended recipient of rotation.
+    pub fn with_aud(mut self, aud: &str) -> Self {
+        self.aud = Some(aud.into());
+        self
+    }
+
+    /// Setter of `iat` claim, time of issuance.
+    pub fn with_iat(mut self, iat: u64) -> Self {
+        self.iat = Some(iat);
+        self
+    }
+
+    /// Sets `iat` claim to now.
+    pub fn issued_now(self) -> Self {
+        self.with_iat(now())
+    }
+
+    /// Setter of `nbf` claim, time before which rotation must not be accepted.
+    pub fn with_nbf(mut self, nbf: u64) -> Self {
+        self.nbf = Some(nbf);
+        self
+    }
+
+    /// Setter of `exp` claim, time after which rotation must not be accepted.
+    pub fn with_exp(mut self, exp: u64) -> Self {
+        self.exp = Some(exp);
+        self
+    }
+
+    /// Setter of `jti` claim, unique identifier of the JWT.
+    pub fn with_jti(mut self, jti: &str) -> Self {
+        self.jti = Some(jti.into());
+        self
+    }
+
+    /// Getter of `sub` claim.
+    pub fn get_sub(&self) -> Option<&str> {
+        self.sub.as_deref()
+    }
+
+    /// Getter of `iss` claim.
+    pub fn get_iss(&self) -> &str {
+        &self.iss
+    }
+
+    /// Getter of `aud` claim.
+    pub fn get_aud(&self) -> Option<&str> {
+        self.aud.as_deref()
+    }
+
+    /// Getter of `iat` claim.
+    pub fn get_iat(&self) -> Option<u64> {
+        self.iat
+    }
+
+    /// Getter of `nbf` claim.
+    pub fn get_nbf(&self) -> Option<u64> {
+        self.nbf
+    }
+
+    /// Getter of `exp` claim.
+    pub fn get_exp(&self) -> Option<u64> {
+        self.exp
+    }
+
+    /// Getter of `jti` claim.
+    pub fn get_jti(&self) -> Option<&str> {
+        self.jti.as_deref()
+    }
+
+    /// Checks if `exp` claim lies in the past.
+    /// Claims without `exp` never expire.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(now())
+    }
+
+    /// Checks if `exp` claim lies before given time.
+    ///
+    /// # Parameters
+    ///
+    /// * `time` - seconds since Unix Epoch
+    pub fn is_expired_at(&self, time: u64) -> bool {
+        matches!(self.exp, Some(exp) if exp <= time)
+    }
+
+    /// Checks if `nbf` claim lies in the future.
+    pub fn is_not_yet_valid(&self) -> bool {
+        matches!(self.nbf, Some(nbf) if nbf > now())
+    }
+
+    /// Validates claims for accepting rotation of `iss` to `sub`:
+    ///     `sub` must be set and differ from `iss`, claims must be within their
+    ///     validity period and `aud`, if set, must match `expected_aud`.
+    /// Signature of the JWT is not checked here.
+    ///
+    /// # Parameters
+    ///
+    /// * `expected_aud` - DID of the receiver, `None` to skip `aud` check
+    pub fn validate(&self, expected_aud: Option<&str>) -> Result<(), CrateError> {
+        let invalid = |reason: &str| Err(CrateError::InvalidPriorClaims(reason.into()));
+        match &self.sub {
+            None => return invalid("sub is not set"),
+            Some(sub) if sub == &self.iss => return invalid("sub equals iss"),
+            _ => {}
+        }
+        if self.is_expired() {
+            return invalid("claims have expired");
+        }
+        if self.is_not_yet_valid() {
+            return invalid("claims are not valid yet");
+        }
+        match (&self.aud, expected_aud) {
+            (Some(aud), Some(expected)) if aud != expected => invalid("aud does not match"),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl FromStr for PriorClaims {
@@ -25,3 +184,46 @@ impl TryFrom<&[u8]> for PriorClaims {
         std::str::from_utf8(jwt)?.parse::<Self>()
     }
 }
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_claim_set() -> Result<(), CrateError> {
+        let claims: PriorClaims = r#"{
+            "sub": "did:example:new",
+            "iss": "did:example:old",
+            "aud": "did:example:bob",
+            "iat": 1516239022,
+            "nbf": 1516239022,
+            "exp": 4102444800,
+            "jti": "1"
+        }"#
+        .parse()?;
+        assert_eq!(claims.get_aud(), Some("did:example:bob"));
+        assert_eq!(claims.get_exp(), Some(4102444800));
+        assert!(claims.validate(Some("did:example:bob")).is_ok());
+        assert!(claims.validate(Some("did:example:carol")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn validation_rejects_invalid_claims() {
+        let claims = PriorClaims::new("did:example:old").issued_now();
+        assert!(claims.validate(None).is_err());
+        let claims = claims.with_sub("did:example:new");
+        assert!(claims.validate(None).is_ok());
+        let expired = claims.clone().with_exp(1);
+        assert!(expired.is_expired());
+        assert!(expired.validate(None).is_err());
+        assert!(claims.with_exp(u64::MAX).with_nbf(u64::MAX).validate(None).is_err());
+    }
+}
@@ -1,16 +1,103 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 /// Enum that represents DIDComm envelope type
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// Values not known to this crate, e.g. custom protocol types, are kept as `Other`
+///     and serialized unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MessageType {
-    #[serde(rename = "application/didcomm-encrypted+json")]
     DidCommJwe,
-    #[serde(rename = "application/didcomm-signed+json")]
     DidCommJws,
-    #[serde(rename = "application/didcomm-plain+json")]
     DidCommRaw,
-    #[serde(rename = "https://didcomm.org/routing/2.0/forward")]
     DidCommForward,
-    #[serde(rename = "https://didcomm.org/out-of-band/2.0/invitation")]
     DidCommInvitation,
+    /// key exported by [`KeyStore::export_key`][crate::KeyStore::export_key], never a message
+    DidCommKeyExport,
+    Other(String),
+}
+
+impl MessageType {
+    /// Serialized value of the type, e.g. `application/didcomm-plain+json`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            MessageType::DidCommJwe => "application/didcomm-encrypted+json",
+            MessageType::DidCommJws => "application/didcomm-signed+json",
+            MessageType::DidCommRaw => "application/didcomm-plain+json",
+            MessageType::DidCommForward => "https://didcomm.org/routing/2.0/forward",
+            MessageType::DidCommInvitation => "https://didcomm.org/out-of-band/2.0/invitation",
+            MessageType::DidCommKeyExport => "application/didcomm-key-export+json",
+            MessageType::Other(other) => other,
+        }
+    }
+}
+
+impl From<&str> for MessageType {
+    fn from(value: &str) -> Self {
+        match value {
+            "application/didcomm-encrypted+json" => MessageType::DidCommJwe,
+            "application/didcomm-signed+json" => MessageType::DidCommJws,
+            "application/didcomm-plain+json" => MessageType::DidCommRaw,
+            "https://didcomm.org/routing/2.0/forward" => MessageType::DidCommForward,
+            "https://didcomm.org/out-of-band/2.0/invitation" => MessageType::DidCommInvitation,
+            "application/didcomm-key-export+json" => MessageType::DidCommKeyExport,
+            other => MessageType::Other(other.into()),
+        }
+    }
+}
+
+impl FromStr for MessageType {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(value.into())
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for MessageType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(value.as_str().into())
+    }
+}
+
+#[cfg(feature = "json-schema")]
+impl schemars::JsonSchema for MessageType {
+    fn schema_name() -> String {
+        "MessageType".into()
+    }
+
+    // serialized as plain string, see `as_str`
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
+/// Value of `return_route` extension header, asking recipient to send
+///     responses over the connection the message arrived on.
+/// Required by most mediators for clients without an endpoint of their own.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnRoute {
+    /// responses are sent to the endpoint of the sender, same as without the header
+    None,
+    /// only responses on the thread of this message are returned
+    Thread,
+    /// all messages for the sender are returned
+    All,
 }
 
 /// Enum that represents DIDComm message payload type
@@ -31,3 +118,21 @@ pub enum ContentType {
     #[serde(rename = "application/octet-stream")]
     BinaryData,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_message_types_round_trip() -> Result<(), serde_json::Error> {
+        let custom = MessageType::from("application/example-custom+json");
+        let serialized = serde_json::to_string(&custom)?;
+        assert_eq!(serialized, r#""application/example-custom+json""#);
+        assert_eq!(serde_json::from_str::<MessageType>(&serialized)?, custom);
+        assert_eq!(
+            serde_json::from_str::<MessageType>(r#""application/didcomm-signed+json""#)?,
+            MessageType::DidCommJws
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::Error;
+
+/// Value of `zip` header for DEFLATE compression as in
+///     [RFC 7516](https://tools.ietf.org/html/rfc7516#section-4.1.3).
+pub(crate) const DEFLATE: &str = "DEF";
+
+/// Default limit of decompressed plaintext size, 4 MiB.
+pub(crate) const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 4 * 1024 * 1024;
+
+/// Compresses plaintext before encryption according to `zip` header.
+/// Plaintext is returned unchanged if `zip` is not set.
+pub(crate) fn compress(plaintext: &[u8], zip: Option<&str>) -> Result<Vec<u8>, Error> {
+    match zip {
+        None => Ok(plaintext.to_vec()),
+        Some(DEFLATE) => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(plaintext)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| Error::Generic(format!("failed to compress plaintext; {}", e)))
+        }
+        Some(other) => Err(Error::UnsupportedCompression(other.into())),
+    }
+}
+
+/// Decompresses decrypted plaintext according to `zip` header.
+/// Plaintext is returned unchanged if `zip` is not set.
+/// Fails with `Error::DecompressedSizeExceeded` once more than `limit` bytes are inflated,
+///     so small payloads can not expand without bound.
+pub(crate) fn decompress(
+    plaintext: &[u8],
+    zip: Option<&str>,
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    match zip {
+        None => Ok(plaintext.to_vec()),
+        Some(DEFLATE) => {
+            let mut decompressed = Vec::new();
+            DeflateDecoder::new(plaintext)
+                .take(limit as u64 + 1)
+                .read_to_end(&mut decompressed)
+                .map_err(|e| Error::Generic(format!("failed to decompress plaintext; {}", e)))?;
+            if decompressed.len() > limit {
+                return Err(Error::DecompressedSizeExceeded(limit));
+            }
+            Ok(decompressed)
+        }
+        Some(other) => Err(Error::UnsupportedCompression(other.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompression_is_limited() -> Result<(), Error> {
+        let plaintext = vec![0; 64 * 1024];
+        let compressed = compress(&plaintext, Some(DEFLATE))?;
+        assert!(compressed.len() < 1024);
+
+        assert_eq!(
+            decompress(&compressed, Some(DEFLATE), plaintext.len())?,
+            plaintext
+        );
+        assert!(matches!(
+            decompress(&compressed, Some(DEFLATE), plaintext.len() - 1),
+            Err(Error::DecompressedSizeExceeded(limit)) if limit == plaintext.len() - 1
+        ));
+        Ok(())
+    }
+}
@@ -1,7 +1,6 @@
 use std::convert::{TryFrom, TryInto};
 
 use aes_gcm::{aead::generic_array::GenericArray, Aes256Gcm};
-use arrayref::array_ref;
 use chacha20poly1305::{
     aead::{Aead, KeyInit},
     XChaCha20Poly1305, XNonce,
@@ -12,9 +11,46 @@ use rand::{prelude::SliceRandom, Rng};
 use sha2::{Digest, Sha256};
 use x25519_dalek::{PublicKey, StaticSecret};
 
-use crate::crypto::CryptoAlgorithm;
+use crate::crypto::{diagnostics, ContentEncAlg, CryptoAlgorithm, HardwareKey, KeyAgreementAlg};
 use crate::{Error, Jwe, Jwk, JwmHeader, KeyAlgorithm, Message, Recipient};
 
+/// Private key used for X25519 key agreement, either as raw bytes or as handle of
+///     a [`HardwareKey`] that never exports them.
+#[derive(Clone, Copy)]
+pub(crate) enum AgreementKey<'a> {
+    Raw(&'a [u8]),
+    Hardware(&'a dyn HardwareKey),
+}
+
+impl AgreementKey<'_> {
+    /// Computes X25519 shared secret of this private key and given public key.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - public key of other party
+    pub(crate) fn agree(&self, public_key: &[u8]) -> Result<[u8; 32], Error> {
+        let public_array = first_32_bytes(public_key)?;
+        match self {
+            AgreementKey::Raw(sk) => {
+                let ss = StaticSecret::from(first_32_bytes(sk)?)
+                    .diffie_hellman(&PublicKey::from(public_array));
+                Ok(*ss.as_bytes())
+            }
+            AgreementKey::Hardware(key) => key
+                .key_agreement(&public_array)?
+                .try_into()
+                .map_err(|_| Error::InvalidKeySize("!32".into())),
+        }
+    }
+}
+
+/// Leading 32 bytes of X25519 key, as raw keys may be followed by additional data.
+fn first_32_bytes(key: &[u8]) -> Result<[u8; 32], Error> {
+    key.get(..32)
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| Error::InvalidKeySize("<32".into()))
+}
+
 /// Decrypts the content encryption key with a key encryption key.
 ///
 /// # Arguments
@@ -28,19 +64,23 @@ use crate::{Error, Jwe, Jwk, JwmHeader, KeyAlgorithm, Message, Recipient};
 /// * `recipient_public_key` - can be provided if key should not be resolved via recipients DID
 pub(crate) fn decrypt_cek(
     jwe: &Jwe,
-    sk: &[u8],
+    sk: AgreementKey<'_>,
     recipient: &Recipient,
     recipient_public_key: Option<Vec<u8>>,
 ) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("unwrap_key", kid = ?recipient.header.kid).entered();
     trace!("decrypting per-recipient JWE value");
-    let alg = jwe
-        .get_alg()
-        .ok_or_else(|| Error::Generic("missing encryption 'alg' in header".to_string()))?;
+    let alg = match recipient_alg(&recipient.header) {
+        Some(alg) => alg.to_string(),
+        None => jwe
+            .get_alg()
+            .ok_or_else(|| Error::Generic("missing encryption 'alg' in header".to_string()))?,
+    };
     trace!("using algorithm {}", &alg);
 
-    let skid = jwe
-        .get_skid()
-        .ok_or_else(|| Error::Generic("missing 'skid' in header".to_string()))?;
+    // only needed to resolve sender key of authcrypt messages if it is not given
+    let skid = jwe.get_skid().unwrap_or_default();
 
     // zE (temporary secret)
     let epk = recipient
@@ -48,22 +88,27 @@ pub(crate) fn decrypt_cek(
         .epk
         .as_ref()
         .ok_or_else(|| Error::Generic("JWM header is missing epk".to_string()))?;
-    let epk_public_array: [u8; 32] = base64_url::decode(&epk.x)?
+    let epk_public: [u8; 32] = base64_url::decode(&epk.x)?
         .try_into()
         .map_err(|_err| Error::Generic("failed to decode epk public key".to_string()))?;
-    let epk_public = PublicKey::from(epk_public_array);
-    let ss = StaticSecret::from(array_ref!(sk, 0, 32).to_owned()).diffie_hellman(&epk_public);
-    let ze = *ss.as_bytes();
-    trace!("ze: {:?}", &ze.as_ref());
+    let ze = sk.agree(&epk_public)?;
+    diagnostics::report("ze", ze.as_ref());
 
     // key encryption key
-    let kek = generate_kek(&skid, sk, ze, &alg, recipient_public_key)?;
-    trace!("kek: {:?}", &kek);
+    let apu = decode_party_info(jwe.get_apu().as_deref())?;
+    let apv = decode_party_info(jwe.get_apv().as_deref())?;
+    let kek = if is_anoncrypt(&alg) {
+        concat_kdf(&ze, &alg, apu.as_ref(), apv.as_ref())?
+    } else {
+        generate_kek(&skid, sk, ze, &alg, (apu, apv), recipient_public_key)?
+    };
+    diagnostics::report("kek", &kek);
 
     let iv = recipient
         .header
         .other
         .get("iv")
+        .and_then(|iv| iv.as_str())
         .ok_or_else(|| Error::Generic("missing iv in header".to_string()))?;
     let iv_bytes = base64_url::decode(&iv)?;
 
@@ -71,13 +116,14 @@ pub(crate) fn decrypt_cek(
         .header
         .other
         .get("tag")
+        .and_then(|tag| tag.as_str())
         .ok_or_else(|| Error::Generic("missing tag in header".to_string()))?;
     let mut ciphertext_and_tag: Vec<u8> = vec![];
     ciphertext_and_tag.extend(base64_url::decode(&recipient.encrypted_key)?);
     ciphertext_and_tag.extend(&base64_url::decode(&tag)?);
 
     match alg.as_ref() {
-        "ECDH-1PU+XC20PKW" => {
+        "ECDH-1PU+XC20PKW" | "ECDH-ES+XC20PKW" => {
             let nonce = XNonce::from_slice(&iv_bytes);
             let kek_key = chacha20poly1305::Key::from_slice(kek.as_slice());
             let crypter = XChaCha20Poly1305::new(kek_key);
@@ -88,7 +134,7 @@ pub(crate) fn decrypt_cek(
 
             Ok(cek)
         }
-        "ECDH-1PU+A256KW" => {
+        "ECDH-1PU+A256KW" | "ECDH-ES+A256KW" => {
             let nonce = GenericArray::from_slice(&iv_bytes);
             let kek_key = GenericArray::from_slice(kek.as_slice());
             let crypter = Aes256Gcm::new(kek_key);
@@ -112,7 +158,9 @@ pub(crate) fn decrypt_cek(
 ///
 /// * `message` - message the content encryption key should be encrypted for
 ///
-/// * `sk` - senders private key
+/// * `sk` - senders private key, not used for anoncrypt
+///
+/// * `alg` - key wrap algorithm for this recipient, e.g. `ECDH-1PU+A256KW`
 ///
 /// * `dest` - recipient to encrypt cek for
 ///
@@ -121,33 +169,42 @@ pub(crate) fn decrypt_cek(
 /// * `recipient_public_key` - can be provided if key should not be resolved via recipients DID
 pub(crate) fn encrypt_cek(
     message: &Message,
-    sk: &[u8],
+    sk: AgreementKey<'_>,
+    alg: &str,
     dest: &str,
     cek: &[u8; 32],
     recipient_public_key: Option<Vec<u8>>,
 ) -> Result<Recipient, Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("wrap_key", recipient = %dest).entered();
     trace!("creating per-recipient JWE value for {}", &dest);
-    let alg = message
-        .jwm_header
-        .alg
-        .as_ref()
-        .ok_or_else(|| Error::Generic("missing encryption 'alg' in header".to_string()))?;
     trace!("using algorithm {}", &alg);
+    if let AgreementKey::Raw(sk) = sk {
+        if !is_anoncrypt(alg) && sk.len() != 32 {
+            return Err(Error::InvalidKeySize("!32".into()));
+        }
+    }
 
     // zE (temporary secret)
     let epk = StaticSecret::random_from_rng(rand_core::OsRng);
     let epk_public = PublicKey::from(&epk);
-    let ze = generate_shared_for_recipient(epk.to_bytes(), dest, recipient_public_key.clone())?;
-    trace!(
-        "ze: {:?} epk_public: {:?}, dest: {:?}",
-        &ze.as_ref(),
-        epk_public,
-        dest
-    );
+    let ze = generate_shared_for_recipient(
+        AgreementKey::Raw(&epk.to_bytes()),
+        dest,
+        recipient_public_key.clone(),
+    )?;
+    trace!("epk_public: {:?}, dest: {:?}", epk_public, dest);
+    diagnostics::report("ze", ze.as_ref());
 
     // key encryption key
-    let kek = generate_kek(dest, sk, ze, alg, recipient_public_key)?;
-    trace!("kek: {:?}", &kek);
+    let apu = decode_party_info(message.jwm_header.apu.as_deref())?;
+    let apv = decode_party_info(message.jwm_header.apv.as_deref())?;
+    let kek = if is_anoncrypt(alg) {
+        concat_kdf(ze.as_ref(), alg, apu.as_ref(), apv.as_ref())?
+    } else {
+        generate_kek(dest, sk, ze, alg, (apu, apv), recipient_public_key)?
+    };
+    diagnostics::report("kek", &kek);
 
     // preparation for initial vector
     let mut rng = rand::thread_rng();
@@ -157,9 +214,12 @@ pub(crate) fn encrypt_cek(
     let mut jwk = Jwk::new();
     jwk.kid = Some(get_did_from_didurl(dest));
 
-    let sealed_cek_and_tag: Vec<u8> = match alg.as_ref() {
-        "ECDH-1PU+A256KW" => {
-            jwk.alg = KeyAlgorithm::Ecdh1puA256kw;
+    let sealed_cek_and_tag: Vec<u8> = match alg {
+        "ECDH-1PU+A256KW" | "ECDH-ES+A256KW" => {
+            jwk.alg = match alg {
+                "ECDH-ES+A256KW" => KeyAlgorithm::EcdhEsA256kw,
+                _ => KeyAlgorithm::Ecdh1puA256kw,
+            };
 
             // initial vector
             iv = rng.gen::<[u8; 12]>().to_vec();
@@ -175,8 +235,11 @@ pub(crate) fn encrypt_cek(
                 .encrypt(nonce, cek.as_ref())
                 .map_err(|e| Error::Generic(e.to_string()))?
         }
-        "ECDH-1PU+XC20PKW" => {
-            jwk.alg = KeyAlgorithm::Ecdh1puXc20pkw;
+        "ECDH-1PU+XC20PKW" | "ECDH-ES+XC20PKW" => {
+            jwk.alg = match alg {
+                "ECDH-ES+XC20PKW" => KeyAlgorithm::EcdhEsXc20pkw,
+                _ => KeyAlgorithm::Ecdh1puXc20pkw,
+            };
 
             // initial vector
             iv = rng.gen::<[u8; 24]>().to_vec();
@@ -217,11 +280,51 @@ pub(crate) fn encrypt_cek(
     })
 }
 
-/// Create a `CryptoAlgorithm` by using headers `alg` value.
+/// Checks if key agreement `alg` is anonymous (ECDH-ES), which uses no sender key.
+pub(crate) fn is_anoncrypt(alg: &str) -> bool {
+    alg.starts_with("ECDH-ES")
+}
+
+/// Anonymous (ECDH-ES) key agreement `alg` with same key wrapping as given `alg`.
+pub(crate) fn anoncrypt_alg(alg: &str) -> Result<&'static str, Error> {
+    KeyAgreementAlg::try_from(alg)
+        .map(|alg| alg.anonymous().as_str())
+        .map_err(|_| Error::Generic(format!("anoncrypt is not supported for {}", alg)))
+}
+
+/// Key wrap algorithm given in per-recipient header, `None` if it is not set
+///     or not supported for wrapping content encryption keys.
+pub(crate) fn recipient_alg(header: &Jwk) -> Option<&'static str> {
+    match header.alg {
+        KeyAlgorithm::Ecdh1puA256kw => Some("ECDH-1PU+A256KW"),
+        KeyAlgorithm::Ecdh1puXc20pkw => Some("ECDH-1PU+XC20PKW"),
+        KeyAlgorithm::EcdhEsA256kw => Some("ECDH-ES+A256KW"),
+        KeyAlgorithm::EcdhEsXc20pkw => Some("ECDH-ES+XC20PKW"),
+        _ => None,
+    }
+}
+
+/// Create a `CryptoAlgorithm` of content encryption of received JWE.
+/// Uses `enc` header or `alg` header if `enc` is not given.
+pub(crate) fn get_crypter_from_jwe(jwe: &Jwe) -> Result<CryptoAlgorithm, Error> {
+    if let Some(enc) = jwe.get_enc() {
+        return ContentEncAlg::try_from(enc.as_str()).map(CryptoAlgorithm::from);
+    }
+    match jwe.get_alg() {
+        Some(alg) => (&alg).try_into(),
+        None => Err(Error::Generic(
+            "missing algorithm in JWE header(s)".to_string(),
+        )),
+    }
+}
+
+/// Create a `CryptoAlgorithm` of content encryption by using headers `enc` value
+///     or `alg` value if `enc` is not set.
 pub(crate) fn get_crypter_from_header(header: &JwmHeader) -> Result<CryptoAlgorithm, Error> {
-    match &header.alg {
-        None => Err(Error::JweParseError),
-        Some(alg) => alg.try_into(),
+    match (&header.enc, &header.alg) {
+        (Some(enc), _) => ContentEncAlg::try_from(enc.as_str()).map(CryptoAlgorithm::from),
+        (None, Some(alg)) => alg.try_into(),
+        (None, None) => Err(Error::JweParseError),
     }
 }
 
@@ -293,34 +396,39 @@ fn concat_kdf(
 ///
 /// * `alg` - encryption algorithm used
 ///
+/// * `party_info` - decoded `apu` and `apv` headers, if set
+///
 /// * `recipient_public_key` - can be provided if key should not be resolved via recipients DID
 fn generate_kek(
     did: &str,
-    sk: &[u8],
+    sk: AgreementKey<'_>,
     ze: impl AsRef<[u8]>,
     alg: &str,
+    party_info: (Option<Vec<u8>>, Option<Vec<u8>>),
     recipient_public_key: Option<Vec<u8>>,
 ) -> Result<Vec<u8>, Error> {
     // zS (shared for recipient)
     let shared = generate_shared_for_recipient(sk, did, recipient_public_key)?;
-    trace!(
-        "sk: {:?} shared: {:?} dest: {:?}",
-        sk,
-        &shared.as_ref(),
-        did
-    );
+    trace!("dest: {:?}", did);
+    diagnostics::report("shared", shared.as_ref());
 
     // shared secret
     let shared_secret = [ze.as_ref(), shared.as_ref()].concat();
-    trace!("shared_secret: {:?}", &shared_secret);
+    diagnostics::report("shared_secret", &shared_secret);
 
     // key encryption key
-    let kek = concat_kdf(&shared_secret, alg, None, None)?;
-    trace!("kek: {:?}", &kek);
+    let (apu, apv) = party_info;
+    let kek = concat_kdf(&shared_secret, alg, apu.as_ref(), apv.as_ref())?;
+    diagnostics::report("kek", &kek);
 
     Ok(kek)
 }
 
+/// Decodes base64url encoded `apu` or `apv` header.
+fn decode_party_info(value: Option<&str>) -> Result<Option<Vec<u8>>, Error> {
+    value.map(base64_url::decode).transpose().map_err(Error::from)
+}
+
 /// Generates shared secret for a message recipient with a senders public key and a recipients
 /// private key. Key is taken from `recipient_public_key`, if it contains a value.
 ///
@@ -342,7 +450,7 @@ fn generate_kek(
 ///
 #[allow(unused_variables)]
 fn generate_shared_for_recipient(
-    sender_private_key: impl AsRef<[u8]>,
+    sender_private_key: AgreementKey<'_>,
     recipient_did: &str,
     recipient_public_key: Option<Vec<u8>>,
 ) -> Result<impl AsRef<[u8]>, Error> {
@@ -351,6 +459,8 @@ fn generate_shared_for_recipient(
         None => {
             #[cfg(feature = "resolve")]
             {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("resolve", did = %recipient_did).entered();
                 let document = resolve_any(recipient_did).ok_or(Error::DidResolveFailed)?;
                 document
                     .find_public_key_for_curve("X25519")
@@ -362,12 +472,7 @@ fn generate_shared_for_recipient(
             }
         }
     };
-    let ss = StaticSecret::from(array_ref!(sender_private_key.as_ref(), 0, 32).to_owned())
-        .diffie_hellman(&PublicKey::from(
-            array_ref!(recipient_public, 0, 32).to_owned(),
-        ));
-
-    Ok(*ss.as_bytes())
+    sender_private_key.agree(&recipient_public)
 }
 
 /// Combines length of array and its its length into a vector.
@@ -408,3 +513,13 @@ fn get_did_from_didurl(url: &str) -> String {
         None => String::default(),
     }
 }
+
+/// Checks if both DID URLs refer to the same DID.
+/// Values not being DID URLs are compared as they are.
+pub(crate) fn refers_to_same_did(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let did = get_did_from_didurl(a);
+    !did.is_empty() && did == get_did_from_didurl(b)
+}
@@ -1,10 +1,14 @@
 #[cfg(feature = "raw-crypto")]
+mod compression;
+#[cfg(feature = "raw-crypto")]
 mod encryption;
 mod getters;
 #[cfg(feature = "raw-crypto")]
 mod receive;
 mod serialization;
 
+#[cfg(feature = "raw-crypto")]
+pub(crate) use compression::*;
 #[cfg(feature = "raw-crypto")]
 pub(crate) use encryption::*;
 pub(crate) use getters::*;
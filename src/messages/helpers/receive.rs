@@ -1,16 +1,18 @@
-use std::convert::TryInto;
+use std::{borrow::Cow, convert::TryFrom};
 
-use arrayref::array_ref;
 #[cfg(feature = "resolve")]
 use ddoresolver_rs::*;
 use serde::{Deserialize, Serialize};
-use serde_json::value::RawValue;
-use x25519_dalek::{PublicKey, StaticSecret};
+use serde_json::{json, value::RawValue, Value};
 
-use crate::crypto::{CryptoAlgorithm, Cypher};
+use crate::crypto::{CryptoAlgorithm, Curve, Cypher, KeyRole};
 use crate::{
-    helpers::{decrypt_cek, get_signing_sender_public_key},
-    Error, Jwe, Jws, Message, MessageType, Recipient, Signature,
+    helpers::{
+        decrypt_cek, get_crypter_from_jwe, get_signing_sender_public_key, is_anoncrypt,
+        recipient_alg, AgreementKey,
+    },
+    DidResolver, Error, InMemoryDidResolver, Jwe, JwmHeader, Jws, Mediated, Message, MessageType,
+    Recipient, Signature,
 };
 
 /// Helper type to check if received message is plain, signed or encrypted
@@ -50,42 +52,16 @@ pub(crate) fn get_message_type(message: &str) -> Result<MessageType, Error> {
 ///
 /// * `encryption_sender_public_key` - public key of message sender, can be omitted if public key
 ///                                    should be automatically resolved (requires `resolve` feature)
+///
+/// * `max_decompressed_size` - limit of plaintext size if it is compressed
 pub(crate) fn receive_jwe(
     incoming: &str,
-    encryption_recipient_private_key: &[u8],
+    encryption_recipient_private_key: AgreementKey<'_>,
     encryption_sender_public_key: Option<Vec<u8>>,
+    max_decompressed_size: usize,
 ) -> Result<String, Error> {
     let jwe: Jwe = serde_json::from_str(incoming)?;
-    let alg = &jwe
-        .get_alg()
-        .ok_or_else(|| Error::Generic("missing algorithm in JWE header(s)".to_string()))?;
-
-    // get public key from input or from senders DID document
-    let sender_public_key = match &encryption_sender_public_key {
-        Some(value) => value.to_vec(),
-        None => {
-            #[cfg(feature = "resolve")]
-            {
-                let skid = &jwe
-                    .get_skid()
-                    .ok_or_else(|| Error::Generic("skid missing".to_string()))?;
-                let document = ddoresolver_rs::resolve_any(skid).ok_or(Error::DidResolveFailed)?;
-                document
-                    .find_public_key_for_curve("X25519")
-                    .ok_or(Error::BadDid)?
-            }
-            #[cfg(not(feature = "resolve"))]
-            {
-                return Err(Error::DidResolveFailed);
-            }
-        }
-    };
-
-    let shared = StaticSecret::from(array_ref!(encryption_recipient_private_key, 0, 32).to_owned())
-        .diffie_hellman(&PublicKey::from(
-            array_ref!(sender_public_key, 0, 32).to_owned(),
-        ));
-    let a: CryptoAlgorithm = alg.try_into()?;
+    let a: CryptoAlgorithm = get_crypter_from_jwe(&jwe)?;
     let m: Message;
     let recipients_from_jwe: Option<Vec<Recipient>>;
     if jwe.recipients.as_ref().is_some() {
@@ -113,39 +89,421 @@ pub(crate) fn receive_jwe(
 
         let key: Vec<u8> =
             key_result.map_err(|e| Error::Generic(format!("could not decrypt cek; {}", &e)))?;
-        m = Message::decrypt(incoming.as_bytes(), a.decrypter(), &key)?;
+        m = Message::decrypt_with_limit(
+            incoming.as_bytes(),
+            a.decrypter(),
+            &key,
+            max_decompressed_size,
+        )?;
     } else {
-        m = Message::decrypt(incoming.as_bytes(), a.decrypter(), shared.as_bytes())?;
+        // get public key from input or from senders DID document
+        let sender_public_key = match &encryption_sender_public_key {
+            Some(value) => value.to_vec(),
+            None => {
+                #[cfg(feature = "resolve")]
+                {
+                    let skid = &jwe
+                        .get_skid()
+                        .ok_or_else(|| Error::Generic("skid missing".to_string()))?;
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!("resolve", did = %skid).entered();
+                    let document =
+                        ddoresolver_rs::resolve_any(skid).ok_or(Error::DidResolveFailed)?;
+                    document
+                        .find_public_key_for_curve("X25519")
+                        .ok_or(Error::BadDid)?
+                }
+                #[cfg(not(feature = "resolve"))]
+                {
+                    return Err(Error::DidResolveFailed);
+                }
+            }
+        };
+        let shared = encryption_recipient_private_key.agree(&sender_public_key)?;
+        m = Message::decrypt_with_limit(
+            incoming.as_bytes(),
+            a.decrypter(),
+            &shared,
+            max_decompressed_size,
+        )?;
     }
 
     Ok(serde_json::to_string(&m)?)
 }
 
+/// Same as [`receive_jwe`] but tries `additional_private_keys` if recipient key fails,
+///     e.g. keys of mediators controlled by the recipient.
+/// Error of the recipient key is returned if no key decrypts the envelope.
+pub(crate) fn receive_jwe_with_any_key(
+    incoming: &str,
+    encryption_recipient_private_key: AgreementKey<'_>,
+    additional_private_keys: &[Vec<u8>],
+    encryption_sender_public_key: Option<Vec<u8>>,
+    max_decompressed_size: usize,
+) -> Result<String, Error> {
+    let result = receive_jwe(
+        incoming,
+        encryption_recipient_private_key,
+        encryption_sender_public_key.clone(),
+        max_decompressed_size,
+    );
+    if result.is_ok() {
+        return result;
+    }
+    additional_private_keys
+        .iter()
+        .find_map(|key| {
+            receive_jwe(
+                incoming,
+                AgreementKey::Raw(key),
+                encryption_sender_public_key.clone(),
+                max_decompressed_size,
+            )
+            .ok()
+        })
+        .map_or(result, Ok)
+}
+
+/// Returns JWE wrapped by serialized message, as sent by senders protecting their `skid`,
+///     `None` for other messages.
+pub(crate) fn wrapped_jwe(message: &str) -> Result<Option<String>, Error> {
+    let message: Message = serde_json::from_str(message)?;
+    if message.jwm_header.typ != MessageType::DidCommJwe || message.body.get("iv").is_none() {
+        return Ok(None);
+    }
+    Ok(serde_json::from_value::<Jwe>(message.body.clone())
+        .ok()
+        .map(|_| message.body.to_string()))
+}
+
+/// Returns `to` and payload of serialized forward message, `None` for other messages
+///     and batch forwards carrying more than one message.
+pub(crate) fn forwarded_payload(message: &str) -> Result<Option<(String, String)>, Error> {
+    let message: Message = serde_json::from_str(message)?;
+    let is_forward = message.jwm_header.typ == MessageType::DidCommForward
+        || message.didcomm_header.m_type == MessageType::DidCommForward.as_str();
+    if !is_forward {
+        return Ok(None);
+    }
+    // batch forwards carrying several messages are split by the application
+    let mut payloads = Mediated::payloads(&message)?;
+    if payloads.len() != 1 {
+        return Ok(None);
+    }
+    let hop = message
+        .didcomm_header
+        .to
+        .first()
+        .cloned()
+        .unwrap_or_default();
+    Ok(Some((hop, String::from_utf8(payloads.remove(0))?)))
+}
+
+/// Checks that JOSE `typ` of envelope header, if given, is the expected envelope type.
+pub(crate) fn check_envelope_typ(
+    header: Option<&JwmHeader>,
+    expected: MessageType,
+) -> Result<(), Error> {
+    match header.map(|header| &header.typ) {
+        Some(typ) if *typ != expected => Err(Error::EnvelopeTypeMismatch(
+            typ.to_string(),
+            expected.to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that key agreement and content encryption algorithms of JWE, if given,
+///     are in `allowed`.
+pub(crate) fn check_jwe_algorithms(jwe: &Jwe, allowed: &[String]) -> Result<(), Error> {
+    let jwe_alg = jwe.get_alg();
+    let recipient_algs = jwe
+        .get_recipients()
+        .into_iter()
+        .map(|recipient| recipient_alg(&recipient.header).map(Into::into))
+        .collect::<Vec<Option<String>>>();
+    let algs = std::iter::once(jwe_alg)
+        .chain(recipient_algs)
+        .chain(std::iter::once(jwe.get_enc()));
+    for alg in algs.flatten() {
+        check_algorithm(&alg, allowed)?;
+    }
+    Ok(())
+}
+
+/// Checks that signature algorithms of JWS, if given, are in `allowed`.
+/// Looks at the same JWS `receive_jws` verifies, which may be wrapped into `body`
+///     of a plain message.
+pub(crate) fn check_jws_algorithms(incoming: &str, allowed: &[String]) -> Result<(), Error> {
+    let jws = match serde_json::from_str::<Message>(incoming) {
+        Ok(message) => message.get_body()?,
+        Err(_) => incoming.to_string(),
+    };
+    if let Ok(jws) = serde_json::from_str::<Jws>(&jws) {
+        for signature in jws.get_signatures() {
+            if let Some(alg) = signature.get_alg() {
+                check_algorithm(&alg, allowed)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `skid` of JWE if its key agreement authenticates the sender,
+///     `None` for anoncrypt envelopes, as their `skid` is not bound to any key.
+pub(crate) fn authenticated_skid(jwe: &Jwe) -> Option<String> {
+    let alg = jwe.get_alg().or_else(|| {
+        jwe.get_recipients()
+            .first()
+            .and_then(|recipient| recipient_alg(&recipient.header))
+            .map(Into::into)
+    });
+    match alg {
+        Some(alg) if is_anoncrypt(&alg) => None,
+        _ => jwe.get_skid().filter(|skid| !skid.is_empty()),
+    }
+}
+
+/// Checks if `key` is a key of DID of `kid` in given `role`, resolved with `resolver`
+///     or, without resolver, only for `did:key`s.
+/// Ed25519 keys of DIDs count as their X25519 key agreement keys, as for `did:key`s.
+/// Key ids which are no DID URLs, e.g. raw keys, are bound to no DID.
+pub(crate) fn is_key_of_kid(
+    kid: &str,
+    key: &[u8],
+    role: KeyRole,
+    resolver: Option<&dyn DidResolver>,
+) -> bool {
+    if !kid.starts_with("did:") {
+        return false;
+    }
+    let did = kid.split('#').next().unwrap_or_default();
+    let resolved = match resolver {
+        Some(resolver) => resolver.resolve(did),
+        None => InMemoryDidResolver::new().resolve(did),
+    };
+    let resolved = match resolved {
+        Ok(resolved) => resolved,
+        Err(_) => return false,
+    };
+    let mut keys = resolved
+        .key_agreement
+        .iter()
+        .chain(&resolved.authentication);
+    keys.any(|(_, material)| {
+        if material.role == role {
+            material.bytes == key
+        } else {
+            role == KeyRole::KeyAgreement
+                && material.curve == Curve::Ed25519
+                && ed25519_to_x25519(&material.bytes).is_some_and(|x25519| x25519[..] == *key)
+        }
+    })
+}
+
+/// X25519 key of Ed25519 public key.
+fn ed25519_to_x25519(key: &[u8]) -> Option<[u8; 32]> {
+    let key = ed25519_dalek::VerifyingKey::try_from(key).ok()?;
+    Some(key.to_montgomery().to_bytes())
+}
+
+/// Checks that `from` of message is the DID of all keys authenticating its sender,
+///     `skid` of JWE or `kid` of verified JWS signature, bound to the key which
+///     decrypted or verified the envelope with [`is_key_of_kid`].
+/// Returns that DID, `None` if sender was not authenticated.
+pub(crate) fn authenticated_sender(
+    from: Option<&str>,
+    kids: &[Option<&str>],
+) -> Result<Option<String>, Error> {
+    let mut authenticated: Option<&str> = None;
+    for did in kids
+        .iter()
+        .flatten()
+        .map(|kid| kid.split('#').next().unwrap_or_default())
+    {
+        if let Some(claimed) = from.or(authenticated).filter(|claimed| *claimed != did) {
+            return Err(Error::SenderMismatch {
+                from: claimed.into(),
+                authenticated: did.into(),
+            });
+        }
+        authenticated = Some(did);
+    }
+    Ok(authenticated.map(Into::into))
+}
+
+fn check_algorithm(alg: &str, allowed: &[String]) -> Result<(), Error> {
+    if allowed.iter().any(|allowed| allowed == alg) {
+        Ok(())
+    } else {
+        Err(Error::AlgorithmNotAllowed { alg: alg.into() })
+    }
+}
+
+/// Top-level fields of v2 plaintext messages, which are not moved into `body` of v1 messages.
+const V2_PLAINTEXT_FIELDS: [&str; 10] = [
+    "id",
+    "type",
+    "typ",
+    "thid",
+    "pthid",
+    "from",
+    "to",
+    "created_time",
+    "expires_time",
+    "attachments",
+];
+
+/// Maps DIDComm v1 fields of plaintext message onto their v2 counterparts:
+///     `@id` to `id`, `@type` to `type` and `~thread` `thid`/`pthid` to `thid`/`pthid`.
+/// v1 content fields are moved into `body`, decorators (`~` prefixed fields) are kept.
+/// Sets `typ` to plaintext media type if missing.
+/// v2 fields present in message are kept, envelopes and messages without
+///     v1 fields are returned unchanged.
+pub(crate) fn v1_aliases_to_v2(message: &str) -> Result<String, Error> {
+    let mut value: Value = serde_json::from_str(message)?;
+    let object = match value.as_object_mut() {
+        Some(object) if ["@id", "@type"].iter().any(|f| object.contains_key(*f)) => object,
+        _ => return Ok(message.into()),
+    };
+    for (v1, v2) in [("@id", "id"), ("@type", "type")] {
+        if let Some(field) = object.remove(v1) {
+            object.entry(v2).or_insert(field);
+        }
+    }
+    if let Some(Value::Object(thread)) = object.get_mut("~thread") {
+        // v1 `pthid` is optional, `Thread` requires it
+        let pthid = thread.entry("pthid").or_insert_with(|| "".into()).clone();
+        let thid = thread.get("thid").cloned();
+        if let Some(thid) = thid {
+            object.entry("thid").or_insert(thid);
+        }
+        if pthid.as_str().is_some_and(|pthid| !pthid.is_empty()) {
+            object.entry("pthid").or_insert(pthid);
+        }
+    }
+    if !object.contains_key("body") {
+        let content: Vec<String> = object
+            .keys()
+            .filter(|key| !key.starts_with('~') && !V2_PLAINTEXT_FIELDS.contains(&key.as_str()))
+            .cloned()
+            .collect();
+        let body = content
+            .into_iter()
+            .filter_map(|key| object.remove_entry(&key))
+            .collect();
+        object.insert("body".into(), Value::Object(body));
+    }
+    object
+        .entry("typ")
+        .or_insert_with(|| MessageType::DidCommRaw.as_str().into());
+    Ok(serde_json::to_string(&value)?)
+}
+
+/// Turns raw bytes handed over by transports into serialized message `receive` accepts.
+/// UTF-8 input is borrowed, byte order marks and surrounding whitespace are stripped,
+///     UTF-16 input with byte order mark is decoded and compact JWS and JWE serializations
+///     are converted into their flattened JSON counterparts.
+pub(crate) fn normalize_incoming(incoming: &[u8]) -> Result<Cow<'_, str>, Error> {
+    let text = if let Some(rest) = incoming.strip_prefix(&[0xfe, 0xff]) {
+        Cow::Owned(decode_utf16(rest, u16::from_be_bytes)?)
+    } else if let Some(rest) = incoming.strip_prefix(&[0xff, 0xfe]) {
+        Cow::Owned(decode_utf16(rest, u16::from_le_bytes)?)
+    } else {
+        Cow::Borrowed(std::str::from_utf8(incoming)?)
+    };
+    let is_padding = |c: char| c.is_whitespace() || c == '\u{feff}';
+    let text = match text {
+        Cow::Borrowed(text) => Cow::Borrowed(text.trim_matches(is_padding)),
+        Cow::Owned(text) => Cow::Owned(text.trim_matches(is_padding).to_string()),
+    };
+    match compact_to_json(&text)? {
+        Some(json) => Ok(Cow::Owned(json)),
+        None => Ok(text),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<String, Error> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::Generic("UTF-16 input of odd length".into()));
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|e| Error::Generic(format!("invalid UTF-16 input; {}", e)))
+}
+
+/// Converts compact JWS (three parts) or JWE (five parts) serialization into flattened
+///     JSON serialization, `None` if `incoming` is not compact.
+/// Compact JWE carries all recipient parameters in its protected header,
+///     which is therefore used as recipient header as well.
+fn compact_to_json(incoming: &str) -> Result<Option<String>, Error> {
+    let is_base64url = |part: &&str| {
+        part.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    };
+    let parts: Vec<&str> = incoming.split('.').collect();
+    if !parts.iter().all(is_base64url) {
+        return Ok(None);
+    }
+    let json = match parts.as_slice() {
+        [protected, payload, signature] => json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": signature,
+        }),
+        [protected, encrypted_key, iv, ciphertext, tag] => {
+            let mut jwe = json!({
+                "protected": protected,
+                "iv": iv,
+                "ciphertext": ciphertext,
+                "tag": tag,
+            });
+            if !encrypted_key.is_empty() {
+                let header: Value = serde_json::from_slice(&base64_url::decode(protected)?)?;
+                jwe["header"] = header;
+                jwe["encrypted_key"] = Value::from(*encrypted_key);
+            }
+            jwe
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(json.to_string()))
+}
+
 /// Receive a serialized message. This function handles receival of [`crate::Jws`] envelopes.
+/// Returns serialized payload message, index of the candidate key that validated
+///     the signature (`None` if key was taken from `kid`) and `kid` of that signature.
 ///
 /// # Arguments
 ///
 /// * `incoming` - incoming message
 ///
-/// * `signing_sender_public_key` - senders public key, can be omitted if public key
+/// * `signing_sender_public_keys` - candidate public keys of sender, can be empty if public key
 ///                                 should be automatically resolved (requires `resolve` feature)
 pub(crate) fn receive_jws(
     incoming: &str,
-    signing_sender_public_key: Option<&[u8]>,
-) -> Result<String, Error> {
+    signing_sender_public_keys: &[&[u8]],
+) -> Result<(String, Option<usize>, Option<String>), Error> {
     // incoming data may be a jws string or a serialized message with jws data
-    let mut message_verified = None::<Message>;
+    let mut message_verified = None::<(Message, Option<usize>)>;
+    let mut verified_kid = None;
     if let Ok(message) = serde_json::from_str::<Message>(incoming) {
         if message.jwm_header.alg.is_none() {
             return Err(Error::JweParseError);
         }
         let body = message.get_body()?;
         let to_verify = body.as_bytes();
-        let key = get_signing_sender_public_key(
-            signing_sender_public_key,
+        message_verified = Some(verify_with_candidates(
+            to_verify,
+            signing_sender_public_keys,
             message.jwm_header.kid.as_ref(),
-        )?;
-        message_verified = Some(Message::verify(to_verify, &key)?);
+        )?);
+        verified_kid = serde_json::from_str::<Jws>(&body)
+            .ok()
+            .and_then(|jws| jws.get_signatures().iter().find_map(|s| s.get_kid()))
+            .or(message.jwm_header.kid);
     } else if let Ok(jws) = serde_json::from_str::<Jws>(incoming) {
         let signatures_values_to_verify: Vec<Signature>;
         if let Some(signatures) = &jws.signatures {
@@ -162,12 +520,13 @@ pub(crate) fn receive_jws(
             if signature_value.get_alg().is_none() {
                 continue;
             }
-            let key = get_signing_sender_public_key(
-                signing_sender_public_key,
+            if let Ok(verified) = verify_with_candidates(
+                to_verify,
+                signing_sender_public_keys,
                 signature_value.get_kid().as_ref(),
-            )?;
-            if let Ok(message_result) = Message::verify(to_verify, &key) {
-                message_verified = Some(message_result);
+            ) {
+                message_verified = Some(verified);
+                verified_kid = signature_value.get_kid();
                 break;
             }
         }
@@ -175,7 +534,21 @@ pub(crate) fn receive_jws(
         return Err(Error::JwsParseError);
     }
 
-    Ok(serde_json::to_string(
-        &message_verified.ok_or(Error::JwsParseError)?,
-    )?)
+    let (message, key_index) = message_verified.ok_or(Error::JwsParseError)?;
+    Ok((serde_json::to_string(&message)?, key_index, verified_kid))
+}
+
+/// Verifies `jws` with candidate keys, falls back to key from `kid` if no candidates given.
+fn verify_with_candidates(
+    jws: &[u8],
+    candidates: &[&[u8]],
+    kid: Option<&String>,
+) -> Result<(Message, Option<usize>), Error> {
+    if candidates.is_empty() {
+        let key = get_signing_sender_public_key(None, kid)?;
+        Ok((Message::verify(jws, &key)?, None))
+    } else {
+        let (message, index) = Message::verify_any(jws, candidates)?;
+        Ok((message, Some(index)))
+    }
 }
@@ -1,3 +1,7 @@
+use serde_json::Value;
+
+use crate::JwmHeader;
+
 /// (de)serialzies between `Vec<u8>` and base64 `String`
 /// see `<https://users.rust-lang.org/t/serialize-a-vec-u8-to-json-as-base64/57781/2>`
 pub(crate) mod serialization_base64_buffer {
@@ -19,12 +23,14 @@ pub(crate) mod serialization_base64_buffer {
 pub(crate) mod serialization_base64_jwm_header {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+    use super::{jwm_header_from_value, jwm_header_to_string};
     use crate::JwmHeader;
 
     pub fn serialize<S: Serializer>(v: &Option<JwmHeader>, s: S) -> Result<S::Ok, S::Error> {
         let base64 = match v {
             Some(v) => {
-                let header_string = serde_json::to_string(&v).map_err(serde::ser::Error::custom)?;
+                let header_string =
+                    jwm_header_to_string(v).map_err(serde::ser::Error::custom)?;
                 Some(base64_url::encode(&header_string))
             }
             None => None,
@@ -38,9 +44,126 @@ pub(crate) mod serialization_base64_jwm_header {
             Some(v) => {
                 let header_buffer =
                     base64_url::decode(v.as_bytes()).map_err(serde::de::Error::custom)?;
-                serde_json::from_slice(&header_buffer).map_err(serde::de::Error::custom)
+                let header = serde_json::from_slice(&header_buffer).map_err(serde::de::Error::custom)?;
+                jwm_header_from_value(header)
+                    .map(Some)
+                    .map_err(serde::de::Error::custom)
             }
             None => Ok(None),
         }
     }
 }
+
+/// (de)serialzies `Option<JwmHeader>` as plain JSON object
+/// keeping header parameters not known to this crate
+pub(crate) mod serialization_jwm_header {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    use super::{jwm_header_from_value, jwm_header_to_value};
+    use crate::JwmHeader;
+
+    pub fn serialize<S: Serializer>(v: &Option<JwmHeader>, s: S) -> Result<S::Ok, S::Error> {
+        v.as_ref().map(jwm_header_to_value).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<JwmHeader>, D::Error> {
+        match <Option<Value>>::deserialize(d)? {
+            Some(v) => jwm_header_from_value(v)
+                .map(Some)
+                .map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// (de)serialzies per-signature `header` of JWS as plain JSON object
+/// `typ` is only given in protected header, so it is not serialized and may be missing
+pub(crate) mod serialization_signature_header {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    use super::{jwm_header_from_value, jwm_header_to_value};
+    use crate::{JwmHeader, MessageType};
+
+    pub fn serialize<S: Serializer>(v: &Option<JwmHeader>, s: S) -> Result<S::Ok, S::Error> {
+        v.as_ref()
+            .map(|header| {
+                let mut value = jwm_header_to_value(header);
+                if let Value::Object(map) = &mut value {
+                    map.remove("typ");
+                }
+                value
+            })
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<JwmHeader>, D::Error> {
+        match <Option<Value>>::deserialize(d)? {
+            Some(mut v) => {
+                if let Value::Object(map) = &mut v {
+                    if !map.contains_key("typ") {
+                        let typ = serde_json::to_value(MessageType::DidCommJws)
+                            .map_err(serde::de::Error::custom)?;
+                        map.insert("typ".into(), typ);
+                    }
+                }
+                jwm_header_from_value(v)
+                    .map(Some)
+                    .map_err(serde::de::Error::custom)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Names of all parameters `JwmHeader` (de)serializes on its own.
+pub(crate) const JWM_HEADER_FIELDS: [&str; 14] = [
+    "typ", "enc", "kid", "skid", "alg", "jku", "jwk", "epk", "apu", "apv", "cty", "zip", "b64",
+    "crit",
+];
+
+/// Converts header into JSON object, re-adding unknown parameters.
+fn jwm_header_to_value(header: &JwmHeader) -> Value {
+    let mut value = serde_json::to_value(header).unwrap_or_default();
+    if let Value::Object(map) = &mut value {
+        for (key, other) in &header.other {
+            map.entry(key.clone()).or_insert_with(|| other.clone());
+        }
+    }
+    value
+}
+
+/// Serializes header into JSON string as used for JWE AAD and JWS signing input.
+/// Known parameters keep their struct order, unknown parameters are appended
+///     sorted by name, so the output is stable for the same header.
+pub(crate) fn jwm_header_to_string(header: &JwmHeader) -> serde_json::Result<String> {
+    let mut json = serde_json::to_string(header)?;
+    // `other` is sorted by name already
+    let extras = header
+        .other
+        .iter()
+        .filter(|(key, _)| !JWM_HEADER_FIELDS.contains(&key.as_str()));
+    for (key, value) in extras {
+        // `typ` is always present, so object is never empty
+        json.pop();
+        json.push(',');
+        json.push_str(&serde_json::to_string(key)?);
+        json.push(':');
+        json.push_str(&serde_json::to_string(value)?);
+        json.push('}');
+    }
+    Ok(json)
+}
+
+/// Parses header from JSON object, collecting unknown parameters into `other`.
+fn jwm_header_from_value(value: Value) -> serde_json::Result<JwmHeader> {
+    let mut header: JwmHeader = serde_json::from_value(value.clone())?;
+    if let Value::Object(map) = value {
+        header.other = map
+            .into_iter()
+            .filter(|(key, _)| !JWM_HEADER_FIELDS.contains(&key.as_str()))
+            .collect();
+    }
+    Ok(header)
+}
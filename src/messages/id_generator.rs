@@ -0,0 +1,81 @@
+use std::sync::{Arc, RwLock};
+
+/// Trait must be implemented for pluggable message id generation.
+/// Used for `id` header of every new message, see [`set_id_generator`].
+///
+/// Implemented for closures `Fn() -> String`.
+pub trait IdGenerator: Send + Sync {
+    /// Returns new, unique message id.
+    fn generate(&self) -> String;
+}
+
+impl<F> IdGenerator for F
+where
+    F: Fn() -> String + Send + Sync,
+{
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+/// Default [`IdGenerator`] producing random UUIDv4 strings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV4IdGenerator;
+
+impl IdGenerator for UuidV4IdGenerator {
+    fn generate(&self) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+static ID_GENERATOR: RwLock<Option<Arc<dyn IdGenerator>>> = RwLock::new(None);
+
+/// Replaces process wide id generator used for new messages.
+///
+/// # Arguments
+///
+/// * `generator` - generator to be used instead of `UuidV4IdGenerator`
+pub fn set_id_generator(generator: Arc<dyn IdGenerator>) {
+    if let Ok(mut current) = ID_GENERATOR.write() {
+        *current = Some(generator);
+    }
+}
+
+/// Restores default `UuidV4IdGenerator` as process wide id generator.
+pub fn reset_id_generator() {
+    if let Ok(mut current) = ID_GENERATOR.write() {
+        *current = None;
+    }
+}
+
+/// Generates id with currently configured generator.
+pub(crate) fn generate_id() -> String {
+    let generator = match ID_GENERATOR.read() {
+        Ok(current) => current.clone(),
+        Err(_) => None,
+    };
+    match generator {
+        Some(generator) => generator.generate(),
+        None => UuidV4IdGenerator.generate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn uuid_generator_generates_unique_ids() {
+        assert_ne!(UuidV4IdGenerator.generate(), UuidV4IdGenerator.generate());
+    }
+
+    #[test]
+    fn closures_can_be_used_as_generator() {
+        let counter = AtomicUsize::new(0);
+        let generator = move || format!("id-{}", counter.fetch_add(1, Ordering::SeqCst));
+        assert_eq!(generator.generate(), "id-0");
+        assert_eq!(generator.generate(), "id-1");
+    }
+}
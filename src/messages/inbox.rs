@@ -0,0 +1,245 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use crate::Message;
+#[cfg(feature = "raw-crypto")]
+use crate::{ReceiveOptions, Result};
+
+/// Single consumption point for received messages, whichever transport
+///     or pickup they arrived with.
+/// Messages with already seen `id` are dropped and messages of the same
+///     sender in the same thread are handed out in `sender_order`.
+/// Clones share the queue, so transports may push from other threads
+///     while the application polls or iterates.
+#[derive(Clone)]
+pub struct Inbox {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    arrived: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Message>,
+    seen: HashSet<String>,
+    /// seen ids from least to most recent, for dropping oldest once capacity is exceeded
+    seen_order: VecDeque<String>,
+    capacity: usize,
+    closed: bool,
+}
+
+impl Default for Inbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inbox {
+    /// Constructor remembering up to 10000 message ids for de-duplication.
+    pub fn new() -> Self {
+        Self::with_capacity(10_000)
+    }
+
+    /// Constructor remembering up to `capacity` message ids for de-duplication.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity` - maximum number of remembered ids, least recent are dropped first
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    capacity,
+                    ..Default::default()
+                }),
+                arrived: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Queues received message.
+    /// Returns `false` if message with same `id` was pushed before or inbox is closed.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - unpacked message
+    pub fn push(&self, message: Message) -> bool {
+        let mut state = self.state();
+        if state.closed || state.seen.contains(message.get_id()) {
+            return false;
+        }
+        let id = message.get_id().to_string();
+        state.seen.insert(id.clone());
+        state.seen_order.push_back(id);
+        while state.seen_order.len() > state.capacity {
+            if let Some(oldest) = state.seen_order.pop_front() {
+                state.seen.remove(&oldest);
+            }
+        }
+        // placed before queued messages of same sender and thread with higher `sender_order`
+        let position = order_key(&message).and_then(|(thid, from, order)| {
+            state.queue.iter().position(|queued| {
+                matches!(order_key(queued), Some((t, f, o)) if t == thid && f == from && o > order)
+            })
+        });
+        let position = position.unwrap_or(state.queue.len());
+        state.queue.insert(position, message);
+        self.shared.arrived.notify_one();
+        true
+    }
+
+    /// Unpacks envelope received from a transport and queues resulting message.
+    /// Returns `Ok(false)` if message is a duplicate.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - packed message as received
+    ///
+    /// * `encryption_recipient_private_key` - our private key for decryption
+    ///
+    /// * `options` - checks applied while receiving, see [`ReceiveOptions`]
+    #[cfg(feature = "raw-crypto")]
+    pub fn push_envelope(
+        &self,
+        envelope: &str,
+        encryption_recipient_private_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<bool> {
+        let message = Message::receive_with_options(
+            envelope,
+            encryption_recipient_private_key,
+            None,
+            None,
+            options,
+        )?;
+        Ok(self.push(message))
+    }
+
+    /// Takes next queued message without blocking.
+    pub fn poll(&self) -> Option<Message> {
+        self.state().queue.pop_front()
+    }
+
+    /// Takes next message, blocking until one arrives, `timeout` elapses
+    ///     or inbox is closed.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout` - maximal time span to wait
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Message> {
+        let state = self.state();
+        let (mut state, _) = match self.shared.arrived.wait_timeout_while(state, timeout, |s| {
+            s.queue.is_empty() && !s.closed
+        }) {
+            Ok(result) => result,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state.queue.pop_front()
+    }
+
+    /// Blocking iterator over messages, ends once inbox is closed and drained.
+    pub fn stream(&self) -> impl Iterator<Item = Message> + '_ {
+        std::iter::from_fn(move || {
+            let mut state = self.state();
+            loop {
+                if let Some(message) = state.queue.pop_front() {
+                    return Some(message);
+                }
+                if state.closed {
+                    return None;
+                }
+                state = match self.shared.arrived.wait(state) {
+                    Ok(state) => state,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+            }
+        })
+    }
+
+    /// Stops accepting messages and wakes up blocked consumers.
+    /// Already queued messages can still be taken.
+    pub fn close(&self) {
+        self.state().closed = true;
+        self.shared.arrived.notify_all();
+    }
+
+    /// Number of queued messages.
+    pub fn len(&self) -> usize {
+        self.state().queue.len()
+    }
+
+    /// Returns `true` if no messages are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        match self.shared.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Thread, sender and `sender_order` of message, `None` without `sender_order`.
+fn order_key(message: &Message) -> Option<(&str, &str, usize)> {
+    let header = &message.didcomm_header;
+    let thread = header.thread.as_ref()?;
+    let thid = header.thid.as_deref().unwrap_or(&thread.thid);
+    let from = header.from.as_deref().unwrap_or_default();
+    Some((thid, from, thread.sender_order?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::Thread;
+
+    fn ordered(thid: &str, order: usize) -> Message {
+        let mut message = Message::new().from("did:example:bob").thid(thid);
+        message.didcomm_header.thread = Some(Thread {
+            sender_order: Some(order),
+            ..Thread::implicit_reply(thid)
+        });
+        message
+    }
+
+    #[test]
+    fn deduplicates_and_orders_by_sender_order() {
+        let inbox = Inbox::new();
+        let second = ordered("thread-1", 1);
+        let first = ordered("thread-1", 0);
+        let other = Message::new();
+        assert!(inbox.push(second.clone()));
+        assert!(inbox.push(other.clone()));
+        assert!(inbox.push(first.clone()));
+        assert!(!inbox.push(second.clone()));
+
+        let ids: Vec<_> = std::iter::from_fn(|| inbox.poll()).collect();
+        let ids: Vec<_> = ids.iter().map(Message::get_id).collect();
+        assert_eq!(ids, [first.get_id(), second.get_id(), other.get_id()]);
+    }
+
+    #[test]
+    fn stream_ends_once_closed() {
+        let inbox = Inbox::new();
+        let producer = inbox.clone();
+        let handle = thread::spawn(move || {
+            producer.push(Message::new());
+            producer.push(Message::new());
+            producer.close();
+        });
+        assert_eq!(inbox.stream().count(), 2);
+        handle.join().unwrap();
+        assert!(!inbox.push(Message::new()));
+        assert!(inbox.recv_timeout(Duration::from_millis(1)).is_none());
+    }
+}
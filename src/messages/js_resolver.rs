@@ -0,0 +1,48 @@
+use js_sys::{Function, Promise, JSON};
+use serde_json::Value;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{AsyncDidResolver, Error, ResolveFuture, ResolvedDid};
+
+/// [`AsyncDidResolver`] backed by a JavaScript function, so browser wallets can reuse
+///     their DID resolution with `Agent::receive_async` and `Agent::pack_async`.
+/// Function is called with DID and returns DID document or DID resolution result,
+///     or promise of either.
+pub struct JsDidResolver {
+    resolve: Function,
+}
+
+impl JsDidResolver {
+    /// Constructor of resolver calling given function.
+    ///
+    /// # Parameters
+    ///
+    /// * `resolve` - JavaScript function `(did: string) => Promise<object>`
+    pub fn new(resolve: Function) -> Self {
+        Self { resolve }
+    }
+}
+
+impl AsyncDidResolver for JsDidResolver {
+    fn resolve<'a>(&'a self, did: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let returned = self
+                .resolve
+                .call1(&JsValue::NULL, &JsValue::from_str(did))
+                .map_err(js_error)?;
+            // plain values are wrapped, so synchronous functions work too
+            let resolved = JsFuture::from(Promise::resolve(&returned))
+                .await
+                .map_err(js_error)?;
+            let json: String = JSON::stringify(&resolved).map_err(js_error)?.into();
+            let resolved: Value = serde_json::from_str(&json)?;
+            ResolvedDid::from_document(resolved.get("didDocument").unwrap_or(&resolved))
+        })
+    }
+}
+
+fn js_error(e: JsValue) -> Error {
+    log::warn!("JavaScript DID resolver failed: {:?}", e);
+    Error::DidResolveFailed
+}
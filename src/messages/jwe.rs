@@ -1,9 +1,16 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
 use base64_url::{decode, encode};
 use rand::{prelude::SliceRandom, Rng};
+use serde_json::Value;
 
 use crate::{
-    messages::helpers::{create_fallback_getter, serialization_base64_jwm_header},
-    Jwk, JwmHeader,
+    messages::helpers::{
+        create_fallback_getter,
+        serialization_base64_jwm_header,
+        serialization_jwm_header,
+    },
+    Error, Jwk, JwmHeader, KeyAlgorithm,
 };
 
 /// This struct presents single recipient of JWE `recipients` collection.
@@ -11,6 +18,7 @@ use crate::{
 /// [Spec](https://tools.ietf.org/html/rfc7516#section-7.2.1)
 ///
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Recipient {
     pub header: Jwk,
 
@@ -24,20 +32,106 @@ impl Recipient {
             encrypted_key,
         }
     }
+
+    /// Copies caller provided values of `template` over generated header.
+    /// Values required for decryption (`epk`, `iv`, `tag`, ...) are kept as generated.
+    #[cfg(feature = "raw-crypto")]
+    pub(crate) fn apply_header_template(&mut self, template: &Jwk) {
+        if template.kid.is_some() {
+            self.header.kid = template.kid.clone();
+        }
+        if template.kty.is_some() {
+            self.header.kty = template.kty.clone();
+        }
+        if template.use_.is_some() {
+            self.header.use_ = template.use_.clone();
+        }
+        if template.crv.is_some() {
+            self.header.crv = template.crv.clone();
+        }
+        if !template.key_ops.is_empty() {
+            self.header.key_ops = template.key_ops.clone();
+        }
+        for (key, value) in &template.other {
+            self.header
+                .other
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Used to pre-populate per-recipient header values of `Message` before sealing.
+/// `seal()` generates key material per `to` entry and applies values of
+///     the recipient whose `kid` refers to the same DID.
+pub struct RecipientBuilder {
+    inner: Jwk,
+}
+
+impl RecipientBuilder {
+    /// Constructor of new instance of the builder.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - key id of recipient, DID URL of one of `to` entries, e.g. `did:example:bob#key-1`
+    ///
+    pub fn new(kid: &str) -> Self {
+        let mut inner = Jwk::new();
+        inner.kid = Some(kid.into());
+        Self { inner }
+    }
+
+    /// Adds additional header parameter, e.g. `apu` or any custom member.
+    /// Parameters required for decryption can not be overridden.
+    /// Values of known members, e.g. `crv`, are ignored if invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header parameter
+    ///
+    /// * `value` - value of the header parameter
+    ///
+    pub fn with_header(mut self, key: &str, value: Value) -> Self {
+        let _ = self.inner.set_header(key, value);
+        self
+    }
+
+    /// Overrides key wrap algorithm of message for this recipient,
+    ///     e.g. `KeyAlgorithm::EcdhEsA256kw` to not authenticate sender to this recipient.
+    /// Supported are `ECDH-1PU` and `ECDH-ES` with `A256KW` or `XC20PKW`,
+    ///     content encryption algorithm is still the one of the message.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - key wrap algorithm of this recipient
+    ///
+    pub fn with_alg(mut self, alg: KeyAlgorithm) -> Self {
+        self.inner.alg = alg;
+        self
+    }
+
+    pub(crate) fn finalize(self) -> Recipient {
+        Recipient::new(self.inner, String::default())
+    }
 }
 
 /// JWE representation of `Message` with public header.
 /// Can be serialized to JSON or Compact representations and from same.
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Jwe {
     /// integrity protected header elements
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "serialization_base64_jwm_header")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<String>"))]
     pub protected: Option<JwmHeader>,
 
     /// header elements that are not integrity protected
+    #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "serialization_jwm_header")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<JwmHeader>"))]
     pub unprotected: Option<JwmHeader>,
 
     /// Top-level recipient data for flat JWE JSON messages.
@@ -121,8 +215,124 @@ impl Jwe {
         decode(&self.ciphertext).unwrap()
     }
 
+    /// Getter of integrity protected header.
+    pub fn get_protected(&self) -> Option<&JwmHeader> {
+        self.protected.as_ref()
+    }
+
+    /// Getter of header elements that are not integrity protected.
+    pub fn get_unprotected(&self) -> Option<&JwmHeader> {
+        self.unprotected.as_ref()
+    }
+
+    /// Getter of all recipients, regardless of general or flat JWE JSON serialization.
+    /// `recipients` is preferred over top-level `recipient`, as in decryption.
+    pub fn get_recipients(&self) -> Vec<&Recipient> {
+        match (&self.recipients, &self.recipient) {
+            (Some(recipients), _) => recipients.iter().collect(),
+            (None, Some(recipient)) => vec![recipient],
+            (None, None) => vec![],
+        }
+    }
+
+    /// Getter of decoded ciphertext.
+    /// Unlike `get_payload` returns `Err` if ciphertext is not valid base64.
+    pub fn get_ciphertext(&self) -> Result<Vec<u8>, Error> {
+        Ok(decode(&self.ciphertext)?)
+    }
+
+    /// Getter of decoded initial vector.
+    /// Unlike `get_iv` returns `Err` if `iv` is not valid base64.
+    pub fn get_iv_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(decode(&self.iv)?)
+    }
+
+    /// Getter of decoded authentication tag.
+    pub fn get_tag(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.tag.as_ref().map(decode).transpose()?)
+    }
+
+    /// Setter of integrity protected header.
+    /// Changing it after encryption invalidates authentication tag.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - protected header, `None` to remove it
+    pub fn protected(mut self, header: Option<JwmHeader>) -> Self {
+        self.protected = header;
+        self
+    }
+
+    /// Setter of header elements that are not integrity protected.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - unprotected header, `None` to remove it
+    pub fn unprotected(mut self, header: Option<JwmHeader>) -> Self {
+        self.unprotected = header;
+        self
+    }
+
+    /// Setter of recipients for general JWE JSON.
+    /// Removes top-level recipient of flat JWE JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipients` - recipients with encrypted content encryption keys
+    pub fn recipients(mut self, recipients: Vec<Recipient>) -> Self {
+        self.recipient = None;
+        self.recipients = Some(recipients);
+        self
+    }
+
+    /// Setter of top-level recipient for flat JWE JSON.
+    /// Removes recipients of general JWE JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient` - recipient with encrypted content encryption key
+    pub fn recipient(mut self, recipient: Recipient) -> Self {
+        self.recipients = None;
+        self.recipient = Some(recipient);
+        self
+    }
+
+    /// Setter of ciphertext, e.g. produced by external encryption hardware.
+    ///
+    /// # Arguments
+    ///
+    /// * `ciphertext` - raw encrypted payload, gets base64 encoded
+    pub fn ciphertext(mut self, ciphertext: impl AsRef<[u8]>) -> Self {
+        self.ciphertext = encode(ciphertext.as_ref());
+        self
+    }
+
+    /// Setter of initial vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `iv` - raw initial vector used for encryption, gets base64 encoded
+    pub fn iv(mut self, iv: impl AsRef<[u8]>) -> Self {
+        self.iv = encode(iv.as_ref());
+        self
+    }
+
+    /// Setter of authentication tag.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - raw authentication tag, gets base64 encoded
+    pub fn tag(mut self, tag: impl AsRef<[u8]>) -> Self {
+        self.tag = Some(encode(tag.as_ref()));
+        self
+    }
+
     create_fallback_getter!(protected, unprotected, alg, String);
 
+    create_fallback_getter!(protected, unprotected, apu, String);
+
+    create_fallback_getter!(protected, unprotected, apv, String);
+
     create_fallback_getter!(protected, unprotected, cty, String);
 
     create_fallback_getter!(protected, unprotected, enc, String);
@@ -152,6 +362,28 @@ impl Jwe {
     }
 }
 
+impl fmt::Display for Jwe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl FromStr for Jwe {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl TryFrom<&[u8]> for Jwe {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
 #[test]
 fn default_jwe_with_random_iv() {
     // Arrange
@@ -161,3 +393,106 @@ fn default_jwe_with_random_iv() {
     // Assert
     assert_ne!(not_expected, decode(&jwe.iv).unwrap());
 }
+
+#[test]
+fn unknown_header_parameters_survive_round_trip() {
+    // Arrange
+    let protected = encode(r#"{"typ":"application/didcomm-encrypted+json","alg":"ECDH-1PU+XC20PKW","nonce":42}"#);
+    let raw_json = format!(
+        r#"{{
+            "protected": "{}",
+            "unprotected": {{ "typ": "application/didcomm-encrypted+json", "url": "https://example.com" }},
+            "recipients": [{{ "header": {{ "key_ops": [], "alg": "ECDH-ES", "iv": "aXY", "extra": 1 }}, "encrypted_key": "a2V5" }}],
+            "ciphertext": "",
+            "iv": "aXY"
+        }}"#,
+        protected
+    );
+    // Act
+    let jwe: Jwe = serde_json::from_str(&raw_json).unwrap();
+    let reparsed: Jwe = serde_json::from_str(&serde_json::to_string(&jwe).unwrap()).unwrap();
+    // Assert
+    let protected = reparsed.protected.unwrap();
+    assert_eq!(protected.other.get("nonce"), Some(&serde_json::json!(42)));
+    assert_eq!(protected.alg, Some("ECDH-1PU+XC20PKW".to_string()));
+    assert_eq!(
+        reparsed.unprotected.unwrap().other.get("url"),
+        Some(&serde_json::json!("https://example.com"))
+    );
+    assert_eq!(
+        reparsed.recipients.unwrap()[0].header.other.get("extra"),
+        Some(&serde_json::json!(1))
+    );
+}
+
+#[test]
+fn jwe_display_from_str_round_trip() {
+    // Arrange
+    let jwe = Jwe::new(None, None, b"payload", None, Some(b"tag"), None);
+    // Act
+    let parsed: Jwe = jwe.to_string().parse().unwrap();
+    let from_bytes = Jwe::try_from(jwe.to_string().as_bytes()).unwrap();
+    // Assert
+    assert_eq!(parsed.get_payload(), b"payload");
+    assert_eq!(from_bytes.get_iv().as_ref(), jwe.get_iv().as_ref());
+    assert!("{}".parse::<Jwe>().is_err());
+}
+
+#[test]
+fn custom_jwe_can_be_constructed_and_inspected() {
+    // Arrange
+    let recipient = Recipient::new(Jwk::new(), encode(b"key"));
+    // Act
+    let jwe = Jwe::default()
+        .protected(Some(JwmHeader::default()))
+        .recipient(recipient.clone())
+        .ciphertext(b"ciphertext")
+        .iv(b"iv")
+        .tag(b"tag");
+    let parsed: Jwe = jwe.to_string().parse().unwrap();
+    // Assert
+    assert!(parsed.get_protected().is_some());
+    assert!(parsed.get_unprotected().is_none());
+    assert_eq!(parsed.get_recipients(), vec![&recipient]);
+    assert_eq!(parsed.get_ciphertext().unwrap(), b"ciphertext");
+    assert_eq!(parsed.get_iv_bytes().unwrap(), b"iv");
+    assert_eq!(parsed.get_tag().unwrap(), Some(b"tag".to_vec()));
+    let general = parsed.recipients(vec![recipient.clone(), recipient]);
+    assert_eq!(general.get_recipients().len(), 2);
+    assert!(general.recipient.is_none());
+}
+
+#[test]
+fn per_recipient_headers_keep_arbitrary_members() {
+    // Arrange
+    let protected = encode(r#"{"typ":"application/didcomm-encrypted+json","enc":"XC20P"}"#);
+    let raw_json = format!(
+        r#"{{
+            "protected": "{}",
+            "recipients": [{{ "header": {{ "kid": "did:example:bob#key-1", "alg": "ECDH-ES+A256KW", "skid": "did:example:alice#key-1" }}, "encrypted_key": "a2V5" }}],
+            "ciphertext": "",
+            "iv": "aXY"
+        }}"#,
+        protected
+    );
+    let builder = RecipientBuilder::new("did:example:bob#key-1")
+        .with_header("crv", serde_json::json!("X25519"))
+        .with_header("hint", serde_json::json!({ "wallet": "bob" }));
+    // Act
+    let jwe: Jwe = raw_json.parse().unwrap();
+    let serialized = serde_json::to_string(&jwe).unwrap();
+    let template = builder.finalize().header;
+    // Assert
+    let header = &jwe.get_recipients()[0].header;
+    assert_eq!(header.alg, KeyAlgorithm::EcdhEsA256kw);
+    assert_eq!(header.get_header("kid"), Some(serde_json::json!("did:example:bob#key-1")));
+    assert_eq!(
+        header.other_headers().get("skid"),
+        Some(&serde_json::json!("did:example:alice#key-1"))
+    );
+    assert!(!serialized.contains("key_ops"));
+    assert_eq!(template.crv.as_deref(), Some("X25519"));
+    assert_eq!(template.get_header("hint"), Some(serde_json::json!({ "wallet": "bob" })));
+    let mut header = Jwk::new();
+    assert!(header.set_header("alg", serde_json::json!("unknown")).is_err());
+}
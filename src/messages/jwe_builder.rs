@@ -0,0 +1,293 @@
+use crate::{Error, Jwe, JwmHeader, Recipient, Result};
+
+/// Used to assemble `Jwe` envelope from parts produced outside of this crate,
+///     e.g. by an HSM or a remote key management service encrypting the payload.
+///
+/// Unlike `Message::seal`, no cryptography is performed: all values are taken as given.
+///     [`build`][JweBuilder::build()] only checks that the envelope has everything
+///     a recipient needs to decrypt it.
+///
+#[derive(Debug, Default)]
+pub struct JweBuilder {
+    protected: Option<JwmHeader>,
+    unprotected: Option<JwmHeader>,
+    recipients: Vec<Recipient>,
+    flat: bool,
+    ciphertext: Option<Vec<u8>>,
+    iv: Option<Vec<u8>>,
+    tag: Option<Vec<u8>>,
+}
+
+impl JweBuilder {
+    /// Constructor of new instance of the builder without any parts set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Setter of integrity protected header.
+    /// Its base64url encoded JSON serialization is additional authenticated data
+    ///     of the encryption.
+    ///
+    /// # Parameters
+    ///
+    /// * `header` - protected header, must contain `enc`
+    ///
+    pub fn protected(mut self, header: JwmHeader) -> Self {
+        self.protected = Some(header);
+        self
+    }
+
+    /// Setter of shared header elements that are not integrity protected, e.g. `skid`.
+    ///
+    /// # Parameters
+    ///
+    /// * `header` - unprotected header
+    ///
+    pub fn unprotected(mut self, header: JwmHeader) -> Self {
+        self.unprotected = Some(header);
+        self
+    }
+
+    /// Appends recipient with its encrypted content encryption key.
+    /// Envelopes without recipients use direct key agreement.
+    ///
+    /// # Parameters
+    ///
+    /// * `recipient` - per-recipient header and encrypted key
+    ///
+    pub fn recipient(mut self, recipient: Recipient) -> Self {
+        self.recipients.push(recipient);
+        self
+    }
+
+    /// Appends recipients with their encrypted content encryption keys.
+    ///
+    /// # Parameters
+    ///
+    /// * `recipients` - per-recipient headers and encrypted keys
+    ///
+    pub fn recipients(mut self, recipients: impl IntoIterator<Item = Recipient>) -> Self {
+        self.recipients.extend(recipients);
+        self
+    }
+
+    /// Uses flattened JWE JSON serialization with top-level recipient
+    ///     instead of general one with `recipients` array.
+    pub fn flat(mut self) -> Self {
+        self.flat = true;
+        self
+    }
+
+    /// Setter of ciphertext, without authentication tag.
+    ///
+    /// # Parameters
+    ///
+    /// * `ciphertext` - raw encrypted payload, gets base64 encoded
+    ///
+    pub fn ciphertext(mut self, ciphertext: impl AsRef<[u8]>) -> Self {
+        self.ciphertext = Some(ciphertext.as_ref().to_vec());
+        self
+    }
+
+    /// Setter of initial vector payload was encrypted with.
+    ///
+    /// # Parameters
+    ///
+    /// * `iv` - raw initial vector, gets base64 encoded
+    ///
+    pub fn iv(mut self, iv: impl AsRef<[u8]>) -> Self {
+        self.iv = Some(iv.as_ref().to_vec());
+        self
+    }
+
+    /// Setter of authentication tag of encryption.
+    ///
+    /// # Parameters
+    ///
+    /// * `tag` - raw authentication tag, gets base64 encoded
+    ///
+    pub fn tag(mut self, tag: impl AsRef<[u8]>) -> Self {
+        self.tag = Some(tag.as_ref().to_vec());
+        self
+    }
+
+    /// Validates parts and assembles `Jwe`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::PropertyIsNotSet` - `ciphertext`, `iv`, `tag`, `protected` header or its `enc`
+    ///   is missing, or `alg` is neither set in a shared header nor in every recipient header
+    ///
+    /// * `Error::FlatSerializationRecipients` - flat serialization with other than one recipient
+    ///
+    pub fn build(self) -> Result<Jwe> {
+        let ciphertext = self
+            .ciphertext
+            .ok_or(Error::PropertyIsNotSet("ciphertext"))?;
+        let iv = self.iv.ok_or(Error::PropertyIsNotSet("iv"))?;
+        let tag = self.tag.ok_or(Error::PropertyIsNotSet("tag"))?;
+        let protected = self.protected.ok_or(Error::PropertyIsNotSet("protected"))?;
+        if protected.enc.is_none() {
+            return Err(Error::PropertyIsNotSet("enc"));
+        }
+        let shared_alg = protected.alg.is_some()
+            || self
+                .unprotected
+                .as_ref()
+                .is_some_and(|header| header.alg.is_some());
+        let recipients_alg = !self.recipients.is_empty()
+            && self
+                .recipients
+                .iter()
+                .all(|recipient| !recipient.header.alg.is_none());
+        if !shared_alg && !recipients_alg {
+            return Err(Error::PropertyIsNotSet("alg"));
+        }
+        let iv = Some(base64_url::encode(&iv));
+        if self.flat {
+            let mut recipients = self.recipients;
+            if recipients.len() != 1 {
+                return Err(Error::FlatSerializationRecipients(recipients.len()));
+            }
+            return Ok(Jwe::new_flat(
+                self.unprotected,
+                recipients.remove(0),
+                ciphertext,
+                Some(protected),
+                Some(tag),
+                iv,
+            ));
+        }
+        let recipients = Some(self.recipients).filter(|recipients| !recipients.is_empty());
+        Ok(Jwe::new(
+            self.unprotected,
+            recipients,
+            ciphertext,
+            Some(protected),
+            Some(tag),
+            iv,
+        ))
+    }
+}
+
+impl Jwe {
+    /// Creates new `JweBuilder`, which validates envelope on `build()`.
+    pub fn builder() -> JweBuilder {
+        JweBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Jwk, MessageType};
+
+    fn protected() -> JwmHeader {
+        JwmHeader {
+            typ: MessageType::DidCommJwe,
+            enc: Some("XC20P".into()),
+            alg: Some("ECDH-1PU".into()),
+            ..JwmHeader::default()
+        }
+    }
+
+    #[test]
+    fn missing_parts_are_reported() {
+        let parts = || Jwe::builder().ciphertext(b"c").iv([1; 24]).tag([2; 16]);
+        assert!(matches!(
+            Jwe::builder().iv([1; 24]).tag([2; 16]).build(),
+            Err(Error::PropertyIsNotSet("ciphertext"))
+        ));
+        assert!(matches!(
+            parts().build(),
+            Err(Error::PropertyIsNotSet("protected"))
+        ));
+        assert!(matches!(
+            parts()
+                .protected(JwmHeader {
+                    alg: None,
+                    ..protected()
+                })
+                .build(),
+            Err(Error::PropertyIsNotSet("alg"))
+        ));
+        assert!(matches!(
+            parts().protected(protected()).flat().build(),
+            Err(Error::FlatSerializationRecipients(0))
+        ));
+    }
+
+    #[test]
+    fn envelope_is_assembled_from_given_parts() -> Result<()> {
+        let recipient = Recipient::new(Jwk::new(), "a2V5".into());
+        let jwe = Jwe::builder()
+            .protected(JwmHeader {
+                alg: None,
+                ..protected()
+            })
+            .unprotected(JwmHeader {
+                skid: Some("did:example:alice#key-1".into()),
+                alg: Some("ECDH-1PU+A256KW".into()),
+                ..JwmHeader::default()
+            })
+            .recipient(recipient.clone())
+            .ciphertext(b"ciphertext")
+            .iv([1; 24])
+            .tag([2; 16])
+            .build()?;
+        let parsed: Jwe = jwe.to_string().parse()?;
+        assert_eq!(parsed.get_ciphertext()?, b"ciphertext");
+        assert_eq!(parsed.get_iv_bytes()?, [1; 24]);
+        assert_eq!(parsed.get_tag()?, Some(vec![2; 16]));
+        assert_eq!(
+            parsed.get_skid().as_deref(),
+            Some("did:example:alice#key-1")
+        );
+        assert_eq!(parsed.get_recipients(), [&recipient]);
+        assert!(parsed.recipients.is_some());
+        Ok(())
+    }
+
+    #[cfg(feature = "raw-crypto")]
+    #[test]
+    fn externally_encrypted_envelope_can_be_received() -> Result<()> {
+        use crate::{
+            crypto::{CryptoAlgorithm, Cypher},
+            helpers::jwm_header_to_string,
+            Message,
+        };
+        use utilities::get_keypair_set;
+
+        let keys = get_keypair_set();
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"]);
+        // done by external system holding the key
+        let shared = x25519_dalek::StaticSecret::from(keys.alice_private)
+            .diffie_hellman(&x25519_dalek::PublicKey::from(keys.bobs_public));
+        let aad = base64_url::encode(&jwm_header_to_string(&protected())?);
+        let iv = [3; 24];
+        let sealed = CryptoAlgorithm::XC20P.encryptor()(
+            &iv,
+            shared.as_bytes(),
+            serde_json::to_string(&message)?.as_bytes(),
+            aad.as_bytes(),
+        )?;
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - 16);
+
+        let jwe = Jwe::builder()
+            .protected(protected())
+            .ciphertext(ciphertext)
+            .iv(iv)
+            .tag(tag)
+            .build()?;
+        let received = Message::receive(
+            &jwe.to_string(),
+            Some(&keys.bobs_private),
+            Some(keys.alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(received.get_didcomm_header(), message.get_didcomm_header());
+        Ok(())
+    }
+}
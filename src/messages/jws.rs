@@ -1,6 +1,13 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
 use crate::{
     helpers::create_fallback_getter,
-    messages::helpers::{serialization_base64_buffer, serialization_base64_jwm_header},
+    messages::helpers::{
+        serialization_base64_buffer,
+        serialization_base64_jwm_header,
+        serialization_signature_header,
+    },
+    Error,
     Jwk,
     JwmHeader,
 };
@@ -11,20 +18,26 @@ use crate::{
 /// [Flattened JWS JSON](https://datatracker.ietf.org/doc/html/rfc7515#section-7.2.2), triggered by
 /// [`.as_flat_jws`][crate::Message::as_flat_jws()].
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Signature {
     /// integrity protected header elements
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "serialization_base64_jwm_header")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<String>"))]
     pub protected: Option<JwmHeader>,
 
-    /// header elements that are not integrity protected
+    /// header elements that are not integrity protected, e.g. `kid`
+    #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "serialization_signature_header")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<JwmHeader>"))]
     pub header: Option<JwmHeader>,
 
     /// signature computed over protected header elements
     #[serde(default)]
     #[serde(with = "serialization_base64_buffer")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "String"))]
     pub signature: Vec<u8>,
 }
 
@@ -51,6 +64,21 @@ impl Signature {
         }
     }
 
+    /// Getter of integrity protected header.
+    pub fn get_protected(&self) -> Option<&JwmHeader> {
+        self.protected.as_ref()
+    }
+
+    /// Getter of header elements that are not integrity protected.
+    pub fn get_header(&self) -> Option<&JwmHeader> {
+        self.header.as_ref()
+    }
+
+    /// Getter of raw signature bytes.
+    pub fn get_signature(&self) -> &[u8] {
+        &self.signature
+    }
+
     create_fallback_getter!(header, protected, alg, String);
 
     create_fallback_getter!(header, protected, cty, String);
@@ -68,9 +96,34 @@ impl Signature {
     create_fallback_getter!(header, protected, skid, String);
 }
 
+/// Result of verifying single signature of a JWS,
+/// as returned by [`Message::verify_signatures`][crate::Message::verify_signatures()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureVerification {
+    /// `kid` of the signature, `None` if signature has no `kid`
+    pub kid: Option<String>,
+
+    /// `true` if signature is valid for key registered for `kid`
+    pub valid: bool,
+}
+
+/// Header `kid` of signatures is placed in when signing,
+/// see [`Message::with_signature_kid`][crate::Message::with_signature_kid()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KidPlacement {
+    /// integrity protected header only
+    #[default]
+    Protected,
+    /// per-signature unprotected `header` only
+    Header,
+    /// both, protected and per-signature unprotected header
+    Both,
+}
+
 /// A struct to generate and serialize [JWS](https://datatracker.ietf.org/doc/html/rfc7515)
 /// envelopes for DIDComm messages.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Jws {
     /// base64 encoded payload of the JWS
     pub payload: String,
@@ -119,4 +172,151 @@ impl Jws {
             signatures: None,
         }
     }
+
+    /// Getter of decoded payload, usually a serialized `Message`.
+    /// Unencoded payload ([RFC 7797](https://datatracker.ietf.org/doc/html/rfc7797)) is returned as is.
+    pub fn get_payload(&self) -> Result<Vec<u8>, Error> {
+        if self.is_unencoded() {
+            Ok(self.payload.as_bytes().to_vec())
+        } else {
+            Ok(base64_url::decode(&self.payload)?)
+        }
+    }
+
+    /// Checks if payload is embedded without base64url encoding,
+    ///     i.e. protected header of signatures has `b64` set to `false`.
+    pub fn is_unencoded(&self) -> bool {
+        self.get_signatures().iter().any(|signature| {
+            signature.protected.as_ref().and_then(|header| header.b64) == Some(false)
+        })
+    }
+
+    /// Getter of all signatures, regardless of general or flattened JWS JSON serialization.
+    /// `signatures` is preferred over top-level `signature`, as in verification.
+    pub fn get_signatures(&self) -> Vec<&Signature> {
+        match (&self.signatures, &self.signature) {
+            (Some(signatures), _) => signatures.iter().collect(),
+            (None, Some(signature)) => vec![signature],
+            (None, None) => vec![],
+        }
+    }
+
+    /// Getter of `kid` of each signature in order of `get_signatures`,
+    ///     taken from per-signature `header` or protected header.
+    /// Signatures are not verified, so this is meant to look up verification keys
+    ///     or reject unknown signers before doing so; `None` if signature has no `kid`.
+    pub fn signer_kids(&self) -> Vec<Option<String>> {
+        self.get_signatures()
+            .iter()
+            .map(|signature| signature.get_kid())
+            .collect()
+    }
+
+    /// Setter of payload.
+    /// Changing it after signing invalidates signatures.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - raw payload, gets base64 encoded
+    pub fn payload(mut self, payload: impl AsRef<[u8]>) -> Self {
+        self.payload = base64_url::encode(payload.as_ref());
+        self
+    }
+
+    /// Setter of signatures for general JWS JSON.
+    /// Removes top-level signature of flattened JWS JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `signatures` - signature values per recipient
+    pub fn signatures(mut self, signatures: Vec<Signature>) -> Self {
+        self.signature = None;
+        self.signatures = Some(signatures);
+        self
+    }
+
+    /// Setter of top-level signature for flattened JWS JSON.
+    /// Removes signatures of general JWS JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `signature_value` - signature value that is used on JWS top-level
+    pub fn signature(mut self, signature_value: Signature) -> Self {
+        self.signatures = None;
+        self.signature = Some(signature_value);
+        self
+    }
+}
+
+impl fmt::Display for Jws {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl FromStr for Jws {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl TryFrom<&[u8]> for Jws {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[test]
+fn custom_jws_can_be_constructed_and_inspected() {
+    // Arrange
+    let signature = Signature::new(Some(JwmHeader::default()), None, b"signature".to_vec());
+    // Act
+    let jws = Jws::new(String::default(), vec![])
+        .payload(br#"{"id":"1"}"#)
+        .signature(signature);
+    let parsed: Jws = jws.to_string().parse().unwrap();
+    // Assert
+    assert_eq!(parsed.get_payload().unwrap(), br#"{"id":"1"}"#);
+    let signatures = parsed.get_signatures();
+    assert_eq!(signatures.len(), 1);
+    assert_eq!(signatures[0].get_signature(), b"signature");
+    assert!(signatures[0].get_protected().is_some());
+    assert!(signatures[0].get_header().is_none());
+    assert!(parsed.signatures.is_none());
+}
+
+#[test]
+fn signer_kids_are_taken_from_both_headers() {
+    // Arrange
+    let protected = JwmHeader {
+        kid: Some("did:example:alice#key-1".into()),
+        ..JwmHeader::default()
+    };
+    let header = JwmHeader {
+        kid: Some("did:example:carol#key-1".into()),
+        ..JwmHeader::default()
+    };
+    // Act
+    let jws = Jws::new(
+        String::default(),
+        vec![
+            Signature::new(Some(protected), None, b"alice".to_vec()),
+            Signature::new(Some(JwmHeader::default()), Some(header), b"carol".to_vec()),
+            Signature::new(Some(JwmHeader::default()), None, b"unknown".to_vec()),
+        ],
+    );
+    let parsed: Jws = jws.to_string().parse().unwrap();
+    // Assert
+    assert_eq!(
+        parsed.signer_kids(),
+        [
+            Some("did:example:alice#key-1".to_string()),
+            Some("did:example:carol#key-1".to_string()),
+            None,
+        ]
+    );
 }
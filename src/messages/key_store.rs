@@ -0,0 +1,418 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde_json::Value;
+
+use crate::{
+    crypto::{passphrase::PassphraseKdf, CryptoAlgorithm, Cypher, KeyMaterial, KeyRole},
+    helpers::jwm_header_to_string,
+    Error,
+    Jwe,
+    JwmHeader,
+    MessageType,
+    Result,
+};
+
+/// Version of key exports written by [`KeyStore::export_key`].
+pub const KEY_EXPORT_VERSION: u32 = 1;
+
+/// `cty` of key exports, JWE content is JSON serialized [`KeyMaterial`].
+pub const KEY_EXPORT_CONTENT_TYPE: &str = "didcomm-key+json";
+
+/// Trait must be implemented for pluggable storage of our private keys,
+///     see [`Agent`][crate::Agent] for using it.
+pub trait KeyStore: Send + Sync {
+    /// Gets key with given id, e.g. `did:example:alice#key-1`.
+    fn get(&self, kid: &str) -> Option<KeyMaterial>;
+
+    /// Inserts or replaces key with given id.
+    fn put(&self, kid: &str, key: KeyMaterial);
+
+    /// Gets ids of all stored keys.
+    fn kids(&self) -> Vec<String>;
+
+    /// Finds key with given role by key id or, if there is no key with that id,
+    ///     first key of the DID it belongs to.
+    /// Returns id and key.
+    fn find(&self, did_or_kid: &str, role: KeyRole) -> Option<(String, KeyMaterial)> {
+        if let Some(key) = self.get(did_or_kid).filter(|key| key.role == role) {
+            return Some((did_or_kid.into(), key));
+        }
+        let did = did_or_kid.split('#').next().unwrap_or_default();
+        self.kids()
+            .into_iter()
+            .filter(|kid| kid.split('#').next() == Some(did))
+            .find_map(|kid| {
+                self.get(&kid)
+                    .filter(|key| key.role == role)
+                    .map(|key| (kid, key))
+            })
+    }
+
+    /// Exports key with given id as JWE encrypted with key derived from passphrase,
+    ///     so it can be moved to another agent with [`import_key`][KeyStore::import_key].
+    ///
+    /// # Errors
+    ///
+    /// `Error::KeyNotFound` if there is no key with id `kid`.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - id of key to export, e.g. `did:example:alice#key-1`
+    ///
+    /// * `passphrase` - secret export is encrypted with
+    fn export_key(&self, kid: &str, passphrase: &[u8]) -> Result<String> {
+        self.export_key_with_kdf(kid, passphrase, PassphraseKdf::generate())
+    }
+
+    /// Same as [`export_key`][KeyStore::export_key] but with given key derivation parameters,
+    ///     e.g. to trade strength for speed on constrained devices.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - id of key to export, e.g. `did:example:alice#key-1`
+    ///
+    /// * `passphrase` - secret export is encrypted with
+    ///
+    /// * `kdf` - parameters of key derivation, salt must be random
+    fn export_key_with_kdf(
+        &self,
+        kid: &str,
+        passphrase: &[u8],
+        kdf: PassphraseKdf,
+    ) -> Result<String> {
+        let key = self
+            .get(kid)
+            .ok_or_else(|| Error::KeyNotFound(kid.into()))?;
+        let mut protected = JwmHeader {
+            typ: MessageType::DidCommKeyExport,
+            alg: Some(kdf.alg.clone()),
+            enc: Some("XC20P".into()),
+            kid: Some(kid.into()),
+            cty: Some(KEY_EXPORT_CONTENT_TYPE.into()),
+            ..JwmHeader::default()
+        };
+        protected
+            .other
+            .insert("ver".into(), KEY_EXPORT_VERSION.into());
+        protected
+            .other
+            .insert("kdf".into(), serde_json::to_value(&kdf)?);
+        let aad = base64_url::encode(&jwm_header_to_string(&protected)?);
+        let iv = rand::random::<[u8; 24]>();
+        let sealed = CryptoAlgorithm::XC20P.encryptor()(
+            &iv,
+            &kdf.derive_key(passphrase)?,
+            &serde_json::to_vec(&key)?,
+            aad.as_bytes(),
+        )?;
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - 16);
+        Ok(Jwe::builder()
+            .protected(protected)
+            .ciphertext(ciphertext)
+            .iv(iv)
+            .tag(tag)
+            .build()?
+            .to_string())
+    }
+
+    /// Decrypts key exported by [`export_key`][KeyStore::export_key] and stores it
+    ///     under the id it was exported with, replacing key with same id if any.
+    /// Returns id of imported key.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidKeyExport` if export is malformed, of unsupported version,
+    ///     its key derivation costs exceed bounds of [`PassphraseKdf::check_costs`]
+    ///     or passphrase is wrong; nothing is stored in that case.
+    ///
+    /// # Parameters
+    ///
+    /// * `blob` - JWE written by `export_key`
+    ///
+    /// * `passphrase` - secret export was encrypted with
+    fn import_key(&self, blob: &str, passphrase: &[u8]) -> Result<String> {
+        let malformed =
+            |e: &dyn std::fmt::Display| Error::InvalidKeyExport(format!("malformed export; {}", e));
+        let jwe: Jwe = blob.parse().map_err(|e| malformed(&e))?;
+        let protected = jwe
+            .get_protected()
+            .filter(|header| {
+                header.typ == MessageType::DidCommKeyExport
+                    && header.cty.as_deref() == Some(KEY_EXPORT_CONTENT_TYPE)
+            })
+            .ok_or_else(|| Error::InvalidKeyExport("not a key export".into()))?;
+        let version = protected.other.get("ver").and_then(Value::as_u64);
+        if version != Some(KEY_EXPORT_VERSION.into()) {
+            return Err(Error::InvalidKeyExport(format!(
+                "unsupported version {}",
+                version.map_or_else(|| "none".into(), |version| version.to_string())
+            )));
+        }
+        if protected.enc.as_deref() != Some("XC20P") {
+            return Err(Error::InvalidKeyExport(format!(
+                "unsupported encryption {}",
+                protected.enc.as_deref().unwrap_or("none")
+            )));
+        }
+        let kid = protected
+            .kid
+            .clone()
+            .ok_or_else(|| Error::InvalidKeyExport("missing kid".into()))?;
+        let kdf: PassphraseKdf = protected
+            .other
+            .get("kdf")
+            .cloned()
+            .ok_or_else(|| Error::InvalidKeyExport("missing kdf".into()))
+            .and_then(|kdf| serde_json::from_value(kdf).map_err(|e| malformed(&e)))?;
+        if protected.alg.as_ref() != Some(&kdf.alg) {
+            return Err(Error::InvalidKeyExport("alg does not match kdf".into()));
+        }
+        let wrapping_key = kdf
+            .derive_key(passphrase)
+            .map_err(|e| Error::InvalidKeyExport(format!("invalid key derivation; {}", e)))?;
+        let aad = base64_url::encode(&jwm_header_to_string(protected)?);
+        let sealed = [
+            jwe.get_ciphertext().map_err(|e| malformed(&e))?,
+            jwe.get_tag()
+                .map_err(|e| malformed(&e))?
+                .unwrap_or_default(),
+        ]
+        .concat();
+        let key = CryptoAlgorithm::XC20P.decrypter()(
+            &jwe.get_iv_bytes().map_err(|e| malformed(&e))?,
+            &wrapping_key,
+            &sealed,
+            aad.as_bytes(),
+        )
+        .map_err(|_| Error::InvalidKeyExport("wrong passphrase or corrupted content".into()))?;
+        let key: KeyMaterial = serde_json::from_slice(&key).map_err(|e| malformed(&e))?;
+        self.put(&kid, key);
+        Ok(kid)
+    }
+}
+
+/// In-memory [`KeyStore`], keys are lost on drop.
+#[derive(Default)]
+pub struct InMemoryKeyStore {
+    keys: Mutex<BTreeMap<String, KeyMaterial>>,
+}
+
+impl InMemoryKeyStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn keys(&self) -> MutexGuard<'_, BTreeMap<String, KeyMaterial>> {
+        match self.keys.lock() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl KeyStore for InMemoryKeyStore {
+    fn get(&self, kid: &str) -> Option<KeyMaterial> {
+        self.keys().get(kid).cloned()
+    }
+
+    fn put(&self, kid: &str, key: KeyMaterial) {
+        self.keys().insert(kid.into(), key);
+    }
+
+    fn kids(&self) -> Vec<String> {
+        self.keys().keys().cloned().collect()
+    }
+}
+
+/// [`KeyStore`] view of keys of a single tenant of a shared key store,
+///     so one process can host many independent identities.
+/// Keys are stored in the shared store under tenant name and key id, so lookups
+///     of one tenant never find keys of another, even for the same DIDs.
+#[derive(Clone)]
+pub struct TenantKeyStore {
+    keys: Arc<dyn KeyStore>,
+    tenant: String,
+}
+
+impl TenantKeyStore {
+    /// Constructor of view scoped to given tenant.
+    ///
+    /// # Parameters
+    ///
+    /// * `keys` - store shared by all tenants
+    ///
+    /// * `tenant` - name of tenant or profile, e.g. id of a hosted mediator client
+    pub fn new(keys: Arc<dyn KeyStore>, tenant: &str) -> Self {
+        Self {
+            keys,
+            tenant: tenant.into(),
+        }
+    }
+
+    /// Name of tenant keys are scoped to.
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Prefix of ids keys of tenant are stored with in shared store;
+    ///     tenant is prefixed with its length, so no tenant name or key id
+    ///     can make ids of two tenants collide.
+    fn prefix(&self) -> String {
+        format!("{}:{}", self.tenant.len(), self.tenant)
+    }
+
+    /// Id key is stored with in shared store.
+    fn scoped(&self, kid: &str) -> String {
+        format!("{}{}", self.prefix(), kid)
+    }
+}
+
+impl KeyStore for TenantKeyStore {
+    fn get(&self, kid: &str) -> Option<KeyMaterial> {
+        self.keys.get(&self.scoped(kid))
+    }
+
+    fn put(&self, kid: &str, key: KeyMaterial) {
+        self.keys.put(&self.scoped(kid), key);
+    }
+
+    fn kids(&self) -> Vec<String> {
+        let prefix = self.prefix();
+        self.keys
+            .kids()
+            .into_iter()
+            .filter_map(|scoped| scoped.strip_prefix(&prefix).map(str::to_string))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::Curve,
+        EnvelopeKind,
+        Message,
+    };
+
+    #[test]
+    fn tenants_do_not_share_keys() {
+        let key = |byte| KeyMaterial {
+            curve: Curve::X25519,
+            role: KeyRole::KeyAgreement,
+            private: true,
+            bytes: vec![byte; 32],
+        };
+        let shared = Arc::new(InMemoryKeyStore::new());
+        let acme = TenantKeyStore::new(shared.clone(), "acme");
+        let globex = TenantKeyStore::new(shared.clone(), "globex");
+        acme.put("did:example:alice#key-1", key(1));
+        globex.put("did:example:alice#key-1", key(2));
+        globex.put("did:example:bob#key-1", key(3));
+
+        let (kid, found) = acme
+            .find("did:example:alice", KeyRole::KeyAgreement)
+            .unwrap();
+        assert_eq!(kid, "did:example:alice#key-1");
+        assert_eq!(found.bytes, vec![1; 32]);
+        assert_eq!(
+            globex.get("did:example:alice#key-1").unwrap().bytes,
+            vec![2; 32]
+        );
+        assert!(acme
+            .find("did:example:bob", KeyRole::KeyAgreement)
+            .is_none());
+        assert_eq!(acme.kids(), ["did:example:alice#key-1"]);
+        assert_eq!(globex.kids().len(), 2);
+        assert!(shared.get("did:example:alice#key-1").is_none());
+
+        // names and ids with separators can't reach keys of other tenants
+        let spaced = TenantKeyStore::new(shared, "acme did:example:alice#key-1");
+        spaced.put("x", key(4));
+        acme.put("did:example:alice#key-1 x", key(5));
+        assert_eq!(
+            acme.get("did:example:alice#key-1").unwrap().bytes,
+            vec![1; 32]
+        );
+        assert_eq!(spaced.kids(), ["x"]);
+        assert_eq!(spaced.get("x").unwrap().bytes, vec![4; 32]);
+    }
+
+    #[test]
+    fn keys_are_exported_and_imported() -> Result<()> {
+        let device = InMemoryKeyStore::new();
+        device.put(
+            "did:example:alice#key-1",
+            KeyMaterial {
+                curve: Curve::Ed25519,
+                role: KeyRole::Signing,
+                private: true,
+                bytes: vec![7; 32],
+            },
+        );
+        assert!(matches!(
+            device.export_key("did:example:alice#key-2", b"correct horse"),
+            Err(Error::KeyNotFound(_))
+        ));
+
+        // cheap parameters keep the test fast, real exports use defaults
+        let kdf = PassphraseKdf::with_costs(64, 1, 1);
+        let blob = device.export_key_with_kdf("did:example:alice#key-1", b"correct horse", kdf)?;
+        assert!(!blob.contains("BwcHBwcH"));
+
+        // exports are no messages, so they are neither routed nor received as such
+        assert_eq!(EnvelopeKind::detect(&blob), EnvelopeKind::Unknown);
+        assert!(matches!(
+            Message::receive(&blob, Some(&[1; 32]), None, None),
+            Err(Error::EnvelopeTypeMismatch(..))
+        ));
+
+        let migrated = InMemoryKeyStore::new();
+        assert!(matches!(
+            migrated.import_key(&blob, b"battery staple"),
+            Err(Error::InvalidKeyExport(e)) if e == "wrong passphrase or corrupted content"
+        ));
+        assert!(migrated.kids().is_empty());
+        assert_eq!(
+            migrated.import_key(&blob, b"correct horse")?,
+            "did:example:alice#key-1"
+        );
+        let imported = migrated.get("did:example:alice#key-1").unwrap();
+        assert_eq!(imported.curve, Curve::Ed25519);
+        assert_eq!(imported.role, KeyRole::Signing);
+        assert_eq!(imported.bytes, vec![7; 32]);
+
+        // protected header is authenticated, so its parameters can't be changed
+        let mut jwe: Jwe = blob.parse()?;
+        let mut protected = jwe.protected.take().unwrap();
+        protected.kid = Some("did:example:mallory#key-1".into());
+        let tampered = jwe.protected(Some(protected.clone())).to_string();
+        assert!(matches!(
+            migrated.import_key(&tampered, b"correct horse"),
+            Err(Error::InvalidKeyExport(_))
+        ));
+        protected.other.insert("ver".into(), 2.into());
+        let mut jwe: Jwe = blob.parse()?;
+        jwe.protected = Some(protected);
+        assert!(matches!(
+            migrated.import_key(&jwe.to_string(), b"correct horse"),
+            Err(Error::InvalidKeyExport(e)) if e == "unsupported version 2"
+        ));
+        let mut jwe: Jwe = blob.parse()?;
+        let mut protected = jwe.protected.take().unwrap();
+        protected.other.insert(
+            "kdf".into(),
+            serde_json::to_value(PassphraseKdf::with_costs(u32::MAX, 1, 1))?,
+        );
+        let expensive = jwe.protected(Some(protected)).to_string();
+        assert!(matches!(
+            migrated.import_key(&expensive, b"correct horse"),
+            Err(Error::InvalidKeyExport(e)) if e.contains("m_cost")
+        ));
+        Ok(())
+    }
+}
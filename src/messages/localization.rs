@@ -0,0 +1,107 @@
+use serde_json::{Map, Value};
+
+use crate::Message;
+
+/// Suffix of body fields holding translations of user facing text,
+///     as in [Aries RFC 0043](https://github.com/hyperledger/aries-rfcs/tree/main/features/0043-l10n).
+pub const L10N_SUFFIX: &str = "~l10n";
+
+/// `lang` header and localized body content.
+impl Message {
+    /// Setter of `lang` header, language of user facing content of the body.
+    ///
+    /// # Parameters
+    ///
+    /// * `lang` - IETF BCP 47 language tag, e.g. `en` or `de-CH`
+    pub fn lang(mut self, lang: &str) -> Self {
+        self.didcomm_header.lang = Some(lang.into());
+        self
+    }
+
+    /// Getter of `lang` header.
+    pub fn get_lang(&self) -> Option<&str> {
+        self.didcomm_header.lang.as_deref()
+    }
+
+    /// Adds translations of user facing body field, e.g. `comment` of a problem report,
+    ///     as `<field>~l10n` object mapping language tags to text.
+    /// Field itself keeps text in the language of `lang` header.
+    /// Translations already present for other languages are kept.
+    ///
+    /// # Parameters
+    ///
+    /// * `field` - name of the top level body field
+    ///
+    /// * `translations` - pairs of language tag and translated text
+    pub fn localize(mut self, field: &str, translations: &[(&str, &str)]) -> Self {
+        let body = self.body_mut();
+        if !body.is_object() {
+            *body = Value::Object(Map::new());
+        }
+        let localized = body
+            .as_object_mut()
+            .map(|body| {
+                body.entry(format!("{}{}", field, L10N_SUFFIX))
+                    .or_insert_with(|| Value::Object(Map::new()))
+            })
+            .and_then(Value::as_object_mut);
+        if let Some(localized) = localized {
+            for (lang, text) in translations {
+                localized.insert(lang.to_string(), Value::String(text.to_string()));
+            }
+        }
+        self
+    }
+
+    /// Gets text of user facing body field in requested language.
+    /// Falls back to the field itself if `lang` header matches or no translation exists.
+    ///
+    /// # Parameters
+    ///
+    /// * `field` - name of the top level body field
+    ///
+    /// * `lang` - requested language tag, primary language is used if region does not match
+    pub fn get_localized(&self, field: &str, lang: &str) -> Option<&str> {
+        let original = self.body_at(&format!("/{}", field)).and_then(Value::as_str);
+        if self.get_lang() == Some(lang) {
+            return original;
+        }
+        let translations = self.body_at(&format!("/{}{}", field, L10N_SUFFIX));
+        let primary = lang.split('-').next().unwrap_or(lang);
+        translations
+            .and_then(|t| t.get(lang).or_else(|| t.get(primary)))
+            .and_then(Value::as_str)
+            .or(original)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn localized_fields_fall_back_to_original() {
+        let message = Message::new()
+            .lang("en")
+            .merge_body(json!({ "comment": "Hello" }))
+            .localize("comment", &[("de", "Hallo"), ("fr", "Bonjour")])
+            .localize("comment", &[("es", "Hola")]);
+
+        assert_eq!(message.body_at("/comment~l10n/fr"), Some(&json!("Bonjour")));
+        assert_eq!(message.get_localized("comment", "en"), Some("Hello"));
+        assert_eq!(message.get_localized("comment", "de-CH"), Some("Hallo"));
+        assert_eq!(message.get_localized("comment", "es"), Some("Hola"));
+        assert_eq!(message.get_localized("comment", "it"), Some("Hello"));
+        assert_eq!(message.get_localized("missing", "de"), None);
+    }
+
+    #[test]
+    fn lang_survives_serialization() -> crate::Result<()> {
+        let message = Message::new().lang("de");
+        let parsed: Message = message.to_string().parse()?;
+        assert_eq!(parsed.get_lang(), Some("de"));
+        Ok(())
+    }
+}
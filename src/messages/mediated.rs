@@ -1,4 +1,10 @@
-use crate::{messages::Shape, Error, Message};
+use serde_json::Value;
+
+#[cfg(feature = "raw-crypto")]
+use crate::crypto::CryptoAlgorithm;
+use crate::{
+    messages::Shape, AttachmentBuilder, AttachmentDataBuilder, Error, Message, MessageType,
+};
 
 /// Mediated Message value
 #[derive(Serialize, Deserialize, Debug)]
@@ -6,7 +12,8 @@ pub struct Mediated {
     /// `DidUrl` of delivery target
     pub next: String,
 
-    /// "inner" message, that should be routed to target
+    /// "inner" message, that should be routed to target;
+    ///     empty in batch forwards, which carry their messages as attachments
     #[serde(rename = "payloads~attach")]
     pub payload: Vec<u8>,
 }
@@ -24,6 +31,76 @@ impl Mediated {
         }
     }
 
+    /// Builds batch forward message carrying many messages for same target
+    ///     as its attachments, e.g. to reduce overhead on links between mediators.
+    /// Forward is to be sealed for the next mediator, which splits it with `split`;
+    ///     its `type` keeps it recognizable as forward once `typ` is set by `as_jwe`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Generic` if `payloads` is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `next` - `DidUrl` of delivery target.
+    ///
+    /// * `payloads` - "inner" messages, that should be routed to target
+    pub fn batch(next: String, payloads: Vec<Vec<u8>>) -> Result<Message, Error> {
+        if payloads.is_empty() {
+            return Err(Error::Generic(
+                "batch forward needs at least one payload".into(),
+            ));
+        }
+        let mut forward = Message::new()
+            .typ(MessageType::DidCommForward)
+            .m_type(MessageType::DidCommForward.as_str())
+            .body(&serde_json::to_string(&Mediated::new(next))?)?;
+        for payload in payloads {
+            forward.append_attachment(
+                AttachmentBuilder::new(false)
+                    .with_data(AttachmentDataBuilder::new().with_raw_payload(payload)),
+            );
+        }
+        Ok(forward)
+    }
+
+    /// Returns all messages carried by forward message, `payloads~attach` of its body
+    ///     first, followed by attachments of batch forwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `forward` - received forward message
+    pub fn payloads(forward: &Message) -> Result<Vec<Vec<u8>>, Error> {
+        let mut payloads = vec![Self::from_message(forward)?.payload];
+        payloads.retain(|payload| !payload.is_empty());
+        for attachment in &forward.attachments {
+            payloads.push(attachment.decoded_content()?);
+        }
+        Ok(payloads)
+    }
+
+    /// Splits forward message into one forward per carried message, all for same target,
+    ///     in order of `payloads`.
+    ///
+    /// # Arguments
+    ///
+    /// * `forward` - received forward message, batch forward or single one
+    pub fn split(forward: &Message) -> Result<Vec<Mediated>, Error> {
+        let next = Self::from_message(forward)?.next;
+        Ok(Self::payloads(forward)?
+            .into_iter()
+            .map(|payload| Mediated::new(next.clone()).with_payload(payload))
+            .collect())
+    }
+
+    /// Body of forward message, which may be serialized into a string.
+    fn from_message(forward: &Message) -> Result<Self, Error> {
+        Ok(match &forward.body {
+            Value::String(body) => serde_json::from_str(body)?,
+            body => serde_json::from_value(body.clone())?,
+        })
+    }
+
     /// Payload setter to be chained in forwarding calls.
     ///
     /// # Example
@@ -37,13 +114,82 @@ impl Mediated {
     pub fn with_payload(self, payload: Vec<u8>) -> Self {
         Mediated { payload, ..self }
     }
+
+    /// Wraps this forward into a new forward envelope for a different next hop,
+    ///     e.g. for mediators routing through upstream relays.
+    /// `next` and the attached end-to-end envelope are kept unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key of the rewrapping mediator
+    ///
+    /// * `from` - DID of the rewrapping mediator
+    ///
+    /// * `next_mediator_did` - DID of next hop, will be `to` of the new envelope
+    ///
+    /// * `next_mediator_public_key` - key used to encrypt content encryption key for next hop;
+    ///   can be provided if key should not be resolved via its DID
+    ///
+    /// * `alg` - encryption algorithm of the new envelope
+    #[cfg(feature = "raw-crypto")]
+    pub fn rewrap(
+        &self,
+        sender_private_key: &[u8],
+        from: &str,
+        next_mediator_did: &str,
+        next_mediator_public_key: Option<Vec<u8>>,
+        alg: &CryptoAlgorithm,
+    ) -> Result<String, Error> {
+        Message::new()
+            .to(&[next_mediator_did])
+            .from(from)
+            .as_jwe(alg, next_mediator_public_key.clone())
+            .typ(MessageType::DidCommForward)
+            .body(&serde_json::to_string(self)?)?
+            .seal(sender_private_key, Some(vec![next_mediator_public_key]))
+    }
 }
 
 impl Shape for Mediated {
     type Err = Error;
 
     fn shape(m: &Message) -> Result<Self, Self::Err> {
-        serde_json::from_str::<Mediated>(&serde_json::to_string(&m.get_body()?)?)
-            .map_err(Error::SerdeError)
+        serde_json::from_value::<Mediated>(m.body.clone()).map_err(Error::SerdeError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_forwards_split_into_single_forwards() -> Result<(), Error> {
+        let batch = Mediated::batch(
+            "did:example:bob".into(),
+            vec![b"first".to_vec(), b"second".to_vec()],
+        )?;
+        assert_eq!(batch.attachments.len(), 2);
+        let received: Message = serde_json::from_str(&serde_json::to_string(&batch)?)?;
+        assert_eq!(
+            Mediated::payloads(&received)?,
+            [b"first".to_vec(), b"second".to_vec()]
+        );
+
+        let split = Mediated::split(&received)?;
+        assert_eq!(split.len(), 2);
+        assert!(split
+            .iter()
+            .all(|forward| forward.next == "did:example:bob"));
+        assert_eq!(split[1].payload, b"second");
+
+        // forwards without batch carry their message in body
+        let single = Message::new().body(&serde_json::to_string(
+            &Mediated::new("did:example:bob".into()).with_payload(b"only".to_vec()),
+        )?)?;
+        assert_eq!(Mediated::payloads(&single)?, [b"only".to_vec()]);
+        assert!(single.attachments.is_empty());
+
+        assert!(Mediated::batch("did:example:bob".into(), vec![]).is_err());
+        Ok(())
     }
 }
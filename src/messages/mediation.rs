@@ -0,0 +1,258 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use serde_json::Value;
+
+use crate::{DidCommMessagingService, Error, Message, Result};
+
+/// Mediation granted to a DID, as result of
+///     [coordinate-mediation](https://didcomm.org/coordinate-mediation/2.0/) protocol.
+/// Messages to `did` have to be forwarded via `mediator_did`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediationRecord {
+    /// DID messages are routed to, either ours or one of the other party
+    pub did: String,
+
+    /// DID of the mediator, `to` of forward messages
+    pub mediator_did: String,
+
+    /// routing keys announced by the mediator
+    pub routing_keys: Vec<String>,
+
+    /// service endpoint of the mediator, if announced
+    pub endpoint: Option<String>,
+
+    /// public key of the mediator, `None` to resolve it from `mediator_did`
+    pub mediator_key: Option<Vec<u8>>,
+}
+
+impl MediationRecord {
+    /// Constructor without routing keys and endpoint.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID messages are routed to
+    ///
+    /// * `mediator_did` - DID of the mediator
+    pub fn new(did: &str, mediator_did: &str) -> Self {
+        Self {
+            did: did.into(),
+            mediator_did: mediator_did.into(),
+            routing_keys: vec![],
+            endpoint: None,
+            mediator_key: None,
+        }
+    }
+
+    /// Creates record from received `mediate-grant` message.
+    /// Understands `routing_did` of v2 grants as well as `routing_keys` and `endpoint` of v1.
+    /// `mediator_did` is taken from `from` header of the grant.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - our DID mediation was requested for
+    ///
+    /// * `grant` - received `mediate-grant` message
+    pub fn from_grant(did: &str, grant: &Message) -> Result<Self> {
+        let mediator_did = grant
+            .get_didcomm_header()
+            .from
+            .as_deref()
+            .filter(|from| !from.is_empty())
+            .ok_or_else(|| Error::Generic("mediate-grant has no `from`".into()))?;
+        let mut record = Self::new(did, mediator_did);
+        for field in ["/routing_did", "/routing_keys"] {
+            match grant.body_at(field) {
+                Some(Value::String(key)) => record.routing_keys.push(key.clone()),
+                Some(Value::Array(keys)) => record
+                    .routing_keys
+                    .extend(keys.iter().filter_map(Value::as_str).map(Into::into)),
+                _ => {}
+            }
+        }
+        record.endpoint = grant
+            .body_at("/endpoint")
+            .and_then(Value::as_str)
+            .map(Into::into);
+        Ok(record)
+    }
+
+    /// Setter of mediator public key, used instead of resolving `mediator_did`.
+    pub fn with_mediator_key(mut self, key: &[u8]) -> Self {
+        self.mediator_key = Some(key.to_vec());
+        self
+    }
+
+    /// Setter of mediator service endpoint.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Service of the mediator messages to `did` have to be sent to,
+    ///     `None` if no valid endpoint was announced.
+    pub fn service(&self) -> Option<DidCommMessagingService> {
+        let mut service = DidCommMessagingService::new(self.endpoint.as_deref()?);
+        service.routing_keys = self.routing_keys.clone();
+        service.validate().ok().map(|_| service)
+    }
+}
+
+/// Trait must be implemented for pluggable mediation storage,
+///     consulted by [`Message::seal_and_route`].
+pub trait MediationStore: Send + Sync {
+    /// Gets mediation of given DID.
+    fn get(&self, did: &str) -> Option<MediationRecord>;
+
+    /// Inserts or replaces mediation of `record.did`.
+    fn put(&self, record: MediationRecord);
+
+    /// Removes mediation of given DID, e.g. after mediation was revoked.
+    fn remove(&self, did: &str) -> Option<MediationRecord>;
+}
+
+/// In-memory [`MediationStore`], records are lost on drop.
+#[derive(Default)]
+pub struct InMemoryMediationStore {
+    records: Mutex<HashMap<String, MediationRecord>>,
+}
+
+impl InMemoryMediationStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn records(&self) -> std::sync::MutexGuard<'_, HashMap<String, MediationRecord>> {
+        match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl MediationStore for InMemoryMediationStore {
+    fn get(&self, did: &str) -> Option<MediationRecord> {
+        self.records().get(did).cloned()
+    }
+
+    fn put(&self, record: MediationRecord) {
+        self.records().insert(record.did.clone(), record);
+    }
+
+    fn remove(&self, did: &str) -> Option<MediationRecord> {
+        self.records().remove(did)
+    }
+}
+
+#[cfg(feature = "raw-crypto")]
+impl Message {
+    /// Seals message and, if recipient has mediation recorded in `mediations`,
+    ///     wraps it into forward message to the mediator, see [`Message::routed_by`].
+    /// Mediation is looked up for first DID in `to` header.
+    /// Sealed without forward wrapping if recipient has no mediation.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient,
+    ///   `None` to resolve them via recipients DID
+    ///
+    /// * `mediations` - store of known mediations
+    pub fn seal_and_route(
+        self,
+        sender_private_key: &[u8],
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mediations: &dyn MediationStore,
+    ) -> Result<String> {
+        let mediation = self
+            .didcomm_header
+            .to
+            .first()
+            .and_then(|to| mediations.get(to));
+        match mediation {
+            Some(mediation) => self.routed_by(
+                sender_private_key,
+                recipient_public_keys,
+                &mediation.mediator_did,
+                mediation.mediator_key,
+            ),
+            None => self.seal(sender_private_key, recipient_public_keys),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn records_are_created_from_grants() -> Result<()> {
+        let grant = Message::new()
+            .from("did:example:mediator")
+            .merge_body(json!({ "routing_did": ["did:example:mediator#key-1"] }));
+        let record = MediationRecord::from_grant("did:example:alice", &grant)?;
+        assert_eq!(record.mediator_did, "did:example:mediator");
+        assert_eq!(record.routing_keys, ["did:example:mediator#key-1"]);
+
+        let grant = Message::new().from("did:example:mediator").merge_body(json!({
+            "endpoint": "https://mediator.example.com",
+            "routing_keys": ["did:key:z6Mk"],
+        }));
+        let record = MediationRecord::from_grant("did:example:alice", &grant)?;
+        assert_eq!(record.endpoint.as_deref(), Some("https://mediator.example.com"));
+        assert_eq!(record.routing_keys, ["did:key:z6Mk"]);
+
+        assert!(MediationRecord::from_grant("did:example:alice", &Message::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "raw-crypto")]
+    fn seal_and_route_forwards_to_recorded_mediator() -> Result<()> {
+        use utilities::{get_keypair_set, KeyPairSet};
+
+        use crate::{crypto::CryptoAlgorithm, Mediated};
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let store = InMemoryMediationStore::new();
+        store.put(
+            MediationRecord::new("did:example:bob", "did:example:mediator")
+                .with_mediator_key(&mediators_public),
+        );
+        let message = || {
+            Message::new()
+                .from("did:example:alice")
+                .to(&["did:example:bob"])
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+        };
+
+        let routed = message().seal_and_route(
+            &alice_private,
+            Some(vec![Some(bobs_public.to_vec())]),
+            &store,
+        )?;
+        let forward = Message::receive(&routed, Some(&mediators_private), Some(alice_public.to_vec()), None)?;
+        let forward: Mediated = serde_json::from_str(&forward.get_body()?)?;
+        assert_eq!(forward.next, "did:example:bob");
+        let payload = String::from_utf8_lossy(&forward.payload);
+        assert!(Message::receive(&payload, Some(&bobs_private), Some(alice_public.to_vec()), None).is_ok());
+
+        store.remove("did:example:bob");
+        let direct = message().seal_and_route(
+            &alice_private,
+            Some(vec![Some(bobs_public.to_vec())]),
+            &store,
+        )?;
+        assert!(Message::receive(&direct, Some(&bobs_private), Some(alice_public.to_vec()), None).is_ok());
+        Ok(())
+    }
+}
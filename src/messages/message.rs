@@ -1,13 +1,61 @@
 #![allow(dead_code)]
-use std::time::SystemTime;
+use std::{convert::TryFrom, fmt, str::FromStr, time::SystemTime};
 
 #[cfg(feature = "raw-crypto")]
 use crate::{
-    crypto::{CryptoAlgorithm, Cypher, SignatureAlgorithm, Signer},
-    helpers::{encrypt_cek, get_crypter_from_header, get_message_type, receive_jwe, receive_jws},
-    Jwe, Mediated,
+    crypto::{
+        diagnostics,
+        ContentEncAlg,
+        CryptoAlgorithm,
+        Cypher,
+        HardwareKey,
+        KeyAgreementAlg,
+        KeyMaterial,
+        KeyRole,
+        SignatureAlgorithm,
+        Signer,
+    },
+    helpers::{
+        anoncrypt_alg,
+        AgreementKey,
+        authenticated_sender,
+        authenticated_skid,
+        check_envelope_typ,
+        check_jwe_algorithms,
+        check_jws_algorithms,
+        encrypt_cek,
+        get_crypter_from_header,
+        get_message_type,
+        forwarded_payload,
+        is_anoncrypt,
+        is_key_of_kid,
+        normalize_incoming,
+        receive_jwe,
+        recipient_alg,
+        receive_jwe_with_any_key,
+        DEFLATE,
+        DEFAULT_MAX_DECOMPRESSED_SIZE,
+        receive_jws,
+        refers_to_same_did,
+        v1_aliases_to_v2,
+        wrapped_jwe,
+    },
+    messages::{audit, receive_pipeline::check_replay},
+    EnvelopeKind, Jwe, Jws, Mediated, Piuri, Profile, ReceiveOptions, SenderInfo,
+};
+use crate::{
+    Attachment,
+    DidCommHeader,
+    Error,
+    Jwk,
+    JwmHeader,
+    KidPlacement,
+    MessageType,
+    PriorClaims,
+    Recipient,
+    RecipientBuilder,
+    ReturnRoute,
 };
-use crate::{Attachment, DidCommHeader, Error, JwmHeader, MessageType, PriorClaims, Recipient};
 #[cfg(feature = "raw-crypto")]
 use base64_url::decode;
 #[cfg(all(feature = "resolve", feature = "raw-crypto"))]
@@ -16,7 +64,7 @@ use ddoresolver_rs::*;
 use rand::{RngCore, SeedableRng};
 #[cfg(feature = "raw-crypto")]
 use rand_chacha::ChaCha20Rng;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 use crate::Result;
 
@@ -37,6 +85,7 @@ use crate::Result;
 ///
 /// [Specification](https://identity.foundation/didcomm-messaging/spec/#message-structure)
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Message {
     /// JOSE header, which is sent as public part with JWE.
     #[serde(flatten)]
@@ -65,6 +114,28 @@ pub struct Message {
     #[serde(skip)]
     pub(crate) serialize_flat_jws: bool,
 
+    /// Header `kid` of signatures is placed in when signing.
+    /// Not part of the serialized JSON and ignored when deserializing.
+    #[serde(skip)]
+    pub(crate) signature_kid: KidPlacement,
+
+    /// Custom headers copied into protected header when sealing or signing.
+    /// Not part of the serialized JSON and ignored when deserializing.
+    #[serde(skip)]
+    pub(crate) integrity_headers: Vec<String>,
+
+    /// Per-recipient header values applied when sealing, see `with_recipient`.
+    /// Not part of the serialized JSON and ignored when deserializing,
+    ///     so they never end up in plaintext or signed payloads.
+    #[serde(skip)]
+    pub(crate) recipient_templates: Vec<Recipient>,
+
+    /// Conformance profile checked when sealing.
+    /// Not part of the serialized JSON and ignored when deserializing.
+    #[cfg(feature = "raw-crypto")]
+    #[serde(skip)]
+    pub(crate) profile: Option<Profile>,
+
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub(crate) attachments: Vec<Attachment>,
 }
@@ -73,9 +144,6 @@ impl Message {
     /// Generates EMPTY default message.
     /// Use extension messages to build final one before `send`ing.
     pub fn new() -> Self {
-        match env_logger::try_init() {
-            Ok(_) | Err(_) => (),
-        }
         Message {
             jwm_header: JwmHeader::default(),
             didcomm_header: DidCommHeader::new(),
@@ -84,6 +152,11 @@ impl Message {
             attachments: Vec::new(),
             serialize_flat_jwe: false,
             serialize_flat_jws: false,
+            signature_kid: KidPlacement::default(),
+            integrity_headers: Vec::new(),
+            recipient_templates: Vec::new(),
+            #[cfg(feature = "raw-crypto")]
+            profile: None,
         }
     }
 
@@ -93,7 +166,74 @@ impl Message {
         if key.is_empty() {
             return self;
         }
-        self.didcomm_header.other.insert(key, value);
+        self.didcomm_header.other.insert(key, Value::String(value));
+        self
+    }
+
+    /// Adds (or updates) custom header with any serializable value,
+    ///     e.g. number, array or object.
+    /// This portion of header is not sent as JOSE header.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header, empty names are ignored
+    ///
+    /// * `value` - value of the header
+    pub fn set_header<T: Serialize>(mut self, key: &str, value: T) -> Result<Self> {
+        if key.is_empty() {
+            return Ok(self);
+        }
+        self.didcomm_header
+            .other
+            .insert(key.to_string(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+
+    /// Gets raw JSON value of custom header.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    pub fn get_header(&self, key: &str) -> Option<&Value> {
+        self.didcomm_header.other.get(key)
+    }
+
+    /// Gets custom header deserialized into `T`.
+    /// Returns `Ok(None)` if header is not set and `Err` if it cannot be deserialized into `T`.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    pub fn get_header_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.get_header(key)
+            .map(|value| T::deserialize(value).map_err(Error::SerdeError))
+            .transpose()
+    }
+
+    /// Marks custom header as integrity-critical: when sealing or signing, its value
+    ///     is copied into the JWE/JWS protected header and thus authenticated, but
+    ///     readable by mediators. Receiving fails with `Error::ProtectedHeaderMismatch`
+    ///     if plaintext copy of header differs from protected one.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of custom header, e.g. set with `add_header_field`
+    pub fn protect_header(mut self, key: &str) -> Self {
+        if !key.is_empty() && !self.integrity_headers.iter().any(|name| name == key) {
+            self.integrity_headers.push(key.to_string());
+        }
+        self
+    }
+
+    /// Restricts `alg`/`enc`/curve combinations `seal`ing this message may produce,
+    ///     violations fail with `Error::ProfileViolation`.
+    ///
+    /// # Parameters
+    ///
+    /// * `profile` - conformance profile, e.g. `Profile::DidCommV2`
+    #[cfg(feature = "raw-crypto")]
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
         self
     }
 
@@ -117,6 +257,36 @@ impl Message {
         self.as_jws(alg)
     }
 
+    /// Sets header `kid` of signatures is placed in when signing, e.g. per-signature
+    ///     unprotected `header` of general JWS. Defaults to protected header.
+    ///
+    /// # Parameters
+    ///
+    /// * `placement` - protected header, per-signature header or both
+    #[cfg(feature = "raw-crypto")]
+    pub fn with_signature_kid(mut self, placement: KidPlacement) -> Self {
+        self.signature_kid = placement;
+        self
+    }
+
+    /// Enables DEFLATE compression of plaintext before encryption by setting `zip` header
+    ///     to `"DEF"`. Has no effect on JWS. Receiving decompresses such messages transparently.
+    #[cfg(feature = "raw-crypto")]
+    pub fn compressed(mut self) -> Self {
+        self.jwm_header.zip = Some(DEFLATE.into());
+        self
+    }
+
+    /// Embeds payload of JWS as is, without base64url encoding ([RFC 7797](https://datatracker.ietf.org/doc/html/rfc7797)),
+    ///     by setting `b64` header to `false` and listing it in `crit` header.
+    /// Avoids base64 expansion of large payloads. Has no effect on JWE.
+    #[cfg(feature = "raw-crypto")]
+    pub fn unencoded_payload(mut self) -> Self {
+        self.jwm_header.b64 = Some(false);
+        self.jwm_header.crit = Some(vec!["b64".into()]);
+        self
+    }
+
     /// Shortcut to `DidCommHeader::get_message_uri`
     ///
     pub fn get_message_uri(&self) -> String {
@@ -132,6 +302,42 @@ impl Message {
         self
     }
 
+    /// Creates new message answering `received`.
+    /// Addresses it back to the sender of `received`, sends it from the DID
+    ///     `received` was addressed to, puts it into same thread and copies `lang` header.
+    ///
+    /// If `received` had more than one recipient, `from` is left unset
+    ///     and should be set with `.from()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `received` - ref to message we're replying to
+    ///
+    pub fn reply(received: &Self) -> Self {
+        let received_header = &received.didcomm_header;
+        let mut reply = Message::new().clear_to();
+        if let Some(from) = &received_header.from {
+            reply = reply.to(&[from]);
+        }
+        let recipients: Vec<&String> = received_header
+            .to
+            .iter()
+            .filter(|to| !to.is_empty())
+            .collect();
+        if let [recipient] = recipients.as_slice() {
+            reply = reply.from(recipient);
+        }
+        reply.didcomm_header.thid = Some(
+            received_header
+                .thid
+                .clone()
+                .unwrap_or_else(|| received_header.id.clone()),
+        );
+        reply.didcomm_header.pthid = received_header.pthid.clone();
+        reply.didcomm_header.lang = received_header.lang.clone();
+        reply
+    }
+
     /// Sets `pthid` to the `parent`'s `thid`.
     /// It defaults to `id` if `thid` is missing.
     ///
@@ -165,6 +371,8 @@ impl Message {
             #[cfg(feature = "resolve")]
             {
                 if let Some(from) = &self.didcomm_header.from {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!("resolve", did = %from).entered();
                     if let Some(document) = resolve_any(from) {
                         match alg {
                             CryptoAlgorithm::XC20P => {
@@ -182,6 +390,29 @@ impl Message {
         self
     }
 
+    /// Same as `as_jwe` but with key agreement `alg` and content encryption `enc`
+    ///     chosen independently, e.g. `ECDH-1PU+XC20PKW` with `A256GCM`.
+    /// `alg` is made anonymous on `seal_anonymous`.
+    ///
+    /// # Arguments
+    ///
+    /// * `alg` - key agreement and wrapping of content encryption key
+    ///
+    /// * `enc` - content encryption
+    ///
+    /// * `recipient_public_key` - key of recipient, set as `kid`
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_jwe_with(
+        mut self,
+        alg: KeyAgreementAlg,
+        enc: ContentEncAlg,
+        recipient_public_key: Option<Vec<u8>>,
+    ) -> Self {
+        self = self.as_jwe(&enc.into(), recipient_public_key);
+        self.jwm_header.as_encrypted_with(alg, enc);
+        self
+    }
+
     /// Creates set of JWM related headers for the JWE
     /// Modifies JWM related header portion to match
     ///     encryption implementation and leaves other
@@ -218,6 +449,34 @@ impl Message {
         Ok(serde_json::to_string(&self.body)?)
     }
 
+    /// Mutable reference to the `body` for in-place modifications.
+    pub fn body_mut(&mut self) -> &mut Value {
+        &mut self.body
+    }
+
+    /// Merges given value into the `body` following JSON merge patch rules
+    ///     ([RFC 7396](https://datatracker.ietf.org/doc/html/rfc7396)):
+    ///     objects are merged recursively, `null` removes a property,
+    ///     any other value replaces existing one.
+    ///
+    /// # Parameters
+    ///
+    /// * `patch` - value to be merged into the body
+    pub fn merge_body(mut self, patch: Value) -> Self {
+        merge_json(&mut self.body, patch);
+        self
+    }
+
+    /// Gets part of the `body` by [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901),
+    ///     e.g. `/foo/bar` or `/items/0`.
+    ///
+    /// # Parameters
+    ///
+    /// * `pointer` - JSON pointer into the body, empty string refers to whole body
+    pub fn body_at(&self, pointer: &str) -> Option<&Value> {
+        self.body.pointer(pointer)
+    }
+
     /// `&DidCommHeader` getter.
     pub fn get_didcomm_header(&self) -> &DidCommHeader {
         &self.didcomm_header
@@ -248,6 +507,16 @@ impl Message {
         self.didcomm_header.from_prior().is_some()
     }
 
+    /// Setter of `from_prior` header, turns message into rotation message.
+    ///
+    /// # Parameters
+    ///
+    /// * `claims` - rotation claims, `sub` should be the new DID used as `from`
+    pub fn from_prior(mut self, claims: PriorClaims) -> Self {
+        self.didcomm_header.set_from_prior(Some(claims));
+        self
+    }
+
     /// Setter of `jwm_header`.
     /// Replaces existing one with provided by consuming both values.
     /// Returns modified instance of `Self`.
@@ -283,6 +552,64 @@ impl Message {
         self
     }
 
+    /// Setter of `apu` header, used as PartyUInfo in key derivation of `seal`.
+    ///
+    /// # Parameters
+    ///
+    /// * `apu` - raw information about the producer, will be base64url encoded
+    pub fn apu(mut self, apu: &[u8]) -> Self {
+        self.jwm_header.apu = Some(base64_url::encode(apu));
+        self
+    }
+
+    /// Getter of decoded `apu` header.
+    /// `Err` is returned if header is not valid base64url.
+    pub fn get_apu(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .jwm_header
+            .apu
+            .as_ref()
+            .map(base64_url::decode)
+            .transpose()?)
+    }
+
+    /// Setter of `apv` header, used as PartyVInfo in key derivation of `seal`.
+    ///
+    /// # Parameters
+    ///
+    /// * `apv` - raw information about the recipient, will be base64url encoded
+    pub fn apv(mut self, apv: &[u8]) -> Self {
+        self.jwm_header.apv = Some(base64_url::encode(apv));
+        self
+    }
+
+    /// Getter of decoded `apv` header.
+    /// `Err` is returned if header is not valid base64url.
+    pub fn get_apv(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .jwm_header
+            .apv
+            .as_ref()
+            .map(base64_url::decode)
+            .transpose()?)
+    }
+
+    /// Setter of `epk` header, e.g. for integrations doing key agreement themselves
+    ///     and sealing with `seal_pre_encrypted`.
+    ///
+    /// # Parameters
+    ///
+    /// * `epk` - ephemeral public key
+    pub fn epk(mut self, epk: Jwk) -> Self {
+        self.jwm_header.epk = Some(epk);
+        self
+    }
+
+    /// Getter of `epk` header.
+    pub fn get_epk(&self) -> Option<&Jwk> {
+        self.jwm_header.epk.as_ref()
+    }
+
     /// Sets times of creation as now and, optional, expires time.
     ///
     /// # Arguments
@@ -300,9 +627,12 @@ impl Message {
     }
 
     /// Setter of `to` header
+    /// Appends given DIDs to `to`, skipping those already present.
     pub fn to(mut self, to: &[&str]) -> Self {
         for s in to {
-            self.didcomm_header.to.push(s.to_string());
+            if !self.didcomm_header.to.iter().any(|e| e == s) {
+                self.didcomm_header.to.push(s.to_string());
+            }
         }
         while let Some(a) = self
             .didcomm_header
@@ -315,6 +645,38 @@ impl Message {
         self
     }
 
+    /// Removes all recipients from `to` header.
+    pub fn clear_to(mut self) -> Self {
+        self.didcomm_header.to.clear();
+        self
+    }
+
+    /// Removes single recipient from `to` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID to be removed, no-op if not present
+    pub fn remove_to(mut self, did: &str) -> Self {
+        self.didcomm_header.to.retain(|e| e != did);
+        self
+    }
+
+    /// Pre-populates per-recipient header values applied when message is `seal`ed.
+    /// `kid` of recipient has to refer to DID of one of `to`, sealing fails otherwise.
+    ///
+    /// # Parameters
+    ///
+    /// * `builder` - prepopulated instance of `RecipientBuilder`
+    pub fn with_recipient(mut self, builder: RecipientBuilder) -> Self {
+        self.recipient_templates.push(builder.finalize());
+        self
+    }
+
+    /// Getter of current recipients in `to` header.
+    pub fn get_to(&self) -> &[String] {
+        &self.didcomm_header.to
+    }
+
     /// Setter of `didcomm_header`.
     /// Replaces existing one with provided by consuming both values.
     /// Returns modified instance of `Self`.
@@ -323,9 +685,57 @@ impl Message {
         self
     }
 
-    /// Gets `Iterator` over key-value pairs of application level headers
+    /// Gets `Iterator` over key-value pairs of application level headers with string values.
+    /// Headers with other JSON values are kept in message but not listed here.
     pub fn get_application_params(&self) -> impl Iterator<Item = (&String, &String)> {
-        self.didcomm_header.other.iter()
+        self.didcomm_header
+            .other
+            .iter()
+            .filter_map(|(key, value)| match value {
+                Value::String(value) => Some((key, value)),
+                _ => None,
+            })
+    }
+
+    /// Gets `Iterator` over application level headers deserialized into `T`.
+    /// Headers which cannot be deserialized into `T` are skipped.
+    pub fn get_application_params_as<T: DeserializeOwned>(
+        &self,
+    ) -> impl Iterator<Item = (&String, T)> {
+        self.didcomm_header
+            .other
+            .iter()
+            .filter_map(|(key, value)| T::deserialize(value).ok().map(|value| (key, value)))
+    }
+
+    /// Gets custom header as string.
+    /// Returns `None` if header is not set or is not a string.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    pub fn get_header_str(&self, key: &str) -> Option<&str> {
+        self.get_header(key).and_then(Value::as_str)
+    }
+
+    /// Gets custom header as unsigned integer.
+    /// Returns `None` if header is not set or is not an unsigned integer.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    pub fn get_header_u64(&self, key: &str) -> Option<u64> {
+        self.get_header(key).and_then(Value::as_u64)
+    }
+
+    /// Gets custom header as boolean.
+    /// Returns `None` if header is not set or is not a boolean.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    pub fn get_header_bool(&self, key: &str) -> Option<bool> {
+        self.get_header(key).and_then(Value::as_bool)
     }
 
     /// Setter of `thid` header
@@ -339,6 +749,115 @@ impl Message {
         self.didcomm_header.pthid = Some(pthid.to_string());
         self
     }
+
+    /// Setter of `return_route` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `return_route` - which responses recipient should return over the same connection
+    pub fn return_route(mut self, return_route: ReturnRoute) -> Self {
+        self.didcomm_header.return_route = Some(return_route);
+        self
+    }
+
+    /// Getter of `return_route` header.
+    pub fn get_return_route(&self) -> Option<ReturnRoute> {
+        self.didcomm_header.return_route
+    }
+
+    /// Setter of `accept` list of envelope media types and profiles we can handle.
+    /// Out-of-band invitations carry it in their body, as required by spec,
+    ///     other messages as header.
+    ///
+    /// # Parameters
+    ///
+    /// * `accept` - media types and profiles in order of preference,
+    ///   e.g. `application/didcomm-encrypted+json` or `didcomm/v2`
+    pub fn accept(mut self, accept: &[&str]) -> Self {
+        if self.is_invitation() {
+            self.merge_body(json!({ "accept": accept }))
+        } else {
+            self.didcomm_header.accept = accept.iter().map(|a| a.to_string()).collect();
+            self
+        }
+    }
+
+    /// Getter of `accept` list, read from body of out-of-band invitations.
+    /// Empty if sender did not announce it.
+    pub fn get_accept(&self) -> Vec<&str> {
+        if !self.is_invitation() {
+            return self.didcomm_header.accept.iter().map(String::as_str).collect();
+        }
+        self.body_at("/accept")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect()
+    }
+
+    fn is_invitation(&self) -> bool {
+        self.didcomm_header.m_type == MessageType::DidCommInvitation.as_str()
+    }
+
+    /// Getter of `id` header.
+    pub fn get_id(&self) -> &str {
+        &self.didcomm_header.id
+    }
+
+    /// Getter of `thid` header.
+    pub fn get_thid(&self) -> Option<&str> {
+        self.didcomm_header.thid.as_deref()
+    }
+
+    /// Getter of `pthid` header.
+    pub fn get_pthid(&self) -> Option<&str> {
+        self.didcomm_header.pthid.as_deref()
+    }
+
+    /// Getter of `type` header.
+    pub fn get_type(&self) -> &str {
+        &self.didcomm_header.m_type
+    }
+
+    /// Getter of `created_time` header in seconds since Unix Epoch.
+    pub fn get_created_time(&self) -> Option<u64> {
+        self.didcomm_header.created_time
+    }
+
+    /// Getter of `expires_time` header in seconds since Unix Epoch.
+    pub fn get_expires_time(&self) -> Option<u64> {
+        self.didcomm_header.expires_time
+    }
+
+    /// Compares messages ignoring values which differ on every packing:
+    ///     `id`, `created_time`, `epk` and `iv` headers as well as per-recipient
+    ///     `epk`, `iv`, `tag` and `encrypted_key`.
+    /// Meant for asserting round-trip equality in tests.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - message to compare with
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.without_volatile_fields() == other.without_volatile_fields()
+    }
+
+    /// Copy of message with fields ignored by `semantically_eq` cleared.
+    fn without_volatile_fields(&self) -> Self {
+        let mut message = self.clone();
+        message.didcomm_header.id = String::default();
+        message.didcomm_header.created_time = None;
+        message.jwm_header.epk = None;
+        message.jwm_header.other.remove("iv");
+        message.didcomm_header.other.remove("iv");
+        for recipient in message.recipients.iter_mut().flatten() {
+            recipient.header.epk = None;
+            recipient.header.other.remove("iv");
+            recipient.header.other.remove("tag");
+            recipient.encrypted_key = String::default();
+        }
+        message
+    }
 }
 
 // Interactions with messages (sending, receiving, etc.)
@@ -351,6 +870,7 @@ impl Message {
     }
 
     /// Presents IV and Payload to be externally encrypted and then sealed with `seal_pre_encrypted` method.
+    /// Use `export_encryption_context` if AAD and recipients are needed as well.
     ///
     /// # Returns
     /// Tuple of bytes where .0 is IV and .1 is payload for encryption
@@ -379,13 +899,14 @@ impl Message {
             ..Default::default()
         };
 
-        if self.recipients.is_none() {
+        let recipients = self.jwe_recipients().map(<[Recipient]>::to_vec);
+        if recipients.is_none() {
             unprotected.kid = Some(d_header.to[0].clone());
         }
 
         let jwe = Jwe::new(
             Some(unprotected),
-            self.recipients.clone(),
+            recipients,
             cyphertext,
             Some(self.jwm_header.clone()),
             None::<&[u8]>,
@@ -413,82 +934,624 @@ impl Message {
         encryption_sender_public_key: Option<Vec<u8>>,
         signing_sender_public_key: Option<&[u8]>,
     ) -> Result<Self> {
-        let mut current_message: String = incoming.to_string();
-
-        if get_message_type(&current_message)? == MessageType::DidCommJwe {
-            let recipient_private_key = encryption_recipient_private_key.ok_or_else(|| {
-                Error::Generic("missing encryption recipient private key".to_string())
-            })?;
-            current_message = receive_jwe(
-                &current_message,
-                recipient_private_key,
-                encryption_sender_public_key,
-            )?;
-        }
-
-        if get_message_type(&current_message)? == MessageType::DidCommJws {
-            current_message = receive_jws(&current_message, signing_sender_public_key)?;
-        }
-
-        Ok(serde_json::from_str(&current_message)?)
+        Self::receive_with_options(
+            incoming,
+            encryption_recipient_private_key,
+            encryption_sender_public_key,
+            signing_sender_public_key,
+            &ReceiveOptions::default(),
+        )
     }
 
-    /// Wrap self to be mediated by some mediator.
-    /// Warning: Should be called on a `Message` instance which is ready to be sent!
-    /// If message is not properly set up for crypto - this method will propagate error from
-    ///     called `.seal()` method.
-    /// Takes one mediator at a time to make sure that mediated chain preserves unchanged.
-    /// This method can be chained any number of times to match all the mediators in the chain.
+    /// Same as [`Message::receive`] but additionally applies checks configured in `options`
+    /// to unpacked message.
     ///
     /// # Arguments
     ///
-    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jws`
     ///
-    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
-    ///                             can be provided if key should not be resolved via recipients DID
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
     ///
-    /// * `mediator_did` - DID of message mediator, will be `to` of mediated envelope
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
     ///
-    /// * `mediator_public_key` - key used to encrypt content encryption key for mediator;
-    ///                           can be provided if key should not be resolved via mediators DID
-    pub fn routed_by(
-        self,
-        sender_private_key: &[u8],
-        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
-        mediator_did: &str,
-        mediator_public_key: Option<Vec<u8>>,
-    ) -> Result<String> {
-        let from = &self.didcomm_header.from.clone().unwrap_or_default();
-        let alg = get_crypter_from_header(&self.jwm_header)?;
-        let body = Mediated::new(self.didcomm_header.to[0].clone()).with_payload(
-            self.seal(sender_private_key, recipient_public_keys)?
-                .as_bytes()
-                .to_vec(),
-        );
-        Message::new()
-            .to(&[mediator_did])
-            .from(from)
-            .as_jwe(&alg, mediator_public_key.clone())
-            .typ(MessageType::DidCommForward)
-            .body(&serde_json::to_string(&body)?)?
-            .seal(sender_private_key, Some(vec![mediator_public_key]))
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn receive_with_options(
+        incoming: &str,
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<Self> {
+        let signing_sender_public_keys: Vec<&[u8]> =
+            signing_sender_public_key.into_iter().collect();
+        Self::receive_with_candidate_keys(
+            incoming,
+            encryption_recipient_private_key,
+            encryption_sender_public_key,
+            &signing_sender_public_keys,
+            options,
+        )
+        .map(|(message, _)| message)
     }
 
-    /// Seals (encrypts) self and returns ready to send JWE
+    /// Same as [`Message::receive_with_options`] but takes raw bytes as handed over by
+    ///     transports, so binary buffers need no conversion before unpacking.
+    /// UTF-8 input is parsed in place; byte order marks and surrounding whitespace are
+    ///     ignored, UTF-16 input with byte order mark is decoded and compact JWS and JWE
+    ///     serializations are accepted next to JSON ones.
     ///
     /// # Arguments
     ///
-    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jwe`, JSON or compact
     ///
-    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
-    ///                             can be provided if key should not be resolved via recipients DID
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn receive_bytes(
+        incoming: &[u8],
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<Self> {
+        Self::receive_with_options(
+            &normalize_incoming(incoming)?,
+            encryption_recipient_private_key,
+            encryption_sender_public_key,
+            signing_sender_public_key,
+            options,
+        )
+    }
+
+    /// Same as [`Message::receive_with_options`] but accepts multiple candidate keys
+    /// for JWS verification, e.g. all authentication keys of sender DID.
+    ///
+    /// # Returns
+    /// Tuple of unpacked message and index of the key in `signing_sender_public_keys`
+    ///     that validated the signature, `None` if message was not signed or key was
+    ///     taken from `kid`
+    ///
+    /// # Arguments
+    ///
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jws`
+    ///
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_keys` - candidate public keys of sender, tried in given order
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn receive_with_candidate_keys(
+        incoming: &str,
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_keys: &[&[u8]],
+        options: &ReceiveOptions,
+    ) -> Result<(Self, Option<usize>)> {
+        Self::receive_audited(
+            incoming,
+            encryption_recipient_private_key.map(AgreementKey::Raw),
+            encryption_sender_public_key,
+            signing_sender_public_keys,
+            options,
+        )
+        .map(|(message, key_index, _)| (message, key_index))
+    }
+
+    /// Same as [`Message::receive_with_options`] but also returns sender related data
+    ///     gathered while unpacking, e.g. forwards unwrapped on the way.
+    ///
+    /// # Arguments
+    ///
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jws`
+    ///
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn receive_with_sender_info(
+        incoming: &str,
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<(Self, SenderInfo)> {
+        let signing_sender_public_keys: Vec<&[u8]> =
+            signing_sender_public_key.into_iter().collect();
+        Self::receive_audited(
+            incoming,
+            encryption_recipient_private_key.map(AgreementKey::Raw),
+            encryption_sender_public_key,
+            &signing_sender_public_keys,
+            options,
+        )
+        .map(|(message, _, sender)| (message, sender))
+    }
+
+    /// Same as [`Message::receive_with_options`] but decrypts with recipient key held
+    ///     in a keystore, so its private key is never exported.
+    ///
+    /// # Arguments
+    ///
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jws`
+    ///
+    /// * `recipient_key` - handle of recipients X25519 key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn receive_hardware(
+        incoming: &str,
+        recipient_key: &dyn HardwareKey,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<Self> {
+        let signing_sender_public_keys: Vec<&[u8]> =
+            signing_sender_public_key.into_iter().collect();
+        Self::receive_audited(
+            incoming,
+            Some(AgreementKey::Hardware(recipient_key)),
+            encryption_sender_public_key,
+            &signing_sender_public_keys,
+            options,
+        )
+        .map(|(message, _, _)| message)
+    }
+
+    /// Implementation of `receive_with_candidate_keys` with audit record.
+    pub(crate) fn receive_audited(
+        incoming: &str,
+        encryption_recipient_private_key: Option<AgreementKey<'_>>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_keys: &[&[u8]],
+        options: &ReceiveOptions,
+    ) -> Result<(Self, Option<usize>, SenderInfo)> {
+        let record = audit::unpack_record(incoming);
+        let result = Self::receive_unaudited(
+            incoming,
+            encryption_recipient_private_key,
+            encryption_sender_public_key,
+            signing_sender_public_keys,
+            options,
+        );
+        let unpacked = result.as_ref().ok().map(|(message, _, _)| message);
+        audit::submit(record, &result, unpacked);
+        result
+    }
+
+    /// Implementation of `receive_with_candidate_keys` without audit record.
+    fn receive_unaudited(
+        incoming: &str,
+        encryption_recipient_private_key: Option<AgreementKey<'_>>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_keys: &[&[u8]],
+        options: &ReceiveOptions,
+    ) -> Result<(Self, Option<usize>, SenderInfo)> {
+        let v1_aliases = |message: String| {
+            if options.v1_aliases {
+                v1_aliases_to_v2(&message)
+            } else {
+                Ok(message)
+            }
+        };
+        let mut current_message: String = v1_aliases(incoming.to_string())?;
+        let mut key_index = None;
+        let mut sender = SenderInfo::default();
+        let mut authcrypt_skid = None;
+        let mut signature_kid = None;
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "receive",
+            id = tracing::field::Empty,
+            thid = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        if get_message_type(&current_message)? == MessageType::DidCommJwe {
+            loop {
+                let jwe: Jwe = serde_json::from_str(&current_message)?;
+                // exported keys are JWEs too, but are never received as messages
+                if let Some(header) = jwe
+                    .protected
+                    .as_ref()
+                    .filter(|header| header.typ == MessageType::DidCommKeyExport)
+                {
+                    return Err(Error::EnvelopeTypeMismatch(
+                        header.typ.to_string(),
+                        MessageType::DidCommJwe.to_string(),
+                    ));
+                }
+                let recipient_private_key = encryption_recipient_private_key.ok_or_else(|| {
+                    Error::Generic("missing encryption recipient private key".to_string())
+                })?;
+                if options.validate_types {
+                    check_envelope_typ(jwe.protected.as_ref(), MessageType::DidCommJwe)?;
+                }
+                if let Some(profile) = &options.profile {
+                    profile.check_jwe(&jwe)?;
+                }
+                if let Some(allowed) = &options.allowed_algorithms {
+                    check_jwe_algorithms(&jwe, allowed)?;
+                }
+                sender.skid = jwe.get_skid();
+                authcrypt_skid = authenticated_skid(&jwe);
+                let max_decompressed_size = options
+                    .max_decompressed_size
+                    .unwrap_or(DEFAULT_MAX_DECOMPRESSED_SIZE);
+                current_message = if options.unwrap_forwards {
+                    receive_jwe_with_any_key(
+                        &current_message,
+                        recipient_private_key,
+                        &options.forward_keys,
+                        encryption_sender_public_key.clone(),
+                        max_decompressed_size,
+                    )?
+                } else {
+                    receive_jwe(
+                        &current_message,
+                        recipient_private_key,
+                        encryption_sender_public_key.clone(),
+                        max_decompressed_size,
+                    )?
+                };
+                #[cfg(feature = "tracing")]
+                tracing::debug!(skid = ?sender.skid, "decrypted JWE envelope");
+                if let Some(wrapped) = wrapped_jwe(&current_message)? {
+                    current_message = wrapped;
+                    continue;
+                }
+                if !options.unwrap_forwards {
+                    break;
+                }
+                match forwarded_payload(&current_message)? {
+                    Some((hop, payload))
+                        if get_message_type(&payload)? == MessageType::DidCommJwe =>
+                    {
+                        sender.forward_hops.push(hop);
+                        current_message = payload;
+                    }
+                    _ => break,
+                }
+            }
+            current_message = v1_aliases(current_message)?;
+        }
+
+        if get_message_type(&current_message)? == MessageType::DidCommJws {
+            if options.validate_types {
+                let jws: Jws = serde_json::from_str(&current_message)?;
+                for signature in jws.get_signatures() {
+                    check_envelope_typ(signature.protected.as_ref(), MessageType::DidCommJws)?;
+                }
+            }
+            if let Some(allowed) = &options.allowed_algorithms {
+                check_jws_algorithms(&current_message, allowed)?;
+            }
+            let (verified, index, kid) = receive_jws(&current_message, signing_sender_public_keys)?;
+            current_message = v1_aliases(verified)?;
+            key_index = index;
+            signature_kid = kid;
+            sender.signed = true;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(key_index = ?key_index, "verified JWS envelope");
+        }
+
+        let mut message: Message = serde_json::from_str(&current_message)?;
+        if options.validate_types {
+            message.get_type().parse::<Piuri>()?;
+        }
+        #[cfg(feature = "tracing")]
+        {
+            span.record("id", message.didcomm_header.id.as_str());
+            span.record("thid", tracing::field::debug(&message.didcomm_header.thid));
+        }
+
+        sender.from = message
+            .didcomm_header
+            .from
+            .clone()
+            .filter(|from| !from.is_empty());
+        sender.skid = sender.skid.filter(|skid| !skid.is_empty());
+        // key ids only authenticate the sender if the keys used are keys of their DIDs;
+        //     sender keys not given are resolved from `skid`
+        let resolver = options.did_resolver.as_deref();
+        let authcrypt_skid = authcrypt_skid.filter(|skid| match &encryption_sender_public_key {
+            Some(key) => is_key_of_kid(skid, key, KeyRole::KeyAgreement, resolver),
+            None => skid.starts_with("did:"),
+        });
+        let signature_kid = signature_kid.filter(|kid| {
+            key_index
+                .and_then(|index| signing_sender_public_keys.get(index))
+                .is_some_and(|key| is_key_of_kid(kid, key, KeyRole::Signing, resolver))
+        });
+        sender.authenticated = authenticated_sender(
+            sender.from.as_deref(),
+            &[authcrypt_skid.as_deref(), signature_kid.as_deref()],
+        )?;
+        sender.anonymous = sender.authenticated.is_none();
+        options.pipeline.run(&mut message, &sender)?;
+        if let Some(guard) = &options.replay_guard {
+            check_replay(guard.as_ref(), &message)?;
+        }
+
+        Ok((message, key_index, sender))
+    }
+
+    /// Wrap self to be mediated by some mediator.
+    /// Warning: Should be called on a `Message` instance which is ready to be sent!
+    /// If message is not properly set up for crypto - this method will propagate error from
+    ///     called `.seal()` method.
+    /// Takes one mediator at a time to make sure that mediated chain preserves unchanged.
+    /// This method can be chained any number of times to match all the mediators in the chain.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///                             can be provided if key should not be resolved via recipients DID
+    ///
+    /// * `mediator_did` - DID of message mediator, will be `to` of mediated envelope
+    ///
+    /// * `mediator_public_key` - key used to encrypt content encryption key for mediator;
+    ///                           can be provided if key should not be resolved via mediators DID
+    pub fn routed_by(
+        self,
+        sender_private_key: &[u8],
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mediator_did: &str,
+        mediator_public_key: Option<Vec<u8>>,
+    ) -> Result<String> {
+        self.routed_by_with_alg(
+            sender_private_key,
+            recipient_public_keys,
+            mediator_did,
+            mediator_public_key,
+            None,
+            false,
+        )
+    }
+
+    /// Same as [`Message::routed_by`] but seals forward with its own algorithm,
+    ///     e.g. one advertised by the mediator instead of the one of final recipients.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///   can be provided if key should not be resolved via recipients DID
+    ///
+    /// * `mediator_did` - DID of message mediator, will be `to` of mediated envelope
+    ///
+    /// * `mediator_public_key` - key used to encrypt content encryption key for mediator;
+    ///   can be provided if key should not be resolved via mediators DID
+    ///
+    /// * `forward_alg` - content encryption of forward, `None` to use the one of self
+    ///
+    /// * `anonymous_forward` - seals forward anonymously even if self is authcrypted,
+    ///   forwards of anonymous messages are always anonymous
+    pub fn routed_by_with_alg(
+        self,
+        sender_private_key: &[u8],
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mediator_did: &str,
+        mediator_public_key: Option<Vec<u8>>,
+        forward_alg: Option<&CryptoAlgorithm>,
+        anonymous_forward: bool,
+    ) -> Result<String> {
+        let forward = self.forward_message(
+            sender_private_key,
+            recipient_public_keys,
+            mediator_did,
+            mediator_public_key.clone(),
+            forward_alg,
+        )?;
+        if anonymous_forward || forward.didcomm_header.from.is_none() {
+            forward.seal_anonymous(Some(vec![mediator_public_key]))
+        } else {
+            forward.seal(sender_private_key, Some(vec![mediator_public_key]))
+        }
+    }
+
+    /// Same as [`Message::routed_by`] but returns forward message before sealing it,
+    ///     e.g. to add headers like `expires_time` or to seal it differently.
+    /// Forward has `from` of self unless self is sealed anonymously, in which case
+    ///     forward has to be sealed with `seal_anonymous` as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///   can be provided if key should not be resolved via recipients DID
+    ///
+    /// * `mediator_did` - DID of message mediator, will be `to` of mediated envelope
+    ///
+    /// * `mediator_public_key` - key set as `kid` of forward; seal forward with same key
+    ///   if it should not be resolved via mediators DID
+    pub fn routed_by_message(
+        self,
+        sender_private_key: &[u8],
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mediator_did: &str,
+        mediator_public_key: Option<Vec<u8>>,
+    ) -> Result<Message> {
+        self.forward_message(
+            sender_private_key,
+            recipient_public_keys,
+            mediator_did,
+            mediator_public_key,
+            None,
+        )
+    }
+
+    /// Seals self and wraps it into forward for `mediator_did`, see `routed_by_with_alg`.
+    fn forward_message(
+        self,
+        sender_private_key: &[u8],
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        mediator_did: &str,
+        mediator_public_key: Option<Vec<u8>>,
+        forward_alg: Option<&CryptoAlgorithm>,
+    ) -> Result<Message> {
+        let from = self.didcomm_header.from.clone().filter(|from| !from.is_empty());
+        // without own algorithm, forward keeps `alg` and `enc` of inner message
+        let (inner_alg, inner_enc) = (self.jwm_header.alg.clone(), self.jwm_header.enc.clone());
+        let alg = match forward_alg {
+            Some(alg) => *alg,
+            None => get_crypter_from_header(&self.jwm_header)?,
+        };
+        // messages without sender are sealed anonymously, as is their forward
+        let anonymous =
+            from.is_none() || self.jwm_header.alg.as_deref().is_some_and(is_anoncrypt);
+        let to = self.didcomm_header.to[0].clone();
+        let sealed = if anonymous {
+            self.seal_anonymous(recipient_public_keys)?
+        } else {
+            self.seal(sender_private_key, recipient_public_keys)?
+        };
+        let body = Mediated::new(to).with_payload(sealed.as_bytes().to_vec());
+        let mut forward = Message::new()
+            .to(&[mediator_did])
+            .as_jwe(&alg, mediator_public_key)
+            .typ(MessageType::DidCommForward)
+            .body(&serde_json::to_string(&body)?)?;
+        if forward_alg.is_none() {
+            forward.jwm_header.alg = inner_alg;
+            forward.jwm_header.enc = inner_enc;
+        }
+        forward.didcomm_header.from = if anonymous { None } else { from };
+        Ok(forward)
+    }
+
+    /// Seals (encrypts) self and returns ready to send JWE
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///                             can be provided if key should not be resolved via recipients DID
     pub fn seal(
+        self,
+        sender_private_key: impl AsRef<[u8]>,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<String> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jwe);
+        let result = self.seal_jwe(sender_private_key, recipient_public_keys);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Seals (encrypts) self anonymously (anoncrypt) and returns ready to send JWE.
+    /// Content encryption key is wrapped with ECDH-ES, so no sender key is used,
+    ///     `from` is removed and no `skid` is set.
+    /// Message must not be signed before to keep sender undisclosed.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///   can be provided if key should not be resolved via recipients DID
+    pub fn seal_anonymous(
         mut self,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<String> {
+        let alg = self.jwm_header.alg.as_deref().ok_or(Error::JweParseError)?;
+        self.jwm_header.alg = Some(anoncrypt_alg(alg)?.to_string());
+        self.didcomm_header.from = None;
+        let record = audit::pack_record(&self, EnvelopeKind::Jwe);
+        let result = self.seal_jwe([0u8; 0], recipient_public_keys);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Same as `seal` with keys detected by `KeyMaterial::detect`, e.g. from `did:key`s or JWKs.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - private X25519 key of sender
+    ///
+    /// * `recipient_public_keys` - public X25519 keys of recipients, same order as `to`
+    pub fn seal_key_material(
+        self,
+        sender_private_key: &KeyMaterial,
+        recipient_public_keys: &[KeyMaterial],
+    ) -> Result<String> {
+        let recipient_public_keys = recipient_public_keys
+            .iter()
+            .map(|key| key.agreement_key().map(|key| Some(key.to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        self.seal(
+            sender_private_key.agreement_key()?,
+            Some(recipient_public_keys),
+        )
+    }
+
+    /// Seals (encrypts) self with sender key held in a keystore and returns ready to send JWE.
+    /// Key agreement for content encryption key wrapping is done via `sender_key` handle,
+    ///     so its private key is never exported.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_key` - handle of senders X25519 key
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient;
+    ///   can be provided if key should not be resolved via recipients DID
+    pub fn seal_hardware(
+        self,
+        sender_key: &dyn HardwareKey,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<String> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jwe);
+        let result = self.seal_jwe_with(AgreementKey::Hardware(sender_key), recipient_public_keys);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Implementation of `seal` without audit record.
+    pub(crate) fn seal_jwe(
+        self,
         sender_private_key: impl AsRef<[u8]>,
         recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
     ) -> Result<String> {
-        if sender_private_key.as_ref().len() != 32 {
-            return Err(Error::InvalidKeySize("!32".into()));
+        self.seal_jwe_with(
+            AgreementKey::Raw(sender_private_key.as_ref()),
+            recipient_public_keys,
+        )
+    }
+
+    /// Implementation of `seal_jwe` and `seal_hardware`.
+    fn seal_jwe_with(
+        mut self,
+        sender_key: AgreementKey<'_>,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<String> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "seal",
+            id = %self.didcomm_header.id,
+            thid = ?self.didcomm_header.thid,
+        )
+        .entered();
+        let anonymous = self.jwm_header.alg.as_deref().is_some_and(is_anoncrypt);
+        if let AgreementKey::Raw(sender_private_key) = sender_key {
+            if !anonymous && sender_private_key.len() != 32 {
+                return Err(Error::InvalidKeySize("!32".into()));
+            }
         }
         let to_len = self.didcomm_header.to.len();
         let public_keys = if let Some(recipient_public_keys_value) = recipient_public_keys {
@@ -506,7 +1569,7 @@ impl Message {
         let mut cek = [0u8; 32];
         let mut rng = ChaCha20Rng::from_seed(Default::default());
         rng.fill_bytes(&mut cek);
-        trace!("sealing message with shared_key: {:?}", &cek.as_ref());
+        diagnostics::report("cek", cek.as_ref());
 
         if to_len == 0_usize {
             return Err(Error::NoJweRecipient);
@@ -516,22 +1579,47 @@ impl Message {
             ));
         }
 
+        // pre-populated recipients only provide header values
+        self.check_recipient_templates()?;
+        let templates = std::mem::take(&mut self.recipient_templates);
         let mut recipients: Vec<Recipient> = vec![];
         // create jwk from static secret per recipient
         for (i, public_key) in public_keys.iter().enumerate().take(to_len) {
-            let rv = encrypt_cek(
-                &self,
-                sender_private_key.as_ref(),
-                &self.didcomm_header.to[i],
-                &cek,
-                public_key.to_owned(),
-            )?;
+            let dest = &self.didcomm_header.to[i];
+            let template = templates.iter().find(|template| {
+                template
+                    .header
+                    .kid
+                    .as_deref()
+                    .map(|kid| refers_to_same_did(kid, dest))
+                    .unwrap_or(false)
+            });
+            // per-recipient key wrap algorithm overrides one of message
+            let alg = match template.and_then(|template| recipient_alg(&template.header)) {
+                Some(alg) => alg,
+                None => self
+                    .jwm_header
+                    .alg
+                    .as_deref()
+                    .ok_or_else(|| Error::Generic("missing encryption 'alg' in header".into()))?,
+            };
+            let mut rv = encrypt_cek(&self, sender_key, alg, dest, &cek, public_key.to_owned())?;
+            if let Some(template) = template {
+                rv.apply_header_template(&template.header);
+            }
             recipients.push(Recipient::new(rv.header, rv.encrypted_key));
         }
         self.recipients = Some(recipients);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(recipients = to_len, "wrapped content encryption key");
         // encrypt original message with static secret
         let alg = get_crypter_from_header(&self.jwm_header)?;
-        self.encrypt(alg.encryptor(), cek.as_ref())
+        let profile = self.profile.take();
+        let sealed = self.encrypt(alg.encryptor(), cek.as_ref())?;
+        if let Some(profile) = profile {
+            profile.check_jwe(&sealed.parse()?)?;
+        }
+        Ok(sealed)
     }
 }
 
@@ -625,15 +1713,41 @@ impl Message {
         signing_algorithm: SignatureAlgorithm,
         signing_sender_private_key: &[u8],
     ) -> Result<String> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jwe);
         let mut to = self.clone();
-        let signed = self
+        let result = self
             .as_jws(&signing_algorithm)
-            .sign(signing_algorithm.signer(), signing_sender_private_key)?;
-        to.body = serde_json::from_str(&signed)?;
-        to.typ(MessageType::DidCommJws).seal(
-            encryption_sender_private_key,
-            encryption_recipient_public_keys,
-        )
+            .sign_jws(signing_algorithm.signer(), signing_sender_private_key)
+            .and_then(|signed| {
+                to.body = serde_json::from_str(&signed)?;
+                to.typ(MessageType::DidCommJws).seal_jwe(
+                    encryption_sender_private_key,
+                    encryption_recipient_public_keys,
+                )
+            });
+        audit::submit(record, &result, None);
+        result
+    }
+}
+
+/// Applies JSON merge patch to `target`.
+fn merge_json(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = json!({});
+            }
+            if let Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(&key);
+                    } else {
+                        merge_json(target.entry(key).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch,
     }
 }
 
@@ -643,7 +1757,31 @@ impl Default for Message {
     }
 }
 
-#[cfg(test)]
+/// Plain JSON representation of the message, same as `as_raw_json`.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Parses plain JSON message. Use `Message::receive` for JWE and JWS envelopes.
+impl FromStr for Message {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl TryFrom<&[u8]> for Message {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(test)]
 mod parse_tests {
     use super::*;
 
@@ -686,6 +1824,181 @@ mod parse_tests {
             &String::from_utf8(iv.unwrap()).unwrap()
         );
     }
+
+    #[test]
+    fn unknown_fields_survive_round_trip() {
+        // Arrange
+        let raw_json = r#"{
+            "typ": "application/didcomm-plain+json",
+            "id": "1",
+            "type": "https://example.com/protocol/1.0/test",
+            "from": null,
+            "body": {},
+            "ext_string": "value",
+            "ext_number": 5,
+            "ext_object": { "nested": [1, 2, 3] }
+        }"#;
+        // Act
+        let message: Message = serde_json::from_str(raw_json).unwrap();
+        let serialized: Value = serde_json::to_value(&message).unwrap();
+        // Assert
+        assert_eq!(serialized["ext_string"], json!("value"));
+        assert_eq!(serialized["ext_number"], json!(5));
+        assert_eq!(serialized["ext_object"], json!({ "nested": [1, 2, 3] }));
+        assert_eq!(
+            message.get_application_params().collect::<Vec<_>>(),
+            vec![(&"ext_string".to_string(), &"value".to_string())]
+        );
+        assert_eq!(
+            message.get_application_params_as::<u64>().collect::<Vec<_>>(),
+            vec![(&"ext_number".to_string(), 5)]
+        );
+        assert_eq!(message.get_header_u64("ext_number"), Some(5));
+        assert_eq!(message.get_header_str("ext_string"), Some("value"));
+        assert_eq!(message.get_header_bool("ext_string"), None);
+    }
+
+    #[test]
+    fn header_getters_test() {
+        // Arrange
+        let parent = Message::new().thid("parent-thread");
+        // Act
+        let m = Message::new()
+            .m_type("https://example.com/protocol/1.0/test")
+            .thid("thread")
+            .with_parent(&parent)
+            .timed(Some(3000000000));
+        // Assert
+        assert_eq!(m.get_id(), m.didcomm_header.id);
+        assert_eq!(m.get_thid(), Some("thread"));
+        assert_eq!(m.get_pthid(), Some("parent-thread"));
+        assert_eq!(m.get_type(), "https://example.com/protocol/1.0/test");
+        assert!(m.get_created_time().is_some());
+        assert_eq!(m.get_expires_time(), Some(3000000000));
+        assert_eq!(Message::new().get_thid(), None);
+    }
+
+    #[test]
+    fn display_from_str_round_trip_test() {
+        // Arrange
+        let m = Message::new()
+            .m_type("https://example.com/protocol/1.0/test")
+            .body(r#"{"foo":"bar"}"#)
+            .unwrap();
+        // Act
+        let parsed: Message = m.to_string().parse().unwrap();
+        let from_bytes = Message::try_from(m.to_string().as_bytes()).unwrap();
+        // Assert
+        assert_eq!(parsed, m);
+        assert_eq!(from_bytes, m);
+        assert!("not json".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn custom_typ_survives_round_trip_test() -> Result<()> {
+        let message = Message::new()
+            .typ(MessageType::from("application/example-protocol+json"))
+            .m_type("https://example.com/protocol/1.0/hello");
+        let parsed: Message = message.to_string().parse()?;
+        assert_eq!(
+            parsed.get_jwm_header().typ,
+            MessageType::Other("application/example-protocol+json".into())
+        );
+        assert_eq!(parsed, message);
+        Ok(())
+    }
+
+    #[test]
+    fn semantically_eq_ignores_volatile_fields_test() {
+        let message = Message::new()
+            .from("did:example:alice")
+            .merge_body(json!({ "foo": "bar" }));
+        let mut repacked = message.clone().timed(None);
+        repacked.didcomm_header.id = "other-id".into();
+
+        assert_ne!(message, repacked);
+        assert!(message.semantically_eq(&repacked));
+        assert!(!message.semantically_eq(&repacked.merge_body(json!({ "foo": "baz" }))));
+    }
+
+    #[test]
+    fn reply_swaps_addressing_test() {
+        // Arrange
+        let received = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .lang("de");
+        // Act
+        let reply = Message::reply(&received);
+        let reply_in_thread = Message::reply(&reply);
+        // Assert
+        assert_eq!(reply.get_to(), ["did:example:alice"]);
+        assert_eq!(reply.didcomm_header.from.as_deref(), Some("did:example:bob"));
+        assert_eq!(reply.get_thid(), Some(received.get_id()));
+        assert_eq!(reply.get_lang(), Some("de"));
+        assert_eq!(reply_in_thread.get_to(), ["did:example:bob"]);
+        assert_eq!(reply_in_thread.get_thid(), Some(received.get_id()));
+    }
+
+    #[test]
+    fn typed_custom_headers_test() {
+        // Arrange
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Hint {
+            priority: u8,
+        }
+        // Act
+        let m = Message::new()
+            .add_header_field("plain".into(), "text".into())
+            .set_header("number", 42)
+            .unwrap()
+            .set_header("object", Hint { priority: 1 })
+            .unwrap();
+        let m: Message = m.to_string().parse().unwrap();
+        // Assert
+        assert_eq!(m.get_header_as::<u64>("number").unwrap(), Some(42));
+        assert_eq!(
+            m.get_header_as::<Hint>("object").unwrap(),
+            Some(Hint { priority: 1 })
+        );
+        assert_eq!(m.get_header_as::<String>("plain").unwrap(), Some("text".into()));
+        assert_eq!(m.get_header_as::<u64>("missing").unwrap(), None);
+        assert!(m.get_header_as::<u64>("plain").is_err());
+    }
+
+    #[test]
+    fn structured_body_access_test() {
+        // Arrange
+        let mut m = Message::new()
+            .body(r#"{"foo":{"bar":1,"baz":2},"keep":true}"#)
+            .unwrap();
+        // Act
+        m.body_mut()["added"] = json!("value");
+        let m = m.merge_body(json!({"foo":{"bar":10,"baz":null},"items":[1,2]}));
+        // Assert
+        assert_eq!(m.body_at("/foo/bar"), Some(&json!(10)));
+        assert_eq!(m.body_at("/foo/baz"), None);
+        assert_eq!(m.body_at("/keep"), Some(&json!(true)));
+        assert_eq!(m.body_at("/items/1"), Some(&json!(2)));
+        assert_eq!(m.body_at("/added"), Some(&json!("value")));
+    }
+
+    #[test]
+    fn to_management_test() {
+        // Act
+        let m = Message::new()
+            .to(&["did:example:bob", "did:example:carol"])
+            .to(&["did:example:bob", "did:example:dave"]);
+        // Assert
+        assert_eq!(
+            m.get_to(),
+            ["did:example:bob", "did:example:carol", "did:example:dave"]
+        );
+        let m = m.remove_to("did:example:carol");
+        assert_eq!(m.get_to(), ["did:example:bob", "did:example:dave"]);
+        let m = m.clear_to().to(&["did:example:erin"]);
+        assert_eq!(m.get_to(), ["did:example:erin"]);
+    }
 }
 
 #[cfg(all(test, feature = "raw-crypto"))]
@@ -958,6 +2271,353 @@ mod crypto_tests {
         assert!(bob_received.is_ok());
     }
 
+    #[test]
+    fn receive_rejects_replayed_message_with_guard() -> Result<()> {
+        let options = ReceiveOptions::new()
+            .with_replay_guard(std::sync::Arc::new(crate::InMemoryReplayGuard::default()));
+        let raw = Message::new().as_raw_json()?;
+
+        let first = Message::receive_with_options(&raw, None, None, None, &options);
+        let second = Message::receive_with_options(&raw, None, None, None, &options);
+
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(Error::ReplayedMessage(_))));
+        assert!(Message::receive(&raw, None, None, None).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_messages_do_not_use_up_ids() -> Result<()> {
+        let reject_mallory = |message: &mut Message, _: &SenderInfo| {
+            match message.didcomm_header.from.as_deref() {
+                Some("did:example:mallory") => Err(Error::SenderRejected("mallory".into())),
+                _ => Ok(()),
+            }
+        };
+        let options = ReceiveOptions::new()
+            .with_replay_guard(std::sync::Arc::new(crate::InMemoryReplayGuard::default()))
+            .with_middleware(std::sync::Arc::new(reject_mallory));
+        let legitimate = Message::new().from("did:example:alice");
+        let spoofed = legitimate.clone().from("did:example:mallory").as_raw_json()?;
+        let legitimate = legitimate.as_raw_json()?;
+
+        assert!(matches!(
+            Message::receive_with_options(&spoofed, None, None, None, &options),
+            Err(Error::SenderRejected(_))
+        ));
+        assert!(Message::receive_with_options(&legitimate, None, None, None, &options).is_ok());
+        assert!(matches!(
+            Message::receive_with_options(&legitimate, None, None, None, &options),
+            Err(Error::ReplayedMessage(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn receive_applies_sender_policy() -> Result<()> {
+        let alice_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let mallory_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let public_keys = [
+            alice_keypair.verifying_key().to_bytes(),
+            mallory_keypair.verifying_key().to_bytes(),
+        ];
+        let resolver = crate::InMemoryDidResolver::new();
+        for (did, key) in ["did:example:alice", "did:example:mallory"]
+            .iter()
+            .zip(&public_keys)
+        {
+            resolver.insert(crate::ResolvedDid::new(did).with_key(
+                &format!("{}#key-1", did),
+                KeyMaterial {
+                    curve: crate::crypto::Curve::Ed25519,
+                    role: KeyRole::Signing,
+                    private: false,
+                    bytes: key.to_vec(),
+                },
+            ));
+        }
+        let resolver = std::sync::Arc::new(resolver);
+        let options = ReceiveOptions::new()
+            .with_did_resolver(resolver.clone())
+            .with_sender_policy(std::sync::Arc::new(crate::SenderAllowList::new(&[
+                "did:example:alice",
+            ])));
+        let signed = |keypair: &ed25519_dalek::SigningKey, from: &str, kid: &str| {
+            Message::new()
+                .from(from)
+                .as_flat_jws(&SignatureAlgorithm::EdDsa)
+                .kid(kid)
+                .sign(SignatureAlgorithm::EdDsa.signer(), &keypair.to_bytes())
+        };
+        let allowed = signed(
+            &alice_keypair,
+            "did:example:alice",
+            "did:example:alice#key-1",
+        )?;
+        let denied = signed(
+            &mallory_keypair,
+            "did:example:mallory",
+            "did:example:mallory#key-1",
+        )?;
+        let spoofed_from = signed(
+            &mallory_keypair,
+            "did:example:alice",
+            "did:example:mallory#key-1",
+        )?;
+        let spoofed_kid = signed(
+            &mallory_keypair,
+            "did:example:alice",
+            "did:example:alice#key-1",
+        )?;
+        let raw_kid = signed(&alice_keypair, "did:example:alice", "key-1")?;
+        let unsigned = Message::new().from("did:example:alice").as_raw_json()?;
+        let candidates: Vec<&[u8]> = public_keys.iter().map(|key| &key[..]).collect();
+        let receive = |incoming: &str, options: &ReceiveOptions| {
+            Message::receive_with_candidate_keys(incoming, None, None, &candidates, options)
+        };
+
+        assert!(receive(&allowed, &options).is_ok());
+        assert!(matches!(
+            receive(&denied, &options),
+            Err(Error::SenderRejected(_))
+        ));
+        assert!(matches!(
+            receive(&spoofed_from, &options),
+            Err(Error::SenderMismatch { from, authenticated })
+                if from == "did:example:alice" && authenticated == "did:example:mallory"
+        ));
+        // kids not bound to the verifying key authenticate nobody
+        assert!(matches!(
+            receive(&spoofed_kid, &options),
+            Err(Error::SenderRejected(_))
+        ));
+        assert!(matches!(
+            receive(&raw_kid, &options),
+            Err(Error::SenderRejected(_))
+        ));
+        let deny_list = ReceiveOptions::new()
+            .with_did_resolver(resolver)
+            .with_sender_policy(std::sync::Arc::new(crate::SenderDenyList::new(&[
+                "did:example:mallory",
+            ])));
+        let unbound = signed(&mallory_keypair, "did:example:mallory", "key-1")?;
+        assert!(matches!(
+            receive(&unbound, &deny_list),
+            Err(Error::SenderRejected(_))
+        ));
+        assert!(matches!(
+            Message::receive_with_options(&unsigned, None, None, None, &options),
+            Err(Error::SenderRejected(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn seal_applies_prepopulated_recipient_headers() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let jwe = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .with_recipient(
+                RecipientBuilder::new("did:example:bob#key-1")
+                    .with_header("apu", json!("YWxpY2U"))
+                    .with_header("iv", json!("not-used")),
+            )
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+
+        let parsed: Jwe = jwe.parse()?;
+        let header = &parsed.recipients.as_ref().expect("no recipients")[0].header;
+        assert_eq!(header.kid.as_deref(), Some("did:example:bob#key-1"));
+        assert_eq!(header.other.get("apu"), Some(&json!("YWxpY2U")));
+        assert_ne!(header.other.get("iv"), Some(&json!("not-used")));
+        assert!(
+            Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None).is_ok()
+        );
+
+        let plaintext = Message::new()
+            .to(&["did:example:bob"])
+            .with_recipient(RecipientBuilder::new("did:example:bob#key-1"))
+            .as_raw_json()?;
+        assert!(!plaintext.contains("recipients"));
+
+        let unmatched = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .with_recipient(RecipientBuilder::new("did:example:carol#key-1"))
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]));
+        assert!(matches!(unmatched, Err(Error::Generic(e)) if e.contains("did:example:carol")));
+        Ok(())
+    }
+
+    #[test]
+    fn recipients_may_use_different_key_wrap_algorithms() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let jwe = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob", "did:example:mediator"])
+            .with_recipient(
+                RecipientBuilder::new("did:example:mediator")
+                    .with_alg(crate::KeyAlgorithm::EcdhEsA256kw),
+            )
+            .as_jwe(&CryptoAlgorithm::XC20P, None)
+            .seal(
+                alice_private,
+                Some(vec![
+                    Some(bobs_public.to_vec()),
+                    Some(mediators_public.to_vec()),
+                ]),
+            )?;
+
+        let parsed: Jwe = jwe.parse()?;
+        assert_eq!(parsed.get_alg(), None);
+        assert_eq!(parsed.get_enc().as_deref(), Some("XC20P"));
+        let algs: Vec<_> = parsed
+            .get_recipients()
+            .iter()
+            .map(|recipient| recipient.header.alg.clone())
+            .collect();
+        assert_eq!(
+            algs,
+            [
+                crate::KeyAlgorithm::Ecdh1puXc20pkw,
+                crate::KeyAlgorithm::EcdhEsA256kw
+            ]
+        );
+        assert!(
+            Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None).is_ok()
+        );
+        assert!(Message::receive(&jwe, Some(&mediators_private), None, None).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn extra_protected_header_fields_are_integrity_protected() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let mut header = JwmHeader::default();
+        header.set_extra("nonce", json!("abc"));
+        let jwe = Message::new()
+            .jwm_header(header)
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+
+        let mut parsed: Jwe = jwe.parse()?;
+        let protected = parsed.protected.as_mut().expect("no protected header");
+        assert_eq!(protected.get_extra("nonce"), Some(&json!("abc")));
+        assert!(
+            Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None).is_ok()
+        );
+
+        protected.set_extra("nonce", json!("forged"));
+        let tampered = parsed.to_string();
+        assert!(
+            Message::receive(&tampered, Some(&bobs_private), Some(alice_public.to_vec()), None)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_messages_are_decompressed_on_receive() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let body = json!({ "credential": "a".repeat(1000) });
+        let seal = |message: Message| {
+            message
+                .from("did:example:alice")
+                .to(&["did:example:bob"])
+                .merge_body(body.clone())
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+                .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))
+        };
+        let plain = seal(Message::new())?;
+        let compressed = seal(Message::new().compressed())?;
+
+        let parsed: Jwe = compressed.parse()?;
+        assert_eq!(parsed.get_protected().and_then(|h| h.zip.as_deref()), Some("DEF"));
+        assert!(compressed.len() < plain.len());
+        let received = Message::receive(
+            &compressed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(received.body_at("/credential"), body.get("credential"));
+        assert!(matches!(
+            Message::receive_with_options(
+                &compressed,
+                Some(&bobs_private),
+                Some(alice_public.to_vec()),
+                None,
+                &ReceiveOptions::new().with_max_decompressed_size(512),
+            ),
+            Err(Error::DecompressedSizeExceeded(512))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn party_info_is_bound_to_key_derivation() -> Result<()> {
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let jwe = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .apu(b"alice")
+            .apv(b"bob")
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+
+        let mut parsed: Jwe = jwe.parse()?;
+        assert_eq!(parsed.get_apu().as_deref(), Some("YWxpY2U"));
+        let received =
+            Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None)?;
+        assert_eq!(received.get_apv()?, Some(b"bob".to_vec()));
+
+        let protected = parsed.protected.as_mut().expect("no protected header");
+        protected.apu(Some(base64_url::encode("mallory")));
+        assert!(Message::receive(
+            &parsed.to_string(),
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None
+        )
+        .is_err());
+        Ok(())
+    }
+
     #[test]
     fn can_pass_explicit_signing_verification_keys() -> Result<()> {
         let KeyPairSet {
@@ -1013,4 +2673,803 @@ mod crypto_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn can_verify_with_candidate_keys() -> Result<()> {
+        // Arrange
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let other_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let jws = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_flat_jws(&SignatureAlgorithm::EdDsa)
+            .kid("did:example:alice#key-2")
+            .sign(SignatureAlgorithm::EdDsa.signer(), &sign_keypair.to_bytes())?;
+        let wrong_key = other_keypair.verifying_key().to_bytes();
+        let right_key = sign_keypair.verifying_key().to_bytes();
+
+        // Act
+        let (_, verified_with) = Message::verify_any(jws.as_bytes(), &[&wrong_key, &right_key])?;
+        let (_, received_with) = Message::receive_with_candidate_keys(
+            &jws,
+            None,
+            None,
+            &[&wrong_key, &right_key],
+            &ReceiveOptions::default(),
+        )?;
+
+        // Assert
+        assert_eq!(verified_with, 1);
+        assert_eq!(received_with, Some(1));
+        assert!(Message::verify_any(jws.as_bytes(), &[&wrong_key]).is_err());
+        assert!(Message::verify_any(jws.as_bytes(), &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn v1_aliases_map_onto_v2_fields() -> Result<()> {
+        let v1 = r#"{
+            "@id": "v1-id",
+            "@type": "https://didcomm.org/trust_ping/1.0/ping",
+            "~thread": { "thid": "v1-thread" },
+            "response_requested": true
+        }"#;
+        let options = ReceiveOptions::new().with_v1_aliases();
+
+        let received = Message::receive_with_options(v1, None, None, None, &options)?;
+
+        assert_eq!(received.get_id(), "v1-id");
+        assert_eq!(
+            received.get_didcomm_header().m_type,
+            "https://didcomm.org/trust_ping/1.0/ping"
+        );
+        assert_eq!(
+            received.get_didcomm_header().thid.as_deref(),
+            Some("v1-thread")
+        );
+        assert_eq!(received.body_at("/response_requested"), Some(&json!(true)));
+        assert!(
+            Message::receive_with_options(v1, None, None, None, &ReceiveOptions::new()).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn type_validation_rejects_mismatching_typ_and_malformed_type() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let options = ReceiveOptions::new().with_type_validation();
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .m_type("https://didcomm.org/trust-ping/2.0/ping");
+        let seal = |message: Message| {
+            message
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+                .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))
+        };
+        let receive = |jwe: &str| {
+            Message::receive_with_options(
+                jwe,
+                Some(&bobs_private),
+                Some(alice_public.to_vec()),
+                None,
+                &options,
+            )
+        };
+
+        assert!(receive(&seal(message.clone())?).is_ok());
+        assert!(matches!(
+            receive(&seal(message.clone().m_type("JWM"))?),
+            Err(Error::InvalidPiuri(_))
+        ));
+        let mut jwe: Jwe = seal(message)?.parse()?;
+        if let Some(protected) = jwe.protected.as_mut() {
+            protected.typ = MessageType::DidCommJws;
+        }
+        assert!(matches!(
+            receive(&serde_json::to_string(&jwe)?),
+            Err(Error::EnvelopeTypeMismatch(..))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn algorithm_allow_list_rejects_other_algorithms() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let message = || {
+            Message::new()
+                .from("did:example:alice")
+                .to(&["did:example:bob"])
+        };
+        let seal = |alg: CryptoAlgorithm| {
+            message()
+                .as_jwe(&alg, Some(bobs_public.to_vec()))
+                .seal_signed(
+                    &alice_private,
+                    Some(vec![Some(bobs_public.to_vec())]),
+                    SignatureAlgorithm::EdDsa,
+                    &sign_keypair.to_bytes(),
+                )
+        };
+        let receive = |jwe: &str, allowed: &[&str]| {
+            Message::receive_with_options(
+                jwe,
+                Some(&bobs_private),
+                Some(alice_public.to_vec()),
+                Some(sign_keypair.verifying_key().as_bytes()),
+                &ReceiveOptions::new().with_allowed_algorithms(allowed),
+            )
+        };
+
+        let xc20p = seal(CryptoAlgorithm::XC20P)?;
+        assert!(receive(&xc20p, &["ECDH-1PU+XC20PKW", "XC20P", "EdDSA"]).is_ok());
+        assert!(matches!(
+            receive(&xc20p, &["ECDH-1PU+XC20PKW", "XC20P"]),
+            Err(Error::AlgorithmNotAllowed { alg }) if alg == "EdDSA"
+        ));
+        assert!(matches!(
+            receive(&xc20p, &["ECDH-1PU+A256KW", "XC20P", "EdDSA"]),
+            Err(Error::AlgorithmNotAllowed { alg }) if alg == "ECDH-1PU+XC20PKW"
+        ));
+        let a256gcm = seal(CryptoAlgorithm::A256GCM)?;
+        assert!(matches!(
+            receive(&a256gcm, &["ECDH-1PU+XC20PKW", "XC20P", "EdDSA"]),
+            Err(Error::AlgorithmNotAllowed { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn anonymous_messages_omit_sender() -> Result<()> {
+        let KeyPairSet {
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+            ..
+        } = get_keypair_set();
+        let message = Message::new()
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()));
+
+        let sealed = message
+            .clone()
+            .seal_anonymous(Some(vec![Some(bobs_public.to_vec())]))?;
+        let jwe: Jwe = sealed.parse()?;
+        assert_eq!(jwe.get_skid(), None);
+        assert_eq!(
+            jwe.get_protected().and_then(|header| header.alg.as_deref()),
+            Some("ECDH-ES+XC20PKW")
+        );
+        let (received, sender) = Message::receive_with_sender_info(
+            &sealed,
+            Some(&bobs_private),
+            None,
+            None,
+            &ReceiveOptions::new(),
+        )?;
+        assert_eq!(received.get_didcomm_header().from, None);
+        assert!(sender.anonymous);
+        assert_eq!(sender.skid, None);
+
+        let forward = message.routed_by(
+            &[],
+            Some(vec![Some(bobs_public.to_vec())]),
+            "did:example:mediator",
+            Some(mediators_public.to_vec()),
+        )?;
+        let (received, sender) = Message::receive_with_sender_info(
+            &forward,
+            Some(&mediators_private),
+            None,
+            None,
+            &ReceiveOptions::new(),
+        )?;
+        assert!(sender.anonymous);
+        let mediated: Mediated = serde_json::from_str(&received.get_body()?)?;
+        let inner = Message::receive(
+            std::str::from_utf8(&mediated.payload)?,
+            Some(&bobs_private),
+            None,
+            None,
+        )?;
+        assert_eq!(inner.get_didcomm_header().from, None);
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_signing_is_byte_identical() -> Result<()> {
+        // Arrange
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let message = |headers: &[&str]| {
+            let mut message = Message::new()
+                .from("did:example:alice")
+                .to(&["did:example:bob"])
+                .as_flat_jws(&SignatureAlgorithm::EdDsa);
+            message.didcomm_header.id = "fixed-id".into();
+            message.didcomm_header.created_time = Some(0);
+            for header in headers {
+                message = message.add_header_field(header.to_string(), "value".into());
+            }
+            message
+        };
+
+        // Act
+        let first = message(&["zulu", "alpha", "mike", "bravo"])
+            .sign(SignatureAlgorithm::EdDsa.signer(), &sign_keypair.to_bytes())?;
+        let second = message(&["bravo", "mike", "alpha", "zulu"])
+            .sign(SignatureAlgorithm::EdDsa.signer(), &sign_keypair.to_bytes())?;
+
+        // Assert
+        assert_eq!(first, second);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_all_requires_every_signature_to_be_valid() -> Result<()> {
+        // Arrange
+        let alice_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let carol_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_flat_jws(&SignatureAlgorithm::EdDsa);
+        let sign = |kid: &str, key: &ed25519_dalek::SigningKey| -> Result<crate::Jws> {
+            message
+                .clone()
+                .kid(kid)
+                .sign(SignatureAlgorithm::EdDsa.signer(), &key.to_bytes())?
+                .parse()
+        };
+        let by_alice = sign("did:example:alice#key-1", &alice_keypair)?;
+        let by_carol = sign("did:example:carol#key-1", &carol_keypair)?;
+        let jws = crate::Jws::new(
+            by_alice.payload.clone(),
+            by_alice
+                .get_signatures()
+                .into_iter()
+                .chain(by_carol.get_signatures())
+                .cloned()
+                .collect(),
+        )
+        .to_string();
+        let mut keys = std::collections::HashMap::new();
+        keys.insert(
+            "did:example:alice#key-1".to_string(),
+            alice_keypair.verifying_key().to_bytes().to_vec(),
+        );
+
+        // Act
+        let partial = Message::verify_signatures(jws.as_bytes(), &keys)?;
+        let partial_all = Message::verify_all(jws.as_bytes(), &keys);
+        keys.insert(
+            "did:example:carol#key-1".to_string(),
+            carol_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        let complete_all = Message::verify_all(jws.as_bytes(), &keys);
+
+        // Assert
+        assert_eq!(partial.len(), 2);
+        assert!(partial[0].valid);
+        assert!(!partial[1].valid);
+        assert_eq!(partial[1].kid.as_deref(), Some("did:example:carol#key-1"));
+        assert!(Message::verify(jws.as_bytes(), &alice_keypair.verifying_key().to_bytes()).is_ok());
+        assert!(partial_all.is_err());
+        assert!(complete_all.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn unencoded_payload_is_signed_and_verified() -> Result<()> {
+        let alice_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let carol_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let alice_public = alice_keypair.verifying_key().to_bytes();
+        let signed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .body(r#"{"large":"content"}"#)?
+            .unencoded_payload()
+            .as_flat_jws(&SignatureAlgorithm::EdDsa)
+            .kid("did:example:alice#key-1")
+            .sign(SignatureAlgorithm::EdDsa.signer(), &alice_keypair.to_bytes())?;
+        let jws: crate::Jws = signed.parse()?;
+        assert!(jws.is_unencoded());
+        assert!(jws.payload.contains(r#""large":"content""#));
+        let protected = jws.get_signatures()[0].protected.clone().unwrap();
+        assert_eq!(protected.crit, Some(vec!["b64".to_string()]));
+
+        let received = Message::receive(&signed, None, None, Some(&alice_public))?;
+        assert_eq!(received.get_body()?, r#"{"large":"content"}"#);
+        assert_eq!(received.get_jwm_header().b64, None);
+
+        let counter_signed = Message::counter_sign(
+            signed.as_bytes(),
+            SignatureAlgorithm::EdDsa,
+            &carol_keypair.to_bytes(),
+            "did:example:carol#key-1",
+        )?;
+        let mut keys = std::collections::HashMap::new();
+        keys.insert("did:example:alice#key-1".to_string(), alice_public.to_vec());
+        keys.insert(
+            "did:example:carol#key-1".to_string(),
+            carol_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        assert!(Message::verify_all(counter_signed.as_bytes(), &keys).is_ok());
+
+        let mut not_critical = jws.clone();
+        if let Some(signature) = not_critical.signature.as_mut() {
+            signature.protected.as_mut().unwrap().crit = None;
+        }
+        assert!(matches!(
+            Message::verify(not_critical.to_string().as_bytes(), &alice_public),
+            Err(Error::InvalidCriticalHeader(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn routed_by_message_can_be_extended_before_sealing() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let forward = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .routed_by_message(
+                &alice_private,
+                Some(vec![Some(bobs_public.to_vec())]),
+                "did:example:mediator",
+                Some(mediators_public.to_vec()),
+            )?
+            .timed(Some(4_102_444_800))
+            .set_header("transport_hint", "ws")?;
+        assert_eq!(forward.get_didcomm_header().to, ["did:example:mediator"]);
+
+        let sealed = forward.seal(alice_private, Some(vec![Some(mediators_public.to_vec())]))?;
+        let received = Message::receive(
+            &sealed,
+            Some(&mediators_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(received.get_expires_time(), Some(4_102_444_800));
+        assert_eq!(
+            received.get_header_as::<String>("transport_hint")?.as_deref(),
+            Some("ws")
+        );
+        let mediated: Mediated = serde_json::from_str(&received.get_body()?)?;
+        Message::receive(
+            std::str::from_utf8(&mediated.payload)?,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn forward_uses_own_algorithm() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let sealed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .routed_by_with_alg(
+                &alice_private,
+                Some(vec![Some(bobs_public.to_vec())]),
+                "did:example:mediator",
+                Some(mediators_public.to_vec()),
+                Some(&CryptoAlgorithm::A256GCM),
+                true,
+            )?;
+        let outer: Jwe = sealed.parse()?;
+        let protected = outer.get_protected().unwrap();
+        assert_eq!(protected.alg.as_deref(), Some("ECDH-ES+A256KW"));
+        assert_eq!(protected.enc.as_deref(), Some("A256GCM"));
+        assert_eq!(outer.get_skid(), None);
+
+        let forward = Message::receive(&sealed, Some(&mediators_private), None, None)?;
+        let mediated: Mediated = serde_json::from_str(&forward.get_body()?)?;
+        let inner = std::str::from_utf8(&mediated.payload)?;
+        let inner_jwe: Jwe = inner.parse()?;
+        assert_eq!(
+            inner_jwe.get_protected().unwrap().alg.as_deref(),
+            Some("ECDH-1PU+XC20PKW")
+        );
+        Message::receive(
+            inner,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn key_agreement_and_content_encryption_are_independent() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let sealed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe_with(
+                KeyAgreementAlg::Ecdh1puXc20pkw,
+                ContentEncAlg::A256GCM,
+                Some(bobs_public.to_vec()),
+            )
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        let protected = sealed.parse::<Jwe>()?.get_protected().unwrap().clone();
+        assert_eq!(protected.alg.as_deref(), Some("ECDH-1PU+XC20PKW"));
+        assert_eq!(protected.enc.as_deref(), Some("A256GCM"));
+        Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+
+        let anonymous = Message::new()
+            .to(&["did:example:bob"])
+            .as_jwe_with(
+                KeyAgreementAlg::Ecdh1puA256kw,
+                ContentEncAlg::XC20P,
+                Some(bobs_public.to_vec()),
+            )
+            .seal_anonymous(Some(vec![Some(bobs_public.to_vec())]))?;
+        let protected = anonymous.parse::<Jwe>()?.get_protected().unwrap().clone();
+        assert_eq!(protected.alg.as_deref(), Some("ECDH-ES+A256KW"));
+        assert_eq!(protected.enc.as_deref(), Some("XC20P"));
+        Message::receive(&anonymous, Some(&bobs_private), None, None)?;
+
+        assert_eq!(
+            CryptoAlgorithm::XC20P.key_agreement_alg(),
+            Some(KeyAgreementAlg::Ecdh1puXc20pkw)
+        );
+        assert_eq!(
+            KeyAgreementAlg::try_from("ECDH-ES+XC20PKW")?,
+            KeyAgreementAlg::EcdhEsXc20pkw
+        );
+        assert!(ContentEncAlg::try_from("A128GCM").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sign_auto_infers_algorithm_from_key() -> Result<()> {
+        let p256_key = p256::ecdsa::SigningKey::random(&mut OsRng);
+        let mut jwk = Jwk::new();
+        jwk.kty = Some("EC".into());
+        jwk.crv = Some("P-256".into());
+        jwk.add_other_header("d".into(), base64_url::encode(&p256_key.to_bytes()));
+        let key = crate::crypto::Key::from_jwk(&jwk)?;
+        assert!(matches!(
+            SignatureAlgorithm::for_key(&key),
+            SignatureAlgorithm::Es256
+        ));
+
+        let jws = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .sign_auto(&key)?;
+        let parsed: Jws = serde_json::from_str(&jws)?;
+        let protected = parsed.get_signatures()[0].protected.clone().unwrap();
+        assert_eq!(protected.alg.as_deref(), Some("ES256"));
+        let public = p256_key.verifying_key().to_sec1_bytes();
+        Message::verify(jws.as_bytes(), &public)?;
+
+        let ed25519_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let jws = Message::new()
+            .sign_auto(&crate::crypto::Key::Ed25519(ed25519_key.to_bytes().to_vec()))?;
+        Message::verify(jws.as_bytes(), ed25519_key.verifying_key().as_bytes())?;
+
+        jwk.crv = Some("X25519".into());
+        assert!(crate::crypto::Key::from_jwk(&jwk).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn kid_is_emitted_in_and_taken_from_signature_header() -> Result<()> {
+        let alice_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let carol_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let jws = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .with_signature_kid(KidPlacement::Header)
+            .sign_multi(&[
+                (
+                    SignatureAlgorithm::EdDsa,
+                    &alice_keypair.to_bytes(),
+                    "did:example:alice#key-1",
+                ),
+                (
+                    SignatureAlgorithm::EdDsa,
+                    &carol_keypair.to_bytes(),
+                    "did:example:carol#key-1",
+                ),
+            ])?;
+        let raw: Value = serde_json::from_str(&jws)?;
+        assert_eq!(
+            raw["signatures"][1]["header"],
+            json!({ "kid": "did:example:carol#key-1" })
+        );
+        let parsed: Jws = jws.parse()?;
+        let signature = parsed.get_signatures()[1];
+        assert_eq!(signature.get_protected().unwrap().kid, None);
+        assert_eq!(signature.get_kid().as_deref(), Some("did:example:carol#key-1"));
+
+        // only carol's key is known, so her signature is selected
+        let mut keys = std::collections::HashMap::new();
+        keys.insert(
+            "did:example:carol#key-1".to_string(),
+            carol_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        let verified = Message::verify_with_keys(jws.as_bytes(), &keys)?;
+        assert_eq!(
+            verified.get_didcomm_header().from.as_deref(),
+            Some("did:example:alice")
+        );
+        keys.insert(
+            "did:example:carol#key-1".to_string(),
+            alice_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        assert!(Message::verify_with_keys(jws.as_bytes(), &keys).is_err());
+
+        let both = Message::new()
+            .kid("did:example:alice#key-1")
+            .as_flat_jws(&SignatureAlgorithm::EdDsa)
+            .with_signature_kid(KidPlacement::Both)
+            .sign(SignatureAlgorithm::EdDsa.signer(), &alice_keypair.to_bytes())?;
+        let signature = both.parse::<Jws>()?.get_signatures()[0].clone();
+        assert_eq!(signature.get_header().unwrap().kid, signature.get_protected().unwrap().kid);
+        Ok(())
+    }
+
+    #[test]
+    fn protected_headers_are_authenticated_and_checked() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .add_header_field("routing_hint".into(), "eu-west".into())
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()));
+
+        let sealed = message
+            .clone()
+            .protect_header("routing_hint")
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        let raw: Value = serde_json::from_str(&sealed)?;
+        let protected: Value = serde_json::from_slice(&base64_url::decode(
+            raw["protected"].as_str().unwrap_or_default(),
+        )?)?;
+        assert_eq!(protected["routing_hint"], "eu-west");
+        let received = Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(received.get_header_str("routing_hint"), Some("eu-west"));
+
+        // protected value differing from plaintext one is rejected
+        let mut forged = message;
+        forged.jwm_header.set_extra("routing_hint", json!("us-east"));
+        let sealed = forged.seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        let result = Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        );
+        assert!(
+            matches!(result, Err(Error::ProtectedHeaderMismatch(key)) if key == "routing_hint")
+        );
+
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let jws = Message::new()
+            .add_header_field("routing_hint".into(), "eu-west".into())
+            .protect_header("routing_hint")
+            .as_jws(&SignatureAlgorithm::EdDsa)
+            .sign(SignatureAlgorithm::EdDsa.signer(), &signing_key.to_bytes())?;
+        let signature = jws.parse::<Jws>()?.get_signatures()[0].clone();
+        assert_eq!(
+            signature.get_protected().unwrap().get_extra("routing_hint"),
+            Some(&json!("eu-west"))
+        );
+        Message::verify(jws.as_bytes(), signing_key.verifying_key().as_bytes())?;
+        Ok(())
+    }
+
+    /// Key in mock keystore, only accessible via `HardwareKey`.
+    struct KeystoreKey {
+        agreement: x25519_dalek::StaticSecret,
+        signing: ed25519_dalek::SigningKey,
+    }
+
+    impl HardwareKey for KeystoreKey {
+        fn handle(&self) -> &str {
+            "keystore:alias"
+        }
+
+        fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+            use ed25519_dalek::Signer as _;
+            Ok(self.signing.sign(data).to_bytes().to_vec())
+        }
+
+        fn key_agreement(&self, public_key: &[u8]) -> Result<Vec<u8>> {
+            let public_key = <[u8; 32]>::try_from(public_key)
+                .map_err(|_| Error::InvalidKeySize("!32".into()))?;
+            let shared = self
+                .agreement
+                .diffie_hellman(&x25519_dalek::PublicKey::from(public_key));
+            Ok(shared.as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn receive_bytes_accepts_padding_utf16_and_compact_forms() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let options = ReceiveOptions::new();
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .body(r#"{"content":"grüße"}"#)?;
+
+        let plain = serde_json::to_string(&message)?;
+        let padded = [&b"\xef\xbb\xbf \r\n"[..], plain.as_bytes(), b"\n\0"].concat();
+        assert!(Message::receive_bytes(&padded, None, None, None, &options).is_err());
+        let padded = &padded[..padded.len() - 1];
+        let received = Message::receive_bytes(padded, None, None, None, &options)?;
+        assert_eq!(received.body, message.body);
+        let utf16: Vec<u8> = std::iter::once(0xfeff)
+            .chain(plain.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let received = Message::receive_bytes(&utf16, None, None, None, &options)?;
+        assert_eq!(received.body, message.body);
+        assert!(Message::receive_bytes(&[0xc3, 0x28], None, None, None, &options).is_err());
+
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let jws: Value = serde_json::from_str(
+            &message
+                .clone()
+                .as_flat_jws(&SignatureAlgorithm::EdDsa)
+                .sign(SignatureAlgorithm::EdDsa.signer(), &signing_key.to_bytes())?,
+        )?;
+        let compact = format!(
+            "{}.{}.{}\r\n",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap(),
+            jws["signature"].as_str().unwrap()
+        );
+        let verifying_key = signing_key.verifying_key().to_bytes();
+        let received = Message::receive_bytes(
+            compact.as_bytes(),
+            None,
+            None,
+            Some(&verifying_key),
+            &options,
+        )?;
+        assert_eq!(received.body, message.body);
+
+        // direct key agreement, so compact JWE has no encrypted key
+        let shared = x25519_dalek::StaticSecret::from(alice_private)
+            .diffie_hellman(&x25519_dalek::PublicKey::from(bobs_public));
+        let jwe: Value = serde_json::from_str(
+            &message
+                .clone()
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+                .encrypt(CryptoAlgorithm::XC20P.encryptor(), shared.as_bytes())?,
+        )?;
+        let compact = ["protected", "encrypted_key", "iv", "ciphertext", "tag"]
+            .iter()
+            .map(|part| jwe[part].as_str().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(".");
+        let received = Message::receive_bytes(
+            compact.as_bytes(),
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &options,
+        )?;
+        assert_eq!(received.body, message.body);
+        Ok(())
+    }
+
+    #[test]
+    fn hardware_keys_seal_receive_and_sign() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let keystore_key = |private: [u8; 32]| KeystoreKey {
+            agreement: x25519_dalek::StaticSecret::from(private),
+            signing: ed25519_dalek::SigningKey::generate(&mut OsRng),
+        };
+        let alice_key = keystore_key(alice_private);
+        let bobs_key = keystore_key(bobs_private);
+        let message = || {
+            Message::new()
+                .from("did:example:alice")
+                .to(&["did:example:bob"])
+        };
+
+        let sealed = message()
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal_hardware(&alice_key, Some(vec![Some(bobs_public.to_vec())]))?;
+        let received = Message::receive(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(
+            received.get_didcomm_header().from.as_deref(),
+            Some("did:example:alice")
+        );
+
+        let sealed = message()
+            .as_jwe(&CryptoAlgorithm::A256GCM, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        Message::receive_hardware(
+            &sealed,
+            &bobs_key,
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new(),
+        )?;
+
+        let signed = message()
+            .as_jws(&SignatureAlgorithm::EdDsa)
+            .sign_hardware(&alice_key)?;
+        let verifying_key = alice_key.signing.verifying_key().to_bytes();
+        Message::verify(signed.as_bytes(), &verifying_key)?;
+        assert!(Message::verify(signed.as_bytes(), &[0; 32]).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,377 @@
+#[cfg(feature = "raw-crypto")]
+use crate::crypto::{CryptoAlgorithm, SignatureAlgorithm};
+use crate::{AttachmentBuilder, Error, IdGenerator, Message, MessageType, Result, ReturnRoute};
+
+/// Used to construct `Message` and validate its headers before it is sealed or signed.
+///
+/// Setters mirror the chained setters of `Message`, but nothing fails until
+///     [`build`][MessageBuilder::build()] is called, which reports all
+///     misconfigurations as typed errors instead of them surfacing inside `seal()`.
+///
+#[derive(Debug, Default)]
+pub struct MessageBuilder {
+    inner: Message,
+    body: Option<String>,
+    encrypted: bool,
+    signed: bool,
+}
+
+impl MessageBuilder {
+    /// Constructor of new instance of the builder with
+    ///     the same defaults as `Message::new()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces `id` of the message with one produced by `generator`
+    ///     instead of the process wide one.
+    ///
+    /// # Parameters
+    ///
+    /// * `generator` - `IdGenerator` to be used for this message
+    ///
+    pub fn id_generator(mut self, generator: &dyn IdGenerator) -> Self {
+        self.inner.didcomm_header.id = generator.generate();
+        self
+    }
+
+    /// Setter of `from` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `from` - DID of the sender
+    ///
+    pub fn from(mut self, from: &str) -> Self {
+        self.inner = self.inner.from(from);
+        self
+    }
+
+    /// Appends recipients to `to` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `to` - DIDs of the recipients
+    ///
+    pub fn to(mut self, to: &[&str]) -> Self {
+        self.inner = self.inner.to(to);
+        self
+    }
+
+    /// Removes all recipients from `to` header.
+    pub fn clear_to(mut self) -> Self {
+        self.inner = self.inner.clear_to();
+        self
+    }
+
+    /// Removes single recipient from `to` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID to be removed
+    ///
+    pub fn remove_to(mut self, did: &str) -> Self {
+        self.inner = self.inner.remove_to(did);
+        self
+    }
+
+    /// Setter of `type` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `m_type` - message type URI
+    ///
+    pub fn m_type(mut self, m_type: &str) -> Self {
+        self.inner = self.inner.m_type(m_type);
+        self
+    }
+
+    /// Setter of `typ` header property.
+    ///
+    /// # Parameters
+    ///
+    /// * `typ` - `MessageType` to be set for `typ` property
+    ///
+    pub fn typ(mut self, typ: MessageType) -> Self {
+        self.inner = self.inner.typ(typ);
+        self
+    }
+
+    /// Setter of `kid` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `kid` - key id used for signing or encryption
+    ///
+    pub fn kid(mut self, kid: &str) -> Self {
+        self.inner = self.inner.kid(kid);
+        self
+    }
+
+    /// Setter of `thid` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `thid` - id of the thread this message belongs to
+    ///
+    pub fn thid(mut self, thid: &str) -> Self {
+        self.inner = self.inner.thid(thid);
+        self
+    }
+
+    /// Setter of `pthid` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `pthid` - id of the parent thread
+    ///
+    pub fn pthid(mut self, pthid: &str) -> Self {
+        self.inner = self.inner.pthid(pthid);
+        self
+    }
+
+    /// Setter of `return_route` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `return_route` - which responses recipient should return over the same connection
+    ///
+    pub fn return_route(mut self, return_route: ReturnRoute) -> Self {
+        self.inner = self.inner.return_route(return_route);
+        self
+    }
+
+    /// Sets `thid` and `pthid` same as those in `replying_to`.
+    ///
+    /// # Parameters
+    ///
+    /// * `replying_to` - ref to message we're replying to
+    ///
+    pub fn reply_to(mut self, replying_to: &Message) -> Self {
+        self.inner = self.inner.reply_to(replying_to);
+        self
+    }
+
+    /// Setter of the `body`.
+    /// Given text has to be a valid JSON string, which is checked on `build()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `body` - stringified JSON body
+    ///
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Adds (or updates) custom header key-value pair.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - name of the header
+    ///
+    /// * `value` - value of the header
+    ///
+    pub fn add_header_field(mut self, key: String, value: String) -> Self {
+        self.inner = self.inner.add_header_field(key, value);
+        self
+    }
+
+    /// Sets time of creation as now and, optional, expires time.
+    ///
+    /// # Parameters
+    ///
+    /// * `expires` - time in seconds since Unix Epoch when message is considered to be invalid
+    ///
+    pub fn timed(mut self, expires: Option<u64>) -> Self {
+        self.inner = self.inner.timed(expires);
+        self
+    }
+
+    /// Appends attachment to the message.
+    ///
+    /// # Parameters
+    ///
+    /// * `attachment` - prepopulated instance of `AttachmentBuilder`
+    ///
+    pub fn attachment(mut self, attachment: AttachmentBuilder) -> Self {
+        self.inner.append_attachment(attachment);
+        self
+    }
+
+    /// Prepares message to be sealed as JWE.
+    /// Requires at least one `to` on `build()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - content encryption algorithm
+    ///
+    /// * `recipient_public_key` - optional key to set as `kid`
+    ///
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_jwe(mut self, alg: &CryptoAlgorithm, recipient_public_key: Option<Vec<u8>>) -> Self {
+        self.inner = self.inner.as_jwe(alg, recipient_public_key);
+        self.encrypted = true;
+        self
+    }
+
+    /// Prepares message to be sealed as flat JWE.
+    /// Requires exactly one `to` on `build()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - content encryption algorithm
+    ///
+    /// * `recipient_public_key` - optional key to set as `kid`
+    ///
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_flat_jwe(
+        mut self,
+        alg: &CryptoAlgorithm,
+        recipient_public_key: Option<Vec<u8>>,
+    ) -> Self {
+        self.inner = self.inner.as_flat_jwe(alg, recipient_public_key);
+        self.encrypted = true;
+        self
+    }
+
+    /// Prepares message to be signed as JWS.
+    /// Requires `kid` on `build()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - signature algorithm
+    ///
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_jws(mut self, alg: &SignatureAlgorithm) -> Self {
+        self.inner = self.inner.as_jws(alg);
+        self.signed = true;
+        self
+    }
+
+    /// Prepares message to be signed as flat JWS.
+    /// Requires `kid` and exactly one `to` on `build()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - signature algorithm
+    ///
+    #[cfg(feature = "raw-crypto")]
+    pub fn as_flat_jws(mut self, alg: &SignatureAlgorithm) -> Self {
+        self.inner = self.inner.as_flat_jws(alg);
+        self.signed = true;
+        self
+    }
+
+    /// Validates configured headers and returns ready to use `Message`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::SerdeError` - `body` is not valid JSON
+    ///
+    /// * `Error::NoJweRecipient` - message is to be encrypted, but has no `to`
+    ///
+    /// * `Error::FlatSerializationRecipients` - flat serialization with other than one `to`
+    ///
+    /// * `Error::PropertyIsNotSet("kid")` - message is to be signed, but has no `kid`
+    ///
+    pub fn build(self) -> Result<Message> {
+        let mut message = self.inner;
+        if let Some(body) = self.body {
+            message = message.body(&body)?;
+        }
+        let to_len = message
+            .didcomm_header
+            .to
+            .iter()
+            .filter(|to| !to.is_empty())
+            .count();
+        if self.encrypted && to_len == 0 {
+            return Err(Error::NoJweRecipient);
+        }
+        if (message.serialize_flat_jwe || message.serialize_flat_jws) && to_len != 1 {
+            return Err(Error::FlatSerializationRecipients(to_len));
+        }
+        if self.signed && message.jwm_header.kid.is_none() {
+            return Err(Error::PropertyIsNotSet("kid"));
+        }
+        Ok(message)
+    }
+}
+
+impl Message {
+    /// Creates new `MessageBuilder`, which validates message on `build()`.
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_plain_message() {
+        let message = Message::builder()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .m_type("https://example.com/protocol/1.0/test")
+            .body(r#"{"foo":"bar"}"#)
+            .build()
+            .expect("failed to build message");
+        assert_eq!(message.didcomm_header.to, vec!["did:example:bob"]);
+        assert_eq!(message.get_body().unwrap(), r#"{"foo":"bar"}"#);
+    }
+
+    #[test]
+    fn uses_given_id_generator() {
+        let message = Message::builder()
+            .id_generator(&|| "fixed-id".to_string())
+            .build()
+            .expect("failed to build message");
+        assert_eq!(message.get_id(), "fixed-id");
+    }
+
+    #[test]
+    fn rejects_invalid_body() {
+        let result = Message::builder().body("not json").build();
+        assert!(matches!(result, Err(Error::SerdeError(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "raw-crypto")]
+    fn rejects_jwe_without_recipients() {
+        let result = Message::builder()
+            .from("did:example:alice")
+            .as_jwe(&CryptoAlgorithm::XC20P, None)
+            .build();
+        assert!(matches!(result, Err(Error::NoJweRecipient)));
+    }
+
+    #[test]
+    #[cfg(feature = "raw-crypto")]
+    fn rejects_flat_jwe_with_multiple_recipients() {
+        let result = Message::builder()
+            .to(&["did:example:bob", "did:example:carol"])
+            .as_flat_jwe(&CryptoAlgorithm::XC20P, None)
+            .build();
+        assert!(matches!(
+            result,
+            Err(Error::FlatSerializationRecipients(2))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "raw-crypto")]
+    fn rejects_jws_without_kid() {
+        let builder = || {
+            Message::builder()
+                .to(&["did:example:bob"])
+                .as_jws(&SignatureAlgorithm::EdDsa)
+        };
+        assert!(matches!(
+            builder().build(),
+            Err(Error::PropertyIsNotSet("kid"))
+        ));
+        assert!(builder().kid("did:example:alice#key-1").build().is_ok());
+    }
+}
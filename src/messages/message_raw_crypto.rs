@@ -1,17 +1,42 @@
-use std::convert::TryInto;
+use std::{collections::HashMap, convert::TryInto};
 
 use base64_url::{decode, encode};
 use serde_json::Value;
 
 use super::Message;
 use crate::{
-    crypto::{SignatureAlgorithm, Signer, SigningMethod, SymmetricCypherMethod},
+    crypto::{
+        AsyncExternalSigner,
+        ExternalSigner,
+        HardwareKey,
+        Key,
+        KeyMaterial,
+        KeyRole,
+        SignatureAlgorithm,
+        Signer,
+        SigningMethod,
+        SymmetricCypherMethod,
+    },
+    messages::{
+        audit,
+        helpers::{
+            compress,
+            decompress,
+            jwm_header_to_string,
+            refers_to_same_did,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        },
+    },
+    EnvelopeKind,
     Error,
     Jwe,
     JwmHeader,
     Jws,
+    KidPlacement,
     MessageType,
+    Recipient,
     Signature,
+    SignatureVerification,
 };
 
 // struct docu is placed in `message.rs`
@@ -30,26 +55,15 @@ impl Message {
     ///
     /// * `cek` - content encryption key to encrypt message with
     pub fn encrypt(self, crypter: SymmetricCypherMethod, cek: &[u8]) -> Result<String, Error> {
-        let mut jwe_header = self.jwm_header.clone();
-        if jwe_header.typ != MessageType::DidCommForward {
-            jwe_header.typ = MessageType::DidCommJwe;
-        }
-        let d_header = self.get_didcomm_header();
+        let jwe_header = self.jwe_protected_header();
         let iv = Jwe::generate_iv();
-        let multi = self.recipients.is_some();
-        jwe_header.skid = Some(d_header.from.clone().unwrap_or_default());
-        if !multi {
-            jwe_header.kid = Some(d_header.to[0].clone());
-        }
-        jwe_header.skid = d_header.from.clone();
-        let aad_string = encode(&serde_json::to_string(&jwe_header)?.as_bytes());
+        let aad_string = encode(&jwm_header_to_string(&jwe_header)?.as_bytes());
         let aad = aad_string.as_bytes();
-        let ciphertext_and_tag = crypter(
-            &decode(&iv)?,
-            cek,
+        let plaintext = compress(
             serde_json::to_string(&self)?.as_bytes(),
-            aad,
+            jwe_header.zip.as_deref(),
         )?;
+        let ciphertext_and_tag = crypter(&decode(&iv)?, cek, &plaintext, aad)?;
         let (ciphertext, tag) = ciphertext_and_tag.split_at(ciphertext_and_tag.len() - 16);
         let jwe = if self.serialize_flat_jwe {
             let recipients = self.recipients.ok_or_else(|| {
@@ -82,6 +96,63 @@ impl Message {
         Ok(serde_json::to_string(&jwe)?)
     }
 
+    /// Recipient entries of JWE built from this message, wrapped ones of a sealed message
+    ///     or else ones pre-populated with `with_recipient`.
+    pub(crate) fn jwe_recipients(&self) -> Option<&[Recipient]> {
+        self.recipients.as_deref().or_else(|| {
+            Some(self.recipient_templates.as_slice()).filter(|templates| !templates.is_empty())
+        })
+    }
+
+    /// Checks that `kid` of every recipient pre-populated with `with_recipient`
+    ///     refers to DID of one of `to`.
+    pub(crate) fn check_recipient_templates(&self) -> Result<(), Error> {
+        for template in &self.recipient_templates {
+            let kid = template.header.kid.as_deref().unwrap_or_default();
+            if !self
+                .didcomm_header
+                .to
+                .iter()
+                .any(|to| refers_to_same_did(kid, to))
+            {
+                return Err(Error::Generic(format!(
+                    "recipient {} does not match any `to`",
+                    kid
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Protected header of JWE built from this message.
+    /// Single recipient messages carry recipient `kid` in it.
+    pub(crate) fn jwe_protected_header(&self) -> JwmHeader {
+        let mut jwe_header = self.jwm_header.clone();
+        if jwe_header.typ != MessageType::DidCommForward {
+            jwe_header.typ = MessageType::DidCommJwe;
+        }
+        let d_header = self.get_didcomm_header();
+        let recipients = self.jwe_recipients();
+        if recipients.is_none() {
+            jwe_header.kid = d_header.to.first().cloned();
+        }
+        jwe_header.skid = d_header.from.clone().filter(|from| !from.is_empty());
+        self.promote_headers(&mut jwe_header);
+        // `alg` of recipients with mixed algorithms is only given in their headers
+        if let Some(recipients) = recipients {
+            if recipients
+                .windows(2)
+                .any(|pair| pair[0].header.alg != pair[1].header.alg)
+            {
+                jwe_header.alg = None;
+            }
+        }
+        // unencoded payload is defined for JWS only
+        jwe_header.b64 = None;
+        jwe_header.crit = None;
+        jwe_header
+    }
+
     /// Decrypts received cypher into instance of `Message`.
     /// Received message should be encrypted with our pub key.
     /// Returns `Ok(Message)` if decryption / deserialization
@@ -98,13 +169,28 @@ impl Message {
         received_message: &[u8],
         decrypter: SymmetricCypherMethod,
         cek: &[u8],
+    ) -> Result<Self, Error> {
+        Self::decrypt_with_limit(
+            received_message,
+            decrypter,
+            cek,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+        )
+    }
+
+    /// Same as `decrypt`, with compressed plaintext limited to `max_decompressed_size` bytes.
+    pub(crate) fn decrypt_with_limit(
+        received_message: &[u8],
+        decrypter: SymmetricCypherMethod,
+        cek: &[u8],
+        max_decompressed_size: usize,
     ) -> Result<Self, Error> {
         let jwe: Jwe = serde_json::from_slice(received_message)?;
         let protected = jwe
             .protected
             .as_ref()
             .ok_or_else(|| Error::Generic("jwe is missing protected header".to_string()))?;
-        let aad_string = encode(&serde_json::to_string(&protected)?.as_bytes());
+        let aad_string = encode(&jwm_header_to_string(protected)?.as_bytes());
         let aad = aad_string.as_bytes();
         let tag = jwe
             .tag
@@ -116,7 +202,16 @@ impl Message {
         ciphertext_and_tag.extend(&decode(&tag)?);
 
         return match decrypter(jwe.get_iv().as_ref(), cek, &ciphertext_and_tag, aad) {
-            Ok(raw_message_bytes) => Ok(serde_json::from_slice(&raw_message_bytes)?),
+            Ok(raw_message_bytes) => {
+                let raw_message_bytes = decompress(
+                    &raw_message_bytes,
+                    protected.zip.as_deref(),
+                    max_decompressed_size,
+                )?;
+                let mut message: Message = serde_json::from_slice(&raw_message_bytes)?;
+                message.merge_protected_headers(protected)?;
+                Ok(message)
+            }
             Err(e) => {
                 error!("decryption failed; {}", &e);
                 Err(Error::PlugCryptoFailure)
@@ -128,12 +223,122 @@ impl Message {
     /// `Err` is returned if message is not properly prepared or data is malformed.
     /// Jws enveloped payload is base64_url encoded
     pub fn sign(
+        self,
+        signer: SigningMethod,
+        signing_sender_private_key: &[u8],
+    ) -> Result<String, Error> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jws);
+        let result = self.sign_jws(signer, signing_sender_private_key);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Signs message with algorithm inferred from type of `key`, sets `alg` header
+    ///     accordingly and turns it into `Jws` envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - typed signing key, e.g. built with `Key::from_jwk`
+    pub fn sign_auto(self, key: &Key) -> Result<String, Error> {
+        let alg = SignatureAlgorithm::for_key(key);
+        self.as_jws(&alg).sign(alg.signer(), key.as_bytes())
+    }
+
+    /// Implementation of `sign` without audit record.
+    pub(crate) fn sign_jws(
         mut self,
         signer: SigningMethod,
         signing_sender_private_key: &[u8],
     ) -> Result<String, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "sign",
+            id = %self.didcomm_header.id,
+            thid = ?self.didcomm_header.thid,
+            alg = ?self.jwm_header.alg,
+        )
+        .entered();
+        let (mut jws_header, payload_string_base64) = self.jws_parts()?;
+        let header = self.signature_header(&mut jws_header);
+        let mut signature_value = create_signature(
+            jws_header,
+            &payload_string_base64,
+            signer,
+            signing_sender_private_key,
+        )?;
+        signature_value.header = header;
+        self.into_jws(payload_string_base64, signature_value)
+    }
+
+    /// Signs message with externally held key, e.g. in a remote wallet or secure enclave,
+    ///     and turns it into `Jws` envelope.
+    /// `alg` and `kid` of protected header have to be set up, e.g. with `as_jws`, to match
+    ///     the key used by `signer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - produces signature over given JWS signing input
+    pub fn sign_external(self, signer: &dyn ExternalSigner) -> Result<String, Error> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jws);
+        let result = self.sign_external_jws(signer);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Signs message with key held in a keystore, e.g. iOS Secure Enclave or Android Keystore,
+    ///     and turns it into `Jws` envelope.
+    /// `alg` and `kid` of protected header have to be set up, e.g. with `as_jws`, to match `key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - handle of signing key
+    pub fn sign_hardware(self, key: &dyn HardwareKey) -> Result<String, Error> {
+        self.sign_external(&|data: &[u8]| key.sign(data))
+    }
+
+    /// Implementation of `sign_external` without audit record.
+    fn sign_external_jws(mut self, signer: &dyn ExternalSigner) -> Result<String, Error> {
+        let (mut jws_header, payload) = self.jws_parts()?;
+        let header = self.signature_header(&mut jws_header);
+        let signing_input = signing_input(&mut jws_header, &payload)?;
+        let signature = signer.sign(signing_input.as_bytes())?;
+        self.into_jws(payload, Signature::new(Some(jws_header), header, signature))
+    }
+
+    /// Async variant of [`Message::sign_external`] for signers which have to be awaited,
+    ///     e.g. remote wallets or multi-party signing services.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - produces signature over given JWS signing input
+    pub async fn sign_external_async(
+        self,
+        signer: &dyn AsyncExternalSigner,
+    ) -> Result<String, Error> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jws);
+        let result = self.sign_external_async_jws(signer).await;
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Implementation of `sign_external_async` without audit record.
+    async fn sign_external_async_jws(
+        mut self,
+        signer: &dyn AsyncExternalSigner,
+    ) -> Result<String, Error> {
+        let (mut jws_header, payload) = self.jws_parts()?;
+        let header = self.signature_header(&mut jws_header);
+        let signing_input = signing_input(&mut jws_header, &payload)?;
+        let signature = signer.sign(signing_input.as_bytes()).await?;
+        self.into_jws(payload, Signature::new(Some(jws_header), header, signature))
+    }
+
+    /// Takes protected header for single signature JWS and encodes payload.
+    /// Drops non jwm plain message header info from `self`.
+    fn jws_parts(&mut self) -> Result<(JwmHeader, String), Error> {
         let mut jws_header = self.jwm_header.clone();
         jws_header.typ = MessageType::DidCommJws;
+        self.promote_headers(&mut jws_header);
         if jws_header.alg.is_none() {
             return Err(Error::JwsParseError);
         }
@@ -141,15 +346,54 @@ impl Message {
         // drop non jwm plain message header info
         self.jwm_header = JwmHeader::default();
 
-        let jws_header_string_base64 = base64_url::encode(&serde_json::to_string(&jws_header)?);
-        let payload_json_string = serde_json::to_string(&self)?;
-        let payload_string_base64 = base64_url::encode(&payload_json_string);
-        let payload_to_sign = format!("{}.{}", &jws_header_string_base64, &payload_string_base64);
-        let signature = signer(signing_sender_private_key, payload_to_sign.as_bytes())?;
-        let signature_value = Signature::new(Some(jws_header), None, signature);
+        let payload = encode_payload(&jws_header, serde_json::to_string(&self)?);
+        Ok((jws_header, payload))
+    }
+
+    /// Copies custom headers marked with `protect_header` into protected header.
+    fn promote_headers(&self, protected: &mut JwmHeader) {
+        for key in &self.integrity_headers {
+            if let Some(value) = self.didcomm_header.other.get(key) {
+                protected.set_extra(key, value.clone());
+            }
+        }
+    }
 
+    /// Takes over unknown parameters of authenticated protected header,
+    ///     checking that plaintext copies of them were not changed.
+    fn merge_protected_headers(&mut self, protected: &JwmHeader) -> Result<(), Error> {
+        for (key, value) in &protected.other {
+            match self.didcomm_header.other.get(key) {
+                Some(copy) if copy != value => {
+                    return Err(Error::ProtectedHeaderMismatch(key.clone()));
+                }
+                _ => {
+                    self.jwm_header.other.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `kid` of protected header into per-signature header, or moves it there,
+    ///     as set by `with_signature_kid`.
+    fn signature_header(&self, protected: &mut JwmHeader) -> Option<JwmHeader> {
+        let kid = match self.signature_kid {
+            KidPlacement::Protected => return None,
+            KidPlacement::Header => protected.kid.take(),
+            KidPlacement::Both => protected.kid.clone(),
+        };
+        Some(JwmHeader {
+            kid: Some(kid?),
+            ..JwmHeader::default()
+        })
+    }
+
+    /// Wraps payload and its signature into flat or general `Jws`,
+    ///     general one repeats signature for each recipient.
+    fn into_jws(self, payload: String, signature_value: Signature) -> Result<String, Error> {
         let jws: Jws = if self.serialize_flat_jws {
-            Jws::new_flat(payload_string_base64, signature_value)
+            Jws::new_flat(payload, signature_value)
         } else {
             let signature_values = self
                 .didcomm_header
@@ -157,58 +401,251 @@ impl Message {
                 .iter()
                 .map(|_| signature_value.clone())
                 .collect();
-            Jws::new(payload_string_base64, signature_values)
+            Jws::new(payload, signature_values)
         };
 
         Ok(serde_json::to_string(&jws)?)
     }
 
+    /// Signs message with multiple keys and turns it into general `Jws` envelope
+    ///     with one `signatures` entry per signer, e.g. for co-signed messages.
+    /// Signers may use different algorithms.
+    /// `Err` is returned if no signer is given or data is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `signers` - tuples of signature algorithm, private key and `kid` of each signer
+    pub fn sign_multi(
+        self,
+        signers: &[(SignatureAlgorithm, &[u8], &str)],
+    ) -> Result<String, Error> {
+        let record = audit::pack_record(&self, EnvelopeKind::Jws);
+        let result = self.sign_multi_jws(signers);
+        audit::submit(record, &result, None);
+        result
+    }
+
+    /// Implementation of `sign_multi` without audit record.
+    fn sign_multi_jws(
+        mut self,
+        signers: &[(SignatureAlgorithm, &[u8], &str)],
+    ) -> Result<String, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "sign",
+            id = %self.didcomm_header.id,
+            thid = ?self.didcomm_header.thid,
+            signers = signers.len(),
+        )
+        .entered();
+        if signers.is_empty() {
+            return Err(Error::Generic("at least one signer is required".into()));
+        }
+        let mut header_template = self.jwm_header.clone();
+        self.promote_headers(&mut header_template);
+
+        // drop non jwm plain message header info
+        self.jwm_header = JwmHeader::default();
+
+        let payload_json_string = serde_json::to_string(&self)?;
+        let payload_string_base64 = encode_payload(&header_template, payload_json_string);
+        let signature_values = signers
+            .iter()
+            .map(|(alg, key, kid)| {
+                let mut jws_header = header_template.clone();
+                jws_header.as_signed(alg);
+                jws_header.kid = Some(kid.to_string());
+                let header = self.signature_header(&mut jws_header);
+                let mut signature_value =
+                    create_signature(jws_header, &payload_string_base64, alg.signer(), key)?;
+                signature_value.header = header;
+                Ok(signature_value)
+            })
+            .collect::<Result<Vec<Signature>, Error>>()?;
+
+        Ok(serde_json::to_string(&Jws::new(
+            payload_string_base64,
+            signature_values,
+        ))?)
+    }
+
+    /// Counter-signs received JWS: appends own signature over original payload
+    ///     and returns it as general `Jws` envelope keeping all original signatures.
+    /// Can be used as evidence of having processed the message, e.g. in receipts.
+    /// Existing signatures are not verified, use `verify` or `verify_all` before.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - received jws message
+    ///
+    /// * `alg` - signature algorithm of counter-signature
+    ///
+    /// * `signing_private_key` - own private key to counter-sign with
+    ///
+    /// * `kid` - own key id, added to protected header of counter-signature
+    pub fn counter_sign(
+        jws: &[u8],
+        alg: SignatureAlgorithm,
+        signing_private_key: &[u8],
+        kid: &str,
+    ) -> Result<String, Error> {
+        let jws: Jws = serde_json::from_slice(jws)?;
+        let mut signature_values: Vec<Signature> =
+            jws.get_signatures().into_iter().cloned().collect();
+        if signature_values.is_empty() {
+            return Err(Error::JwsParseError);
+        }
+        let mut jws_header = JwmHeader::default();
+        jws_header.as_signed(&alg);
+        jws_header.kid = Some(kid.to_string());
+        // counter-signature has to use same payload encoding
+        if let Some(original) = signature_values[0].get_protected() {
+            jws_header.b64 = original.b64;
+            jws_header.crit = original.crit.clone();
+        }
+        signature_values.push(create_signature(
+            jws_header,
+            &jws.payload,
+            alg.signer(),
+            signing_private_key,
+        )?);
+
+        Ok(serde_json::to_string(&Jws::new(jws.payload, signature_values))?)
+    }
+
+    /// Same as `verify` with public key detected by `KeyMaterial::detect`.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - serialized JWS
+    ///
+    /// * `signing_sender_key` - public signing key of sender
+    pub fn verify_key_material(
+        jws: &[u8],
+        signing_sender_key: &KeyMaterial,
+    ) -> Result<Message, Error> {
+        if signing_sender_key.role != KeyRole::Signing {
+            return Err(Error::UnsupportedKeyMaterial(
+                "key agreement keys can't verify signatures".into(),
+            ));
+        }
+        Self::verify(jws, &signing_sender_key.bytes)
+    }
+
+    /// Verifies JWS with key registered for `kid` of its signatures, taken from
+    ///     per-signature `header` or protected header, and returns payload message
+    ///     on first successful verification.
+    /// `Err` return if no signature with known `kid` is valid or data is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - to be verified jws message
+    ///
+    /// * `keys` - public keys of signers by `kid`
+    pub fn verify_with_keys(
+        jws: &[u8],
+        keys: &HashMap<String, Vec<u8>>,
+    ) -> Result<Message, Error> {
+        let parsed: Jws = serde_json::from_slice(jws)?;
+        for signature_value in parsed.get_signatures() {
+            let key = match signature_value.get_kid().and_then(|kid| keys.get(&kid)) {
+                Some(key) => key,
+                None => continue,
+            };
+            if validate_signature(signature_value, &parsed.payload, key)? {
+                let mut message: Message = serde_json::from_slice(&parsed.get_payload()?)?;
+                if let Some(protected_header) = &signature_value.protected {
+                    message.merge_protected_headers(protected_header)?;
+                }
+                return Ok(message);
+            }
+        }
+        Err(Error::JwsParseError)
+    }
+
     /// Verifies signature and returns payload message on verification success.
     /// `Err` return if signature invalid or data is malformed.
     /// Expects Jws's payload to be a valid serialized `Message` and base64_url encoded.
     pub fn verify(jws: &[u8], signing_sender_public_key: &[u8]) -> Result<Message, Error> {
         let jws: Jws = serde_json::from_slice(jws)?;
-
-        let signatures_values_to_verify: Vec<Signature>;
-        if let Some(signatures) = &jws.signatures {
-            signatures_values_to_verify = signatures.clone();
-        } else if let Some(signature_value) = &jws.signature {
-            signatures_values_to_verify = vec![signature_value.clone()];
-        } else {
+        let signatures_values_to_verify = jws.get_signatures();
+        if signatures_values_to_verify.is_empty() {
             return Err(Error::JwsParseError);
         }
-        let payload = &jws.payload;
 
-        let mut verified = false;
+        let mut verified_header = None;
         for signature_value in signatures_values_to_verify {
-            let alg = &signature_value.get_alg().ok_or(Error::JweParseError)?;
-            let signature = &signature_value.signature[..];
-            let verifier: SignatureAlgorithm = alg.try_into()?;
-            let protected_header = signature_value
-                .protected
-                .as_ref()
-                .ok_or(Error::JwsParseError)?;
-            let encoded_header = base64_url::encode(&serde_json::to_string(&protected_header)?);
-            let payload_to_verify = format!("{}.{}", &encoded_header, &payload);
-            if verifier.validator()(
-                signing_sender_public_key,
-                payload_to_verify.as_bytes(),
-                signature,
-            )? {
-                verified = true;
+            if validate_signature(signature_value, &jws.payload, signing_sender_public_key)? {
+                verified_header = signature_value.protected.clone();
                 break;
             }
         }
 
-        if verified {
+        if let Some(protected_header) = verified_header {
             // body in JWS envelope should be a valid JWM message, so parse it into message
-            let message: Message = serde_json::from_slice(&base64_url::decode(&jws.payload)?)?;
+            let mut message: Message = serde_json::from_slice(&jws.get_payload()?)?;
+            message.merge_protected_headers(&protected_header)?;
             Ok(message)
         } else {
             Err(Error::JwsParseError)
         }
     }
 
+    /// Verifies every signature of JWS against key registered for its `kid`
+    ///     and reports result per signature in order of JWS `signatures`.
+    /// Signatures without `kid` or with unknown `kid` are reported as not valid.
+    /// `Err` return only if data is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - to be verified jws message
+    ///
+    /// * `keys` - public keys of signers by `kid`
+    pub fn verify_signatures(
+        jws: &[u8],
+        keys: &HashMap<String, Vec<u8>>,
+    ) -> Result<Vec<SignatureVerification>, Error> {
+        let jws: Jws = serde_json::from_slice(jws)?;
+        jws.get_signatures()
+            .into_iter()
+            .map(|signature_value| {
+                let kid = signature_value.get_kid();
+                let valid = match kid.as_ref().and_then(|kid| keys.get(kid)) {
+                    Some(key) => {
+                        validate_signature(signature_value, &jws.payload, key).unwrap_or(false)
+                    }
+                    None => false,
+                };
+                Ok(SignatureVerification { kid, valid })
+            })
+            .collect()
+    }
+
+    /// Verifies every signature of JWS against key registered for its `kid`
+    ///     and returns payload message only if all of them are valid.
+    /// Unlike `verify`, a single valid signature is not sufficient.
+    /// `Err` return if any signature is invalid, JWS has no signatures or data is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - to be verified jws message
+    ///
+    /// * `keys` - public keys of signers by `kid`
+    pub fn verify_all(jws: &[u8], keys: &HashMap<String, Vec<u8>>) -> Result<Message, Error> {
+        let results = Message::verify_signatures(jws, keys)?;
+        if results.is_empty() || results.iter().any(|result| !result.valid) {
+            return Err(Error::JwsParseError);
+        }
+        let jws: Jws = serde_json::from_slice(jws)?;
+        let mut message: Message = serde_json::from_slice(&jws.get_payload()?)?;
+        for signature_value in jws.get_signatures() {
+            if let Some(protected_header) = &signature_value.protected {
+                message.merge_protected_headers(protected_header)?;
+            }
+        }
+        Ok(message)
+    }
+
     /// Verifies signature and returns payload message on verification success.
     /// `Err` return if signature invalid or data is malformed.
     /// Expects Jws's payload to be a valid serialized `Message` and base64_url encoded.
@@ -222,6 +659,89 @@ impl Message {
         let jws_string = serde_json::to_string(jws)?;
         Message::verify(&jws_string.into_bytes(), signing_sender_public_key)
     }
+
+    /// Verifies signature against multiple candidate keys, e.g. all authentication keys
+    ///     of sender DID, and returns payload message on first successful verification.
+    /// `Err` return if no key validates signature or data is malformed.
+    ///
+    /// # Returns
+    /// Tuple of payload message and index of key in `candidate_keys` that validated signature
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - to be verified jws message
+    ///
+    /// * `candidate_keys` - public keys to try in given order
+    pub fn verify_any(jws: &[u8], candidate_keys: &[&[u8]]) -> Result<(Message, usize), Error> {
+        let mut last_error = Error::JwsParseError;
+        for (index, key) in candidate_keys.iter().enumerate() {
+            match Message::verify(jws, key) {
+                Ok(message) => return Ok((message, index)),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// Signs `payload` (base64 encoded) together with `protected` header.
+fn create_signature(
+    mut protected: JwmHeader,
+    payload: &str,
+    signer: SigningMethod,
+    key: &[u8],
+) -> Result<Signature, Error> {
+    let payload_to_sign = signing_input(&mut protected, payload)?;
+    let signature = signer(key, payload_to_sign.as_bytes())?;
+    Ok(Signature::new(Some(protected), None, signature))
+}
+
+/// Builds JWS signing input of protected header and encoded `payload`.
+fn signing_input(protected: &mut JwmHeader, payload: &str) -> Result<String, Error> {
+    // compression is defined for JWE only
+    protected.zip = None;
+    let encoded_header = base64_url::encode(&jwm_header_to_string(protected)?);
+    Ok(format!("{}.{}", &encoded_header, payload))
+}
+
+/// Payload of JWS with given protected header, base64url encoded unless `b64` is `false`.
+fn encode_payload(protected: &JwmHeader, payload_json_string: String) -> String {
+    if protected.b64 == Some(false) {
+        payload_json_string
+    } else {
+        base64_url::encode(&payload_json_string)
+    }
+}
+
+/// Checks `crit` header of JWS signature: only `b64` is understood and it has to be
+///     listed if and only if it is present.
+fn check_critical(protected: &JwmHeader) -> Result<(), Error> {
+    let crit = protected.crit.as_deref().unwrap_or_default();
+    if let Some(unknown) = crit.iter().find(|parameter| *parameter != "b64") {
+        return Err(Error::InvalidCriticalHeader(unknown.clone()));
+    }
+    if protected.b64.is_some() != crit.iter().any(|parameter| parameter == "b64") {
+        return Err(Error::InvalidCriticalHeader("b64".into()));
+    }
+    Ok(())
+}
+
+/// Checks single signature of JWS over its protected header and `payload`.
+fn validate_signature(
+    signature_value: &Signature,
+    payload: &str,
+    key: &[u8],
+) -> Result<bool, Error> {
+    let alg = &signature_value.get_alg().ok_or(Error::JweParseError)?;
+    let verifier: SignatureAlgorithm = alg.try_into()?;
+    let protected_header = signature_value
+        .protected
+        .as_ref()
+        .ok_or(Error::JwsParseError)?;
+    check_critical(protected_header)?;
+    let encoded_header = base64_url::encode(&jwm_header_to_string(protected_header)?);
+    let payload_to_verify = format!("{}.{}", &encoded_header, payload);
+    verifier.validator()(key, payload_to_verify.as_bytes(), &signature_value.signature)
 }
 
 #[cfg(test)]
@@ -0,0 +1,356 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde_json::Value;
+
+use crate::{Message, PackMiddleware, ReceiveMiddleware, Result, SenderInfo};
+
+/// Received plaintext message together with metadata of its unpacking.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredMessage {
+    /// unpacked message
+    pub message: Message,
+
+    /// sender related data of the envelope it was unpacked from
+    pub sender: SenderInfo,
+
+    /// seconds since Unix Epoch of receiving
+    pub received_time: u64,
+
+    /// `true` once we acknowledged message by listing it in `ack` header of a sent message
+    pub acked: bool,
+}
+
+impl StoredMessage {
+    /// Constructor of not yet acknowledged message received now.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - unpacked message
+    ///
+    /// * `sender` - sender related data of its envelope
+    pub fn new(message: Message, sender: SenderInfo) -> Self {
+        Self {
+            message,
+            sender,
+            received_time: now(),
+            acked: false,
+        }
+    }
+
+    /// Thread of the message, its `thid` or `id`.
+    pub fn thid(&self) -> &str {
+        self.message
+            .get_thid()
+            .unwrap_or_else(|| self.message.get_id())
+    }
+}
+
+/// Criteria to select stored messages by, all set criteria must match.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MessageQuery {
+    /// thread messages belong to
+    pub thid: Option<String>,
+
+    /// `from` DID of messages
+    pub from: Option<String>,
+
+    /// `type` of messages
+    pub m_type: Option<String>,
+
+    /// earliest receive time, seconds since Unix Epoch, inclusive
+    pub received_after: Option<u64>,
+
+    /// latest receive time, seconds since Unix Epoch, exclusive
+    pub received_before: Option<u64>,
+
+    /// acknowledgement status of messages
+    pub acked: Option<bool>,
+
+    /// maximum number of returned messages, most recent are returned
+    pub limit: Option<usize>,
+}
+
+impl MessageQuery {
+    /// Constructor of query matching all messages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts query to messages of given thread.
+    ///
+    /// # Parameters
+    ///
+    /// * `thid` - `thid` of messages, or `id` of first message of thread
+    pub fn thread(mut self, thid: &str) -> Self {
+        self.thid = Some(thid.into());
+        self
+    }
+
+    /// Restricts query to messages with given `from` DID.
+    ///
+    /// # Parameters
+    ///
+    /// * `did` - DID of sender
+    pub fn from(mut self, did: &str) -> Self {
+        self.from = Some(did.into());
+        self
+    }
+
+    /// Restricts query to messages of given `type`.
+    ///
+    /// # Parameters
+    ///
+    /// * `m_type` - message type URI
+    pub fn m_type(mut self, m_type: &str) -> Self {
+        self.m_type = Some(m_type.into());
+        self
+    }
+
+    /// Restricts query to messages received within time range.
+    ///
+    /// # Parameters
+    ///
+    /// * `after` - earliest receive time, seconds since Unix Epoch, inclusive
+    ///
+    /// * `before` - latest receive time, seconds since Unix Epoch, exclusive
+    pub fn received_between(mut self, after: Option<u64>, before: Option<u64>) -> Self {
+        self.received_after = after;
+        self.received_before = before;
+        self
+    }
+
+    /// Restricts query to acknowledged or not yet acknowledged messages.
+    ///
+    /// # Parameters
+    ///
+    /// * `acked` - required acknowledgement status
+    pub fn acked(mut self, acked: bool) -> Self {
+        self.acked = Some(acked);
+        self
+    }
+
+    /// Limits number of returned messages to most recent ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `limit` - maximum number of messages
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// `true` if stored message matches all criteria, `limit` is not considered.
+    pub fn matches(&self, stored: &StoredMessage) -> bool {
+        let header = stored.message.get_didcomm_header();
+        self.thid
+            .as_deref()
+            .is_none_or(|thid| stored.thid() == thid)
+            && self
+                .from
+                .as_ref()
+                .is_none_or(|from| header.from.as_ref() == Some(from))
+            && self
+                .m_type
+                .as_ref()
+                .is_none_or(|m_type| &header.m_type == m_type)
+            && self
+                .received_after
+                .is_none_or(|after| stored.received_time >= after)
+            && self
+                .received_before
+                .is_none_or(|before| stored.received_time < before)
+            && self.acked.is_none_or(|acked| stored.acked == acked)
+    }
+
+    /// Filters messages by criteria, orders them by receive time and applies limit.
+    /// Helper for implementations of [`MessageStore::query`].
+    ///
+    /// # Parameters
+    ///
+    /// * `messages` - candidates, e.g. all stored messages
+    pub fn apply(&self, messages: impl IntoIterator<Item = StoredMessage>) -> Vec<StoredMessage> {
+        let mut matching: Vec<_> = messages
+            .into_iter()
+            .filter(|stored| self.matches(stored))
+            .collect();
+        matching.sort_by(|a, b| {
+            a.received_time
+                .cmp(&b.received_time)
+                .then_with(|| a.message.get_id().cmp(b.message.get_id()))
+        });
+        if let Some(limit) = self.limit {
+            matching.drain(..matching.len().saturating_sub(limit));
+        }
+        matching
+    }
+}
+
+/// Trait must be implemented for pluggable storage of received messages,
+///     see [`MessageRecorder`] for keeping it up to date.
+pub trait MessageStore: Send + Sync {
+    /// Gets message with given `id`.
+    fn get(&self, id: &str) -> Option<StoredMessage>;
+
+    /// Inserts or replaces message with `stored.message` `id`.
+    fn put(&self, stored: StoredMessage);
+
+    /// Gets messages matching query, oldest first.
+    fn query(&self, query: &MessageQuery) -> Vec<StoredMessage>;
+
+    /// Marks message as acknowledged.
+    /// Returns `false` if message is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - `id` of the acknowledged message
+    fn mark_acked(&self, id: &str) -> bool {
+        match self.get(id) {
+            Some(mut stored) => {
+                stored.acked = true;
+                self.put(stored);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// In-memory [`MessageStore`], messages are lost on drop.
+#[derive(Default)]
+pub struct InMemoryMessageStore {
+    messages: Mutex<HashMap<String, StoredMessage>>,
+}
+
+impl InMemoryMessageStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn messages(&self) -> std::sync::MutexGuard<'_, HashMap<String, StoredMessage>> {
+        match self.messages.lock() {
+            Ok(messages) => messages,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl MessageStore for InMemoryMessageStore {
+    fn get(&self, id: &str) -> Option<StoredMessage> {
+        self.messages().get(id).cloned()
+    }
+
+    fn put(&self, stored: StoredMessage) {
+        self.messages()
+            .insert(stored.message.get_id().into(), stored);
+    }
+
+    fn query(&self, query: &MessageQuery) -> Vec<StoredMessage> {
+        query.apply(self.messages().values().cloned())
+    }
+}
+
+/// Keeps a [`MessageStore`] of received messages, so they can be queried later.
+/// Added to [`ReceivePipeline`][crate::ReceivePipeline] it stores every received
+///     message with its [`SenderInfo`].
+/// Added to [`Packer`][crate::Packer] it marks messages listed in `ack` header
+///     of sent messages as acknowledged.
+#[derive(Clone)]
+pub struct MessageRecorder {
+    store: Arc<dyn MessageStore>,
+}
+
+impl MessageRecorder {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `store` - storage shared between packing and receiving
+    pub fn new(store: Arc<dyn MessageStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl PackMiddleware for MessageRecorder {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        let acked = message
+            .get_header("ack")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        for id in acked {
+            self.store.mark_acked(id);
+        }
+        Ok(())
+    }
+}
+
+impl ReceiveMiddleware for MessageRecorder {
+    fn handle(&self, message: &mut Message, sender: &SenderInfo) -> Result<()> {
+        self.store
+            .put(StoredMessage::new(message.clone(), sender.clone()));
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use super::*;
+    use crate::{Packer, ReceivePipeline};
+
+    #[test]
+    fn received_messages_are_stored_and_queried() -> Result<()> {
+        let store = Arc::new(InMemoryMessageStore::new());
+        let recorder = Arc::new(MessageRecorder::new(store.clone()));
+        let pipeline = ReceivePipeline::new().with(recorder.clone());
+        let bob = SenderInfo {
+            from: Some("did:example:bob".into()),
+            signed: true,
+            ..Default::default()
+        };
+        let mut ping = Message::new()
+            .from("did:example:bob")
+            .m_type("https://didcomm.org/trust-ping/2.0/ping");
+        pipeline.run(&mut ping, &bob)?;
+        let mut chat = Message::new()
+            .from("did:example:carol")
+            .m_type("https://didcomm.org/basicmessage/2.0/message")
+            .thid(ping.get_id());
+        pipeline.run(&mut chat, &SenderInfo::default())?;
+
+        let thread = store.query(&MessageQuery::new().thread(ping.get_id()));
+        assert_eq!(thread.len(), 2);
+        let from_bob = store.query(&MessageQuery::new().from("did:example:bob"));
+        assert_eq!(from_bob.len(), 1);
+        assert_eq!(from_bob[0].message, ping);
+        assert!(from_bob[0].sender.signed);
+        let chats = MessageQuery::new().m_type("https://didcomm.org/basicmessage/2.0/message");
+        assert_eq!(store.query(&chats)[0].message, chat);
+        assert_eq!(store.query(&MessageQuery::new().limit(1)).len(), 1);
+        let later = now() + 60;
+        assert!(store
+            .query(&MessageQuery::new().received_between(Some(later), None))
+            .is_empty());
+
+        Packer::new()
+            .with(recorder)
+            .prepare(Message::new().set_header("ack", [ping.get_id()])?)?;
+        let unacked = store.query(&MessageQuery::new().acked(false));
+        assert_eq!(unacked.len(), 1);
+        assert_eq!(unacked[0].message, chat);
+        assert!(store.get(ping.get_id()).unwrap().acked);
+        Ok(())
+    }
+}
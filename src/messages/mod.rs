@@ -1,25 +1,115 @@
 mod attachment;
+mod attachment_cache;
+mod attachment_policy;
+mod audit;
+mod connection;
+mod envelope_kind;
 mod headers;
 pub(crate) mod helpers;
+mod id_generator;
+mod inbox;
 mod jwe;
+mod jwe_builder;
 mod jws;
+mod localization;
 mod mediated;
+mod mediation;
 mod message;
+mod message_builder;
+mod message_store;
+mod packer;
 mod problem_report;
+mod receipts;
+mod receive_failure;
+mod receive_options;
+mod receive_pipeline;
+mod replay_guard;
+mod sender_policy;
+mod service;
+mod thread_store;
+mod timestamps;
+mod vc_attachment;
 
+#[cfg(feature = "raw-crypto")]
+mod agent;
+#[cfg(feature = "raw-crypto")]
+mod broadcast;
+#[cfg(feature = "raw-crypto")]
+mod channel;
+#[cfg(feature = "raw-crypto")]
+mod did_resolver;
+#[cfg(feature = "raw-crypto")]
+mod ephemeral;
+#[cfg(feature = "raw-crypto")]
+mod external_crypto;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod js_resolver;
+#[cfg(feature = "raw-crypto")]
+mod key_store;
 #[cfg(feature = "raw-crypto")]
 mod message_raw_crypto;
+#[cfg(feature = "raw-crypto")]
+mod pack_encrypted;
+#[cfg(feature = "raw-crypto")]
+mod profile;
 
 #[cfg(feature = "out-of-band")]
 pub mod out_of_band;
 
+#[cfg(feature = "qr")]
+mod qr;
+
+#[cfg(feature = "raw-crypto")]
+pub use agent::*;
 pub use attachment::*;
+pub use attachment_cache::*;
+pub use attachment_policy::*;
+pub use audit::*;
+#[cfg(feature = "raw-crypto")]
+pub use broadcast::*;
+#[cfg(feature = "raw-crypto")]
+pub use channel::*;
+pub use connection::*;
+#[cfg(feature = "raw-crypto")]
+pub use did_resolver::*;
+pub use envelope_kind::*;
+#[cfg(feature = "raw-crypto")]
+pub use ephemeral::*;
+#[cfg(feature = "raw-crypto")]
+pub use external_crypto::*;
 pub use headers::*;
+pub use id_generator::*;
+pub use inbox::*;
 pub use jwe::*;
+pub use jwe_builder::*;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub use js_resolver::*;
 pub use jws::*;
+#[cfg(feature = "raw-crypto")]
+pub use key_store::*;
+pub use localization::*;
 pub use mediated::*;
+pub use mediation::*;
 pub use message::*;
+pub use message_builder::*;
+pub use message_store::*;
+#[cfg(feature = "raw-crypto")]
+pub use pack_encrypted::*;
+pub use packer::*;
 pub use problem_report::*;
+#[cfg(feature = "raw-crypto")]
+pub use profile::*;
+#[cfg(feature = "qr")]
+pub use qr::*;
+pub use receipts::*;
+pub use receive_failure::*;
+pub use receive_options::*;
+pub use receive_pipeline::*;
+pub use replay_guard::*;
+pub use sender_policy::*;
+pub use service::*;
+pub use thread_store::*;
+pub use vc_attachment::*;
 
 /// trait that can be used to verify body, see example [here][crate]
 pub trait Shape: Sized {
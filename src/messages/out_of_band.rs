@@ -1,5 +1,17 @@
-use super::{AttachmentBuilder, Message, MessageType};
-use crate::Result;
+use super::{AttachmentBuilder, AttachmentDataBuilder, EnvelopeKind, Message, MessageType};
+#[cfg(feature = "raw-crypto")]
+use crate::ReceiveOptions;
+use crate::{messages::id_generator::generate_id, Error, Result};
+
+/// Name of the query parameter carrying encoded message in out-of-band URLs.
+pub const OOB_QUERY_PARAM: &str = "_oob";
+
+/// Scheme of `didcomm://` URIs.
+pub const DIDCOMM_URI_SCHEME: &str = "didcomm://";
+
+/// Maximal length of out-of-band URLs, longer ones are not reliably handled
+///     by browsers, messengers and QR code readers.
+pub const OOB_URL_MAX_LENGTH: usize = 2048;
 
 impl Message {
     /// Transforms given `Message` into out_of_band invitation
@@ -27,4 +39,133 @@ impl Message {
         }
         self.body(std::str::from_utf8(body.as_ref()).unwrap())
     }
+
+    /// Attaches request message to out-of-band invitation, to be handled by receiver
+    ///     right away, e.g. a presentation request.
+    /// Media type of attachment is set by envelope of `request`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if `request` is no plaintext, signed or encrypted message.
+    ///
+    /// # Parameters
+    ///
+    /// * `request` - serialized request message, plaintext or packed
+    pub fn with_attached_request(mut self, request: &str) -> Result<Self> {
+        let media_type = EnvelopeKind::detect(request)
+            .media_type()
+            .ok_or_else(|| Error::AttachmentError("request is no DIDComm message".into()))?;
+        self.append_attachment(
+            AttachmentBuilder::new(false)
+                .with_id(&generate_id())
+                .with_media_type(media_type)
+                .with_data(AttachmentDataBuilder::new().with_json(request)),
+        );
+        Ok(self)
+    }
+
+    /// Returns serialized request messages attached to out-of-band invitation,
+    ///     see `with_attached_request`, in order of attachments.
+    /// Attachments with other media types are skipped.
+    pub fn attached_requests(&self) -> Result<Vec<String>> {
+        self.attachment_iter()
+            .filter(|attachment| {
+                attachment
+                    .media_type
+                    .as_deref()
+                    .and_then(EnvelopeKind::from_media_type)
+                    .is_some()
+            })
+            .map(|attachment| Ok(String::from_utf8(attachment.decoded_content()?)?))
+            .collect()
+    }
+
+    /// Receives all request messages attached to out-of-band invitation,
+    ///     see [`Message::receive_with_options`] for key handling.
+    /// First request failing to be received fails the whole invitation.
+    ///
+    /// # Arguments
+    ///
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked messages
+    #[cfg(feature = "raw-crypto")]
+    pub fn receive_attached_requests(
+        &self,
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<Vec<Message>> {
+        self.attached_requests()?
+            .iter()
+            .map(|request| {
+                Message::receive_with_options(
+                    request,
+                    encryption_recipient_private_key,
+                    encryption_sender_public_key.clone(),
+                    signing_sender_public_key,
+                    options,
+                )
+            })
+            .collect()
+    }
+
+    /// Encodes plaintext message as out-of-band URL,
+    ///     e.g. `https://example.com/path?_oob=<base64url>`.
+    /// `Err` is returned if URL is longer than `OOB_URL_MAX_LENGTH`.
+    ///
+    /// # Parameters
+    ///
+    /// * `base` - URL the `_oob` query parameter is appended to
+    pub fn to_oob_url(&self, base: &str) -> Result<String> {
+        let separator = if base.contains('?') { '&' } else { '?' };
+        let encoded = base64_url::encode(&serde_json::to_string(self)?);
+        let url = format!("{}{}{}={}", base, separator, OOB_QUERY_PARAM, encoded);
+        check_oob_length(&url)?;
+        Ok(url)
+    }
+
+    /// Encodes plaintext message as `didcomm://?_oob=<base64url>` URI.
+    /// `Err` is returned if URI is longer than `OOB_URL_MAX_LENGTH`.
+    pub fn to_didcomm_uri(&self) -> Result<String> {
+        self.to_oob_url(DIDCOMM_URI_SCHEME)
+    }
+
+    /// Decodes message from out-of-band URL or `didcomm://` URI created with `to_oob_url`
+    ///     or `to_didcomm_uri`.
+    /// `Err` is returned if URL is too long, has no `_oob` parameter or it holds no message.
+    ///
+    /// # Parameters
+    ///
+    /// * `url` - out-of-band URL or `didcomm://` URI
+    pub fn from_oob_url(url: &str) -> Result<Self> {
+        check_oob_length(url)?;
+        let query = url
+            .split('#')
+            .next()
+            .and_then(|url| url.split_once('?'))
+            .map(|(_, query)| query)
+            .unwrap_or_default();
+        let encoded = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == OOB_QUERY_PARAM)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                Error::Generic(format!("URL has no `{}` query parameter", OOB_QUERY_PARAM))
+            })?;
+        Ok(serde_json::from_slice(&base64_url::decode(encoded)?)?)
+    }
+}
+
+fn check_oob_length(url: &str) -> Result<()> {
+    if url.len() > OOB_URL_MAX_LENGTH {
+        return Err(Error::OutOfBandUrlTooLong(url.len(), OOB_URL_MAX_LENGTH));
+    }
+    Ok(())
 }
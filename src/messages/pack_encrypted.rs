@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{
+    crypto::CryptoAlgorithm,
+    DidCommMessagingService,
+    Error,
+    Jwe,
+    Mediated,
+    Message,
+    MessageType,
+    Result,
+};
+
+/// Options of [`Message::pack_encrypted`], same as `PackEncryptedOptions`
+///     of DIDComm SDKs for other languages.
+#[derive(Debug, Clone)]
+pub struct PackEncryptedOptions {
+    /// wraps authcrypt envelope into anoncrypt one, so `skid` is only visible to recipients
+    pub protect_sender: bool,
+
+    /// wraps envelope into forward messages for `routing_keys` of `messaging_service`
+    pub forward: bool,
+
+    /// additional headers of forward messages
+    pub forward_headers: HashMap<String, Value>,
+
+    /// service the envelope will be sent to; without one, envelope is not forwarded
+    pub messaging_service: Option<DidCommMessagingService>,
+
+    /// keys of `routing_keys` of `messaging_service`, same order;
+    ///   can be provided if keys should not be resolved via mediators DIDs
+    pub routing_public_keys: Option<Vec<Option<Vec<u8>>>>,
+
+    /// content encryption of anoncrypt envelopes, including forwards
+    pub enc_alg_anon: CryptoAlgorithm,
+
+    /// content encryption of authcrypt envelopes
+    pub enc_alg_auth: CryptoAlgorithm,
+}
+
+impl Default for PackEncryptedOptions {
+    fn default() -> Self {
+        Self {
+            protect_sender: false,
+            forward: true,
+            forward_headers: HashMap::new(),
+            messaging_service: None,
+            routing_public_keys: None,
+            enc_alg_anon: CryptoAlgorithm::XC20P,
+            enc_alg_auth: CryptoAlgorithm::A256GCM,
+        }
+    }
+}
+
+impl PackEncryptedOptions {
+    /// Constructor with default options: no sender protection,
+    ///     forwarding if `messaging_service` is set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Output of [`Message::pack_encrypted`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackResult {
+    /// serialized envelope, ready to be sent to `service_endpoint`
+    pub envelope: String,
+
+    /// `kid` of all recipients of the message
+    pub to_kids: Vec<String>,
+
+    /// `skid` of sender, `None` for anoncrypt envelopes
+    pub from_kid: Option<String>,
+
+    /// URI of `messaging_service`, if given
+    pub service_endpoint: Option<String>,
+}
+
+impl Message {
+    /// Seals (encrypts) self with authcrypt if sender key is given, with anoncrypt otherwise,
+    ///     and optionally protects sender and forwards envelope through mediators.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_private_key` - encryption key of sender, `None` to seal anonymously
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipients,
+    ///   same order as `to`; can be provided if keys should not be resolved via recipients DIDs
+    ///
+    /// * `options` - algorithms, sender protection and forwarding
+    pub fn pack_encrypted(
+        mut self,
+        sender_private_key: Option<&[u8]>,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        options: &PackEncryptedOptions,
+    ) -> Result<PackResult> {
+        let to = self.didcomm_header.to.clone();
+        let mut envelope = match sender_private_key {
+            Some(sender_private_key) => {
+                self.jwm_header.as_encrypted(&options.enc_alg_auth);
+                self.seal(sender_private_key, recipient_public_keys.clone())?
+            }
+            None => {
+                self.jwm_header.as_encrypted(&options.enc_alg_anon);
+                self.seal_anonymous(recipient_public_keys.clone())?
+            }
+        };
+        let jwe: Jwe = serde_json::from_str(&envelope)?;
+        let to_kids = jwe
+            .get_recipients()
+            .iter()
+            .filter_map(|recipient| recipient.header.kid.clone())
+            .collect();
+        let from_kid = jwe.get_skid().filter(|skid| !skid.is_empty());
+
+        if options.protect_sender && from_kid.is_some() {
+            let to: Vec<&str> = to.iter().map(String::as_str).collect();
+            let mut wrapper = Message::new().to(&to);
+            wrapper.jwm_header.as_encrypted(&options.enc_alg_anon);
+            wrapper.body = serde_json::from_str(&envelope)?;
+            envelope = wrapper.seal_anonymous(recipient_public_keys)?;
+        }
+
+        if let Some(service) = options
+            .messaging_service
+            .as_ref()
+            .filter(|_| options.forward)
+        {
+            let routing_public_keys = match &options.routing_public_keys {
+                Some(keys) if keys.len() != service.routing_keys.len() => {
+                    return Err(Error::Generic(
+                        "`routing_keys` and `routing_public_keys` must have same length"
+                            .to_string(),
+                    ));
+                }
+                Some(keys) => keys.clone(),
+                None => vec![None; service.routing_keys.len()],
+            };
+            let mut next = to.first().cloned().ok_or(Error::NoJweRecipient)?;
+            for (routing_key, public_key) in service.routing_keys.iter().zip(routing_public_keys) {
+                let body = Mediated::new(next).with_payload(envelope.into_bytes());
+                let mut forward = Message::new();
+                forward.jwm_header.as_encrypted(&options.enc_alg_anon);
+                forward = forward
+                    .to(&[routing_key])
+                    .typ(MessageType::DidCommForward)
+                    .body(&serde_json::to_string(&body)?)?;
+                for (key, value) in &options.forward_headers {
+                    forward = forward.set_header(key, value)?;
+                }
+                envelope = forward.seal_anonymous(Some(vec![public_key]))?;
+                next = routing_key.clone();
+            }
+        }
+
+        Ok(PackResult {
+            envelope,
+            to_kids,
+            from_kid,
+            service_endpoint: options
+                .messaging_service
+                .as_ref()
+                .map(|service| service.uri.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{
+        crypto::{Curve, KeyMaterial, KeyRole},
+        InMemoryDidResolver,
+        ReceiveOptions,
+        ResolvedDid,
+        SenderInfo,
+    };
+
+    #[test]
+    fn packs_with_sender_protection_and_forwards() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let options = PackEncryptedOptions {
+            protect_sender: true,
+            forward_headers: HashMap::from([("priority".to_string(), Value::from(99))]),
+            messaging_service: Some(
+                DidCommMessagingService::new("https://mediator.example")
+                    .with_routing_keys(&["did:example:mediator#key-1"]),
+            ),
+            routing_public_keys: Some(vec![Some(mediators_public.to_vec())]),
+            ..PackEncryptedOptions::new()
+        };
+        let packed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .pack_encrypted(
+                Some(&alice_private),
+                Some(vec![Some(bobs_public.to_vec())]),
+                &options,
+            )?;
+        assert_eq!(packed.to_kids, ["did:example:bob"]);
+        assert_eq!(packed.from_kid.as_deref(), Some("did:example:alice"));
+        assert_eq!(
+            packed.service_endpoint.as_deref(),
+            Some("https://mediator.example")
+        );
+
+        // mediator sees neither sender nor content
+        let outer: Jwe = serde_json::from_str(&packed.envelope)?;
+        assert_eq!(outer.get_skid(), None);
+        let forward = Message::receive(&packed.envelope, Some(&mediators_private), None, None)?;
+        assert_eq!(forward.get_header_as::<u64>("priority")?, Some(99));
+        let mediated: Mediated = serde_json::from_str(&forward.get_body()?)?;
+        assert_eq!(mediated.next, "did:example:bob");
+
+        // skid authenticates sender only if resolved key of alice decrypted the envelope
+        let resolver = InMemoryDidResolver::new();
+        resolver.insert(ResolvedDid::new("did:example:alice").with_key(
+            "did:example:alice#key-1",
+            KeyMaterial {
+                curve: Curve::X25519,
+                role: KeyRole::KeyAgreement,
+                private: false,
+                bytes: alice_public.to_vec(),
+            },
+        ));
+        let (received, sender): (Message, SenderInfo) = Message::receive_with_sender_info(
+            std::str::from_utf8(&mediated.payload)?,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new().with_did_resolver(std::sync::Arc::new(resolver)),
+        )?;
+        assert_eq!(
+            received.get_didcomm_header().from.as_deref(),
+            Some("did:example:alice")
+        );
+        assert_eq!(sender.skid.as_deref(), Some("did:example:alice"));
+        assert_eq!(sender.authenticated.as_deref(), Some("did:example:alice"));
+
+        let anonymous = Message::new().to(&["did:example:bob"]).pack_encrypted(
+            None,
+            Some(vec![Some(bobs_public.to_vec())]),
+            &PackEncryptedOptions::new(),
+        )?;
+        assert_eq!(anonymous.from_kid, None);
+        assert_eq!(anonymous.service_endpoint, None);
+        Message::receive(&anonymous.envelope, Some(&bobs_private), None, None)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde_json::Value;
+
+#[cfg(feature = "raw-crypto")]
+use crate::crypto::{SignatureAlgorithm, SigningMethod};
+use crate::{Error, Message, Result};
+
+/// Trait must be implemented for steps of [`Packer`].
+/// Middleware runs on plaintext message before it is sealed or signed.
+///     It may modify the message, e.g. by adding headers, or refuse to send it
+///     by returning `Err`.
+///
+/// Implemented for closures `Fn(&mut Message) -> Result<()>`.
+pub trait PackMiddleware: Send + Sync {
+    /// Inspects or modifies plaintext message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - message about to be packed, as modified by previous middleware
+    fn handle(&self, message: &mut Message) -> Result<()>;
+}
+
+impl<F> PackMiddleware for F
+where
+    F: Fn(&mut Message) -> Result<()> + Send + Sync,
+{
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        self(message)
+    }
+}
+
+/// Packs outgoing messages after running ordered list of [`PackMiddleware`] on them.
+/// Configured once and reused for every sent message instead of repeating
+///     same header handling at each call site.
+#[derive(Clone, Default)]
+pub struct Packer {
+    middleware: Vec<Arc<dyn PackMiddleware>>,
+}
+
+impl Packer {
+    /// Constructor of packer without middleware, which packs messages unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends middleware to the end of the pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `middleware` - middleware run after previously added ones
+    pub fn with(mut self, middleware: Arc<dyn PackMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Runs all middleware in order and returns resulting plaintext message.
+    /// First middleware returning `Err` stops the pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message to be packed
+    pub fn prepare(&self, mut message: Message) -> Result<Message> {
+        for middleware in &self.middleware {
+            let result = middleware.handle(&mut message);
+            #[cfg(feature = "tracing")]
+            if let Err(e) = &result {
+                tracing::debug!(error = %e, "message rejected by pack middleware");
+            }
+            result?;
+        }
+        Ok(message)
+    }
+
+    /// Runs middleware and seals message, see [`Message::seal`].
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message prepared with `as_jwe` or `as_flat_jwe`
+    ///
+    /// * `sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `recipient_public_keys` - keys used to encrypt content encryption key for recipient
+    #[cfg(feature = "raw-crypto")]
+    pub fn seal(
+        &self,
+        message: Message,
+        sender_private_key: impl AsRef<[u8]>,
+        recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<String> {
+        self.prepare(message)?
+            .seal(sender_private_key, recipient_public_keys)
+    }
+
+    /// Runs middleware and signs message, see [`Message::sign`].
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message prepared with `as_jws` or `as_flat_jws`
+    ///
+    /// * `signer` - signing method of used algorithm
+    ///
+    /// * `signing_sender_private_key` - signing key
+    #[cfg(feature = "raw-crypto")]
+    pub fn sign(
+        &self,
+        message: Message,
+        signer: SigningMethod,
+        signing_sender_private_key: &[u8],
+    ) -> Result<String> {
+        self.prepare(message)?
+            .sign(signer, signing_sender_private_key)
+    }
+
+    /// Runs middleware, signs and seals message, see [`Message::seal_signed`].
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - message prepared with `as_jwe`
+    ///
+    /// * `encryption_sender_private_key` - encryption key for inner message payload JWE encryption
+    ///
+    /// * `encryption_recipient_public_keys` - keys used to encrypt content encryption key
+    ///
+    /// * `signing_algorithm` - signature algorithm used
+    ///
+    /// * `signing_sender_private_key` - signing key for enveloped message JWS
+    #[cfg(feature = "raw-crypto")]
+    pub fn seal_signed(
+        &self,
+        message: Message,
+        encryption_sender_private_key: &[u8],
+        encryption_recipient_public_keys: Option<Vec<Option<Vec<u8>>>>,
+        signing_algorithm: SignatureAlgorithm,
+        signing_sender_private_key: &[u8],
+    ) -> Result<String> {
+        self.prepare(message)?.seal_signed(
+            encryption_sender_private_key,
+            encryption_recipient_public_keys,
+            signing_algorithm,
+            signing_sender_private_key,
+        )
+    }
+}
+
+/// Middleware setting `created_time` to now if it is not set
+///     and, optional, `expires_time` relative to it if that is not set either.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingHeaders {
+    ttl: Option<Duration>,
+}
+
+impl TimingHeaders {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `ttl` - time span for which sent messages stay valid, `None` for no expiry
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self { ttl }
+    }
+}
+
+impl PackMiddleware for TimingHeaders {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        if message.didcomm_header.created_time.is_none() {
+            let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+            message.didcomm_header.created_time = Some(now.as_secs());
+        }
+        if let (Some(ttl), None) = (self.ttl, message.didcomm_header.expires_time) {
+            *message = message.clone().expires_in(ttl);
+        }
+        Ok(())
+    }
+}
+
+/// Middleware applying default expirations per message `type`
+///     and, optional, capping expiration of every sent message.
+/// Messages already having `expires_time` keep it unless it exceeds the cap.
+#[derive(Debug, Clone, Default)]
+pub struct TtlPolicy {
+    default_ttl: Option<Duration>,
+    type_ttls: HashMap<String, Duration>,
+    max_ttl: Option<Duration>,
+}
+
+impl TtlPolicy {
+    /// Constructor of policy which leaves messages unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Setter of expiration for messages of types without own expiration.
+    ///
+    /// # Parameters
+    ///
+    /// * `ttl` - time span for which sent messages stay valid
+    pub fn with_default(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Setter of expiration for messages of given type.
+    ///
+    /// # Parameters
+    ///
+    /// * `m_type` - `type` header of messages
+    ///
+    /// * `ttl` - time span for which sent messages of this type stay valid
+    pub fn with_type(mut self, m_type: &str, ttl: Duration) -> Self {
+        self.type_ttls.insert(m_type.into(), ttl);
+        self
+    }
+
+    /// Setter of maximal expiration, applied to all messages,
+    ///     including those without or with longer `expires_time`.
+    ///
+    /// # Parameters
+    ///
+    /// * `ttl` - longest time span any sent message stays valid
+    pub fn with_max(mut self, ttl: Duration) -> Self {
+        self.max_ttl = Some(ttl);
+        self
+    }
+}
+
+impl PackMiddleware for TtlPolicy {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        if message.didcomm_header.expires_time.is_none() {
+            let ttl = self
+                .type_ttls
+                .get(&message.didcomm_header.m_type)
+                .or(self.default_ttl.as_ref());
+            if let Some(ttl) = ttl {
+                *message = message.clone().expires_in(*ttl);
+            }
+        }
+        if let Some(max_ttl) = self.max_ttl {
+            let capped = message.clone().expires_in(max_ttl);
+            let max_expires = capped.didcomm_header.expires_time;
+            match message.didcomm_header.expires_time {
+                Some(expires) if Some(expires) <= max_expires => {}
+                _ => *message = capped,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Middleware refusing to pack messages missing any of the required headers.
+/// Checks both standard DIDComm headers, e.g. `from` or `thid`, and custom ones.
+#[derive(Debug, Clone, Default)]
+pub struct RequiredHeaders {
+    headers: Vec<String>,
+}
+
+impl RequiredHeaders {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `headers` - names of headers every sent message must have
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+        }
+    }
+}
+
+impl PackMiddleware for RequiredHeaders {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        // custom headers are flattened into serialized header
+        let header = serde_json::to_value(&message.didcomm_header)?;
+        for name in &self.headers {
+            if header.get(name).is_none_or(is_unset) {
+                return Err(Error::Generic(format!(
+                    "required header `{}` is not set",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `Message::new` fills `from` and `to` with empty strings, which count as not set.
+fn is_unset(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(values) => values.iter().all(is_unset),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn middleware_runs_in_order() -> Result<()> {
+        let packer = Packer::new()
+            .with(Arc::new(|message: &mut Message| {
+                *message = message.clone().add_header_field("trace_id".into(), "1".into());
+                Ok(())
+            }))
+            .with(Arc::new(RequiredHeaders::new(&["trace_id", "id"])))
+            .with(Arc::new(TimingHeaders::new(Some(Duration::from_secs(60)))));
+        let message = packer.prepare(Message::new())?;
+
+        assert_eq!(message.get_header("trace_id"), Some(&"1".into()));
+        let created = message.get_created_time().unwrap();
+        assert_eq!(message.get_expires_time(), Some(created + 60));
+        Ok(())
+    }
+
+    #[test]
+    fn ttl_policy_applies_type_defaults_and_cap() -> Result<()> {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let packer = Packer::new().with(Arc::new(
+            TtlPolicy::new()
+                .with_default(Duration::from_secs(60 * 60))
+                .with_type("https://example.com/ping", Duration::from_secs(30))
+                .with_max(day),
+        ));
+
+        let default = packer.prepare(Message::new())?;
+        let created = default.get_created_time().unwrap();
+        assert_eq!(default.get_expires_time(), Some(created + 60 * 60));
+        let ping = packer.prepare(Message::new().m_type("https://example.com/ping"))?;
+        let created = ping.get_created_time().unwrap();
+        assert_eq!(ping.get_expires_time(), Some(created + 30));
+        let long = packer.prepare(Message::new().expires_in(day * 7))?;
+        let created = long.get_created_time().unwrap();
+        assert_eq!(long.get_expires_time(), Some(created + day.as_secs()));
+        Ok(())
+    }
+
+    #[test]
+    fn required_headers_reject_incomplete_messages() {
+        let packer = Packer::new().with(Arc::new(RequiredHeaders::new(&["from"])));
+
+        assert!(packer.prepare(Message::new()).is_err());
+        assert!(packer.prepare(Message::new().from("did:example:alice")).is_ok());
+    }
+}
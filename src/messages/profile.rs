@@ -0,0 +1,187 @@
+use std::convert::TryFrom;
+
+use crate::{
+    crypto::{ContentEncAlg, KeyAgreementAlg},
+    helpers::recipient_alg,
+    Error,
+    Jwe,
+    Result,
+};
+
+/// Conformance profile restricting which `alg`/`enc`/curve combinations are sealed
+///     with [`Message::with_profile`][crate::Message::with_profile()] and accepted with
+///     [`ReceiveOptions::with_profile`][crate::ReceiveOptions::with_profile()].
+/// Envelopes violating the profile fail with `Error::ProfileViolation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Profile {
+    /// Suites sanctioned by DIDComm v2: `ECDH-ES+A256KW` and `ECDH-1PU+A256KW`
+    ///     key wrapping with `A256GCM` or `XC20P` content encryption on X25519.
+    DidCommV2,
+
+    /// Same as `DidCommV2`, but only anoncrypt (`ECDH-ES+A256KW`) is allowed.
+    DidCommV2AnoncryptOnly,
+
+    /// Explicitly listed combinations.
+    Custom {
+        /// allowed `alg` and `enc` pairs
+        suites: Vec<(KeyAgreementAlg, ContentEncAlg)>,
+
+        /// allowed curves of ephemeral keys, e.g. `X25519`
+        curves: Vec<String>,
+    },
+}
+
+impl Profile {
+    /// Checks if combination is allowed by this profile.
+    ///
+    /// # Parameters
+    ///
+    /// * `alg` - key agreement and wrapping of content encryption key
+    ///
+    /// * `enc` - content encryption
+    ///
+    /// * `crv` - curve of ephemeral key
+    pub fn allows(&self, alg: KeyAgreementAlg, enc: ContentEncAlg, crv: &str) -> bool {
+        match self {
+            Profile::DidCommV2 => {
+                matches!(
+                    alg,
+                    KeyAgreementAlg::EcdhEsA256kw | KeyAgreementAlg::Ecdh1puA256kw
+                ) && crv == "X25519"
+            }
+            Profile::DidCommV2AnoncryptOnly => {
+                alg == KeyAgreementAlg::EcdhEsA256kw && crv == "X25519"
+            }
+            Profile::Custom { suites, curves } => {
+                suites.contains(&(alg, enc)) && curves.iter().any(|curve| curve == crv)
+            }
+        }
+    }
+
+    /// Checks all recipients of JWE against this profile.
+    /// Unknown or missing `alg`/`enc` values are violations as well.
+    ///
+    /// # Parameters
+    ///
+    /// * `jwe` - sealed or received JWE
+    pub fn check_jwe(&self, jwe: &Jwe) -> Result<()> {
+        let enc = jwe.get_enc();
+        let jwe_alg = jwe.get_alg();
+        let recipients = jwe.get_recipients();
+        if recipients.is_empty() {
+            return Err(Error::NoJweRecipient);
+        }
+        for recipient in recipients {
+            let alg = recipient_alg(&recipient.header).or(jwe_alg.as_deref());
+            let crv = recipient
+                .header
+                .epk
+                .as_ref()
+                .map(|epk| epk.crv.as_str())
+                .unwrap_or_default();
+            let allowed = match (alg, enc.as_deref()) {
+                (Some(alg), Some(enc)) => {
+                    match (KeyAgreementAlg::try_from(alg), ContentEncAlg::try_from(enc)) {
+                        (Ok(alg), Ok(enc)) => self.allows(alg, enc, crv),
+                        _ => false,
+                    }
+                }
+                _ => false,
+            };
+            if !allowed {
+                return Err(Error::ProfileViolation(format!(
+                    "alg {} with enc {} on curve {}",
+                    alg.unwrap_or("none"),
+                    enc.as_deref().unwrap_or("none"),
+                    if crv.is_empty() { "none" } else { crv },
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::{crypto::CryptoAlgorithm, Message, ReceiveOptions};
+
+    #[test]
+    fn profiles_restrict_sealed_and_received_suites() -> Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let message = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"]);
+
+        // XC20PKW key wrapping is not sanctioned by the spec
+        let non_conforming = message
+            .clone()
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        let result = message
+            .clone()
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .with_profile(Profile::DidCommV2)
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]));
+        assert!(matches!(result, Err(Error::ProfileViolation(_))));
+        let result = Message::receive_with_options(
+            &non_conforming,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new().with_profile(Profile::DidCommV2),
+        );
+        assert!(matches!(result, Err(Error::ProfileViolation(_))));
+
+        let authcrypt = message
+            .clone()
+            .as_jwe(&CryptoAlgorithm::A256GCM, Some(bobs_public.to_vec()))
+            .with_profile(Profile::DidCommV2)
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+        Message::receive_with_options(
+            &authcrypt,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new().with_profile(Profile::DidCommV2),
+        )?;
+        let result = Message::receive_with_options(
+            &authcrypt,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new().with_profile(Profile::DidCommV2AnoncryptOnly),
+        );
+        assert!(matches!(result, Err(Error::ProfileViolation(_))));
+
+        let anoncrypt = message
+            .as_jwe(&CryptoAlgorithm::A256GCM, Some(bobs_public.to_vec()))
+            .with_profile(Profile::DidCommV2AnoncryptOnly)
+            .seal_anonymous(Some(vec![Some(bobs_public.to_vec())]))?;
+        let custom = Profile::Custom {
+            suites: vec![(KeyAgreementAlg::EcdhEsA256kw, ContentEncAlg::A256GCM)],
+            curves: vec!["X25519".to_string()],
+        };
+        Message::receive_with_options(
+            &anoncrypt,
+            Some(&bobs_private),
+            None,
+            None,
+            &ReceiveOptions::new().with_profile(custom),
+        )?;
+        assert!(!Profile::DidCommV2.allows(
+            KeyAgreementAlg::Ecdh1puA256kw,
+            ContentEncAlg::A256GCM,
+            "P-256"
+        ));
+        Ok(())
+    }
+}
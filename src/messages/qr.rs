@@ -0,0 +1,92 @@
+use qrcode::{render::svg, Color, EcLevel, QrCode};
+
+use crate::{Error, Message, Result};
+
+/// Maximal length of out-of-band URLs rendered as QR codes.
+/// Longer URLs produce dense codes which many phone cameras fail to scan,
+///     shortened URLs pointing to the invitation should be used instead.
+pub const QR_URL_BUDGET: usize = 1024;
+
+/// Number of light modules around the code, as required by QR code specification.
+const QUIET_ZONE: usize = 4;
+
+/// Out-of-band invitations as QR codes, see [`Message::to_oob_url`].
+impl Message {
+    /// Renders out-of-band URL of this message as SVG QR code.
+    /// `Err` is returned if URL is longer than `QR_URL_BUDGET`.
+    ///
+    /// # Parameters
+    ///
+    /// * `base` - URL the `_oob` query parameter is appended to
+    pub fn to_qr_svg(&self, base: &str) -> Result<String> {
+        Ok(qr_code(&self.to_oob_url(base)?)?
+            .render::<svg::Color>()
+            .min_dimensions(256, 256)
+            .build())
+    }
+
+    /// Renders out-of-band URL of this message as grayscale PNG QR code.
+    /// `Err` is returned if URL is longer than `QR_URL_BUDGET`.
+    ///
+    /// # Parameters
+    ///
+    /// * `base` - URL the `_oob` query parameter is appended to
+    ///
+    /// * `module_size` - width of single module in pixels
+    pub fn to_qr_png(&self, base: &str, module_size: usize) -> Result<Vec<u8>> {
+        let code = qr_code(&self.to_oob_url(base)?)?;
+        let module_size = module_size.max(1);
+        let modules = code.width() + 2 * QUIET_ZONE;
+        let size = modules * module_size;
+        let colors = code.to_colors();
+        let mut pixels = vec![u8::MAX; size * size];
+        for (i, _) in colors.iter().enumerate().filter(|(_, c)| **c == Color::Dark) {
+            let x = (i % code.width() + QUIET_ZONE) * module_size;
+            let y = (i / code.width() + QUIET_ZONE) * module_size;
+            for row in y..y + module_size {
+                pixels[row * size + x..row * size + x + module_size].fill(0);
+            }
+        }
+
+        let mut png = vec![];
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        Ok(png)
+    }
+}
+
+fn qr_code(url: &str) -> Result<QrCode> {
+    if url.len() > QR_URL_BUDGET {
+        return Err(Error::QrCodeTooLarge(url.len(), QR_URL_BUDGET));
+    }
+    QrCode::with_error_correction_level(url, EcLevel::M).map_err(|e| Error::Generic(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invitation_renders_as_qr_code() -> Result<()> {
+        let invitation = Message::new()
+            .from("did:example:alice")
+            .as_out_of_band_invitation(r#"{"goal_code":"connect"}"#, None)?;
+
+        let svg = invitation.to_qr_svg("https://example.com")?;
+        assert!(svg.contains("<svg"));
+        let png = invitation.to_qr_png("https://example.com", 4)?;
+        assert_eq!(&png[1..4], b"PNG");
+
+        let large = Message::new().body(&format!(r#"{{"comment":"{}"}}"#, "a".repeat(1024)))?;
+        assert!(matches!(
+            large.to_qr_svg("https://example.com"),
+            Err(Error::QrCodeTooLarge(_, QR_URL_BUDGET))
+        ));
+        Ok(())
+    }
+}
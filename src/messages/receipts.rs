@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use serde_json::Value;
+
+use crate::{Message, PackMiddleware, ReceiveMiddleware, Result, SenderInfo};
+
+/// `type` of acknowledgement messages.
+pub const ACK_TYPE: &str = "https://didcomm.org/notification/1.0/ack";
+
+/// `type` of problem report messages.
+pub const PROBLEM_REPORT_TYPE: &str = "https://didcomm.org/report-problem/2.0/problem-report";
+
+/// Delivery state of a sent message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryState {
+    /// Message was sent, no answer seen yet
+    Sent,
+    /// Message was acknowledged by an `ack` header or ack message on its thread
+    Acked,
+    /// Problem report was received for the thread of the message
+    Failed,
+    /// Message was neither acknowledged nor failed before its deadline
+    Expired,
+}
+
+/// Delivery bookkeeping of a single sent message, keyed by its `id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// `id` of the sent message
+    pub message_id: String,
+
+    /// thread of the sent message, its `thid` or `id`
+    pub thid: String,
+
+    /// recipients of the sent message
+    pub to: Vec<String>,
+
+    /// current delivery state
+    pub state: DeliveryState,
+
+    /// seconds since Unix Epoch after which unanswered message is expired, if any
+    pub deadline: Option<u64>,
+
+    /// `code` of received problem report, if message failed
+    pub problem_code: Option<String>,
+
+    /// seconds since Unix Epoch of last update
+    pub updated_time: u64,
+}
+
+impl Receipt {
+    /// Constructor of receipt in `Sent` state.
+    ///
+    /// # Parameters
+    ///
+    /// * `message` - sent message
+    pub fn new(message: &Message) -> Self {
+        let header = &message.didcomm_header;
+        Self {
+            message_id: header.id.clone(),
+            thid: header.thid.clone().unwrap_or_else(|| header.id.clone()),
+            to: header.to.iter().filter(|to| !to.is_empty()).cloned().collect(),
+            state: DeliveryState::Sent,
+            deadline: header.expires_time,
+            problem_code: None,
+            updated_time: now(),
+        }
+    }
+
+    fn is_overdue(&self, now: u64) -> bool {
+        self.state == DeliveryState::Sent && self.deadline.is_some_and(|deadline| now > deadline)
+    }
+
+    fn update(&mut self, state: DeliveryState) {
+        self.state = state;
+        self.updated_time = now();
+    }
+}
+
+/// Trait must be implemented for pluggable receipt storage,
+///     see [`ReceiptTracker`] for keeping it up to date.
+pub trait ReceiptStore: Send + Sync {
+    /// Gets receipt of message with given id.
+    fn get(&self, message_id: &str) -> Option<Receipt>;
+
+    /// Inserts or replaces receipt of `receipt.message_id`.
+    fn put(&self, receipt: Receipt);
+
+    /// Gets receipts of messages sent in given thread.
+    fn by_thread(&self, thid: &str) -> Vec<Receipt>;
+
+    /// Gets receipts still in `Sent` state.
+    fn pending(&self) -> Vec<Receipt>;
+}
+
+/// In-memory [`ReceiptStore`], receipts are lost on drop.
+#[derive(Default)]
+pub struct InMemoryReceiptStore {
+    receipts: Mutex<HashMap<String, Receipt>>,
+}
+
+impl InMemoryReceiptStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn receipts(&self) -> std::sync::MutexGuard<'_, HashMap<String, Receipt>> {
+        match self.receipts.lock() {
+            Ok(receipts) => receipts,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl ReceiptStore for InMemoryReceiptStore {
+    fn get(&self, message_id: &str) -> Option<Receipt> {
+        self.receipts().get(message_id).cloned()
+    }
+
+    fn put(&self, receipt: Receipt) {
+        self.receipts().insert(receipt.message_id.clone(), receipt);
+    }
+
+    fn by_thread(&self, thid: &str) -> Vec<Receipt> {
+        self.receipts()
+            .values()
+            .filter(|receipt| receipt.thid == thid)
+            .cloned()
+            .collect()
+    }
+
+    fn pending(&self) -> Vec<Receipt> {
+        self.receipts()
+            .values()
+            .filter(|receipt| receipt.state == DeliveryState::Sent)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Correlates sent messages with received acknowledgements and problem reports.
+/// Added to [`Packer`][crate::Packer] it records every prepared message as `Sent`.
+/// Added to [`ReceivePipeline`][crate::ReceivePipeline] it marks messages
+///     listed in `ack` header of received messages and messages on the thread
+///     of received ack messages as `Acked`, and messages on the thread
+///     a problem report refers to (its `pthid`) as `Failed`.
+/// Messages unanswered past their `expires_time`, or the tracker's timeout,
+///     are reported as `Expired`.
+#[derive(Clone)]
+pub struct ReceiptTracker {
+    store: Arc<dyn ReceiptStore>,
+    timeout: Option<Duration>,
+}
+
+impl ReceiptTracker {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `store` - storage shared between packing and receiving
+    pub fn new(store: Arc<dyn ReceiptStore>) -> Self {
+        Self {
+            store,
+            timeout: None,
+        }
+    }
+
+    /// Setter of deadline for messages without `expires_time` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout` - time span after sending a message is expected to be answered
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Gets delivery state of sent message, `None` if message was not tracked.
+    /// Overdue messages are marked `Expired` on access.
+    ///
+    /// # Parameters
+    ///
+    /// * `message_id` - `id` of sent message
+    pub fn state(&self, message_id: &str) -> Option<DeliveryState> {
+        let mut receipt = self.store.get(message_id)?;
+        if receipt.is_overdue(now()) {
+            receipt.update(DeliveryState::Expired);
+            self.store.put(receipt.clone());
+        }
+        Some(receipt.state)
+    }
+
+    /// Marks all overdue messages `Expired` and returns their receipts.
+    pub fn expire(&self) -> Vec<Receipt> {
+        let now = now();
+        self.store
+            .pending()
+            .into_iter()
+            .filter(|receipt| receipt.is_overdue(now))
+            .map(|mut receipt| {
+                receipt.update(DeliveryState::Expired);
+                self.store.put(receipt.clone());
+                receipt
+            })
+            .collect()
+    }
+
+    fn settle(&self, receipts: Vec<Receipt>, state: DeliveryState, problem_code: Option<&str>) {
+        for mut receipt in receipts
+            .into_iter()
+            .filter(|receipt| receipt.state == DeliveryState::Sent)
+        {
+            receipt.problem_code = problem_code.map(Into::into);
+            receipt.update(state);
+            self.store.put(receipt);
+        }
+    }
+}
+
+impl PackMiddleware for ReceiptTracker {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        let mut receipt = Receipt::new(message);
+        if receipt.deadline.is_none() {
+            receipt.deadline = self.timeout.map(|timeout| now() + timeout.as_secs());
+        }
+        self.store.put(receipt);
+        Ok(())
+    }
+}
+
+impl ReceiveMiddleware for ReceiptTracker {
+    fn handle(&self, message: &mut Message, _sender: &SenderInfo) -> Result<()> {
+        let acked = message
+            .get_header("ack")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter_map(|id| self.store.get(id))
+            .collect();
+        self.settle(acked, DeliveryState::Acked, None);
+
+        let header = &message.didcomm_header;
+        if header.m_type == ACK_TYPE {
+            if let Some(thid) = &header.thid {
+                self.settle(self.store.by_thread(thid), DeliveryState::Acked, None);
+            }
+        } else if header.m_type == PROBLEM_REPORT_TYPE {
+            if let Some(thid) = header.pthid.as_ref().or(header.thid.as_ref()) {
+                let code = message.body_at("/code").and_then(Value::as_str);
+                self.settle(self.store.by_thread(thid), DeliveryState::Failed, code);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Packer, ReceivePipeline};
+
+    #[test]
+    fn correlates_acks_and_problem_reports() -> Result<()> {
+        let store = Arc::new(InMemoryReceiptStore::new());
+        let tracker = Arc::new(ReceiptTracker::new(store));
+        let packer = Packer::new().with(tracker.clone());
+        let pipeline = ReceivePipeline::new().with(tracker.clone());
+        let bob = SenderInfo::default();
+
+        let acked = packer.prepare(Message::new().to(&["did:example:bob"]))?;
+        let failed = packer.prepare(Message::new().to(&["did:example:bob"]))?;
+        let expired = packer.prepare(Message::new().timed(Some(1)))?;
+        assert_eq!(tracker.state(acked.get_id()), Some(DeliveryState::Sent));
+
+        let mut ack = Message::new().set_header("ack", [acked.get_id()])?;
+        pipeline.run(&mut ack, &bob)?;
+        let mut report = Message::new()
+            .m_type(PROBLEM_REPORT_TYPE)
+            .pthid(failed.get_id())
+            .merge_body(json!({ "code": "e.p.xfer.cant-use-endpoint" }));
+        pipeline.run(&mut report, &bob)?;
+
+        assert_eq!(tracker.state(acked.get_id()), Some(DeliveryState::Acked));
+        assert_eq!(tracker.state(failed.get_id()), Some(DeliveryState::Failed));
+        assert_eq!(tracker.expire()[0].message_id, expired.get_id());
+        assert_eq!(tracker.state(expired.get_id()), Some(DeliveryState::Expired));
+        assert_eq!(tracker.state("unknown"), None);
+        Ok(())
+    }
+}
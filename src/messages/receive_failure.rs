@@ -0,0 +1,150 @@
+use crate::{EnvelopeKind, Error, Jwe, JwmHeader, Jws};
+#[cfg(feature = "raw-crypto")]
+use crate::{Message, ReceiveOptions};
+
+/// Failure of receiving a message with everything that could be parsed
+///     from its outermost envelope, e.g. to log for which `kid` no key was found.
+/// Envelope is not decrypted or verified, so values are diagnostics only.
+#[derive(Debug, thiserror::Error)]
+#[error("receiving {envelope:?} envelope failed: {error}")]
+pub struct ReceiveFailure {
+    /// error the receive failed with
+    #[source]
+    pub error: Error,
+
+    /// kind of received envelope
+    pub envelope: EnvelopeKind,
+
+    /// protected header of JWE or of first JWS signature
+    pub protected: Option<JwmHeader>,
+
+    /// `kid` of all JWE recipients
+    pub recipient_kids: Vec<String>,
+
+    /// sender key id of JWE
+    pub skid: Option<String>,
+}
+
+impl ReceiveFailure {
+    /// Gathers diagnostics of failed receive from raw payload.
+    ///
+    /// # Parameters
+    ///
+    /// * `incoming` - raw, serialized message as received by transport
+    ///
+    /// * `error` - error receiving `incoming` failed with
+    pub fn inspect(incoming: &str, error: Error) -> Self {
+        let mut failure = ReceiveFailure {
+            error,
+            envelope: EnvelopeKind::detect(incoming),
+            protected: None,
+            recipient_kids: vec![],
+            skid: None,
+        };
+        match failure.envelope {
+            EnvelopeKind::Jwe | EnvelopeKind::Forward => {
+                if let Ok(jwe) = serde_json::from_str::<Jwe>(incoming) {
+                    failure.protected = jwe.protected.clone();
+                    failure.recipient_kids = jwe
+                        .get_recipients()
+                        .iter()
+                        .filter_map(|recipient| recipient.header.kid.clone())
+                        .collect();
+                    failure.skid = jwe.get_skid();
+                }
+            }
+            EnvelopeKind::Jws => {
+                if let Ok(jws) = serde_json::from_str::<Jws>(incoming) {
+                    failure.protected = jws
+                        .get_signatures()
+                        .first()
+                        .and_then(|signature| signature.protected.clone());
+                }
+            }
+            EnvelopeKind::Plain | EnvelopeKind::Unknown => {}
+        }
+        failure
+    }
+}
+
+impl From<Box<ReceiveFailure>> for Error {
+    fn from(failure: Box<ReceiveFailure>) -> Self {
+        failure.error
+    }
+}
+
+#[cfg(feature = "raw-crypto")]
+impl Message {
+    /// Same as [`Message::receive_with_options`] but fails with [`ReceiveFailure`]
+    ///     holding diagnostics of the received envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `incoming` - serialized message as `Message`/`Jws`/`Jws`
+    ///
+    /// * `encryption_recipient_private_key` - recipients private key, used to decrypt `kek` in JWE
+    ///
+    /// * `encryption_sender_public_key` - senders public key, used to decrypt `kek` in JWE
+    ///
+    /// * `signing_sender_public_key` - senders public key, the JWS envelope was signed with
+    ///
+    /// * `options` - additional checks for unpacked message
+    pub fn try_receive(
+        incoming: &str,
+        encryption_recipient_private_key: Option<&[u8]>,
+        encryption_sender_public_key: Option<Vec<u8>>,
+        signing_sender_public_key: Option<&[u8]>,
+        options: &ReceiveOptions,
+    ) -> Result<Self, Box<ReceiveFailure>> {
+        Self::receive_with_options(
+            incoming,
+            encryption_recipient_private_key,
+            encryption_sender_public_key,
+            signing_sender_public_key,
+            options,
+        )
+        .map_err(|error| Box::new(ReceiveFailure::inspect(incoming, error)))
+    }
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    use super::*;
+    use crate::crypto::CryptoAlgorithm;
+
+    #[test]
+    fn reports_envelope_of_failed_receive() -> crate::Result<()> {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_public,
+            mediators_private,
+            ..
+        } = get_keypair_set();
+        let sealed = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+
+        let failure = Message::try_receive(
+            &sealed,
+            Some(&mediators_private),
+            Some(alice_public.to_vec()),
+            None,
+            &ReceiveOptions::new(),
+        )
+        .unwrap_err();
+        assert_eq!(failure.envelope, EnvelopeKind::Jwe);
+        assert_eq!(failure.recipient_kids, ["did:example:bob"]);
+        assert_eq!(failure.skid.as_deref(), Some("did:example:alice"));
+        assert!(failure.protected.is_some());
+
+        let failure = ReceiveFailure::inspect("not json", Error::JweParseError);
+        assert_eq!(failure.envelope, EnvelopeKind::Unknown);
+        assert!(failure.recipient_kids.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+#[cfg(feature = "raw-crypto")]
+use crate::{DidResolver, Profile};
+use crate::{
+    ReceiveMiddleware,
+    ReceivePipeline,
+    ReplayGuard,
+    SenderCheck,
+    SenderPolicy,
+};
+
+/// Additional checks applied by
+/// [`Message::receive_with_options`][crate::Message::receive_with_options()]
+/// after envelope has been unpacked.
+///
+/// Default value performs no additional checks and behaves same as
+/// [`Message::receive`][crate::Message::receive()].
+#[derive(Clone, Default)]
+pub struct ReceiveOptions {
+    pub(crate) pipeline: ReceivePipeline,
+    pub(crate) replay_guard: Option<Arc<dyn ReplayGuard>>,
+    pub(crate) unwrap_forwards: bool,
+    pub(crate) forward_keys: Vec<Vec<u8>>,
+    pub(crate) v1_aliases: bool,
+    pub(crate) validate_types: bool,
+    pub(crate) allowed_algorithms: Option<Vec<String>>,
+    pub(crate) max_decompressed_size: Option<usize>,
+    #[cfg(feature = "raw-crypto")]
+    pub(crate) profile: Option<Profile>,
+    #[cfg(feature = "raw-crypto")]
+    pub(crate) did_resolver: Option<Arc<dyn DidResolver>>,
+}
+
+impl ReceiveOptions {
+    /// Constructor with no additional checks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects messages with ids already seen by `guard`
+    /// with `Error::ReplayedMessage`.
+    /// Ids are checked and recorded only after all middleware accepted the message,
+    ///     so rejected or spoofed messages don't use up ids of legitimate ones.
+    /// Guard is kept when pipeline is replaced with `with_pipeline`.
+    ///
+    /// # Parameters
+    ///
+    /// * `guard` - replay guard, shared between receive calls
+    pub fn with_replay_guard(mut self, guard: Arc<dyn ReplayGuard>) -> Self {
+        self.replay_guard = Some(guard);
+        self
+    }
+
+    /// Rejects messages not accepted by `policy` with `Error::SenderRejected`.
+    /// Shortcut for adding `SenderCheck` middleware.
+    ///
+    /// # Parameters
+    ///
+    /// * `policy` - sender policy, e.g. `SenderAllowList` or a closure
+    pub fn with_sender_policy(self, policy: Arc<dyn SenderPolicy>) -> Self {
+        self.with_middleware(Arc::new(SenderCheck::new(policy)))
+    }
+
+    /// Appends middleware to the end of receive pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `middleware` - middleware run after previously added ones
+    pub fn with_middleware(mut self, middleware: Arc<dyn ReceiveMiddleware>) -> Self {
+        self.pipeline = self.pipeline.with(middleware);
+        self
+    }
+
+    /// Keeps unwrapping received forward messages until final message is reached,
+    ///     instead of returning the outermost forward.
+    /// Meant for recipients controlling their mediators' keys, e.g. in tests
+    ///     and single process agents.
+    /// Each encrypted envelope is decrypted with recipient key first and given keys after.
+    /// `to` of unwrapped forwards is reported as `SenderInfo::forward_hops`.
+    /// Batch forwards carrying more than one message are returned as they are,
+    ///     to be split with [`Mediated::split`][crate::Mediated::split()].
+    ///
+    /// # Parameters
+    ///
+    /// * `mediator_private_keys` - keys of mediators forwards were encrypted for
+    pub fn with_forward_unwrapping(mut self, mediator_private_keys: &[&[u8]]) -> Self {
+        self.unwrap_forwards = true;
+        self.forward_keys = mediator_private_keys.iter().map(|k| k.to_vec()).collect();
+        self
+    }
+
+    /// Lenient profile for partners not yet migrated to DIDComm v2:
+    ///     `@id`, `@type` and `~thread.thid` of received plaintext messages
+    ///     are mapped onto `id`, `type` and `thid`.
+    /// Content fields of v1 messages are moved into `body`.
+    pub fn with_v1_aliases(mut self) -> Self {
+        self.v1_aliases = true;
+        self
+    }
+
+    /// Rejects envelopes whose JOSE `typ` does not match their content,
+    ///     e.g. JWE with `application/didcomm-signed+json`, with `Error::EnvelopeTypeMismatch`,
+    ///     and messages whose `type` is not a well-formed PIURI with `Error::InvalidPiuri`.
+    pub fn with_type_validation(mut self) -> Self {
+        self.validate_types = true;
+        self
+    }
+
+    /// Rejects JWE envelopes, including nested ones, with `alg`/`enc`/curve
+    ///     combinations not allowed by `profile` with `Error::ProfileViolation`.
+    ///
+    /// # Parameters
+    ///
+    /// * `profile` - conformance profile, e.g. `Profile::DidCommV2`
+    #[cfg(feature = "raw-crypto")]
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Resolves DIDs of `skid` and signature `kid` to check the keys which decrypted
+    ///     or verified the envelope belong to them, before their DID is reported as
+    ///     `SenderInfo::authenticated`.
+    /// Without resolver only `did:key` senders can be authenticated.
+    ///
+    /// # Parameters
+    ///
+    /// * `resolver` - resolver of sender DIDs, e.g. the one of an [`Agent`][crate::Agent]
+    #[cfg(feature = "raw-crypto")]
+    pub fn with_did_resolver(mut self, resolver: Arc<dyn DidResolver>) -> Self {
+        self.did_resolver = Some(resolver);
+        self
+    }
+
+    /// Rejects envelopes using `alg` or `enc` values not in `algorithms`
+    ///     with `Error::AlgorithmNotAllowed`, e.g. A256CBC or ES256K where policy forbids them.
+    /// Applies to key agreement and content encryption of JWE, including nested ones,
+    ///     and to signatures of JWS.
+    ///
+    /// # Parameters
+    ///
+    /// * `algorithms` - allowed names, e.g. `&["ECDH-1PU+A256KW", "XC20P", "EdDSA"]`
+    pub fn with_allowed_algorithms(mut self, algorithms: &[&str]) -> Self {
+        self.allowed_algorithms = Some(algorithms.iter().map(|alg| alg.to_string()).collect());
+        self
+    }
+
+    /// Limits size of plaintext decompressed from JWE with `zip` header,
+    ///     larger ones are rejected with `Error::DecompressedSizeExceeded`.
+    /// Defaults to 4 MiB.
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - maximum size of decompressed plaintext
+    pub fn with_max_decompressed_size(mut self, bytes: usize) -> Self {
+        self.max_decompressed_size = Some(bytes);
+        self
+    }
+
+    /// Replaces receive pipeline, dropping previously added middleware.
+    ///
+    /// # Parameters
+    ///
+    /// * `pipeline` - preconfigured pipeline, can be shared between options
+    pub fn with_pipeline(mut self, pipeline: ReceivePipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+}
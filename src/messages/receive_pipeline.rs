@@ -0,0 +1,225 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Error, Message, ReplayGuard, Result, SenderInfo, SenderPolicy};
+
+/// Trait must be implemented for steps of [`ReceivePipeline`].
+/// Middleware runs after envelope has been unpacked and before `Message` is returned
+///     to the application. It may reject the message by returning `Err`
+///     or annotate it, e.g. by adding headers.
+///
+/// Implemented for closures `Fn(&mut Message, &SenderInfo) -> Result<()>`.
+pub trait ReceiveMiddleware: Send + Sync {
+    /// Inspects or modifies unpacked message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - unpacked message, as modified by previous middleware
+    ///
+    /// * `sender` - information about sender gathered while unpacking envelopes
+    fn handle(&self, message: &mut Message, sender: &SenderInfo) -> Result<()>;
+}
+
+impl<F> ReceiveMiddleware for F
+where
+    F: Fn(&mut Message, &SenderInfo) -> Result<()> + Send + Sync,
+{
+    fn handle(&self, message: &mut Message, sender: &SenderInfo) -> Result<()> {
+        self(message, sender)
+    }
+}
+
+/// Ordered list of [`ReceiveMiddleware`], run by
+/// [`Message::receive_with_options`][crate::Message::receive_with_options()]
+/// via [`ReceiveOptions::with_pipeline`][crate::ReceiveOptions::with_pipeline()].
+/// First middleware returning `Err` stops the pipeline and rejects the message.
+#[derive(Clone, Default)]
+pub struct ReceivePipeline {
+    middleware: Vec<Arc<dyn ReceiveMiddleware>>,
+}
+
+impl ReceivePipeline {
+    /// Constructor of empty pipeline, which accepts every message unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends middleware to the end of the pipeline.
+    ///
+    /// # Parameters
+    ///
+    /// * `middleware` - middleware run after previously added ones
+    pub fn with(mut self, middleware: Arc<dyn ReceiveMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Number of middleware in the pipeline.
+    pub fn len(&self) -> usize {
+        self.middleware.len()
+    }
+
+    /// Returns `true` if pipeline has no middleware.
+    pub fn is_empty(&self) -> bool {
+        self.middleware.is_empty()
+    }
+
+    /// Runs all middleware in order on unpacked message.
+    #[cfg(feature = "raw-crypto")]
+    pub(crate) fn run(&self, message: &mut Message, sender: &SenderInfo) -> Result<()> {
+        for middleware in &self.middleware {
+            let result = middleware.handle(message, sender);
+            #[cfg(feature = "tracing")]
+            if let Err(e) = &result {
+                tracing::debug!(error = %e, "message rejected by receive middleware");
+            }
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// Middleware rejecting messages with ids already seen by a [`ReplayGuard`]
+/// with `Error::ReplayedMessage`.
+/// Id is recorded as seen as soon as the check runs, so it has to be the last
+///     middleware which may reject messages; otherwise a rejected message uses up
+///     its id and a legitimate one with the same id is refused as replay.
+/// [`ReceiveOptions::with_replay_guard`][crate::ReceiveOptions::with_replay_guard()]
+///     takes care of that by checking after the whole pipeline.
+pub struct ReplayCheck {
+    guard: Arc<dyn ReplayGuard>,
+}
+
+impl ReplayCheck {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `guard` - replay guard, shared between receive calls
+    pub fn new(guard: Arc<dyn ReplayGuard>) -> Self {
+        Self { guard }
+    }
+}
+
+impl ReceiveMiddleware for ReplayCheck {
+    fn handle(&self, message: &mut Message, _: &SenderInfo) -> Result<()> {
+        check_replay(self.guard.as_ref(), message)
+    }
+}
+
+/// Records id of message with `guard`, failing if it has been seen before.
+pub(crate) fn check_replay(guard: &dyn ReplayGuard, message: &Message) -> Result<()> {
+    let header = &message.didcomm_header;
+    if guard.seen(&header.id, header.expires_time) {
+        return Err(Error::ReplayedMessage(header.id.clone()));
+    }
+    Ok(())
+}
+
+/// Middleware rejecting messages not accepted by a [`SenderPolicy`]
+/// with `Error::SenderRejected`.
+pub struct SenderCheck {
+    policy: Arc<dyn SenderPolicy>,
+}
+
+impl SenderCheck {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `policy` - sender policy, e.g. `SenderAllowList` or a closure
+    pub fn new(policy: Arc<dyn SenderPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl ReceiveMiddleware for SenderCheck {
+    fn handle(&self, _: &mut Message, sender: &SenderInfo) -> Result<()> {
+        if !self.policy.accepts(sender) {
+            return Err(Error::SenderRejected(sender.from.clone().unwrap_or_default()));
+        }
+        Ok(())
+    }
+}
+
+/// Middleware rejecting messages with `expires_time` in the past
+/// with `Error::MessageExpired`. Messages without `expires_time` are accepted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExpiryCheck {
+    leeway: Duration,
+}
+
+impl ExpiryCheck {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `leeway` - tolerated clock skew between sender and receiver
+    pub fn new(leeway: Duration) -> Self {
+        Self { leeway }
+    }
+}
+
+impl ReceiveMiddleware for ExpiryCheck {
+    fn handle(&self, message: &mut Message, _: &SenderInfo) -> Result<()> {
+        let header = &message.didcomm_header;
+        if let Some(expires) = header.expires_time {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs();
+            if expires.saturating_add(self.leeway.as_secs()) < now {
+                return Err(Error::MessageExpired(header.id.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use super::*;
+    use crate::SenderAllowList;
+
+    #[test]
+    fn middleware_runs_in_order_and_can_reject() {
+        let pipeline = ReceivePipeline::new()
+            .with(Arc::new(|message: &mut Message, _: &SenderInfo| {
+                *message = message.clone().add_header_field("checked".into(), "1".into());
+                Ok(())
+            }))
+            .with(Arc::new(SenderCheck::new(Arc::new(SenderAllowList::new(&[
+                "did:example:alice",
+            ])))));
+        let mut message = Message::new();
+        let alice = SenderInfo {
+            from: Some("did:example:alice".into()),
+            authenticated: Some("did:example:alice".into()),
+            ..Default::default()
+        };
+
+        assert!(pipeline.run(&mut message, &alice).is_ok());
+        assert_eq!(message.get_header("checked"), Some(&"1".into()));
+        assert!(matches!(
+            pipeline.run(&mut Message::new(), &SenderInfo::default()),
+            Err(Error::SenderRejected(_))
+        ));
+    }
+
+    #[test]
+    fn expiry_check_rejects_expired_messages() {
+        let check = ExpiryCheck::new(Duration::from_secs(5));
+        let sender = SenderInfo::default();
+        let mut expired = Message::new();
+        expired.didcomm_header.expires_time = Some(1);
+        let mut valid = Message::new().expires_in(Duration::from_secs(60));
+
+        assert!(matches!(
+            check.handle(&mut expired, &sender),
+            Err(Error::MessageExpired(_))
+        ));
+        assert!(check.handle(&mut valid, &sender).is_ok());
+        assert!(check.handle(&mut Message::new(), &sender).is_ok());
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// Trait must be implemented for pluggable replay protection.
+/// Consulted by [`Message::receive_with_options`][crate::Message::receive_with_options()]
+/// once message is unpacked.
+pub trait ReplayGuard: Send + Sync {
+    /// Records message `id` and returns `true` if it has been seen before.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - `id` header of received message
+    ///
+    /// * `expires` - `expires_time` header of received message; `id` is kept at least until then
+    fn seen(&self, id: &str, expires: Option<u64>) -> bool;
+}
+
+/// In-memory [`ReplayGuard`] which remembers message ids for a configurable window.
+/// Once `capacity` is exceeded least recently seen ids are dropped first.
+/// Ids are kept ordered by expiry and by last sighting, so each message costs
+///     logarithmic time in number of remembered ids.
+pub struct InMemoryReplayGuard {
+    capacity: usize,
+    window: Duration,
+    inner: Mutex<SeenIds>,
+}
+
+#[derive(Default)]
+struct SeenIds {
+    /// message id -> unix time in seconds until which id is remembered and number of last sighting
+    entries: HashMap<String, (u64, u64)>,
+    /// time and number of sighting of ids, ordered from soonest to latest expiry
+    by_expiry: BTreeSet<(u64, u64)>,
+    /// number of sighting -> id, ordered from least to most recently seen
+    by_recency: BTreeMap<u64, String>,
+    /// number of next sighting
+    next_sighting: u64,
+}
+
+impl SeenIds {
+    /// Forgets ids remembered until before `now`, soonest expiring first.
+    fn forget_expired(&mut self, now: u64) {
+        while let Some(&(until, sighting)) = self.by_expiry.first() {
+            if until >= now {
+                break;
+            }
+            self.by_expiry.remove(&(until, sighting));
+            if let Some(id) = self.by_recency.remove(&sighting) {
+                self.entries.remove(&id);
+            }
+        }
+    }
+
+    /// Remembers `id` as most recently seen until at least `until`.
+    /// Returns `true` if `id` was remembered already.
+    fn remember(&mut self, id: &str, until: u64) -> bool {
+        let sighting = self.next_sighting;
+        self.next_sighting += 1;
+        let previous = self.entries.get(id).copied();
+        let until = match previous {
+            Some((previous_until, previous_sighting)) => {
+                self.by_expiry.remove(&(previous_until, previous_sighting));
+                self.by_recency.remove(&previous_sighting);
+                std::cmp::max(previous_until, until)
+            }
+            None => until,
+        };
+        self.entries.insert(id.to_string(), (until, sighting));
+        self.by_expiry.insert((until, sighting));
+        self.by_recency.insert(sighting, id.to_string());
+        previous.is_some()
+    }
+
+    /// Forgets least recently seen id.
+    fn forget_least_recent(&mut self) {
+        if let Some((sighting, id)) = self.by_recency.pop_first() {
+            if let Some((until, _)) = self.entries.remove(&id) {
+                self.by_expiry.remove(&(until, sighting));
+            }
+        }
+    }
+}
+
+impl InMemoryReplayGuard {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - maximum number of ids to remember, at least one is remembered
+    ///
+    /// * `window` - minimal time span in which duplicates are rejected
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            window,
+            inner: Mutex::new(SeenIds::default()),
+        }
+    }
+
+    /// Number of currently remembered ids.
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .map(|inner| inner.entries.len())
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if no ids are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn seen_at(&self, id: &str, expires: Option<u64>, now: u64) -> bool {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        // forget everything outside of window
+        inner.forget_expired(now);
+
+        let until = std::cmp::max(now + self.window.as_secs(), expires.unwrap_or_default());
+        let seen = inner.remember(id, until);
+        while inner.entries.len() > self.capacity {
+            inner.forget_least_recent();
+        }
+        seen
+    }
+}
+
+impl Default for InMemoryReplayGuard {
+    /// Remembers up to 10 000 ids for 24 hours.
+    fn default() -> Self {
+        Self::new(10_000, Duration::from_secs(24 * 60 * 60))
+    }
+}
+
+impl ReplayGuard for InMemoryReplayGuard {
+    fn seen(&self, id: &str, expires: Option<u64>) -> bool {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or_default();
+        self.seen_at(id, expires, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_duplicate_within_window() {
+        let guard = InMemoryReplayGuard::new(10, Duration::from_secs(60));
+        assert!(!guard.seen_at("a", None, 1000));
+        assert!(guard.seen_at("a", None, 1030));
+        assert!(!guard.seen_at("b", None, 1030));
+    }
+
+    #[test]
+    fn forgets_ids_after_window_and_expiry() {
+        let guard = InMemoryReplayGuard::new(10, Duration::from_secs(60));
+        assert!(!guard.seen_at("a", None, 1000));
+        assert!(!guard.seen_at("b", Some(5000), 1000));
+        assert!(!guard.seen_at("a", None, 1100));
+        assert!(guard.seen_at("b", None, 1100));
+    }
+
+    #[test]
+    fn evicts_least_recently_seen_over_capacity() {
+        let guard = InMemoryReplayGuard::new(2, Duration::from_secs(60));
+        assert!(!guard.seen_at("a", None, 1000));
+        assert!(!guard.seen_at("b", None, 1000));
+        assert!(guard.seen_at("a", None, 1000));
+        assert!(!guard.seen_at("c", None, 1000));
+        assert_eq!(guard.len(), 2);
+        assert!(!guard.seen_at("b", None, 1000));
+    }
+
+    #[test]
+    fn remembers_last_id_with_zero_capacity() {
+        let guard = InMemoryReplayGuard::new(0, Duration::from_secs(60));
+        assert!(!guard.seen_at("a", None, 1000));
+        assert!(guard.seen_at("a", None, 1000));
+        assert!(!guard.seen_at("b", None, 1000));
+        assert_eq!(guard.len(), 1);
+    }
+
+    #[test]
+    fn keeps_later_expiry_of_duplicates() {
+        let guard = InMemoryReplayGuard::new(10, Duration::from_secs(60));
+        assert!(!guard.seen_at("a", Some(5000), 1000));
+        assert!(guard.seen_at("a", None, 1030));
+        assert!(guard.seen_at("a", None, 4000));
+        assert!(!guard.seen_at("a", None, 5100));
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+/// Sender related data of an unpacked message, handed to [`SenderPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SenderInfo {
+    /// `from` header of plaintext message, as claimed by sender
+    pub from: Option<String>,
+
+    /// DID of sender proven by authcrypt `skid` or `kid` of verified signature,
+    ///     `None` if sender was not authenticated;
+    ///     receive fails if it does not match `from`
+    pub authenticated: Option<String>,
+
+    /// `skid` of JWE envelope, `None` if message was not encrypted or is anonymous
+    pub skid: Option<String>,
+
+    /// `true` if message was wrapped into JWS and its signature was verified
+    pub signed: bool,
+
+    /// `true` if sender of message is not authenticated,
+    ///     e.g. anoncrypt or unsigned plaintext messages
+    pub anonymous: bool,
+
+    /// `to` of forward messages unwrapped on receive, outermost first,
+    ///     see [`ReceiveOptions::with_forward_unwrapping`][crate::ReceiveOptions::with_forward_unwrapping()]
+    pub forward_hops: Vec<String>,
+}
+
+/// Trait must be implemented for pluggable sender policies.
+/// Consulted by [`Message::receive_with_options`][crate::Message::receive_with_options()]
+/// once message is unpacked - rejected messages are never returned to the application.
+///
+/// Implemented for closures `Fn(&SenderInfo) -> bool`.
+pub trait SenderPolicy: Send + Sync {
+    /// Returns `true` if message from given sender should be accepted.
+    fn accepts(&self, sender: &SenderInfo) -> bool;
+}
+
+impl<F> SenderPolicy for F
+where
+    F: Fn(&SenderInfo) -> bool + Send + Sync,
+{
+    fn accepts(&self, sender: &SenderInfo) -> bool {
+        self(sender)
+    }
+}
+
+/// Accepts only messages with authenticated sender DID contained in the list.
+/// Anonymous messages are rejected, whatever their `from` claims.
+#[derive(Debug, Clone, Default)]
+pub struct SenderAllowList(pub HashSet<String>);
+
+impl SenderAllowList {
+    /// Creates allow list from given DIDs.
+    pub fn new(dids: &[&str]) -> Self {
+        Self(dids.iter().map(|did| did.to_string()).collect())
+    }
+}
+
+impl SenderPolicy for SenderAllowList {
+    fn accepts(&self, sender: &SenderInfo) -> bool {
+        sender
+            .authenticated
+            .as_ref()
+            .map(|did| self.0.contains(did))
+            .unwrap_or(false)
+    }
+}
+
+/// Rejects messages with authenticated sender DID contained in the list,
+///     or claiming such DID in `from` without being authenticated,
+///     e.g. signed with key ids which are no DID URLs.
+/// Other anonymous messages are accepted; combine with a policy requiring
+///     authentication to keep listed senders from hiding behind anonymity.
+#[derive(Debug, Clone, Default)]
+pub struct SenderDenyList(pub HashSet<String>);
+
+impl SenderDenyList {
+    /// Creates deny list from given DIDs.
+    pub fn new(dids: &[&str]) -> Self {
+        Self(dids.iter().map(|did| did.to_string()).collect())
+    }
+}
+
+impl SenderPolicy for SenderDenyList {
+    fn accepts(&self, sender: &SenderInfo) -> bool {
+        [&sender.authenticated, &sender.from]
+            .iter()
+            .filter_map(|did| did.as_ref())
+            .all(|did| !self.0.contains(did))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender(authenticated: Option<&str>) -> SenderInfo {
+        SenderInfo {
+            from: authenticated.map(String::from),
+            authenticated: authenticated.map(String::from),
+            anonymous: authenticated.is_none(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn allow_list_accepts_listed_senders_only() {
+        let policy = SenderAllowList::new(&["did:example:alice"]);
+        assert!(policy.accepts(&sender(Some("did:example:alice"))));
+        assert!(!policy.accepts(&sender(Some("did:example:mallory"))));
+        assert!(!policy.accepts(&sender(None)));
+        let spoofed = SenderInfo {
+            from: Some("did:example:alice".into()),
+            anonymous: true,
+            ..Default::default()
+        };
+        assert!(!policy.accepts(&spoofed));
+    }
+
+    #[test]
+    fn deny_list_rejects_listed_senders_only() {
+        let policy = SenderDenyList::new(&["did:example:mallory"]);
+        assert!(policy.accepts(&sender(Some("did:example:alice"))));
+        assert!(!policy.accepts(&sender(Some("did:example:mallory"))));
+        assert!(policy.accepts(&sender(None)));
+        let unauthenticated = SenderInfo {
+            from: Some("did:example:mallory".into()),
+            anonymous: true,
+            ..Default::default()
+        };
+        assert!(!policy.accepts(&unauthenticated));
+    }
+
+    #[test]
+    fn closures_can_be_used_as_policy() {
+        let policy = |sender: &SenderInfo| sender.signed;
+        assert!(!policy.accepts(&sender(Some("did:example:alice"))));
+    }
+}
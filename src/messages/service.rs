@@ -0,0 +1,217 @@
+use std::convert::TryFrom;
+
+use serde_json::{json, Value};
+
+use crate::{Error, Result};
+
+/// `type` of DIDComm v2 service entries of DID documents.
+pub const DIDCOMM_MESSAGING_SERVICE_TYPE: &str = "DIDCommMessaging";
+
+/// `accept` entry of services supporting DIDComm v2 messages.
+pub const DIDCOMM_V2_PROFILE: &str = "didcomm/v2";
+
+/// [`DIDCommMessaging`](https://identity.foundation/didcomm-messaging/spec/#did-document-service-endpoint)
+///     service endpoint of a DID document or out-of-band invitation.
+/// Parsed from both DIDComm v2 entries, with `serviceEndpoint` object,
+///     and legacy entries with `serviceEndpoint` string and `recipientKeys`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DidCommMessagingService {
+    /// `id` of the service entry, if given
+    pub id: Option<String>,
+
+    /// URI of the endpoint, e.g. `https://` or `ws://` URL or DID of a mediator
+    pub uri: String,
+
+    /// media types and profiles accepted by the endpoint, empty if not announced
+    pub accept: Vec<String>,
+
+    /// key ids of mediators messages have to be forwarded through, outermost last
+    pub routing_keys: Vec<String>,
+
+    /// keys of the recipient, only given by legacy service entries
+    pub recipient_keys: Vec<String>,
+}
+
+impl DidCommMessagingService {
+    /// Constructor of service without routing.
+    ///
+    /// # Parameters
+    ///
+    /// * `uri` - URI of the endpoint
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Setter of routing keys.
+    pub fn with_routing_keys(mut self, routing_keys: &[&str]) -> Self {
+        self.routing_keys = routing_keys.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    /// Setter of accepted media types and profiles.
+    pub fn with_accept(mut self, accept: &[&str]) -> Self {
+        self.accept = accept.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    /// Checks that `uri` is an HTTP(S) or WebSocket URL or a DID
+    ///     and that routing keys are DID URLs.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(Error::InvalidService(reason));
+        let scheme = self.uri.split(':').next().unwrap_or_default();
+        if !matches!(scheme, "http" | "https" | "ws" | "wss" | "did") || !self.uri.contains(':') {
+            return invalid(format!("unsupported endpoint URI `{}`", self.uri));
+        }
+        if let Some(key) = self.routing_keys.iter().find(|key| !key.starts_with("did:")) {
+            return invalid(format!("routing key `{}` is not a DID URL", key));
+        }
+        Ok(())
+    }
+
+    /// Checks if endpoint is an HTTP(S) URL.
+    pub fn is_http(&self) -> bool {
+        self.uri.starts_with("http://") || self.uri.starts_with("https://")
+    }
+
+    /// Checks if endpoint is a WebSocket URL.
+    pub fn is_ws(&self) -> bool {
+        self.uri.starts_with("ws://") || self.uri.starts_with("wss://")
+    }
+
+    /// Checks if endpoint accepts given media type or profile.
+    /// Endpoints not announcing `accept` are assumed to accept anything.
+    pub fn accepts(&self, media_type: &str) -> bool {
+        self.accept.is_empty() || self.accept.iter().any(|a| a == media_type)
+    }
+
+    /// Serializes service as DIDComm v2 service entry.
+    pub fn to_value(&self) -> Value {
+        let mut endpoint = json!({ "uri": self.uri });
+        if !self.accept.is_empty() {
+            endpoint["accept"] = json!(self.accept);
+        }
+        if !self.routing_keys.is_empty() {
+            endpoint["routingKeys"] = json!(self.routing_keys);
+        }
+        let mut service = json!({
+            "type": DIDCOMM_MESSAGING_SERVICE_TYPE,
+            "serviceEndpoint": endpoint,
+        });
+        if let Some(id) = &self.id {
+            service["id"] = json!(id);
+        }
+        service
+    }
+}
+
+impl TryFrom<&Value> for DidCommMessagingService {
+    type Error = Error;
+
+    /// Parses and validates service entry.
+    /// For `serviceEndpoint` arrays first entry is used.
+    fn try_from(service: &Value) -> Result<Self> {
+        let endpoint = match &service["serviceEndpoint"] {
+            Value::Array(endpoints) => endpoints.first().unwrap_or(&Value::Null),
+            endpoint => endpoint,
+        };
+        let (uri, details) = match endpoint {
+            Value::String(uri) => (Some(uri.as_str()), service),
+            Value::Object(details) => (details.get("uri").and_then(Value::as_str), endpoint),
+            _ => (None, service),
+        };
+        let uri = uri.ok_or_else(|| Error::InvalidService("service has no endpoint URI".into()))?;
+        let strings = |field: &str| -> Vec<String> {
+            details
+                .get(field)
+                .or_else(|| service.get(field))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(Into::into)
+                .collect()
+        };
+        let parsed = Self {
+            id: service.get("id").and_then(Value::as_str).map(Into::into),
+            uri: uri.to_string(),
+            accept: strings("accept"),
+            routing_keys: strings("routingKeys"),
+            recipient_keys: strings("recipientKeys"),
+        };
+        parsed.validate()?;
+        Ok(parsed)
+    }
+}
+
+/// Trait must be implemented for transports used by [`Agent`][crate::Agent].
+/// Implemented by `HttpTransport` with `http` feature and for closures
+///     `Fn(&DidCommMessagingService, &str) -> Result<Option<String>>`.
+pub trait Transport: Send + Sync {
+    /// Delivers packed message to service.
+    /// Returns message routed back in response, if any.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidService` if service is not supported by this transport.
+    ///
+    /// # Parameters
+    ///
+    /// * `service` - service of the recipient or its mediator
+    ///
+    /// * `envelope` - packed message
+    fn deliver(&self, service: &DidCommMessagingService, envelope: &str) -> Result<Option<String>>;
+}
+
+impl<F> Transport for F
+where
+    F: Fn(&DidCommMessagingService, &str) -> Result<Option<String>> + Send + Sync,
+{
+    fn deliver(&self, service: &DidCommMessagingService, envelope: &str) -> Result<Option<String>> {
+        self(service, envelope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_v2_and_legacy_services() -> Result<()> {
+        let v2 = json!({
+            "id": "did:example:bob#didcomm-1",
+            "type": "DIDCommMessaging",
+            "serviceEndpoint": {
+                "uri": "https://bob.example.com",
+                "accept": ["didcomm/v2"],
+                "routingKeys": ["did:example:mediator#key-1"],
+            },
+        });
+        let service = DidCommMessagingService::try_from(&v2)?;
+        assert!(service.is_http() && service.accepts(DIDCOMM_V2_PROFILE));
+        assert_eq!(service.routing_keys, ["did:example:mediator#key-1"]);
+        assert_eq!(service.to_value(), v2);
+
+        let legacy = json!({
+            "type": "did-communication",
+            "serviceEndpoint": "wss://bob.example.com",
+            "recipientKeys": ["did:key:z6Mk"],
+        });
+        let service = DidCommMessagingService::try_from(&legacy)?;
+        assert!(service.is_ws());
+        assert_eq!(service.recipient_keys, ["did:key:z6Mk"]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_services() {
+        let no_uri = json!({ "serviceEndpoint": {} });
+        assert!(DidCommMessagingService::try_from(&no_uri).is_err());
+        let bad_scheme = json!({ "serviceEndpoint": "ftp://example.com" });
+        assert!(DidCommMessagingService::try_from(&bad_scheme).is_err());
+        let bad_key = DidCommMessagingService::new("https://example.com").with_routing_keys(&["key"]);
+        assert!(matches!(bad_key.validate(), Err(Error::InvalidService(_))));
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{Error, Message, PackMiddleware, ReceiveMiddleware, Result, SenderInfo};
+
+/// Whether thread still accepts messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThreadState {
+    Open,
+    Closed,
+}
+
+/// Bookkeeping of a single thread, keyed by `thid`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadRecord {
+    /// `thid` of the thread, `id` of its first message
+    pub thid: String,
+
+    /// `pthid` of the thread, if it was started off a parent thread
+    pub pthid: Option<String>,
+
+    /// DIDs seen as `from` or `to` of messages in the thread
+    pub participants: Vec<String>,
+
+    /// index of last message we sent to the thread, `None` if we did not send any
+    pub sender_order: Option<usize>,
+
+    /// highest `sender_order` received per sender DID
+    pub received_orders: HashMap<String, usize>,
+
+    /// open or closed
+    pub state: ThreadState,
+
+    /// `id` of last message sent or received in the thread
+    pub last_message_id: String,
+
+    /// seconds since Unix Epoch of last update
+    pub updated_time: u64,
+}
+
+impl ThreadRecord {
+    /// Constructor of open thread without participants.
+    ///
+    /// # Parameters
+    ///
+    /// * `thid` - thread id
+    pub fn new(thid: &str) -> Self {
+        Self {
+            thid: thid.into(),
+            pthid: None,
+            participants: vec![],
+            sender_order: None,
+            received_orders: HashMap::new(),
+            state: ThreadState::Open,
+            last_message_id: String::default(),
+            updated_time: 0,
+        }
+    }
+
+    fn add_participant(&mut self, did: &str) {
+        if !did.is_empty() && !self.participants.iter().any(|p| p == did) {
+            self.participants.push(did.into());
+        }
+    }
+}
+
+/// Trait must be implemented for pluggable thread bookkeeping storage,
+///     see [`ThreadTracker`] for keeping it up to date.
+pub trait ThreadStore: Send + Sync {
+    /// Gets record of thread with given `thid`.
+    fn get(&self, thid: &str) -> Option<ThreadRecord>;
+
+    /// Inserts or replaces record of thread with `record.thid`.
+    fn put(&self, record: ThreadRecord);
+
+    /// Gets records of threads with given parent thread id.
+    fn children(&self, pthid: &str) -> Vec<ThreadRecord>;
+
+    /// Marks thread as closed, further messages in it are rejected by [`ThreadTracker`].
+    /// Returns `false` if thread is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `thid` - id of the thread to close
+    fn close(&self, thid: &str) -> bool {
+        match self.get(thid) {
+            Some(mut record) => {
+                record.state = ThreadState::Closed;
+                self.put(record);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// In-memory [`ThreadStore`], records are lost on drop.
+#[derive(Default)]
+pub struct InMemoryThreadStore {
+    threads: Mutex<HashMap<String, ThreadRecord>>,
+}
+
+impl InMemoryThreadStore {
+    /// Constructor of empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of tracked threads.
+    pub fn len(&self) -> usize {
+        self.threads
+            .lock()
+            .map(|threads| threads.len())
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if no threads are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn threads(&self) -> std::sync::MutexGuard<'_, HashMap<String, ThreadRecord>> {
+        match self.threads.lock() {
+            Ok(threads) => threads,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl ThreadStore for InMemoryThreadStore {
+    fn get(&self, thid: &str) -> Option<ThreadRecord> {
+        self.threads().get(thid).cloned()
+    }
+
+    fn put(&self, record: ThreadRecord) {
+        self.threads().insert(record.thid.clone(), record);
+    }
+
+    fn children(&self, pthid: &str) -> Vec<ThreadRecord> {
+        self.threads()
+            .values()
+            .filter(|record| record.pthid.as_deref() == Some(pthid))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Keeps [`ThreadStore`] up to date with sent and received messages.
+/// Added to [`Packer`][crate::Packer] it counts `sender_order` of sent messages
+///     and writes it into their `~thread` decorator, if present.
+/// Added to [`ReceivePipeline`][crate::ReceivePipeline] it records received `sender_order`s.
+/// Messages in closed threads are rejected with `Error::ThreadClosed` in both directions.
+#[derive(Clone)]
+pub struct ThreadTracker {
+    store: Arc<dyn ThreadStore>,
+}
+
+impl ThreadTracker {
+    /// Constructor.
+    ///
+    /// # Parameters
+    ///
+    /// * `store` - storage shared between packing and receiving
+    pub fn new(store: Arc<dyn ThreadStore>) -> Self {
+        Self { store }
+    }
+
+    /// Loads or creates record of message thread and applies shared updates.
+    fn record_for(&self, message: &Message) -> Result<ThreadRecord> {
+        let header = &message.didcomm_header;
+        let thid = header.thid.as_deref().unwrap_or(&header.id);
+        let mut record = self
+            .store
+            .get(thid)
+            .unwrap_or_else(|| ThreadRecord::new(thid));
+        if record.state == ThreadState::Closed {
+            return Err(Error::ThreadClosed(record.thid));
+        }
+        if record.pthid.is_none() {
+            record.pthid = header.pthid.clone();
+        }
+        if let Some(from) = &header.from {
+            record.add_participant(from);
+        }
+        for to in &header.to {
+            record.add_participant(to);
+        }
+        record.last_message_id = header.id.clone();
+        record.updated_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        Ok(record)
+    }
+}
+
+impl PackMiddleware for ThreadTracker {
+    fn handle(&self, message: &mut Message) -> Result<()> {
+        let mut record = self.record_for(message)?;
+        let order = record.sender_order.map_or(0, |order| order + 1);
+        record.sender_order = Some(order);
+        if let Some(thread) = &mut message.didcomm_header.thread {
+            thread.sender_order = Some(order);
+            if !record.received_orders.is_empty() {
+                thread.received_orders = Some(record.received_orders.clone());
+            }
+        }
+        self.store.put(record);
+        Ok(())
+    }
+}
+
+impl ReceiveMiddleware for ThreadTracker {
+    fn handle(&self, message: &mut Message, sender: &SenderInfo) -> Result<()> {
+        let mut record = self.record_for(message)?;
+        let order = message
+            .didcomm_header
+            .thread
+            .as_ref()
+            .and_then(|thread| thread.sender_order);
+        if let (Some(from), Some(order)) = (&sender.from, order) {
+            let highest = record.received_orders.entry(from.clone()).or_insert(order);
+            *highest = std::cmp::max(*highest, order);
+        }
+        self.store.put(record);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "raw-crypto"))]
+mod tests {
+    use super::*;
+    use crate::{Packer, ReceivePipeline, Thread};
+
+    #[test]
+    fn tracks_sent_and_received_messages() -> Result<()> {
+        let store = Arc::new(InMemoryThreadStore::new());
+        let tracker = Arc::new(ThreadTracker::new(store.clone()));
+        let packer = Packer::new().with(tracker.clone());
+        let pipeline = ReceivePipeline::new().with(tracker);
+
+        let mut request = Message::new().from("did:example:alice").to(&["did:example:bob"]);
+        let thid = request.get_id().to_string();
+        request.didcomm_header.thread = Some(Thread::implicit(&thid));
+        let request = packer.prepare(request)?;
+        let second = packer.prepare(Message::new().thid(&thid))?;
+
+        let mut response = Message::reply(&request);
+        response.didcomm_header.thread = Some(Thread {
+            sender_order: Some(3),
+            ..Thread::implicit_reply(&thid)
+        });
+        let bob = SenderInfo {
+            from: Some("did:example:bob".into()),
+            ..Default::default()
+        };
+        pipeline.run(&mut response, &bob)?;
+
+        let record = store.get(&thid).expect("thread not tracked");
+        assert_eq!(record.sender_order, Some(1));
+        assert_eq!(record.received_orders.get("did:example:bob"), Some(&3));
+        assert_eq!(record.participants, ["did:example:alice", "did:example:bob"]);
+        assert_eq!(record.last_message_id, response.get_id());
+        assert_eq!(second.get_thid(), Some(thid.as_str()));
+
+        assert!(store.close(&thid));
+        assert!(matches!(
+            packer.prepare(Message::new().thid(&thid)),
+            Err(Error::ThreadClosed(_))
+        ));
+        Ok(())
+    }
+}
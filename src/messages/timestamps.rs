@@ -0,0 +1,197 @@
+#[cfg(any(feature = "chrono", feature = "time"))]
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::Message;
+
+/// Timestamp headers as date time types instead of raw seconds since Unix Epoch.
+impl Message {
+    /// Getter of `created_time` header as `DateTime<Utc>`.
+    #[cfg(feature = "chrono")]
+    pub fn get_created_datetime(&self) -> Option<DateTime<Utc>> {
+        self.didcomm_header.created_time.and_then(to_datetime)
+    }
+
+    /// Getter of `expires_time` header as `DateTime<Utc>`.
+    #[cfg(feature = "chrono")]
+    pub fn get_expires_datetime(&self) -> Option<DateTime<Utc>> {
+        self.didcomm_header.expires_time.and_then(to_datetime)
+    }
+
+    /// Sets `expires_time` header to given point in time.
+    /// Times before Unix Epoch are clamped to it.
+    ///
+    /// # Parameters
+    ///
+    /// * `expires` - time after which message is considered to be invalid
+    #[cfg(feature = "chrono")]
+    pub fn expires_at(mut self, expires: DateTime<Utc>) -> Self {
+        self.didcomm_header.expires_time = Some(u64::try_from(expires.timestamp()).unwrap_or(0));
+        self
+    }
+
+    /// Sets `expires_time` header relative to `created_time`.
+    /// Sets `created_time` to now if it was not set before.
+    ///
+    /// # Parameters
+    ///
+    /// * `ttl` - time span for which message stays valid
+    pub fn expires_in(mut self, ttl: Duration) -> Self {
+        let created = match self.didcomm_header.created_time {
+            Some(created) => created,
+            None => {
+                let now = now();
+                self.didcomm_header.created_time = Some(now);
+                now
+            }
+        };
+        self.didcomm_header.expires_time = Some(created.saturating_add(ttl.as_secs()));
+        self
+    }
+
+    /// Getter of `sent_time` header, seconds since Unix Epoch.
+    pub fn get_sent_time(&self) -> Option<u64> {
+        self.didcomm_header.sent_time
+    }
+
+    /// Sets `sent_time` header.
+    ///
+    /// # Parameters
+    ///
+    /// * `sent` - seconds since Unix Epoch when message is handed over to transport
+    pub fn sent_time(mut self, sent: u64) -> Self {
+        self.didcomm_header.sent_time = Some(sent);
+        self
+    }
+
+    /// Sets `sent_time` header to now.
+    pub fn sent_now(self) -> Self {
+        self.sent_time(now())
+    }
+
+    /// Getter of `delay_milli` header.
+    pub fn get_delay_milli(&self) -> Option<u64> {
+        self.didcomm_header.delay_milli
+    }
+
+    /// Sets `delay_milli` header, e.g. by mediator which queued the message.
+    ///
+    /// # Parameters
+    ///
+    /// * `delay` - milliseconds message was held back before delivery
+    pub fn delay_milli(mut self, delay: u64) -> Self {
+        self.didcomm_header.delay_milli = Some(delay);
+        self
+    }
+
+    /// Time between `created_time` and `sent_time`, i.e. queueing delay at sender side.
+    /// `None` if any of them is not set or `sent_time` is before `created_time`.
+    pub fn queueing_delay(&self) -> Option<Duration> {
+        let created = self.didcomm_header.created_time?;
+        let sent = self.didcomm_header.sent_time?;
+        sent.checked_sub(created).map(Duration::from_secs)
+    }
+
+    /// Time between `created_time` and `received_at`, e.g. to measure delivery latency
+    ///     of a received message.
+    /// `None` if `created_time` is not set or lies after `received_at`.
+    ///
+    /// # Parameters
+    ///
+    /// * `received_at` - receive clock reading
+    pub fn latency_at(&self, received_at: SystemTime) -> Option<Duration> {
+        let created = Duration::from_secs(self.didcomm_header.created_time?);
+        received_at
+            .duration_since(SystemTime::UNIX_EPOCH + created)
+            .ok()
+    }
+
+    /// Same as `latency_at` with current time as receive clock.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency_at(SystemTime::now())
+    }
+
+    /// Getter of `created_time` header as `time::OffsetDateTime` in UTC.
+    #[cfg(feature = "time")]
+    pub fn get_created_offset_datetime(&self) -> Option<time::OffsetDateTime> {
+        self.didcomm_header
+            .created_time
+            .and_then(to_offset_datetime)
+    }
+
+    /// Getter of `expires_time` header as `time::OffsetDateTime` in UTC.
+    #[cfg(feature = "time")]
+    pub fn get_expires_offset_datetime(&self) -> Option<time::OffsetDateTime> {
+        self.didcomm_header
+            .expires_time
+            .and_then(to_offset_datetime)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "chrono")]
+fn to_datetime(seconds: u64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(i64::try_from(seconds).ok()?, 0).single()
+}
+
+#[cfg(feature = "time")]
+fn to_offset_datetime(seconds: u64) -> Option<time::OffsetDateTime> {
+    time::OffsetDateTime::from_unix_timestamp(i64::try_from(seconds).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn timestamps_convert_to_datetime() {
+        let expires = Utc.timestamp_opt(2_000_000_000, 0).unwrap();
+        let message = Message::new().timed(None).expires_at(expires);
+        assert_eq!(message.get_expires_time(), Some(2_000_000_000));
+        assert_eq!(message.get_expires_datetime(), Some(expires));
+        assert!(message.get_created_datetime().is_some());
+        assert_eq!(Message::new().get_created_datetime(), None);
+    }
+
+    #[test]
+    fn expires_in_is_relative_to_created_time() {
+        let message = Message::new().expires_in(Duration::from_secs(60));
+        let created = message.get_created_time().expect("created_time not set");
+        assert_eq!(message.get_expires_time(), Some(created + 60));
+    }
+
+    #[test]
+    fn latency_is_measured_from_created_time() {
+        let mut message = Message::new().sent_time(1_000_002).delay_milli(1500);
+        message.didcomm_header.created_time = Some(1_000_000);
+        let received_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_005);
+
+        assert_eq!(message.latency_at(received_at), Some(Duration::from_secs(5)));
+        assert_eq!(message.queueing_delay(), Some(Duration::from_secs(2)));
+        assert_eq!(message.get_delay_milli(), Some(1500));
+        assert_eq!(Message::new().latency(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "time")]
+    fn timestamps_convert_to_offset_datetime() {
+        let mut message = Message::new();
+        message.didcomm_header.expires_time = Some(2_000_000_000);
+        assert_eq!(
+            message
+                .get_expires_offset_datetime()
+                .map(|t| t.unix_timestamp()),
+            Some(2_000_000_000)
+        );
+    }
+}
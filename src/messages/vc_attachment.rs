@@ -0,0 +1,155 @@
+use serde_json::Value;
+
+use crate::{Attachment, AttachmentBuilder, AttachmentDataBuilder, Error, Result};
+
+/// `format` of attachments holding JWT encoded verifiable credentials.
+pub const VC_JWT_FORMAT: &str = "jwt_vc";
+
+/// `media_type` of attachments holding JWT encoded verifiable credentials.
+pub const VC_JWT_MEDIA_TYPE: &str = "application/jwt";
+
+/// `format` of attachments holding JSON-LD verifiable credentials,
+///     as used by Issue Credential and Present Proof protocols.
+pub const VC_JSONLD_FORMAT: &str = "aries/ld-proof-vc@v1.0";
+
+/// `media_type` of attachments holding JSON-LD verifiable credentials.
+pub const VC_JSONLD_MEDIA_TYPE: &str = "application/ld+json";
+
+const VC_CONTEXTS: [&str; 2] = [
+    "https://www.w3.org/2018/credentials/v1",
+    "https://www.w3.org/ns/credentials/v2",
+];
+
+/// Verifiable credential attachments.
+/// Structural checks only, proofs and signatures are not verified.
+impl Attachment {
+    /// Creates attachment holding JWT encoded verifiable credential as base64 data.
+    ///
+    /// # Parameters
+    ///
+    /// * `jwt` - credential in JWT compact serialization
+    pub fn from_vc_jwt(jwt: &str) -> Self {
+        AttachmentBuilder::new(false)
+            .with_media_type(VC_JWT_MEDIA_TYPE)
+            .with_format(VC_JWT_FORMAT)
+            .with_data(AttachmentDataBuilder::new().with_raw_payload(jwt))
+            .finalize()
+    }
+
+    /// Creates attachment holding JSON-LD verifiable credential as json data.
+    ///
+    /// # Parameters
+    ///
+    /// * `value` - credential as JSON-LD document
+    pub fn from_vc_jsonld(value: &Value) -> Result<Self> {
+        Ok(AttachmentBuilder::new(false)
+            .with_media_type(VC_JSONLD_MEDIA_TYPE)
+            .with_format(VC_JSONLD_FORMAT)
+            .with_data(AttachmentDataBuilder::new().with_json(&serde_json::to_string(value)?))
+            .finalize())
+    }
+
+    /// Extracts JWT encoded verifiable credential.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if content is not a JWT with a `vc` claim.
+    pub fn vc_jwt(&self) -> Result<String> {
+        let jwt = String::from_utf8(self.decoded_content()?)?;
+        let jwt = jwt.trim();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        if parts.len() != 3 {
+            return Err(Error::AttachmentError(
+                "credential is not a compact JWT".into(),
+            ));
+        }
+        let claims: Value = serde_json::from_slice(&base64_url::decode(parts[1])?)?;
+        if !claims["vc"].is_object() {
+            return Err(Error::AttachmentError(
+                "credential JWT has no vc claim".into(),
+            ));
+        }
+        Ok(jwt.to_string())
+    }
+
+    /// Extracts JSON-LD verifiable credential.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AttachmentError` if content misses credentials `@context`,
+    ///     `VerifiableCredential` type, `issuer` or `credentialSubject`.
+    pub fn vc_jsonld(&self) -> Result<Value> {
+        let credential: Value = self.content_as()?;
+        let invalid = |reason: &str| Err(Error::AttachmentError(reason.into()));
+        let context = match &credential["@context"] {
+            Value::Array(contexts) => contexts.first().and_then(Value::as_str),
+            other => other.as_str(),
+        };
+        if !context.is_some_and(|context| VC_CONTEXTS.contains(&context)) {
+            return invalid("credential has no credentials @context");
+        }
+        let is_credential = match &credential["type"] {
+            Value::Array(types) => types.iter().any(|t| t == "VerifiableCredential"),
+            other => other == "VerifiableCredential",
+        };
+        if !is_credential {
+            return invalid("credential type is not VerifiableCredential");
+        }
+        if credential["issuer"].is_null() || credential["credentialSubject"].is_null() {
+            return invalid("credential has no issuer or credentialSubject");
+        }
+        Ok(credential)
+    }
+}
+
+impl From<Attachment> for AttachmentBuilder {
+    fn from(attachment: Attachment) -> Self {
+        AttachmentBuilder::from_attachment(attachment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Message;
+
+    fn credential() -> Value {
+        json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential", "UniversityDegreeCredential"],
+            "issuer": "did:example:issuer",
+            "issuanceDate": "2010-01-01T19:23:24Z",
+            "credentialSubject": { "id": "did:example:alice" },
+        })
+    }
+
+    #[test]
+    fn can_attach_and_extract_jsonld_credential() -> Result<()> {
+        let mut message = Message::new();
+        message.append_attachment(Attachment::from_vc_jsonld(&credential())?.into());
+
+        let attachment = message
+            .attachments_with_format(VC_JSONLD_FORMAT)
+            .next()
+            .expect("no credential attachment");
+        assert_eq!(attachment.vc_jsonld()?, credential());
+        let mut not_credential = credential();
+        not_credential["type"] = json!("Diploma");
+        assert!(Attachment::from_vc_jsonld(&not_credential)?.vc_jsonld().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn can_attach_and_extract_jwt_credential() -> Result<()> {
+        let claims = base64_url::encode(&json!({ "vc": credential() }).to_string());
+        let jwt = format!("eyJhbGciOiJFZERTQSJ9.{}.c2lnbmF0dXJl", claims);
+        let attachment = Attachment::from_vc_jwt(&jwt);
+
+        assert_eq!(attachment.media_type.as_deref(), Some(VC_JWT_MEDIA_TYPE));
+        assert_eq!(attachment.vc_jwt()?, jwt);
+        assert!(Attachment::from_vc_jwt("not.a-jwt").vc_jwt().is_err());
+        Ok(())
+    }
+}
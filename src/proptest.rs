@@ -0,0 +1,374 @@
+//! [proptest](https://docs.rs/proptest) strategies for messages, their headers and attachments,
+//!     and [`round_trip`] helper to property-test pack/unpack integrations.
+//!
+//! Available with `proptest` feature only.
+//!
+//! Generated messages are plain, sent from `testkit::ALICE_DID` to `testkit::BOB_DID`
+//!     and can be packed into any envelope without further preparation.
+//!
+//! ```rust,ignore
+//! use didcomm_rs::proptest::{message, round_trip, RoundTripOptions};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn messages_survive_my_transport(message in message()) {
+//!         round_trip(&message, &RoundTripOptions::new().signed(true))?;
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use proptest::{collection, option, prelude::*, test_runner::TestCaseError};
+use serde_json::{Map, Value};
+
+use crate::{
+    crypto::{CryptoAlgorithm, SignatureAlgorithm, Signer},
+    testkit::{get_keypair_set, ALICE_DID, BOB_DID},
+    Attachment,
+    AttachmentData,
+    DidCommHeader,
+    JwmHeader,
+    Message,
+    MessageType,
+    ReceiveOptions,
+};
+
+/// How [`round_trip`] packs and receives messages.
+#[derive(Clone)]
+pub struct RoundTripOptions {
+    /// content encryption of JWE envelope, `None` to skip encryption
+    pub encryption: Option<CryptoAlgorithm>,
+
+    /// signs message with Ed25519 key of Alice before encryption, if any
+    pub signed: bool,
+
+    /// options message is received with
+    pub receive_options: ReceiveOptions,
+}
+
+impl Default for RoundTripOptions {
+    fn default() -> Self {
+        Self {
+            encryption: Some(CryptoAlgorithm::XC20P),
+            signed: false,
+            receive_options: ReceiveOptions::default(),
+        }
+    }
+}
+
+impl RoundTripOptions {
+    /// Constructor of options packing messages as XC20P encrypted, unsigned JWE.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets content encryption of envelope.
+    ///
+    /// # Parameters
+    ///
+    /// * `encryption` - algorithm, `None` to send messages plain or only signed
+    pub fn encryption(mut self, encryption: Option<CryptoAlgorithm>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Sets whether messages are signed before encryption.
+    ///
+    /// # Parameters
+    ///
+    /// * `signed` - `true` to sign messages with Ed25519 key of Alice
+    pub fn signed(mut self, signed: bool) -> Self {
+        self.signed = signed;
+        self
+    }
+
+    /// Sets options messages are received with, e.g. to exercise own middleware.
+    ///
+    /// # Parameters
+    ///
+    /// * `receive_options` - options passed to `Message::receive_with_options`
+    pub fn receive_options(mut self, receive_options: ReceiveOptions) -> Self {
+        self.receive_options = receive_options;
+        self
+    }
+}
+
+impl std::fmt::Debug for RoundTripOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoundTripOptions")
+            .field("encryption", &self.encryption)
+            .field("signed", &self.signed)
+            .finish()
+    }
+}
+
+/// Packs message as configured in `options`, with keys of `testkit`, and receives it again
+///     as Bob. Returns received message.
+///
+/// # Errors
+///
+/// `TestCaseError::Fail` if message can't be packed or received, or if received
+///     message differs from sent one in its DIDComm headers, body or attachments.
+///
+/// # Parameters
+///
+/// * `message` - plain message, e.g. generated by [`message`]
+///
+/// * `options` - envelope to pack message into
+pub fn round_trip(message: &Message, options: &RoundTripOptions) -> Result<Message, TestCaseError> {
+    let keys = get_keypair_set();
+    let packed = match (options.encryption, options.signed) {
+        (None, false) => message.clone().as_raw_json(),
+        (None, true) => message.clone().as_jws(&SignatureAlgorithm::EdDsa).sign(
+            SignatureAlgorithm::EdDsa.signer(),
+            &keys.alice_signing_private,
+        ),
+        (Some(alg), false) => message
+            .clone()
+            .as_jwe(&alg, Some(keys.bobs_public.to_vec()))
+            .seal(
+                keys.alice_private,
+                Some(vec![Some(keys.bobs_public.to_vec())]),
+            ),
+        (Some(alg), true) => message
+            .clone()
+            .as_jwe(&alg, Some(keys.bobs_public.to_vec()))
+            .seal_signed(
+                &keys.alice_private,
+                Some(vec![Some(keys.bobs_public.to_vec())]),
+                SignatureAlgorithm::EdDsa,
+                &keys.alice_signing_private,
+            ),
+    }
+    .map_err(|e| TestCaseError::fail(format!("packing failed; {}", e)))?;
+    let received = Message::receive_with_options(
+        &packed,
+        Some(&keys.bobs_private),
+        Some(keys.alice_public.to_vec()),
+        Some(&keys.alice_signing_public),
+        &options.receive_options,
+    )
+    .map_err(|e| TestCaseError::fail(format!("receiving failed; {}\n{}", e, packed)))?;
+    if received.didcomm_header != message.didcomm_header
+        || received.body != message.body
+        || received.attachments != message.attachments
+    {
+        return Err(TestCaseError::fail(format!(
+            "received message differs from sent one\n sent: {}\nreceived: {}",
+            message, received
+        )));
+    }
+    Ok(received)
+}
+
+/// DIDs of `did:example` method.
+pub fn did() -> impl Strategy<Value = String> {
+    "[a-z0-9]{1,16}".prop_map(|id| format!("did:example:{}", id))
+}
+
+/// Message type URIs of didcomm.org protocols.
+pub fn message_type_uri() -> impl Strategy<Value = String> {
+    ("[a-z][a-z-]{0,15}", 1..3u8, "[a-z][a-z-]{0,15}").prop_map(|(protocol, major, name)| {
+        format!("https://didcomm.org/{}/{}.0/{}", protocol, major, name)
+    })
+}
+
+/// JSON values without floats, which may not survive serialization unchanged.
+pub fn json_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(3, 32, 4, |inner| {
+        prop_oneof![
+            collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+            collection::btree_map("[a-z_]{1,8}", inner, 0..4)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// Message bodies, always JSON objects.
+pub fn body() -> impl Strategy<Value = Value> {
+    collection::btree_map("[a-z_]{1,12}", json_value(), 0..6)
+        .prop_map(|map| Value::Object(map.into_iter().collect::<Map<_, _>>()))
+}
+
+/// `typ` of messages.
+pub fn message_type() -> impl Strategy<Value = MessageType> {
+    prop_oneof![
+        Just(MessageType::DidCommRaw),
+        Just(MessageType::DidCommJws),
+        Just(MessageType::DidCommJwe),
+        Just(MessageType::DidCommForward),
+        Just(MessageType::DidCommInvitation),
+    ]
+}
+
+/// JOSE headers, as found on envelopes.
+pub fn jwm_header() -> impl Strategy<Value = JwmHeader> {
+    (
+        message_type(),
+        option::of(prop_oneof![
+            Just("XC20P"),
+            Just("A256GCM"),
+            Just("A256CBC-HS512")
+        ]),
+        option::of(did().prop_map(|did| format!("{}#key-1", did))),
+        option::of(did().prop_map(|did| format!("{}#key-1", did))),
+        option::of(prop_oneof![
+            Just("ECDH-1PU+A256KW"),
+            Just("ECDH-ES+A256KW"),
+            Just("EdDSA")
+        ]),
+        option::of(Just("JWM")),
+    )
+        .prop_map(|(typ, enc, kid, skid, alg, cty)| JwmHeader {
+            typ,
+            enc: enc.map(Into::into),
+            kid,
+            skid,
+            alg: alg.map(Into::into),
+            cty: cty.map(Into::into),
+            ..Default::default()
+        })
+}
+
+/// DIDComm headers of plain messages from `testkit::ALICE_DID` to `testkit::BOB_DID`,
+///     with random thread, type, times and custom headers.
+pub fn didcomm_header() -> impl Strategy<Value = DidCommHeader> {
+    (
+        "[a-z0-9-]{1,36}",
+        option::of("[a-z0-9-]{1,36}"),
+        option::of("[a-z0-9-]{1,36}"),
+        message_type_uri(),
+        option::of(0..4_102_444_800u64),
+        option::of(4_102_444_800..8_204_889_600u64),
+        collection::btree_map("x_[a-z_]{1,8}", json_value(), 0..3),
+    )
+        .prop_map(
+            |(id, thid, pthid, m_type, created_time, expires_time, other)| {
+                let mut header = DidCommHeader::new();
+                header.id = id;
+                header.thid = thid;
+                header.pthid = pthid;
+                header.m_type = m_type;
+                header.to = vec![BOB_DID.into()];
+                header.from = Some(ALICE_DID.into());
+                header.created_time = created_time;
+                header.expires_time = expires_time;
+                header.other = other.into_iter().collect::<BTreeMap<_, _>>();
+                header
+            },
+        )
+}
+
+/// Data of attachments, either inline base64 or JSON content or links with hash.
+pub fn attachment_data() -> impl Strategy<Value = AttachmentData> {
+    prop_oneof![
+        collection::vec(any::<u8>(), 0..64).prop_map(|content| AttachmentData {
+            base64: Some(base64_url::encode(&content)),
+            ..Default::default()
+        }),
+        json_value().prop_map(|json| AttachmentData {
+            json: Some(json.to_string()),
+            ..Default::default()
+        }),
+        (
+            collection::vec("https://example.com/[a-z0-9]{1,16}", 1..3),
+            "[a-f0-9]{64}"
+        )
+            .prop_map(|(links, hash)| AttachmentData {
+                links,
+                hash: Some(hash),
+                ..Default::default()
+            }),
+    ]
+}
+
+/// Attachments with random metadata.
+pub fn attachment() -> impl Strategy<Value = Attachment> {
+    (
+        option::of("[a-z0-9-]{1,36}"),
+        option::of(any::<String>()),
+        option::of("[a-z0-9_-]{1,16}\\.[a-z]{1,4}"),
+        option::of(prop_oneof![
+            Just("application/json"),
+            Just("image/png"),
+            Just("text/plain")
+        ]),
+        option::of(any::<usize>()),
+        attachment_data(),
+    )
+        .prop_map(
+            |(id, description, filename, media_type, byte_count, data)| Attachment {
+                id,
+                description,
+                filename,
+                media_type: media_type.map(Into::into),
+                format: None,
+                lastmod_time: None,
+                byte_count,
+                data,
+            },
+        )
+}
+
+/// Plain messages with random DIDComm headers, body and up to three attachments.
+pub fn message() -> impl Strategy<Value = Message> {
+    (
+        didcomm_header(),
+        body(),
+        collection::vec(attachment(), 0..3),
+    )
+        .prop_map(|(didcomm_header, body, attachments)| Message {
+            didcomm_header,
+            body,
+            attachments,
+            ..Message::new()
+        })
+}
+
+/// Options covering every envelope [`round_trip`] can pack into,
+///     with content encryption algorithms `seal` implements.
+pub fn round_trip_options() -> impl Strategy<Value = RoundTripOptions> {
+    (
+        option::of(prop_oneof![
+            Just(CryptoAlgorithm::XC20P),
+            Just(CryptoAlgorithm::A256GCM),
+        ]),
+        any::<bool>(),
+    )
+        .prop_map(|(encryption, signed)| {
+            RoundTripOptions::new()
+                .encryption(encryption)
+                .signed(signed)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn generated_messages_survive_round_trip(
+            message in message(),
+            options in round_trip_options(),
+        ) {
+            round_trip(&message, &options)?;
+        }
+
+        #[test]
+        fn generated_headers_survive_serialization(header in jwm_header()) {
+            let serialized = serde_json::to_string(&header).unwrap();
+            prop_assert_eq!(serde_json::from_str::<JwmHeader>(&serialized).unwrap(), header);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! [JSON Schemas](https://json-schema.org/) of serialized messages and envelopes.
+//!
+//! Available with `json-schema` feature only.
+//!
+//! Lets services not written in Rust validate payloads they exchange with agents
+//! using this crate. Schemas describe the JSON serialization, e.g. `protected`
+//! headers are base64url encoded strings.
+
+use schemars::{schema::RootSchema, schema_for};
+
+use crate::{Attachment, Jwe, Jws, Message};
+
+/// Schema of plain (raw) DIDComm [`Message`].
+pub fn message_schema() -> RootSchema {
+    schema_for!(Message)
+}
+
+/// Schema of single [`Attachment`] of a message.
+pub fn attachment_schema() -> RootSchema {
+    schema_for!(Attachment)
+}
+
+/// Schema of [`Jwe`] envelope, general and flattened JSON serialization.
+pub fn jwe_schema() -> RootSchema {
+    schema_for!(Jwe)
+}
+
+/// Schema of [`Jws`] envelope, general and flattened JSON serialization.
+pub fn jws_schema() -> RootSchema {
+    schema_for!(Jws)
+}
+
+/// All schemas by name of the described type, e.g. to write them into files.
+pub fn all_schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("Message", message_schema()),
+        ("Attachment", attachment_schema()),
+        ("Jwe", jwe_schema()),
+        ("Jws", jws_schema()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_describe_serialized_fields() -> serde_json::Result<()> {
+        let message = serde_json::to_value(message_schema())?;
+        let properties = &message["properties"];
+        for field in ["id", "type", "body", "attachments"] {
+            assert!(properties.get(field).is_some(), "`{}` missing", field);
+        }
+        let jwe = serde_json::to_value(jwe_schema())?;
+        assert!(jwe["properties"].get("ciphertext").is_some());
+        assert!(jwe["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"ciphertext".into()));
+        assert_eq!(all_schemas().len(), 4);
+        Ok(())
+    }
+}
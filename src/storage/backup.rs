@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, time::SystemTime};
+
+use super::{
+    Storage,
+    StorageRecord,
+    CONNECTIONS_NAMESPACE,
+    ENCRYPTED_MESSAGES_NAMESPACE,
+    KEYS_NAMESPACE,
+    MEDIATIONS_NAMESPACE,
+    MESSAGES_NAMESPACE,
+    STORE_KEYS_NAMESPACE,
+    THREADS_NAMESPACE,
+};
+use crate::{
+    crypto::{passphrase::PassphraseKdf, CryptoAlgorithm, Cypher},
+    Error,
+    Result,
+};
+
+/// Version of state backups written by [`export_state`].
+pub const STATE_BACKUP_VERSION: u32 = 1;
+
+/// Namespaces of messaging subsystems included in state backups.
+pub const STATE_NAMESPACES: [&str; 7] = [
+    KEYS_NAMESPACE,
+    CONNECTIONS_NAMESPACE,
+    MEDIATIONS_NAMESPACE,
+    THREADS_NAMESPACE,
+    MESSAGES_NAMESPACE,
+    STORE_KEYS_NAMESPACE,
+    ENCRYPTED_MESSAGES_NAMESPACE,
+];
+
+/// Unencrypted part of backup file, authenticated as additional data.
+#[derive(Serialize, Deserialize)]
+struct BackupHeader {
+    version: u32,
+    kdf: PassphraseKdf,
+    enc: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    #[serde(flatten)]
+    header: BackupHeader,
+    iv: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupContent {
+    created_time: u64,
+    namespaces: BTreeMap<String, Vec<StorageRecord>>,
+}
+
+/// Exports keys, connections, mediation records, threads and received messages
+///     of `storage` into a single backup file, encrypted with key derived from passphrase.
+/// Backups can be restored with [`import_state`], e.g. when migrating devices.
+///
+/// # Errors
+///
+/// `Error::StorageError` if records can't be read.
+///
+/// # Parameters
+///
+/// * `storage` - storage used by [`RecordStore`][super::RecordStore]
+///
+/// * `passphrase` - secret backup is encrypted with
+pub fn export_state(storage: &dyn Storage, passphrase: &[u8]) -> Result<Vec<u8>> {
+    export_state_with_kdf(storage, passphrase, PassphraseKdf::generate())
+}
+
+/// Same as [`export_state`] but with given key derivation parameters,
+///     e.g. to trade strength for speed on constrained devices.
+///
+/// # Parameters
+///
+/// * `storage` - storage used by [`RecordStore`][super::RecordStore]
+///
+/// * `passphrase` - secret backup is encrypted with
+///
+/// * `kdf` - parameters of key derivation, salt must be random
+pub fn export_state_with_kdf(
+    storage: &dyn Storage,
+    passphrase: &[u8],
+    kdf: PassphraseKdf,
+) -> Result<Vec<u8>> {
+    let mut namespaces = BTreeMap::new();
+    for namespace in STATE_NAMESPACES.iter() {
+        namespaces.insert(namespace.to_string(), storage.query(namespace, &[])?);
+    }
+    let content = serde_json::to_vec(&BackupContent {
+        created_time: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs(),
+        namespaces,
+    })?;
+    let header = BackupHeader {
+        version: STATE_BACKUP_VERSION,
+        kdf,
+        enc: "XC20P".into(),
+    };
+    let key = header.kdf.derive_key(passphrase)?;
+    let iv = rand::random::<[u8; 24]>();
+    let ciphertext =
+        CryptoAlgorithm::XC20P.encryptor()(&iv, &key, &content, &serde_json::to_vec(&header)?)?;
+    Ok(serde_json::to_vec(&BackupFile {
+        header,
+        iv: base64_url::encode(&iv),
+        ciphertext: base64_url::encode(&ciphertext),
+    })?)
+}
+
+/// Restores records of backup written by [`export_state`] into `storage`.
+/// Stored records with same keys are replaced, others are kept.
+/// Returns number of restored records.
+///
+/// # Errors
+///
+/// `Error::InvalidBackup` if backup is malformed, of unsupported version,
+///     its key derivation costs exceed bounds of [`PassphraseKdf::check_costs`]
+///     or passphrase is wrong; nothing is restored in that case.
+///
+/// # Parameters
+///
+/// * `storage` - storage to restore records into
+///
+/// * `backup` - content of backup file
+///
+/// * `passphrase` - secret backup was encrypted with
+pub fn import_state(storage: &dyn Storage, backup: &[u8], passphrase: &[u8]) -> Result<usize> {
+    let file: BackupFile = serde_json::from_slice(backup)
+        .map_err(|e| Error::InvalidBackup(format!("malformed backup file; {}", e)))?;
+    if file.header.version != STATE_BACKUP_VERSION {
+        return Err(Error::InvalidBackup(format!(
+            "unsupported version {}",
+            file.header.version
+        )));
+    }
+    if file.header.enc != "XC20P" {
+        return Err(Error::InvalidBackup(format!(
+            "unsupported encryption {}",
+            file.header.enc
+        )));
+    }
+    let key = file
+        .header
+        .kdf
+        .derive_key(passphrase)
+        .map_err(|e| Error::InvalidBackup(format!("invalid key derivation; {}", e)))?;
+    let content = CryptoAlgorithm::XC20P.decrypter()(
+        &base64_url::decode(&file.iv)?,
+        &key,
+        &base64_url::decode(&file.ciphertext)?,
+        &serde_json::to_vec(&file.header)?,
+    )
+    .map_err(|_| Error::InvalidBackup("wrong passphrase or corrupted content".into()))?;
+    let content: BackupContent = serde_json::from_slice(&content)
+        .map_err(|e| Error::InvalidBackup(format!("malformed content; {}", e)))?;
+    let mut restored = 0;
+    for (namespace, records) in content.namespaces {
+        for record in records {
+            storage.put(&namespace, record)?;
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        crypto::{Curve, KeyMaterial, KeyRole},
+        storage::{InMemoryStorage, PlaintextKeyStore, RecordStore},
+        Connection,
+        ConnectionStore,
+        KeyStore,
+        MediationRecord,
+        MediationStore,
+        ThreadRecord,
+        ThreadStore,
+    };
+
+    #[test]
+    fn state_is_exported_and_imported() -> Result<()> {
+        let device = Arc::new(InMemoryStorage::new());
+        let store = RecordStore::new(device.clone());
+        KeyStore::put(
+            &PlaintextKeyStore::new(device.clone()),
+            "did:example:alice#key-1",
+            KeyMaterial {
+                curve: Curve::X25519,
+                role: KeyRole::KeyAgreement,
+                private: true,
+                bytes: vec![7; 32],
+            },
+        );
+        ConnectionStore::put(&store, Connection::new("invitation"));
+        MediationStore::put(
+            &store,
+            MediationRecord::new("did:example:alice", "did:example:mediator"),
+        );
+        ThreadStore::put(&store, ThreadRecord::new("thread"));
+        device.put("app", StorageRecord::new("setting", b"on".to_vec()))?;
+
+        // cheap parameters keep the test fast, real backups use defaults
+        let kdf = PassphraseKdf::with_costs(64, 1, 1);
+        let backup = export_state_with_kdf(device.as_ref(), b"correct horse", kdf)?;
+        assert!(!String::from_utf8_lossy(&backup).contains("did:example:alice"));
+
+        let migrated = Arc::new(InMemoryStorage::new());
+        assert!(matches!(
+            import_state(migrated.as_ref(), &backup, b"battery staple"),
+            Err(Error::InvalidBackup(_))
+        ));
+        assert!(migrated.query(KEYS_NAMESPACE, &[])?.is_empty());
+        assert_eq!(
+            import_state(migrated.as_ref(), &backup, b"correct horse")?,
+            4
+        );
+
+        let restored = RecordStore::new(migrated.clone());
+        assert_eq!(
+            KeyStore::get(
+                &PlaintextKeyStore::new(migrated.clone()),
+                "did:example:alice#key-1"
+            )
+            .map(|key| key.bytes),
+            Some(vec![7; 32])
+        );
+        assert!(ConnectionStore::get(&restored, "invitation").is_some());
+        assert!(MediationStore::get(&restored, "did:example:alice").is_some());
+        assert!(ThreadStore::get(&restored, "thread").is_some());
+        assert_eq!(migrated.get("app", "setting")?, None);
+
+        let mut file: serde_json::Value = serde_json::from_slice(&backup)?;
+        file["version"] = 2.into();
+        assert!(matches!(
+            import_state(migrated.as_ref(), &serde_json::to_vec(&file)?, b"correct horse"),
+            Err(Error::InvalidBackup(e)) if e == "unsupported version 2"
+        ));
+        let mut file: serde_json::Value = serde_json::from_slice(&backup)?;
+        file["kdf"]["m_cost"] = u32::MAX.into();
+        assert!(matches!(
+            import_state(migrated.as_ref(), &serde_json::to_vec(&file)?, b"correct horse"),
+            Err(Error::InvalidBackup(e)) if e.contains("m_cost")
+        ));
+        Ok(())
+    }
+}
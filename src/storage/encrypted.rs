@@ -0,0 +1,256 @@
+use std::{convert::TryInto, sync::Arc};
+
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use super::{Storage, StorageRecord};
+use crate::{
+    crypto::{CryptoAlgorithm, Cypher},
+    Error,
+    KeyStore,
+    MessageQuery,
+    MessageStore,
+    Result,
+    StoredMessage,
+};
+
+/// Namespace of messages stored by [`EncryptedMessageStore`], keyed by message `id`.
+pub const ENCRYPTED_MESSAGES_NAMESPACE: &str = "encrypted_messages";
+
+/// Namespace of wrapped store keys of [`EncryptedMessageStore`], keyed by id of wrapping key.
+pub const STORE_KEYS_NAMESPACE: &str = "store_keys";
+
+/// HKDF info prefix of keys wrapping store keys, followed by id of private key.
+const WRAPPING_KEY_LABEL: &[u8] = b"didcomm-rs message store v1";
+
+/// [`MessageStore`] keeping received messages encrypted at rest.
+/// Messages are encrypted with XChaCha20-Poly1305 under a random store key,
+///     which is itself stored wrapped by a key derived with HKDF-SHA256
+///     from a private key of a [`KeyStore`].
+/// Tags used for queries are keyed hashes, so neither content nor
+///     correspondents of past conversations can be read from the storage
+///     without the key store.
+/// With [`PlaintextKeyStore`][super::PlaintextKeyStore] over the same `Storage` as key store,
+///     the private key sits unencrypted next to the messages it protects;
+///     keep it in a separate or hardware backed key store for encryption at rest.
+pub struct EncryptedMessageStore {
+    storage: Arc<dyn Storage>,
+    key: [u8; 32],
+}
+
+impl EncryptedMessageStore {
+    /// Opens store, unwrapping its store key or creating one on first use.
+    ///
+    /// # Errors
+    ///
+    /// `Error::KeyNotFound` if `keys` has no private key with id `kid`.
+    ///
+    /// `Error::StorageError` if wrapped store key can't be read, written or
+    ///     unwrapped with the key.
+    ///
+    /// # Parameters
+    ///
+    /// * `storage` - storage encrypted messages and wrapped store key are kept in
+    ///
+    /// * `keys` - key store holding the wrapping key
+    ///
+    /// * `kid` - id of private key wrapping the store key, e.g. `did:example:alice#key-1`
+    pub fn open(storage: Arc<dyn Storage>, keys: &dyn KeyStore, kid: &str) -> Result<Self> {
+        let wrapping_key = keys
+            .get(kid)
+            .filter(|key| key.private)
+            .ok_or_else(|| Error::KeyNotFound(kid.into()))?;
+        let wrapping_key = derive_wrapping_key(&wrapping_key.bytes, kid)?;
+        let key = match storage.get(STORE_KEYS_NAMESPACE, kid)? {
+            Some(wrapped) => open_sealed(&wrapping_key, kid, &wrapped.value)?
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::StorageError("malformed store key".into()))?,
+            None => {
+                let key = rand::random::<[u8; 32]>();
+                let wrapped = seal(&wrapping_key, kid, &key)?;
+                storage.put(STORE_KEYS_NAMESPACE, StorageRecord::new(kid, wrapped))?;
+                key
+            }
+        };
+        Ok(Self { storage, key })
+    }
+
+    /// Keyed hash of tag value, so equal values can be found without revealing them.
+    fn blind(&self, name: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.input(self.key);
+        hasher.input(name.as_bytes());
+        hasher.input([0]);
+        hasher.input(value.as_bytes());
+        base64_url::encode(hasher.result().as_slice())
+    }
+
+    fn decrypt(&self, record: StorageRecord) -> Option<StoredMessage> {
+        let stored = open_sealed(&self.key, &record.key, &record.value).and_then(|plaintext| {
+            serde_json::from_slice::<StoredMessage>(&plaintext).map_err(Into::into)
+        });
+        match stored {
+            Ok(stored) => Some(stored),
+            Err(e) => {
+                log::error!("unreadable encrypted message {}: {}", record.key, e);
+                None
+            }
+        }
+    }
+}
+
+impl MessageStore for EncryptedMessageStore {
+    fn get(&self, id: &str) -> Option<StoredMessage> {
+        match self.storage.get(ENCRYPTED_MESSAGES_NAMESPACE, id) {
+            Ok(record) => record.and_then(|record| self.decrypt(record)),
+            Err(e) => {
+                log::error!("failed to read encrypted message {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, stored: StoredMessage) {
+        let id = stored.message.get_id().to_string();
+        let header = stored.message.get_didcomm_header();
+        let mut record = StorageRecord::new(&id, vec![])
+            .with_tag("thid", &self.blind("thid", stored.thid()))
+            .with_tag("type", &self.blind("type", &header.m_type))
+            .with_tag("acked", &self.blind("acked", &stored.acked.to_string()));
+        if let Some(from) = &header.from {
+            record = record.with_tag("from", &self.blind("from", from));
+        }
+        let sealed = serde_json::to_vec(&stored)
+            .map_err(Into::into)
+            .and_then(|plaintext| seal(&self.key, &id, &plaintext))
+            .and_then(|value| {
+                record.value = value;
+                self.storage.put(ENCRYPTED_MESSAGES_NAMESPACE, record)
+            });
+        if let Err(e) = sealed {
+            log::error!("failed to store encrypted message {}: {}", id, e);
+        }
+    }
+
+    fn query(&self, query: &MessageQuery) -> Vec<StoredMessage> {
+        let acked = query.acked.map(|acked| acked.to_string());
+        let tags: Vec<_> = [
+            ("thid", query.thid.as_deref()),
+            ("from", query.from.as_deref()),
+            ("type", query.m_type.as_deref()),
+            ("acked", acked.as_deref()),
+        ]
+        .iter()
+        .filter_map(|(name, value)| value.map(|value| (*name, self.blind(name, value))))
+        .collect();
+        let tags: Vec<_> = tags
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        match self.storage.query(ENCRYPTED_MESSAGES_NAMESPACE, &tags) {
+            Ok(records) => query.apply(records.into_iter().filter_map(|r| self.decrypt(r))),
+            Err(e) => {
+                log::error!("failed to query encrypted messages: {}", e);
+                vec![]
+            }
+        }
+    }
+}
+
+/// Derives key wrapping store key from private key `kid`, bound to label and `kid`.
+fn derive_wrapping_key(private_key: &[u8], kid: &str) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, private_key)
+        .expand(&[WRAPPING_KEY_LABEL, kid.as_bytes()].concat(), &mut key)
+        .map_err(|e| Error::Generic(format!("{:?}", e)))?;
+    Ok(key)
+}
+
+/// Encrypts with random nonce bound to `aad`; nonce is prepended to ciphertext.
+fn seal(key: &[u8], aad: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let iv = rand::random::<[u8; 24]>();
+    let ciphertext = CryptoAlgorithm::XC20P.encryptor()(&iv, key, plaintext, aad.as_bytes())?;
+    Ok([&iv[..], &ciphertext].concat())
+}
+
+fn open_sealed(key: &[u8], aad: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < 24 {
+        return Err(Error::StorageError(format!(
+            "sealed value of {} is truncated",
+            aad
+        )));
+    }
+    let (iv, ciphertext) = sealed.split_at(24);
+    CryptoAlgorithm::XC20P.decrypter()(iv, key, ciphertext, aad.as_bytes())
+        .map_err(|_| Error::StorageError(format!("sealed value of {} can't be decrypted", aad)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::{Curve, KeyMaterial, KeyRole},
+        storage::InMemoryStorage,
+        InMemoryKeyStore,
+        Message,
+        SenderInfo,
+    };
+
+    #[test]
+    fn messages_are_encrypted_at_rest() -> Result<()> {
+        let storage = Arc::new(InMemoryStorage::new());
+        let keys = InMemoryKeyStore::new();
+        let key = |byte| KeyMaterial {
+            curve: Curve::X25519,
+            role: KeyRole::KeyAgreement,
+            private: true,
+            bytes: vec![byte; 32],
+        };
+        keys.put("did:example:alice#key-1", key(1));
+        keys.put("did:example:mallory#key-1", key(2));
+        assert!(matches!(
+            EncryptedMessageStore::open(storage.clone(), &keys, "did:example:alice#key-2"),
+            Err(Error::KeyNotFound(_))
+        ));
+
+        let store = EncryptedMessageStore::open(storage.clone(), &keys, "did:example:alice#key-1")?;
+        let message = Message::new()
+            .from("did:example:bob")
+            .m_type("https://didcomm.org/basicmessage/2.0/message")
+            .body(r#"{"content":"meet at noon"}"#)?;
+        store.put(StoredMessage::new(message.clone(), SenderInfo::default()));
+        let raw = &storage.query(ENCRYPTED_MESSAGES_NAMESPACE, &[])?[0];
+        assert!(!String::from_utf8_lossy(&raw.value).contains("noon"));
+        assert!(!format!("{:?}", raw).contains("did:example:bob"));
+
+        // store key is kept wrapped, so reopening with the same key reads messages
+        let reopened =
+            EncryptedMessageStore::open(storage.clone(), &keys, "did:example:alice#key-1")?;
+        let from_bob = reopened.query(&MessageQuery::new().from("did:example:bob"));
+        assert_eq!(from_bob.len(), 1);
+        assert_eq!(from_bob[0].message, message);
+        assert!(reopened.mark_acked(message.get_id()));
+        assert_eq!(reopened.query(&MessageQuery::new().acked(false)), vec![]);
+
+        // wrapped key can't be unwrapped with a key derived from another private key
+        let wrapped = storage
+            .get(STORE_KEYS_NAMESPACE, "did:example:alice#key-1")?
+            .unwrap();
+        assert!(open_sealed(
+            &derive_wrapping_key(&[2; 32], "did:example:alice#key-1")?,
+            "did:example:alice#key-1",
+            &wrapped.value
+        )
+        .is_err());
+        storage.put(
+            STORE_KEYS_NAMESPACE,
+            StorageRecord::new("did:example:mallory#key-1", wrapped.value),
+        )?;
+        assert!(matches!(
+            EncryptedMessageStore::open(storage, &keys, "did:example:mallory#key-1"),
+            Err(Error::StorageError(_))
+        ));
+        Ok(())
+    }
+}
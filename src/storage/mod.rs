@@ -0,0 +1,533 @@
+//! Pluggable persistence of records of all stateful subsystems.
+//!
+//! [`Storage`] is a namespaced key-value store with tags to query records by.
+//! [`RecordStore`] implements `ThreadStore`, `ConnectionStore`, `MediationStore` and
+//! `MessageStore` on top of any storage, so records of all of them are persisted
+//! the same way and backends can be swapped without touching the subsystems.
+//! Private keys are only persisted unencrypted on explicit opt-in with [`PlaintextKeyStore`].
+//! Besides [`InMemoryStorage`], backends using `sled` and SQLite are available
+//! with `sled-storage` and `sqlite-storage` features.
+//! With `raw-crypto` feature stored state can be exported into an encrypted
+//! backup file with [`export_state`] and restored with [`import_state`], and
+//! received messages can be kept encrypted at rest with [`EncryptedMessageStore`].
+#[cfg(feature = "raw-crypto")]
+mod backup;
+#[cfg(feature = "raw-crypto")]
+mod encrypted;
+#[cfg(feature = "sled-storage")]
+pub mod sled;
+#[cfg(feature = "sqlite-storage")]
+pub mod sqlite;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "raw-crypto")]
+pub use self::{backup::*, encrypted::*};
+
+#[cfg(feature = "raw-crypto")]
+use crate::{crypto::KeyMaterial, KeyStore};
+use crate::{
+    Connection,
+    ConnectionStore,
+    MediationRecord,
+    MediationStore,
+    MessageQuery,
+    MessageStore,
+    Result,
+    StoredMessage,
+    ThreadRecord,
+    ThreadStore,
+};
+
+/// Namespace of [`ThreadRecord`]s, keyed by `thid`.
+pub const THREADS_NAMESPACE: &str = "threads";
+
+/// Namespace of [`Connection`]s, keyed by `id`.
+pub const CONNECTIONS_NAMESPACE: &str = "connections";
+
+/// Namespace of [`MediationRecord`]s, keyed by `did`.
+pub const MEDIATIONS_NAMESPACE: &str = "mediations";
+
+/// Namespace of [`StoredMessage`]s, keyed by message `id`.
+pub const MESSAGES_NAMESPACE: &str = "messages";
+
+/// Namespace of private keys, keyed by key id.
+pub const KEYS_NAMESPACE: &str = "keys";
+
+/// Single stored value with tags it can be queried by.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageRecord {
+    /// key, unique within namespace
+    pub key: String,
+
+    /// serialized record
+    pub value: Vec<u8>,
+
+    /// names and values records can be queried by
+    pub tags: BTreeMap<String, String>,
+}
+
+impl StorageRecord {
+    /// Constructor of record without tags.
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - key, unique within namespace
+    ///
+    /// * `value` - serialized record
+    pub fn new(key: &str, value: Vec<u8>) -> Self {
+        Self {
+            key: key.into(),
+            value,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Adds or replaces tag.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - name of tag
+    ///
+    /// * `value` - value of tag
+    pub fn with_tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.insert(name.into(), value.into());
+        self
+    }
+
+    /// `true` if record has all given tags with given values.
+    pub fn matches(&self, tags: &[(&str, &str)]) -> bool {
+        tags.iter()
+            .all(|(name, value)| self.tags.get(*name).map(String::as_str) == Some(*value))
+    }
+}
+
+/// Trait must be implemented for pluggable record storage,
+///     see [`RecordStore`] for using it with messaging subsystems.
+pub trait Storage: Send + Sync {
+    /// Gets record with given key.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if backend fails.
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>>;
+
+    /// Inserts or replaces record with `record.key`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if backend fails.
+    fn put(&self, namespace: &str, record: StorageRecord) -> Result<()>;
+
+    /// Removes record with given key and returns it.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if backend fails.
+    fn remove(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>>;
+
+    /// Gets records having all given tags, all records of namespace if `tags` is empty.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if backend fails.
+    ///
+    /// # Parameters
+    ///
+    /// * `namespace` - namespace to search
+    ///
+    /// * `tags` - names and values of tags records must have
+    fn query(&self, namespace: &str, tags: &[(&str, &str)]) -> Result<Vec<StorageRecord>>;
+}
+
+/// In-memory [`Storage`], records are lost on drop.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    namespaces: Mutex<HashMap<String, BTreeMap<String, StorageRecord>>>,
+}
+
+impl InMemoryStorage {
+    /// Constructor of empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn namespaces(&self) -> MutexGuard<'_, HashMap<String, BTreeMap<String, StorageRecord>>> {
+        match self.namespaces.lock() {
+            Ok(namespaces) => namespaces,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        Ok(self
+            .namespaces()
+            .get(namespace)
+            .and_then(|records| records.get(key))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, record: StorageRecord) -> Result<()> {
+        self.namespaces()
+            .entry(namespace.into())
+            .or_default()
+            .insert(record.key.clone(), record);
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        Ok(self
+            .namespaces()
+            .get_mut(namespace)
+            .and_then(|records| records.remove(key)))
+    }
+
+    fn query(&self, namespace: &str, tags: &[(&str, &str)]) -> Result<Vec<StorageRecord>> {
+        Ok(self
+            .namespaces()
+            .get(namespace)
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .filter(|record| record.matches(tags))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Thread, connection, mediation and message store persisting records as JSON
+///     in namespaces of a [`Storage`].
+/// Store traits can't fail, so failures of storage are logged and reads
+///     fall back to "not found".
+#[derive(Clone)]
+pub struct RecordStore {
+    storage: Arc<dyn Storage>,
+}
+
+impl RecordStore {
+    /// Constructor of store on top of given storage.
+    ///
+    /// # Parameters
+    ///
+    /// * `storage` - backend, can be shared with other stores
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Underlying storage, e.g. to keep further records of the application in it.
+    pub fn storage(&self) -> &Arc<dyn Storage> {
+        &self.storage
+    }
+
+    fn load<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Option<T> {
+        let record = self.storage.get(namespace, key);
+        decode(namespace, record.map(|record| record.into_iter().collect()))
+            .into_iter()
+            .next()
+    }
+
+    fn load_all<T: DeserializeOwned>(&self, namespace: &str, tags: &[(&str, &str)]) -> Vec<T> {
+        decode(namespace, self.storage.query(namespace, tags))
+    }
+
+    fn store<T: Serialize>(&self, namespace: &str, key: &str, tags: &[(&str, &str)], value: &T) {
+        let stored = serde_json::to_vec(value)
+            .map_err(Into::into)
+            .and_then(|value| {
+                let record = tags
+                    .iter()
+                    .fold(StorageRecord::new(key, value), |record, (name, value)| {
+                        record.with_tag(name, value)
+                    });
+                self.storage.put(namespace, record)
+            });
+        if let Err(e) = stored {
+            log::error!("failed to store {} record {}: {}", namespace, key, e);
+        }
+    }
+}
+
+fn decode<T: DeserializeOwned>(namespace: &str, records: Result<Vec<StorageRecord>>) -> Vec<T> {
+    let records = records.unwrap_or_else(|e| {
+        log::error!("failed to read {} records: {}", namespace, e);
+        vec![]
+    });
+    records
+        .into_iter()
+        .filter_map(|record| match serde_json::from_slice(&record.value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::error!("malformed {} record {}: {}", namespace, record.key, e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl ThreadStore for RecordStore {
+    fn get(&self, thid: &str) -> Option<ThreadRecord> {
+        self.load(THREADS_NAMESPACE, thid)
+    }
+
+    fn put(&self, record: ThreadRecord) {
+        let tags: Vec<_> = record
+            .pthid
+            .iter()
+            .map(|pthid| ("pthid", pthid.as_str()))
+            .collect();
+        self.store(THREADS_NAMESPACE, &record.thid, &tags, &record);
+    }
+
+    fn children(&self, pthid: &str) -> Vec<ThreadRecord> {
+        self.load_all(THREADS_NAMESPACE, &[("pthid", pthid)])
+    }
+}
+
+impl ConnectionStore for RecordStore {
+    fn get(&self, id: &str) -> Option<Connection> {
+        self.load(CONNECTIONS_NAMESPACE, id)
+    }
+
+    fn put(&self, connection: Connection) {
+        let tags: Vec<_> = [
+            ("our_did", connection.our_did.as_deref()),
+            ("their_did", connection.their_did.as_deref()),
+        ]
+        .iter()
+        .filter_map(|(name, did)| did.map(|did| (*name, did)))
+        .collect();
+        self.store(CONNECTIONS_NAMESPACE, &connection.id, &tags, &connection);
+    }
+
+    fn all(&self) -> Vec<Connection> {
+        self.load_all(CONNECTIONS_NAMESPACE, &[])
+    }
+
+    fn find_by_their_did(&self, did: &str) -> Option<Connection> {
+        self.load_all(CONNECTIONS_NAMESPACE, &[("their_did", did)])
+            .into_iter()
+            .next()
+    }
+
+    fn find_by_our_did(&self, did: &str) -> Option<Connection> {
+        self.load_all(CONNECTIONS_NAMESPACE, &[("our_did", did)])
+            .into_iter()
+            .next()
+    }
+}
+
+impl MediationStore for RecordStore {
+    fn get(&self, did: &str) -> Option<MediationRecord> {
+        self.load(MEDIATIONS_NAMESPACE, did)
+    }
+
+    fn put(&self, record: MediationRecord) {
+        self.store(MEDIATIONS_NAMESPACE, &record.did, &[], &record);
+    }
+
+    fn remove(&self, did: &str) -> Option<MediationRecord> {
+        let removed = self.storage.remove(MEDIATIONS_NAMESPACE, did);
+        decode(
+            MEDIATIONS_NAMESPACE,
+            removed.map(|record| record.into_iter().collect()),
+        )
+        .into_iter()
+        .next()
+    }
+}
+
+impl MessageStore for RecordStore {
+    fn get(&self, id: &str) -> Option<StoredMessage> {
+        self.load(MESSAGES_NAMESPACE, id)
+    }
+
+    fn put(&self, stored: StoredMessage) {
+        let header = stored.message.get_didcomm_header();
+        let mut tags = vec![
+            ("thid", stored.thid()),
+            ("type", header.m_type.as_str()),
+            ("acked", if stored.acked { "true" } else { "false" }),
+        ];
+        tags.extend(header.from.as_deref().map(|from| ("from", from)));
+        self.store(MESSAGES_NAMESPACE, stored.message.get_id(), &tags, &stored);
+    }
+
+    fn query(&self, query: &MessageQuery) -> Vec<StoredMessage> {
+        let acked = query.acked.map(|acked| acked.to_string());
+        let tags: Vec<_> = [
+            ("thid", query.thid.as_deref()),
+            ("from", query.from.as_deref()),
+            ("type", query.m_type.as_deref()),
+            ("acked", acked.as_deref()),
+        ]
+        .iter()
+        .filter_map(|(name, value)| value.map(|value| (*name, value)))
+        .collect();
+        query.apply(self.load_all(MESSAGES_NAMESPACE, &tags))
+    }
+}
+
+/// [`KeyStore`] persisting private keys as plain JSON records in a [`Storage`].
+/// Keys are readable by anyone with access to the storage, e.g. to files of
+///     `sled` or SQLite backends, so use it only with storage protected otherwise,
+///     e.g. by disk encryption or a platform keychain.
+#[cfg(feature = "raw-crypto")]
+#[derive(Clone)]
+pub struct PlaintextKeyStore {
+    records: RecordStore,
+}
+
+#[cfg(feature = "raw-crypto")]
+impl PlaintextKeyStore {
+    /// Constructor of key store on top of given storage.
+    ///
+    /// # Parameters
+    ///
+    /// * `storage` - backend keys are persisted unencrypted in
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            records: RecordStore::new(storage),
+        }
+    }
+}
+
+#[cfg(feature = "raw-crypto")]
+impl KeyStore for PlaintextKeyStore {
+    fn get(&self, kid: &str) -> Option<KeyMaterial> {
+        self.records.load(KEYS_NAMESPACE, kid)
+    }
+
+    fn put(&self, kid: &str, key: KeyMaterial) {
+        let did = kid.split('#').next().unwrap_or_default();
+        self.records.store(KEYS_NAMESPACE, kid, &[("did", did)], &key);
+    }
+
+    fn kids(&self) -> Vec<String> {
+        match self.records.storage.query(KEYS_NAMESPACE, &[]) {
+            Ok(records) => records.into_iter().map(|record| record.key).collect(),
+            Err(e) => {
+                log::error!("failed to read {} records: {}", KEYS_NAMESPACE, e);
+                vec![]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{ConnectionState, DidCommMessagingService, Message, SenderInfo};
+
+    /// Checks behaviour every [`Storage`] backend must have.
+    pub(crate) fn check_storage(storage: &dyn Storage) -> Result<()> {
+        storage.put(
+            "a",
+            StorageRecord::new("1", b"one".to_vec()).with_tag("kind", "odd"),
+        )?;
+        storage.put(
+            "a",
+            StorageRecord::new("2", b"two".to_vec()).with_tag("kind", "even"),
+        )?;
+        storage.put("b", StorageRecord::new("1", b"other".to_vec()))?;
+        assert_eq!(storage.get("a", "1")?.unwrap().value, b"one");
+        assert_eq!(storage.get("b", "1")?.unwrap().value, b"other");
+        assert_eq!(storage.get("a", "3")?, None);
+        assert_eq!(storage.query("a", &[])?.len(), 2);
+        let even = storage.query("a", &[("kind", "even")])?;
+        assert_eq!(even.len(), 1);
+        assert_eq!(even[0].key, "2");
+        assert!(storage.query("a", &[("kind", "prime")])?.is_empty());
+
+        storage.put("a", StorageRecord::new("2", b"deux".to_vec()))?;
+        assert!(storage.query("a", &[("kind", "even")])?.is_empty());
+        assert_eq!(storage.remove("a", "2")?.unwrap().value, b"deux");
+        assert_eq!(storage.remove("a", "2")?, None);
+        assert_eq!(storage.query("a", &[])?.len(), 1);
+        assert!(storage.query("c", &[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn in_memory_storage_backs_all_stores() -> Result<()> {
+        check_storage(&InMemoryStorage::new())?;
+
+        let storage: Arc<dyn Storage> = Arc::new(InMemoryStorage::new());
+        let store = RecordStore::new(storage.clone());
+        let mut child = ThreadRecord::new("child");
+        child.pthid = Some("parent".into());
+        ThreadStore::put(&store, ThreadRecord::new("parent"));
+        ThreadStore::put(&store, child.clone());
+        assert_eq!(store.children("parent"), vec![child]);
+        assert!(store.close("parent"));
+
+        let mut connection = Connection::new("invitation");
+        connection.our_did = Some("did:example:alice".into());
+        connection.their_did = Some("did:example:bob".into());
+        connection.state = ConnectionState::Active;
+        connection
+            .their_services
+            .push(DidCommMessagingService::new("https://example.com"));
+        ConnectionStore::put(&store, connection.clone());
+        assert_eq!(
+            store.find_by_their_did("did:example:bob"),
+            Some(connection.clone())
+        );
+        assert_eq!(store.find_by_our_did("did:example:alice"), Some(connection));
+        assert_eq!(store.find_by_our_did("did:example:bob"), None);
+
+        let mediation = MediationRecord::new("did:example:alice", "did:example:mediator");
+        MediationStore::put(&store, mediation.clone());
+        assert_eq!(
+            MediationStore::remove(&store, "did:example:alice"),
+            Some(mediation)
+        );
+
+        let received = StoredMessage::new(
+            Message::new().from("did:example:bob"),
+            SenderInfo::default(),
+        );
+        MessageStore::put(&store, received.clone());
+        assert!(store.mark_acked(received.message.get_id()));
+        let acked = MessageQuery::new().from("did:example:bob").acked(true);
+        assert_eq!(MessageStore::query(&store, &acked).len(), 1);
+        assert!(MessageStore::query(&store, &acked.thread("other")).is_empty());
+
+        // records survive the store, as they are kept by storage
+        let reopened = RecordStore::new(storage);
+        assert_eq!(
+            ThreadStore::get(&reopened, "parent").map(|thread| thread.state),
+            Some(crate::ThreadState::Closed)
+        );
+        assert_eq!(ConnectionStore::all(&reopened).len(), 1);
+        assert_eq!(MediationStore::get(&reopened, "did:example:alice"), None);
+        Ok(())
+    }
+
+    #[cfg(feature = "raw-crypto")]
+    #[test]
+    fn keys_are_stored_as_records() -> Result<()> {
+        use crate::crypto::{Curve, KeyRole};
+
+        let store = PlaintextKeyStore::new(Arc::new(InMemoryStorage::new()));
+        let key = KeyMaterial {
+            curve: Curve::X25519,
+            role: KeyRole::KeyAgreement,
+            private: true,
+            bytes: vec![7; 32],
+        };
+        KeyStore::put(&store, "did:example:alice#key-1", key);
+        assert_eq!(KeyStore::kids(&store), ["did:example:alice#key-1"]);
+        let (kid, found) = store
+            .find("did:example:alice", KeyRole::KeyAgreement)
+            .unwrap();
+        assert_eq!(kid, "did:example:alice#key-1");
+        assert_eq!(found.bytes, vec![7; 32]);
+        Ok(())
+    }
+}
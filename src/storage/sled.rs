@@ -0,0 +1,99 @@
+//! [`Storage`] persisting records in an embedded [sled](https://docs.rs/sled) database.
+use std::path::Path;
+
+use super::{Storage, StorageRecord};
+use crate::{Error, Result};
+
+/// [`Storage`] keeping each namespace in its own sled tree,
+///     records are stored as JSON and queried by scanning their namespace.
+#[derive(Clone)]
+pub struct SledStorage {
+    db: sled::Db,
+}
+
+impl SledStorage {
+    /// Opens or creates database at given path.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if database can't be opened.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - directory of the database
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_db(sled::open(path).map_err(storage_error)?))
+    }
+
+    /// Constructor using already opened database, e.g. a temporary one.
+    ///
+    /// # Parameters
+    ///
+    /// * `db` - sled database
+    pub fn from_db(db: sled::Db) -> Self {
+        Self { db }
+    }
+
+    fn tree(&self, namespace: &str) -> Result<sled::Tree> {
+        self.db.open_tree(namespace).map_err(storage_error)
+    }
+}
+
+impl Storage for SledStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        match self.tree(namespace)?.get(key).map_err(storage_error)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&self, namespace: &str, record: StorageRecord) -> Result<()> {
+        let value = serde_json::to_vec(&record)?;
+        let tree = self.tree(namespace)?;
+        tree.insert(record.key.as_bytes(), value)
+            .map_err(storage_error)?;
+        tree.flush().map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        let tree = self.tree(namespace)?;
+        let removed = tree.remove(key).map_err(storage_error)?;
+        tree.flush().map_err(storage_error)?;
+        match removed {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn query(&self, namespace: &str, tags: &[(&str, &str)]) -> Result<Vec<StorageRecord>> {
+        let mut records = vec![];
+        for entry in self.tree(namespace)?.iter() {
+            let (_, value) = entry.map_err(storage_error)?;
+            let record: StorageRecord = serde_json::from_slice(&value)?;
+            if record.matches(tags) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn storage_error(e: sled::Error) -> Error {
+    Error::StorageError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::check_storage;
+
+    #[test]
+    fn sled_storage_stores_and_queries_records() -> Result<()> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .map_err(storage_error)?;
+        check_storage(&SledStorage::from_db(db))
+    }
+}
@@ -0,0 +1,224 @@
+//! [`Storage`] persisting records in a SQLite database.
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{Storage, StorageRecord};
+use crate::{Error, Result};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS didcomm_records (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value BLOB NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );
+    CREATE TABLE IF NOT EXISTS didcomm_record_tags (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        name TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (namespace, key, name)
+    );
+    CREATE INDEX IF NOT EXISTS didcomm_record_tags_by_value
+        ON didcomm_record_tags (namespace, name, value);
+";
+
+/// [`Storage`] keeping records in `didcomm_records` table and their tags in
+///     indexed `didcomm_record_tags` table of a SQLite database.
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens or creates database file at given path.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if database can't be opened or its tables created.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - path of the database file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// Creates database living in memory only, records are lost on drop.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if database can't be created.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    /// Constructor using already opened connection, creates tables if missing.
+    ///
+    /// # Errors
+    ///
+    /// `Error::StorageError` if tables can't be created.
+    ///
+    /// # Parameters
+    ///
+    /// * `connection` - connection to SQLite database
+    pub fn from_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        match self.connection.lock() {
+            Ok(connection) => connection,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        read_record(&self.connection(), namespace, key)
+    }
+
+    fn put(&self, namespace: &str, record: StorageRecord) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(storage_error)?;
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO didcomm_records (namespace, key, value) VALUES (?1, ?2, ?3)",
+                params![namespace, record.key, record.value],
+            )
+            .map_err(storage_error)?;
+        transaction
+            .execute(
+                "DELETE FROM didcomm_record_tags WHERE namespace = ?1 AND key = ?2",
+                params![namespace, record.key],
+            )
+            .map_err(storage_error)?;
+        for (name, value) in &record.tags {
+            transaction
+                .execute(
+                    "INSERT INTO didcomm_record_tags (namespace, key, name, value) VALUES (?1, ?2, ?3, ?4)",
+                    params![namespace, record.key, name, value],
+                )
+                .map_err(storage_error)?;
+        }
+        transaction.commit().map_err(storage_error)
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> Result<Option<StorageRecord>> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(storage_error)?;
+        let removed = read_record(&transaction, namespace, key)?;
+        for table in &["didcomm_records", "didcomm_record_tags"] {
+            transaction
+                .execute(
+                    &format!("DELETE FROM {} WHERE namespace = ?1 AND key = ?2", table),
+                    params![namespace, key],
+                )
+                .map_err(storage_error)?;
+        }
+        transaction.commit().map_err(storage_error)?;
+        Ok(removed)
+    }
+
+    fn query(&self, namespace: &str, tags: &[(&str, &str)]) -> Result<Vec<StorageRecord>> {
+        let connection = self.connection();
+        let mut keys: Option<Vec<String>> = None;
+        for (name, value) in tags {
+            let mut statement = connection
+                .prepare_cached(
+                    "SELECT key FROM didcomm_record_tags
+                     WHERE namespace = ?1 AND name = ?2 AND value = ?3 ORDER BY key",
+                )
+                .map_err(storage_error)?;
+            let tagged = statement
+                .query_map(params![namespace, name, value], |row| row.get(0))
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+                .map_err(storage_error)?;
+            keys = Some(match keys {
+                Some(keys) => keys
+                    .into_iter()
+                    .filter(|key| tagged.contains(key))
+                    .collect(),
+                None => tagged,
+            });
+        }
+        let keys = match keys {
+            Some(keys) => keys,
+            None => {
+                let mut statement = connection
+                    .prepare_cached(
+                        "SELECT key FROM didcomm_records WHERE namespace = ?1 ORDER BY key",
+                    )
+                    .map_err(storage_error)?;
+                let keys = statement
+                    .query_map(params![namespace], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+                    .map_err(storage_error)?;
+                keys
+            }
+        };
+        let mut records = vec![];
+        for key in keys {
+            records.extend(read_record(&connection, namespace, &key)?);
+        }
+        Ok(records)
+    }
+}
+
+fn read_record(
+    connection: &Connection,
+    namespace: &str,
+    key: &str,
+) -> Result<Option<StorageRecord>> {
+    let value: Option<Vec<u8>> = connection
+        .query_row(
+            "SELECT value FROM didcomm_records WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_error)?;
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let mut statement = connection
+        .prepare_cached(
+            "SELECT name, value FROM didcomm_record_tags WHERE namespace = ?1 AND key = ?2",
+        )
+        .map_err(storage_error)?;
+    let tags = statement
+        .query_map(params![namespace, key], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<BTreeMap<String, String>>>())
+        .map_err(storage_error)?;
+    Ok(Some(StorageRecord {
+        key: key.into(),
+        value,
+        tags,
+    }))
+}
+
+fn storage_error(e: rusqlite::Error) -> Error {
+    Error::StorageError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::check_storage;
+
+    #[test]
+    fn sqlite_storage_stores_and_queries_records() -> Result<()> {
+        check_storage(&SqliteStorage::open_in_memory()?)
+    }
+}
@@ -0,0 +1,220 @@
+//! Deterministic keys, sample DIDs and envelope fixtures for unit tests of applications
+//!     built on top of this crate.
+//!
+//! **Never use these keys outside of tests - their private parts are public.**
+
+use ed25519_dalek::SigningKey;
+use serde_json::{json, Value};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    crypto::{CryptoAlgorithm, SignatureAlgorithm, Signer},
+    Message,
+    Result,
+};
+
+/// DID of sample sender.
+pub const ALICE_DID: &str = "did:example:alice";
+
+/// DID of sample recipient.
+pub const BOB_DID: &str = "did:example:bob";
+
+/// DID of sample mediator.
+pub const MEDIATOR_DID: &str = "did:example:mediator";
+
+/// `type` of sample messages.
+pub const SAMPLE_MESSAGE_TYPE: &str = "https://didcomm.org/trust-ping/2.0/ping";
+
+const ALICE_PRIVATE: &str = "5046adc1dba838867b2bbbfdd0c3423e58b57970b5267a90f57960924a87f156";
+const BOBS_PRIVATE: &str = "f068e2f7ccc3eee220065e1dc937d34d548ec59be6488fea5ae1397e63f81c52";
+const MEDIATOR_PRIVATE: &str = "88aff351a303e9b6781e37bed44c29b73c09313d501811e62ac2b0b0129cbf7d";
+const ALICE_SIGNING_SEED: [u8; 32] = [0xa1; 32];
+const BOBS_SIGNING_SEED: [u8; 32] = [0xb0; 32];
+
+/// Fixed X25519 key agreement and Ed25519 signing key pairs of sample parties.
+/// Same values on every call.
+#[derive(Debug, Clone)]
+pub struct KeyPairSet {
+    pub alice_public: [u8; 32],
+    pub alice_private: [u8; 32],
+    pub bobs_public: [u8; 32],
+    pub bobs_private: [u8; 32],
+    pub mediators_public: [u8; 32],
+    pub mediators_private: [u8; 32],
+    pub alice_signing_public: [u8; 32],
+    pub alice_signing_private: [u8; 32],
+    pub bobs_signing_public: [u8; 32],
+    pub bobs_signing_private: [u8; 32],
+}
+
+/// Gets deterministic key pairs of sample parties.
+pub fn get_keypair_set() -> KeyPairSet {
+    let (alice_private, alice_public) = x25519_keypair(ALICE_PRIVATE);
+    let (bobs_private, bobs_public) = x25519_keypair(BOBS_PRIVATE);
+    let (mediators_private, mediators_public) = x25519_keypair(MEDIATOR_PRIVATE);
+    let alice_signing = SigningKey::from_bytes(&ALICE_SIGNING_SEED);
+    let bobs_signing = SigningKey::from_bytes(&BOBS_SIGNING_SEED);
+
+    KeyPairSet {
+        alice_public,
+        alice_private,
+        bobs_public,
+        bobs_private,
+        mediators_public,
+        mediators_private,
+        alice_signing_public: alice_signing.verifying_key().to_bytes(),
+        alice_signing_private: alice_signing.to_bytes(),
+        bobs_signing_public: bobs_signing.verifying_key().to_bytes(),
+        bobs_signing_private: bobs_signing.to_bytes(),
+    }
+}
+
+/// Builds minimal DID document with one key agreement and one authentication key,
+///     both as `JsonWebKey2020` verification methods.
+///
+/// # Parameters
+///
+/// * `did` - DID of the document subject
+///
+/// * `key_agreement_public` - X25519 public key
+///
+/// * `signing_public` - Ed25519 public key
+pub fn sample_did_document(
+    did: &str,
+    key_agreement_public: &[u8; 32],
+    signing_public: &[u8; 32],
+) -> Value {
+    let key_agreement_id = format!("{}#key-x25519-1", did);
+    let signing_id = format!("{}#key-ed25519-1", did);
+    json!({
+        "@context": [
+            "https://www.w3.org/ns/did/v1",
+            "https://w3id.org/security/suites/jws-2020/v1",
+        ],
+        "id": did,
+        "verificationMethod": [
+            {
+                "id": key_agreement_id,
+                "type": "JsonWebKey2020",
+                "controller": did,
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "X25519",
+                    "x": base64_url::encode(key_agreement_public),
+                },
+            },
+            {
+                "id": signing_id,
+                "type": "JsonWebKey2020",
+                "controller": did,
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": base64_url::encode(signing_public),
+                },
+            },
+        ],
+        "keyAgreement": [key_agreement_id],
+        "authentication": [signing_id],
+    })
+}
+
+/// Plain sample message from `ALICE_DID` to `BOB_DID`.
+pub fn sample_message() -> Message {
+    Message::new()
+        .m_type(SAMPLE_MESSAGE_TYPE)
+        .from(ALICE_DID)
+        .to(&[BOB_DID])
+        .merge_body(json!({ "response_requested": true }))
+}
+
+/// `sample_message` encrypted by Alice for Bob as JWE,
+///     can be received with `bobs_private` and `alice_public` keys.
+pub fn sample_jwe() -> Result<String> {
+    let KeyPairSet {
+        alice_private,
+        bobs_public,
+        ..
+    } = get_keypair_set();
+    sample_message()
+        .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+        .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))
+}
+
+/// `sample_message` signed by Alice as flat JWS,
+///     can be verified with `alice_signing_public` key.
+pub fn sample_jws() -> Result<String> {
+    let keys = get_keypair_set();
+    sample_message()
+        .kid(&format!("{}#key-ed25519-1", ALICE_DID))
+        .as_flat_jws(&SignatureAlgorithm::EdDsa)
+        .sign(
+            SignatureAlgorithm::EdDsa.signer(),
+            &keys.alice_signing_private,
+        )
+}
+
+/// Asserts that messages are equal apart from values which differ on every packing,
+///     see `Message::semantically_eq`.
+/// Panics with both messages printed as JSON otherwise.
+///
+/// # Parameters
+///
+/// * `left` - e.g. message as sent
+///
+/// * `right` - e.g. same message as received
+#[track_caller]
+pub fn assert_semantically_eq(left: &Message, right: &Message) {
+    assert!(
+        left.semantically_eq(right),
+        "messages are not semantically equal\n left: {}\nright: {}",
+        left,
+        right
+    );
+}
+
+fn x25519_keypair(private_hex: &str) -> ([u8; 32], [u8; 32]) {
+    let mut private = [0u8; 32];
+    // constants above are valid hex of 32 bytes
+    hex::decode_to_slice(private_hex, &mut private).expect("invalid test key");
+    let secret = StaticSecret::from(private);
+    (secret.to_bytes(), PublicKey::from(&secret).to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_deterministic() {
+        let first = get_keypair_set();
+        let second = get_keypair_set();
+        assert_eq!(first.alice_public, second.alice_public);
+        assert_eq!(first.bobs_signing_public, second.bobs_signing_public);
+    }
+
+    #[test]
+    fn fixtures_can_be_received() -> Result<()> {
+        let keys = get_keypair_set();
+        let received = Message::receive(
+            &sample_jwe()?,
+            Some(&keys.bobs_private),
+            Some(keys.alice_public.to_vec()),
+            None,
+        )?;
+        assert_eq!(received.get_type(), SAMPLE_MESSAGE_TYPE);
+        let received_again = Message::receive(
+            &sample_jwe()?,
+            Some(&keys.bobs_private),
+            Some(keys.alice_public.to_vec()),
+            None,
+        )?;
+        assert_semantically_eq(&received, &received_again);
+        let verified = Message::verify(sample_jws()?.as_bytes(), &keys.alice_signing_public)?;
+        assert_eq!(verified.get_didcomm_header().from.as_deref(), Some(ALICE_DID));
+        let document =
+            sample_did_document(ALICE_DID, &keys.alice_public, &keys.alice_signing_public);
+        assert_eq!(document["id"], ALICE_DID);
+        Ok(())
+    }
+}
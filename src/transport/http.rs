@@ -0,0 +1,255 @@
+//! HTTP(S) transport posting packed messages to `DIDCommMessaging` service endpoints.
+use std::{thread, time::Duration};
+
+use super::check_service;
+use crate::{DidCommMessagingService, EnvelopeKind, Error, Result, ReturnRoute, Transport};
+
+/// `Accept` header of requests expecting a returned message in the response body.
+const RETURN_ROUTE_ACCEPT: &str = "application/didcomm-encrypted+json, \
+    application/didcomm-signed+json, application/didcomm-plain+json";
+
+/// Blocking HTTP(S) client for sending packed messages.
+/// Failed sends are retried on connection errors and `5xx` or `429` responses,
+///     with delay doubling after each attempt.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    agent: ureq::Agent,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpTransport {
+    /// Constructor with 30 seconds timeout and two retries.
+    pub fn new() -> Self {
+        Self::with_timeout(Duration::from_secs(30))
+    }
+
+    /// Constructor with given timeout of a single request and two retries.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout` - time span a single request, including connecting, may take
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Setter of retry policy.
+    ///
+    /// # Parameters
+    ///
+    /// * `retries` - number of retries after first failed attempt, `0` to disable
+    ///
+    /// * `delay` - delay before first retry, doubled for each further one
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Posts packed message to endpoint with `Content-Type` matching its envelope.
+    /// Returns response body if endpoint delivered a message in it,
+    ///     as it does for messages sent with `return_route`, `None` if body is empty.
+    ///
+    /// # Parameters
+    ///
+    /// * `endpoint` - URI of `DIDCommMessaging` service of the recipient
+    ///
+    /// * `envelope` - packed message, e.g. output of `seal` or `sign`
+    pub fn send(&self, endpoint: &str, envelope: &str) -> Result<Option<String>> {
+        self.post(endpoint, envelope, None)
+    }
+
+    /// Posts packed message sent with given `return_route` header.
+    /// For `thread` and `all` response body is expected to be a packed message
+    ///     returned by the recipient, `Err` is returned if it is something else.
+    /// For `none` response body is ignored.
+    /// `return_route` is passed explicitly, as it can not be read from encrypted envelopes.
+    ///
+    /// # Parameters
+    ///
+    /// * `endpoint` - URI of `DIDCommMessaging` service of the recipient
+    ///
+    /// * `envelope` - packed message with `return_route` header
+    ///
+    /// * `return_route` - value of `return_route` header of the packed message
+    pub fn send_with_return_route(
+        &self,
+        endpoint: &str,
+        envelope: &str,
+        return_route: ReturnRoute,
+    ) -> Result<Option<String>> {
+        if return_route == ReturnRoute::None {
+            self.post(endpoint, envelope, None)?;
+            return Ok(None);
+        }
+        let response = self.post(endpoint, envelope, Some(RETURN_ROUTE_ACCEPT))?;
+        match response {
+            Some(body) if EnvelopeKind::detect(&body).media_type().is_none() => Err(
+                Error::TransportError("returned response is not a DIDComm envelope".into()),
+            ),
+            response => Ok(response),
+        }
+    }
+
+    fn post(&self, endpoint: &str, envelope: &str, accept: Option<&str>) -> Result<Option<String>> {
+        let content_type = EnvelopeKind::detect(envelope)
+            .media_type()
+            .ok_or_else(|| Error::TransportError("not a DIDComm envelope".into()))?;
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let mut request = self.agent.post(endpoint).set("Content-Type", content_type);
+            if let Some(accept) = accept {
+                request = request.set("Accept", accept);
+            }
+            let result = request.send_string(envelope);
+            let retryable = match &result {
+                Err(ureq::Error::Status(status, _)) => *status >= 500 || *status == 429,
+                Err(ureq::Error::Transport(_)) => true,
+                Ok(_) => false,
+            };
+            if retryable && attempt < self.retries {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(endpoint, attempt, "retrying failed send");
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+                continue;
+            }
+            let body = result
+                .map_err(|e| Error::TransportError(e.to_string()))?
+                .into_string()
+                .map_err(|e| Error::TransportError(e.to_string()))?;
+            return Ok(Some(body).filter(|body| !body.trim().is_empty()));
+        }
+    }
+
+    /// Posts packed message to `DIDCommMessaging` service of the recipient.
+    /// `Err` is returned if service is not an HTTP(S) endpoint
+    ///     or does not accept DIDComm v2 messages.
+    ///
+    /// # Parameters
+    ///
+    /// * `service` - service taken from DID document or out-of-band invitation
+    ///
+    /// * `envelope` - packed message, e.g. output of `seal` or `sign`
+    pub fn send_to_service(
+        &self,
+        service: &DidCommMessagingService,
+        envelope: &str,
+    ) -> Result<Option<String>> {
+        check_service(service, service.is_http())?;
+        self.send(&service.uri, envelope)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn deliver(&self, service: &DidCommMessagingService, envelope: &str) -> Result<Option<String>> {
+        self.send_to_service(service, envelope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::Message;
+
+    /// Serves given responses one connection each and returns received requests.
+    fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/didcomm", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 8192];
+                let read = stream.read(&mut buffer).unwrap();
+                requests.push(String::from_utf8_lossy(&buffer[..read]).to_string());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn posts_envelope_and_returns_routed_response() -> Result<()> {
+        let (endpoint, server) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\n{\"a\":1}\n",
+        ]);
+        let transport = HttpTransport::new().with_retries(1, Duration::from_millis(1));
+        let message = Message::new().m_type("https://example.com/test/1.0/ping");
+
+        let response = transport.send(&endpoint, &message.to_string())?;
+        assert_eq!(response.as_deref(), Some("{\"a\":1}\n"));
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1]
+            .to_lowercase()
+            .contains("content-type: application/didcomm-plain+json"));
+        Ok(())
+    }
+
+    #[test]
+    fn return_route_expects_envelope_in_response() -> Result<()> {
+        let (endpoint, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\n{\"type\":\"x\"}",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ]);
+        let transport = HttpTransport::new();
+        let message = Message::new()
+            .m_type("https://example.com/test/1.0/ping")
+            .return_route(ReturnRoute::All)
+            .to_string();
+
+        let response = transport.send_with_return_route(&endpoint, &message, ReturnRoute::All)?;
+        assert_eq!(response.as_deref(), Some("{\"type\":\"x\"}"));
+        let response = transport.send_with_return_route(&endpoint, &message, ReturnRoute::Thread);
+        assert!(matches!(response, Err(Error::TransportError(_))));
+        let response = transport.send_with_return_route(&endpoint, &message, ReturnRoute::None)?;
+        assert_eq!(response, None);
+        let requests = server.join().unwrap();
+        assert!(requests[0].to_lowercase().contains("accept: application/didcomm-encrypted+json"));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_http_services() {
+        let transport = HttpTransport::new();
+        let ws = DidCommMessagingService::new("wss://example.com");
+        let v1 = DidCommMessagingService::new("https://example.com").with_accept(&["didcomm/aip2"]);
+        for service in [ws, v1] {
+            let result = transport.send_to_service(&service, "{}");
+            assert!(matches!(result, Err(Error::InvalidService(_))));
+        }
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (endpoint, server) =
+            serve(vec!["HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n"]);
+        let transport = HttpTransport::new().with_retries(3, Duration::from_millis(1));
+        let message = Message::new().m_type("https://example.com/test/1.0/ping");
+
+        let result = transport.send(&endpoint, &message.to_string());
+        assert!(matches!(result, Err(Error::TransportError(_))));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
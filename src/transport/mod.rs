@@ -0,0 +1,24 @@
+//! Optional clients delivering packed messages to `DIDCommMessaging` service endpoints.
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+use crate::{DidCommMessagingService, Error, Result, DIDCOMM_V2_PROFILE};
+
+/// Checks that service uses transport's scheme and accepts DIDComm v2 messages.
+fn check_service(service: &DidCommMessagingService, scheme_matches: bool) -> Result<()> {
+    if !scheme_matches {
+        return Err(Error::InvalidService(format!(
+            "endpoint `{}` is not supported by this transport",
+            service.uri
+        )));
+    }
+    if !service.accepts(DIDCOMM_V2_PROFILE) {
+        return Err(Error::InvalidService(format!(
+            "endpoint `{}` does not accept {}",
+            service.uri, DIDCOMM_V2_PROFILE
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! WebSocket transport keeping a connection to a mediator open for live delivery.
+use std::net::TcpStream;
+
+use serde_json::json;
+use tungstenite::{stream::MaybeTlsStream, Message as Frame, WebSocket};
+
+use super::check_service;
+use crate::{DidCommMessagingService, Error, Message, Result, ReturnRoute};
+
+/// `type` of pickup protocol message switching live delivery mode on or off.
+pub const LIVE_DELIVERY_CHANGE_TYPE: &str =
+    "https://didcomm.org/messagepickup/3.0/live-delivery-change";
+
+/// Blocking WebSocket client for sending packed messages and receiving
+///     messages delivered by a mediator over the same connection.
+pub struct WsTransport {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsTransport {
+    /// Opens connection to `ws://` or `wss://` endpoint.
+    ///
+    /// # Parameters
+    ///
+    /// * `endpoint` - URI of `DIDCommMessaging` service of the mediator
+    pub fn connect(endpoint: &str) -> Result<Self> {
+        let (socket, _) = tungstenite::connect(endpoint).map_err(transport_error)?;
+        Ok(Self { socket })
+    }
+
+    /// Opens connection to WebSocket `DIDCommMessaging` service.
+    /// `Err` is returned if service is not a WebSocket endpoint
+    ///     or does not accept DIDComm v2 messages.
+    ///
+    /// # Parameters
+    ///
+    /// * `service` - service of the mediator
+    pub fn connect_service(service: &DidCommMessagingService) -> Result<Self> {
+        check_service(service, service.is_ws())?;
+        Self::connect(&service.uri)
+    }
+
+    /// Sends packed message, e.g. output of `seal`, as text frame.
+    ///
+    /// # Parameters
+    ///
+    /// * `envelope` - packed message
+    pub fn send(&mut self, envelope: &str) -> Result<()> {
+        self.socket
+            .send(Frame::Text(envelope.into()))
+            .map_err(transport_error)
+    }
+
+    /// Blocks until next envelope arrives.
+    /// Returns `None` once connection was closed.
+    pub fn receive(&mut self) -> Result<Option<String>> {
+        loop {
+            match self.socket.read() {
+                Ok(Frame::Text(envelope)) => return Ok(Some(envelope)),
+                Ok(Frame::Binary(envelope)) => return Ok(Some(String::from_utf8(envelope)?)),
+                Ok(Frame::Close(_)) => {
+                    // sends queued reply completing the closing handshake
+                    let _ = self.socket.flush();
+                    return Ok(None);
+                }
+                Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::AlreadyClosed) => return Ok(None),
+                // pings are answered by `read`
+                Ok(_) => continue,
+                Err(e) => return Err(transport_error(e)),
+            }
+        }
+    }
+
+    /// Iterator over incoming envelopes, ends once connection was closed.
+    pub fn incoming(&mut self) -> impl Iterator<Item = Result<String>> + '_ {
+        std::iter::from_fn(move || self.receive().transpose())
+    }
+
+    /// Closes connection.
+    pub fn close(mut self) -> Result<()> {
+        match self.socket.close(None) {
+            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => Ok(()),
+            Err(e) => Err(transport_error(e)),
+        }
+    }
+}
+
+/// Creates pickup protocol message switching live delivery mode on or off.
+/// Mediator delivers messages over the connection this message was sent with,
+///     so it has to be packed and sent through [`WsTransport::send`].
+///
+/// # Parameters
+///
+/// * `live_delivery` - `true` to receive messages as soon as mediator gets them
+pub fn live_delivery_change(live_delivery: bool) -> Message {
+    Message::new()
+        .m_type(LIVE_DELIVERY_CHANGE_TYPE)
+        .return_route(ReturnRoute::All)
+        .merge_body(json!({ "live_delivery": live_delivery }))
+}
+
+fn transport_error(e: tungstenite::Error) -> Error {
+    Error::TransportError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    #[test]
+    fn sends_and_receives_envelopes() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("ws://{}/didcomm", listener.local_addr().unwrap());
+        let mediator = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let request = socket.read().unwrap().into_text().unwrap();
+            socket.send(Frame::Text("first".into())).unwrap();
+            socket.send(Frame::Binary(b"second".to_vec())).unwrap();
+            socket.close(None).unwrap();
+            while socket.read().is_ok() {}
+            request
+        });
+
+        let mut transport = WsTransport::connect(&endpoint)?;
+        let change = live_delivery_change(true);
+        transport.send(&change.to_string())?;
+        let delivered = transport.incoming().collect::<Result<Vec<_>>>()?;
+        drop(transport);
+
+        assert_eq!(delivered, ["first", "second"]);
+        let request: Message = mediator.join().unwrap().parse()?;
+        assert_eq!(request.get_didcomm_header().m_type, LIVE_DELIVERY_CHANGE_TYPE);
+        assert_eq!(request.get_return_route(), Some(ReturnRoute::All));
+        assert_eq!(request.body_at("/live_delivery"), Some(&json!(true)));
+        Ok(())
+    }
+}
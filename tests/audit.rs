@@ -0,0 +1,70 @@
+#[cfg(feature = "raw-crypto")]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use didcomm_rs::{
+        clear_audit_sink,
+        crypto::{CryptoAlgorithm, SignatureAlgorithm, Signer},
+        set_audit_sink,
+        AuditDirection,
+        AuditRecord,
+        EnvelopeKind,
+        Message,
+    };
+    use rand_core::OsRng;
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    #[test]
+    fn pack_and_unpack_are_recorded() {
+        // Arrange
+        let records: Arc<Mutex<Vec<AuditRecord>>> = Arc::new(Mutex::new(vec![]));
+        let sink = records.clone();
+        set_audit_sink(Arc::new(move |record: &AuditRecord| {
+            sink.lock().unwrap().push(record.clone())
+        }));
+        let KeyPairSet {
+            alice_public,
+            alice_private,
+            bobs_private,
+            bobs_public,
+            ..
+        } = get_keypair_set();
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+
+        // Act
+        let jwe = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .m_type("https://example.com/test/1.0/ping")
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))
+            .unwrap();
+        Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None).unwrap();
+        let failed = Message::receive(&jwe, Some(&[0; 32]), Some(alice_public.to_vec()), None);
+        Message::new()
+            .from("did:example:alice")
+            .as_jws(&SignatureAlgorithm::EdDsa)
+            .sign(SignatureAlgorithm::EdDsa.signer(), &sign_keypair.to_bytes())
+            .unwrap();
+        clear_audit_sink();
+        Message::receive(&jwe, Some(&bobs_private), Some(alice_public.to_vec()), None).unwrap();
+
+        // Assert
+        let records = records.lock().unwrap();
+        assert!(failed.is_err());
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].direction, AuditDirection::Pack);
+        assert_eq!(records[0].envelope, EnvelopeKind::Jwe);
+        assert_eq!(records[0].to, vec!["did:example:bob".to_string()]);
+        assert_eq!(records[1].direction, AuditDirection::Unpack);
+        assert_eq!(records[1].from.as_deref(), Some("did:example:alice"));
+        assert_eq!(
+            records[1].message_type.as_deref(),
+            Some("https://example.com/test/1.0/ping")
+        );
+        assert!(records[1].error.is_none());
+        assert!(records[2].error.is_some());
+        assert_eq!(records[2].message_id, None);
+        assert_eq!(records[3].envelope, EnvelopeKind::Jws);
+    }
+}
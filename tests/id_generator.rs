@@ -0,0 +1,26 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use didcomm_rs::{reset_id_generator, set_id_generator, Message};
+
+#[test]
+fn global_id_generator_is_used_for_new_messages() {
+    // Arrange
+    let counter = AtomicUsize::new(0);
+    set_id_generator(Arc::new(move || {
+        format!("test-{}", counter.fetch_add(1, Ordering::SeqCst))
+    }));
+
+    // Act
+    let first = Message::new();
+    let second = Message::new();
+    reset_id_generator();
+    let third = Message::new();
+
+    // Assert
+    assert_eq!(first.get_id(), "test-0");
+    assert_eq!(second.get_id(), "test-1");
+    assert!(!third.get_id().starts_with("test-"));
+}
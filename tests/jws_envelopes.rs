@@ -4,13 +4,15 @@ mod tests {
     extern crate chacha20poly1305;
     extern crate sodiumoxide;
 
+    use std::{collections::HashMap, sync::Arc};
+
     #[cfg(feature = "resolve")]
     pub use ddoresolver_rs::*;
     use didcomm_rs::crypto::{SignatureAlgorithm, Signer};
-    use didcomm_rs::{Error, Message};
+    use didcomm_rs::{Error, JwmHeader, Message, Packer, TimingHeaders};
 
     use rand_core::OsRng;
-    use serde_json::Value;
+    use serde_json::{json, Value};
 
     #[test]
     fn can_create_flattened_jws_json() -> Result<(), Error> {
@@ -99,4 +101,162 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn extra_protected_header_fields_survive_sign_and_verify() -> Result<(), Error> {
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let mut header = JwmHeader::default();
+        header.set_extra("url", json!("https://example.com/inbox"));
+        let jws_string = Message::new()
+            .jwm_header(header)
+            .from("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+            .to(&["did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG"])
+            .kid(&hex::encode(sign_keypair.verifying_key().to_bytes()))
+            .as_flat_jws(&SignatureAlgorithm::EdDsa)
+            .sign(SignatureAlgorithm::EdDsa.signer(), &sign_keypair.to_bytes())?;
+
+        let received = Message::verify(jws_string.as_bytes(), &sign_keypair.verifying_key().to_bytes())?;
+
+        assert_eq!(
+            received.get_jwm_header().get_extra("url"),
+            Some(&json!("https://example.com/inbox"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_create_co_signed_general_jws_json() -> Result<(), Error> {
+        let ed_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let k256_keypair = k256::ecdsa::SigningKey::random(&mut OsRng);
+        let ed_private = ed_keypair.to_bytes();
+        let k256_private = k256_keypair.to_bytes();
+        let jws_string = Message::new()
+            .from("did:example:governance")
+            .to(&["did:example:registry"])
+            .sign_multi(&[
+                (SignatureAlgorithm::EdDsa, &ed_private, "did:example:alice#key-1"),
+                (SignatureAlgorithm::Es256k, &k256_private, "did:example:carol#key-1"),
+            ])?;
+
+        let mut keys = HashMap::new();
+        keys.insert(
+            "did:example:alice#key-1".to_string(),
+            ed_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        keys.insert(
+            "did:example:carol#key-1".to_string(),
+            k256_keypair.verifying_key().to_sec1_bytes().to_vec(),
+        );
+        let jws_object: Value = serde_json::from_str(&jws_string)?;
+
+        assert_eq!(jws_object["signatures"].as_array().map(Vec::len), Some(2));
+        assert!(Message::verify_all(jws_string.as_bytes(), &keys).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_counter_sign_received_jws() -> Result<(), Error> {
+        let alice_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let bob_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let received = Message::new()
+            .from("did:example:alice")
+            .to(&["did:example:bob"])
+            .kid("did:example:alice#key-1")
+            .as_flat_jws(&SignatureAlgorithm::EdDsa)
+            .sign(SignatureAlgorithm::EdDsa.signer(), &alice_keypair.to_bytes())?;
+
+        let counter_signed = Message::counter_sign(
+            received.as_bytes(),
+            SignatureAlgorithm::EdDsa,
+            &bob_keypair.to_bytes(),
+            "did:example:bob#key-1",
+        )?;
+
+        let mut keys = HashMap::new();
+        keys.insert(
+            "did:example:alice#key-1".to_string(),
+            alice_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        keys.insert(
+            "did:example:bob#key-1".to_string(),
+            bob_keypair.verifying_key().to_bytes().to_vec(),
+        );
+        let original = Message::verify(received.as_bytes(), &alice_keypair.verifying_key().to_bytes())?;
+        let verified = Message::verify_all(counter_signed.as_bytes(), &keys)?;
+        assert_eq!(verified.get_id(), original.get_id());
+
+        Ok(())
+    }
+
+    #[test]
+    fn packer_middleware_runs_before_signing() -> Result<(), Error> {
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let packer = Packer::new()
+            .with(Arc::new(TimingHeaders::new(None)))
+            .with(Arc::new(|message: &mut Message| {
+                *message = message.clone().set_header("trace_id", "abc")?;
+                Ok(())
+            }));
+        let message = Message::new()
+            .from("did:example:alice")
+            .as_flat_jws(&SignatureAlgorithm::EdDsa);
+        let jws_string = packer.sign(
+            message,
+            SignatureAlgorithm::EdDsa.signer(),
+            &sign_keypair.to_bytes(),
+        )?;
+
+        let verified = Message::verify(
+            jws_string.as_bytes(),
+            &sign_keypair.verifying_key().to_bytes(),
+        )?;
+        assert_eq!(verified.get_header("trace_id"), Some(&json!("abc")));
+        assert!(verified.get_created_time().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_sign_with_external_signers() -> Result<(), Error> {
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let key = sign_keypair.to_bytes();
+        let message = || {
+            Message::new()
+                .from("did:example:alice")
+                .as_flat_jws(&SignatureAlgorithm::EdDsa)
+        };
+        let public_key = sign_keypair.verifying_key().to_bytes();
+
+        let external = move |data: &[u8]| SignatureAlgorithm::EdDsa.signer()(&key, data);
+        let jws_string = message().sign_external(&external)?;
+        assert!(Message::verify(jws_string.as_bytes(), &public_key).is_ok());
+
+        let remote = move |data: Vec<u8>| async move {
+            SignatureAlgorithm::EdDsa.signer()(&key, &data)
+        };
+        let jws_string = block_on(message().sign_external_async(&remote))?;
+        assert!(Message::verify(jws_string.as_bytes(), &public_key).is_ok());
+
+        Ok(())
+    }
+
+    /// Polls future to completion on current thread, enough for futures not waiting on IO.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct NoopWaker;
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
 }
@@ -24,3 +24,82 @@ fn sets_m_type_correctly_for_out_of_band_invitation_message() -> Result<(), Erro
 
     Ok(())
 }
+
+#[test]
+#[cfg(feature = "out-of-band")]
+fn invitation_survives_oob_url_round_trip() -> Result<(), Error> {
+    let invitation = Message::new()
+        .from("did:example:alice")
+        .as_out_of_band_invitation(r#"{"goal_code":"connect"}"#, None)?;
+
+    let url = invitation.to_oob_url("https://example.com/path?lang=en")?;
+    assert!(url.starts_with("https://example.com/path?lang=en&_oob="));
+    let decoded = Message::from_oob_url(&url)?;
+    assert_eq!(decoded.get_id(), invitation.get_id());
+    assert_eq!(decoded.body_at("/goal_code"), invitation.body_at("/goal_code"));
+
+    let uri = invitation.to_didcomm_uri()?;
+    assert!(uri.starts_with("didcomm://?_oob="));
+    assert_eq!(Message::from_oob_url(&uri)?.get_id(), invitation.get_id());
+
+    assert!(Message::from_oob_url("https://example.com/path?lang=en").is_err());
+    let large = Message::new().body(&format!(r#"{{"comment":"{}"}}"#, "a".repeat(2048)))?;
+    assert!(matches!(
+        large.to_oob_url("https://example.com"),
+        Err(Error::OutOfBandUrlTooLong(_, 2048))
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(all(feature = "out-of-band", feature = "raw-crypto"))]
+fn attached_requests_are_extracted_and_received() -> Result<(), Error> {
+    use didcomm_rs::{crypto::CryptoAlgorithm, ReceiveOptions};
+    use utilities::{get_keypair_set, KeyPairSet};
+
+    let KeyPairSet {
+        alice_private,
+        alice_public,
+        bobs_private,
+        bobs_public,
+        ..
+    } = get_keypair_set();
+    let plain = Message::new()
+        .from("did:example:alice")
+        .m_type("https://didcomm.org/present-proof/3.0/request-presentation")
+        .as_raw_json()?;
+    let sealed = Message::new()
+        .from("did:example:alice")
+        .to(&["did:example:bob"])
+        .m_type("https://didcomm.org/trust-ping/2.0/ping")
+        .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+        .seal(alice_private, Some(vec![Some(bobs_public.to_vec())]))?;
+
+    let invitation = Message::new()
+        .from("did:example:alice")
+        .as_out_of_band_invitation(r#"{"goal_code":"connect"}"#, None)?
+        .with_attached_request(&plain)?
+        .with_attached_request(&sealed)?;
+    assert!(invitation.clone().with_attached_request("not json").is_err());
+    assert_eq!(
+        invitation.attachment_iter().nth(1).unwrap().media_type.as_deref(),
+        Some("application/didcomm-encrypted+json")
+    );
+
+    let received: Message = serde_json::from_str(&invitation.as_raw_json()?)?;
+    assert_eq!(received.attached_requests()?, [plain, sealed]);
+    let requests = received.receive_attached_requests(
+        Some(&bobs_private),
+        Some(alice_public.to_vec()),
+        None,
+        &ReceiveOptions::new(),
+    )?;
+    assert_eq!(
+        requests[0].get_type(),
+        "https://didcomm.org/present-proof/3.0/request-presentation"
+    );
+    assert_eq!(requests[1].get_type(), "https://didcomm.org/trust-ping/2.0/ping");
+
+    Ok(())
+}
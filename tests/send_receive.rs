@@ -6,7 +6,7 @@ mod tests {
     use super::common::sample_dids;
     #[cfg(not(feature = "resolve"))]
     use didcomm_rs::crypto::{SignatureAlgorithm, Signer};
-    use didcomm_rs::{crypto::CryptoAlgorithm, Jwe, Mediated, Message};
+    use didcomm_rs::{crypto::CryptoAlgorithm, Jwe, Mediated, Message, ReceiveOptions, Shape};
     #[cfg(not(feature = "resolve"))]
     use rand_core::OsRng;
     use serde_json::Value;
@@ -225,4 +225,234 @@ mod tests {
             serde_json::from_str(&bob_received.unwrap().get_body().unwrap()).unwrap();
         assert_eq!(sample_body.to_string(), bob_received_body.to_string());
     }
+
+    #[test]
+    fn receive_unwraps_forwards_with_controlled_mediator_keys() {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let mediator = "did:key:z6MknGc3ocHs3zdPiJbnaaqDi58NGb4pk1Sp9WxWufuXSdxf";
+        let sealed = Message::new()
+            .from("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+            .to(&["did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG"])
+            .body(sample_dids::TEST_DID_SIGN_1)
+            .expect("failed to add body")
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .routed_by(
+                &alice_private,
+                Some(vec![Some(bobs_public.to_vec())]),
+                mediator,
+                Some(mediators_public.to_vec()),
+            )
+            .unwrap();
+
+        let options = ReceiveOptions::new().with_forward_unwrapping(&[&mediators_private]);
+        let (received, sender) = Message::receive_with_sender_info(
+            &sealed,
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(sender.forward_hops, [mediator]);
+        let sample_body: Value = serde_json::from_str(sample_dids::TEST_DID_SIGN_1).unwrap();
+        let received_body: Value = serde_json::from_str(&received.get_body().unwrap()).unwrap();
+        assert_eq!(sample_body, received_body);
+
+        let forward = Message::receive(
+            &sealed,
+            Some(&mediators_private),
+            Some(alice_public.to_vec()),
+            None,
+        );
+        let forward = forward.unwrap().get_body().unwrap();
+        assert!(forward.contains("payloads~attach"));
+    }
+
+    #[test]
+    fn receive_returns_batch_forwards_for_splitting() {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        let bob = "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG";
+        let seal_for_bob = |body: &str| {
+            Message::new()
+                .from("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+                .to(&[bob])
+                .body(body)
+                .expect("failed to add body")
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+                .seal(alice_private.as_slice(), Some(vec![Some(bobs_public.to_vec())]))
+                .unwrap()
+                .into_bytes()
+        };
+        let payloads = vec![seal_for_bob(r#"{"n":1}"#), seal_for_bob(r#"{"n":2}"#)];
+        let seal_for_mediator = |forward: Message| {
+            forward
+                .to(&["did:key:z6MknGc3ocHs3zdPiJbnaaqDi58NGb4pk1Sp9WxWufuXSdxf"])
+                .as_jwe(&CryptoAlgorithm::XC20P, Some(mediators_public.to_vec()))
+                .seal(alice_private.as_slice(), Some(vec![Some(mediators_public.to_vec())]))
+                .unwrap()
+        };
+        let options = ReceiveOptions::new().with_forward_unwrapping(&[&mediators_private]);
+
+        // batch of single message is unwrapped like any forward
+        let single = Mediated::batch(bob.into(), payloads[..1].to_vec()).unwrap();
+        let (received, sender) = Message::receive_with_sender_info(
+            &seal_for_mediator(single),
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(sender.forward_hops.len(), 1);
+        assert_eq!(received.get_body().unwrap(), r#"{"n":1}"#);
+
+        // larger batches are returned to be split by mediator
+        let batch = Mediated::batch(bob.into(), payloads.clone()).unwrap();
+        let (forward, sender) = Message::receive_with_sender_info(
+            &seal_for_mediator(batch),
+            Some(&mediators_private),
+            Some(alice_public.to_vec()),
+            None,
+            &options,
+        )
+        .unwrap();
+        assert!(sender.forward_hops.is_empty());
+        let split = Mediated::split(&forward).unwrap();
+        assert_eq!(split.len(), 2);
+        for (n, forward) in split.iter().enumerate() {
+            assert_eq!(forward.next, bob);
+            let received = Message::receive(
+                &String::from_utf8_lossy(&forward.payload),
+                Some(&bobs_private),
+                Some(alice_public.to_vec()),
+                None,
+            )
+            .unwrap();
+            assert_eq!(received.get_body().unwrap(), format!(r#"{{"n":{}}}"#, n + 1));
+        }
+    }
+
+    #[test]
+    fn mediator_rewraps_forward_for_next_hop() {
+        let KeyPairSet {
+            alice_private,
+            alice_public,
+            bobs_private,
+            bobs_public,
+            mediators_private,
+            mediators_public,
+        } = get_keypair_set();
+        // alice's keys double as keys of the upstream relay
+        let (relay_private, relay_public) = (alice_private, alice_public);
+        let mediator = "did:key:z6MknGc3ocHs3zdPiJbnaaqDi58NGb4pk1Sp9WxWufuXSdxf";
+        let sealed = Message::new()
+            .from("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+            .to(&["did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG"])
+            .body(sample_dids::TEST_DID_SIGN_1)
+            .expect("failed to add body")
+            .as_jwe(&CryptoAlgorithm::XC20P, Some(bobs_public.to_vec()))
+            .routed_by(
+                &alice_private,
+                Some(vec![Some(bobs_public.to_vec())]),
+                mediator,
+                Some(mediators_public.to_vec()),
+            )
+            .unwrap();
+
+        let received = Message::receive(
+            &sealed,
+            Some(&mediators_private),
+            Some(alice_public.to_vec()),
+            None,
+        )
+        .unwrap();
+        let forward = Mediated::shape(&received).unwrap();
+        let rewrapped = forward
+            .rewrap(
+                &mediators_private,
+                mediator,
+                "did:example:relay",
+                Some(relay_public.to_vec()),
+                &CryptoAlgorithm::XC20P,
+            )
+            .unwrap();
+
+        let received = Message::receive(
+            &rewrapped,
+            Some(&relay_private),
+            Some(mediators_public.to_vec()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(received.get_didcomm_header().to, ["did:example:relay"]);
+        let relayed = Mediated::shape(&received).unwrap();
+        assert_eq!(relayed.next, forward.next);
+        assert_eq!(relayed.payload, forward.payload);
+        let bob_received = Message::receive(
+            &String::from_utf8_lossy(&relayed.payload),
+            Some(&bobs_private),
+            Some(alice_public.to_vec()),
+            None,
+        );
+        assert!(bob_received.is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "resolve"))]
+    fn crypto_closures_are_used_across_threads() {
+        use didcomm_rs::crypto::Cypher;
+
+        let sign_keypair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let signed: Vec<_> = (0..4)
+            .map(|i| {
+                // signer is created here and moved into the signing thread
+                let signer = SignatureAlgorithm::EdDsa.signer();
+                let key = sign_keypair.to_bytes();
+                std::thread::spawn(move || {
+                    Message::new()
+                        .from("did:example:alice")
+                        .body(&format!(r#"{{"index":{}}}"#, i))
+                        .unwrap()
+                        .as_jws(&SignatureAlgorithm::EdDsa)
+                        .sign(signer, &key)
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        // closures are shared by reference between threads
+        let validator = SignatureAlgorithm::EdDsa.validator();
+        let encryptor = CryptoAlgorithm::XC20P.encryptor();
+        let public_key = sign_keypair.verifying_key().to_bytes();
+        let signature =
+            SignatureAlgorithm::EdDsa.signer()(&sign_keypair.to_bytes(), b"data").unwrap();
+        std::thread::scope(|scope| {
+            for jws in &signed {
+                let (validator, encryptor) = (&validator, &encryptor);
+                let (public_key, signature) = (&public_key, &signature);
+                scope.spawn(move || {
+                    assert!(Message::verify(jws.as_bytes(), public_key).is_ok());
+                    assert!(validator(public_key, b"data", signature).unwrap());
+                    assert!(encryptor(&[0; 24], &[1; 32], jws.as_bytes(), &[]).is_ok());
+                });
+            }
+        });
+    }
 }